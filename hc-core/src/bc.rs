@@ -0,0 +1,437 @@
+//! bc-like programs, for readable scripts: infix expressions with variables
+//! and functions.
+//!
+//! ```text
+//! # Hypotenuse of the right triangle with sides a and b.
+//! define hyp(a, b) {
+//!     s = a^2 + b^2
+//!     return sqrt(s)
+//! }
+//! scale = 20
+//! x = 3
+//! hyp(x, 4)
+//! ```
+//!
+//! Statements end with a line break or `;`, and `#` starts a comment. The
+//! value of each statement made of an expression alone is printed. As with
+//! [`crate::expr`], expressions compile down to the operations of the stack:
+//! variables push their value, and functions, defined in the program or
+//! built-in such as `sqrt`, become operations taking their arguments off the
+//! stack. `scale` is the precision. There are no conditionals or loops.
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::expr::{self, ExprError, Scope};
+use crate::format::format_number;
+use crate::ops::{self, Operation};
+use crate::stack::{apply_on_stack, InstantStack, Op, StackError, DEFAULT_PRECISION};
+
+// Functions calling functions nest, up to that depth.
+const MAX_DEPTH: usize = 100;
+// The width of the printed numbers, which have no layout to fit.
+const WIDTH: u64 = 1000;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BcError {
+    #[error("line {0}: {1}")]
+    Syntax(usize, String),
+    #[error("line {0}: {1}")]
+    Expr(usize, ExprError),
+}
+
+/// A statement of a program, each with its line.
+#[derive(Debug)]
+enum Statement {
+    Define(Arc<Function>),
+    Assign(String, String),
+    Return(String),
+    Print(String),
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Vec<(usize, Statement)>,
+}
+
+/// The state of programs run in turn: the variables, functions and
+/// precision, and what was printed.
+pub struct Bc {
+    variables: HashMap<String, BigDecimal>,
+    functions: HashMap<String, Arc<Function>>,
+    precision: u64,
+    output: String,
+}
+
+impl Default for Bc {
+    fn default() -> Self {
+        Bc {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            precision: DEFAULT_PRECISION,
+            output: String::new(),
+        }
+    }
+}
+
+impl Bc {
+    /// What the programs printed so far, which is then cleared.
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Run a program. It stops at the first error, with the statements
+    /// before it done.
+    pub fn run(&mut self, program: &str) -> Result<(), BcError> {
+        let statements = parse(program)?;
+        let mut printed = vec![];
+        let mut env = Env {
+            variables: std::mem::take(&mut self.variables),
+            functions: std::mem::take(&mut self.functions),
+            precision: self.precision,
+            depth: 0,
+        };
+        let result = env.run(&statements, Some(&mut printed));
+        for v in printed {
+            writeln!(self.output, "{}", format_number(&v, WIDTH, false, 10)).unwrap();
+        }
+        self.variables = env.variables;
+        self.functions = env.functions;
+        self.precision = env.precision;
+        result.map(|_| ())
+    }
+}
+
+/// What expressions can refer to while running statements.
+struct Env {
+    variables: HashMap<String, BigDecimal>,
+    functions: HashMap<String, Arc<Function>>,
+    precision: u64,
+    depth: usize, // Of the function calls.
+}
+
+impl Env {
+    /// Run the statements, collecting the values to print if given, up to
+    /// the value returned, if any.
+    fn run(
+        &mut self,
+        statements: &[(usize, Statement)],
+        mut printed: Option<&mut Vec<BigDecimal>>,
+    ) -> Result<Option<BigDecimal>, BcError> {
+        for (line, statement) in statements {
+            let eval = |expr: &str| self.eval(expr).map_err(|err| BcError::Expr(*line, err));
+            match statement {
+                Statement::Define(f) if self.depth == 0 => {
+                    self.functions.insert(f.name.clone(), f.clone());
+                }
+                Statement::Define(_) => {
+                    return Err(BcError::Syntax(*line, "functions can't be nested".into()))
+                }
+                Statement::Assign(name, expr) if name == "scale" => {
+                    let scale = eval(expr)?;
+                    self.precision = scale
+                        .to_u64()
+                        .filter(|&p| p > 0 && scale.is_integer())
+                        .ok_or_else(|| {
+                            BcError::Syntax(*line, "scale must be a positive integer".into())
+                        })?;
+                }
+                Statement::Assign(name, expr) => {
+                    let value = eval(expr)?;
+                    self.variables.insert(name.clone(), value);
+                }
+                Statement::Return(_) if self.depth == 0 => {
+                    return Err(BcError::Syntax(
+                        *line,
+                        "return outside of a function".into(),
+                    ))
+                }
+                Statement::Return(expr) => return Ok(Some(eval(expr)?)),
+                Statement::Print(expr) => {
+                    let value = eval(expr)?;
+                    if let Some(printed) = printed.as_deref_mut() {
+                        printed.push(value);
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The value of an expression, computed on a stack of its own.
+    fn eval(&self, expr: &str) -> Result<BigDecimal, ExprError> {
+        let mut s = InstantStack::new(VecDeque::new(), self.precision);
+        for op in expr::compile_in(expr, self)? {
+            apply_on_stack(&mut s, op)?;
+        }
+        let value = s.values().next().map(|v| v.into_owned());
+        Ok(value.unwrap_or_default())
+    }
+}
+
+impl Scope for Env {
+    fn variable(&self, name: &str) -> Option<BigDecimal> {
+        match name {
+            "scale" => Some(self.precision.into()),
+            _ => self.variables.get(name).cloned(),
+        }
+    }
+
+    fn function(&self, name: &str, args: usize) -> Result<Op, String> {
+        let arity = match (self.functions.get(name), ops::builtin(name)) {
+            (Some(f), _) if f.params.len() == args => {
+                if self.depth >= MAX_DEPTH {
+                    return Err("functions nested too deeply".into());
+                }
+                return Ok(Op::Run(Arc::new(Call {
+                    function: f.clone(),
+                    variables: self.variables.clone(),
+                    functions: self.functions.clone(),
+                    depth: self.depth + 1,
+                })));
+            }
+            (Some(f), _) => f.params.len(),
+            (None, Some(Op::Run(op))) if op.arity() == args => return Ok(Op::Run(op)),
+            (None, Some(Op::Run(op))) => op.arity(),
+            _ => return Err(format!("unknown function '{name}'")),
+        };
+        Err(match arity {
+            1 => format!("{name} takes 1 argument"),
+            n => format!("{name} takes {n} arguments"),
+        })
+    }
+}
+
+/// A call of a function of the program, as an operation on its arguments.
+/// The function sees the variables as they were where it's called.
+struct Call {
+    function: Arc<Function>,
+    variables: HashMap<String, BigDecimal>,
+    functions: HashMap<String, Arc<Function>>,
+    depth: usize,
+}
+
+impl Operation for Call {
+    fn name(&self) -> &str {
+        &self.function.name
+    }
+
+    fn description(&self) -> &str {
+        "defined in the program"
+    }
+
+    fn arity(&self) -> usize {
+        self.function.params.len()
+    }
+
+    fn execute(&self, s: &mut InstantStack) -> Result<(), StackError> {
+        let arity = self.arity();
+        if s.stack.len() < arity {
+            return Err(StackError::MissingValue(arity));
+        }
+        let args: Vec<BigDecimal> = s.stack.drain(0..arity).rev().collect();
+        let mut variables = self.variables.clone();
+        variables.extend(self.function.params.iter().cloned().zip(args));
+        let mut env = Env {
+            variables,
+            functions: self.functions.clone(),
+            precision: s.precision,
+            depth: self.depth,
+        };
+        let result = env
+            .run(&self.function.body, None)
+            .map_err(|err| StackError::InvalidArgument(format!("in {}, {err}", self.name())))?;
+        // Like in bc, functions without a return value return 0.
+        s.push_front(result.unwrap_or_default());
+        Ok(())
+    }
+}
+
+/// The pieces of a program: the text of the statements, and the braces
+/// around the bodies of the functions. Each comes with its line.
+#[derive(Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Open,
+    Close,
+}
+
+fn pieces(program: &str) -> Vec<(usize, Piece)> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let (mut line, mut text_line) = (1, 1);
+    let mut comment = false;
+    for c in program.chars() {
+        match c {
+            '\n' | ';' | '{' | '}' if !comment || c == '\n' => {
+                flush(&mut pieces, &mut text, text_line);
+                match c {
+                    '\n' => line += 1,
+                    '{' => pieces.push((line, Piece::Open)),
+                    '}' => pieces.push((line, Piece::Close)),
+                    _ => {}
+                }
+                comment = false;
+            }
+            _ if comment => {}
+            '#' => comment = true,
+            c => {
+                if text.trim().is_empty() {
+                    text_line = line;
+                }
+                text.push(c);
+            }
+        }
+    }
+    flush(&mut pieces, &mut text, text_line);
+    pieces
+}
+
+/// Add the text so far as a piece, if any.
+fn flush(pieces: &mut Vec<(usize, Piece)>, text: &mut String, line: usize) {
+    if !text.trim().is_empty() {
+        pieces.push((line, Piece::Text(text.trim().to_owned())));
+    }
+    text.clear();
+}
+
+fn parse(program: &str) -> Result<Vec<(usize, Statement)>, BcError> {
+    let mut pieces = pieces(program).into_iter();
+    statements(&mut pieces, None)
+}
+
+/// The statements up to the end of the program, or of the body of the
+/// function starting at the given line.
+fn statements(
+    pieces: &mut impl Iterator<Item = (usize, Piece)>,
+    body: Option<usize>,
+) -> Result<Vec<(usize, Statement)>, BcError> {
+    let mut list = vec![];
+    loop {
+        let (line, text) = match (pieces.next(), body) {
+            (None, None) => return Ok(list),
+            (None, Some(line)) => return Err(BcError::Syntax(line, "missing '}'".into())),
+            (Some((_, Piece::Close)), Some(_)) => return Ok(list),
+            (Some((line, Piece::Close)), None) => {
+                return Err(BcError::Syntax(line, "unexpected '}'".into()))
+            }
+            (Some((line, Piece::Open)), _) => {
+                return Err(BcError::Syntax(line, "unexpected '{'".into()))
+            }
+            (Some((line, Piece::Text(text))), _) => (line, text),
+        };
+        let statement = match text.split_once(char::is_whitespace) {
+            Some(("define", header)) => {
+                let (name, params) = signature(header)
+                    .ok_or_else(|| BcError::Syntax(line, "invalid function definition".into()))?;
+                if pieces.next().map(|(_, p)| p) != Some(Piece::Open) {
+                    return Err(BcError::Syntax(line, "missing '{' after define".into()));
+                }
+                let body = statements(pieces, Some(line))?;
+                Statement::Define(Arc::new(Function { name, params, body }))
+            }
+            _ if text == "return" => Statement::Return("0".into()),
+            Some(("return", expr)) => Statement::Return(expr.to_owned()),
+            _ => match text.split_once('=') {
+                Some((name, expr)) if is_name(name.trim()) => {
+                    Statement::Assign(name.trim().to_owned(), expr.to_owned())
+                }
+                _ => Statement::Print(text),
+            },
+        };
+        list.push((line, statement));
+    }
+}
+
+/// The name and parameters of a function, from `name(a, b)`.
+fn signature(header: &str) -> Option<(String, Vec<String>)> {
+    let (name, params) = header.trim().strip_suffix(')')?.split_once('(')?;
+    let params: Vec<String> = match params.trim() {
+        "" => vec![],
+        params => params.split(',').map(|p| p.trim().to_owned()).collect(),
+    };
+    let valid = is_name(name.trim()) && params.iter().all(|p| is_name(p));
+    valid.then(|| (name.trim().to_owned(), params))
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the program, and return what it printed.
+    fn bc(program: &str) -> Result<String, BcError> {
+        let mut bc = Bc::default();
+        bc.run(program)?;
+        Ok(bc.take_output())
+    }
+
+    #[test]
+    fn variables_and_functions() -> Result<(), BcError> {
+        let program = "
+            # Hypotenuse of the right triangle with sides a and b.
+            define hyp(a, b) {
+                s = a^2 + b^2
+                return sqrt(s)
+            }
+            x = 3; hyp(x, 4)
+            define twice(n) { return 2 * n }
+            twice(hyp(6, 8)) + x
+            scale = 3; scale
+            1 / 3
+        ";
+        assert_eq!(bc(program)?, "5\n23\n3\n0.333\n");
+        // Functions without a return value return 0, and variables persist.
+        let mut bc = Bc::default();
+        bc.run("define nothing() { 1 }\ny = nothing() + 1")?;
+        bc.run("y * 10; pi()")?;
+        assert_eq!(bc.take_output(), "10\n3.141592653589\n");
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let syntax = |line, msg: &str| Err(BcError::Syntax(line, msg.into()));
+        assert_eq!(
+            bc("1\n\nx + 1"),
+            Err(BcError::Expr(
+                3,
+                ExprError::Syntax("unknown variable 'x'".into(), 1)
+            ))
+        );
+        assert_eq!(
+            bc("sqrt(1, 2)"),
+            Err(BcError::Expr(
+                1,
+                ExprError::Syntax("sqrt takes 1 argument".into(), 1)
+            ))
+        );
+        assert_eq!(bc("define f(x) {\nreturn x"), syntax(1, "missing '}'"));
+        assert_eq!(bc("return 1"), syntax(1, "return outside of a function"));
+        assert_eq!(
+            bc("define f(1) { }"),
+            syntax(1, "invalid function definition")
+        );
+        assert_eq!(
+            bc("scale = 0"),
+            syntax(1, "scale must be a positive integer")
+        );
+        // Endless recursion is cut short.
+        assert!(bc("define f(x) { return f(x) }\nf(1)").is_err());
+        // What was printed before an error is kept.
+        let mut bc = Bc::default();
+        assert!(bc.run("1; 1/0; 2").is_err());
+        assert_eq!(bc.take_output(), "1\n");
+    }
+}
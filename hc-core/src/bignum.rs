@@ -0,0 +1,128 @@
+//! The big integers holding the digits of the numbers in the inexact
+//! operations of `math`, which spend their time multiplying, dividing and
+//! taking square roots of them.
+//!
+//! They are those of num-bigint by default, as BigDecimal's, or GMP's with
+//! the `gmp` feature, much faster once the numbers have thousands of digits
+//! (`k`). Both round the same way, so that the results don't depend on it.
+use bigdecimal::num_bigint::BigInt;
+
+/// The arithmetic the inexact operations need from big integers.
+pub(crate) trait Integer: Sized {
+    fn from_bigint(n: &BigInt) -> Self;
+
+    fn into_bigint(self) -> BigInt;
+
+    /// 10 to the power `n`.
+    fn pow10(n: u32) -> Self;
+
+    fn mul(&self, other: &Self) -> Self;
+
+    /// The quotient rounded towards zero, as for primitive integers.
+    fn div(&self, other: &Self) -> Self;
+
+    /// The square root rounded down. The integer must not be negative.
+    fn isqrt(&self) -> Self;
+}
+
+/// The big integers used for the arithmetic.
+#[cfg(not(feature = "gmp"))]
+pub(crate) type Backend = BigInt;
+
+/// The big integers used for the arithmetic.
+#[cfg(feature = "gmp")]
+pub(crate) type Backend = rug::Integer;
+
+impl Integer for BigInt {
+    fn from_bigint(n: &BigInt) -> Self {
+        n.clone()
+    }
+
+    fn into_bigint(self) -> BigInt {
+        self
+    }
+
+    fn pow10(n: u32) -> Self {
+        BigInt::from(10).pow(n)
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn div(&self, other: &Self) -> Self {
+        self / other
+    }
+
+    fn isqrt(&self) -> Self {
+        BigInt::sqrt(self)
+    }
+}
+
+#[cfg(feature = "gmp")]
+impl Integer for rug::Integer {
+    fn from_bigint(n: &BigInt) -> Self {
+        use bigdecimal::num_bigint::Sign;
+        let (sign, digits) = n.to_u32_digits();
+        let magnitude = rug::Integer::from_digits(&digits, rug::integer::Order::Lsf);
+        match sign {
+            Sign::Minus => -magnitude,
+            _ => magnitude,
+        }
+    }
+
+    fn into_bigint(self) -> BigInt {
+        use bigdecimal::num_bigint::Sign;
+        let sign = match self.cmp0() {
+            std::cmp::Ordering::Less => Sign::Minus,
+            std::cmp::Ordering::Equal => Sign::NoSign,
+            std::cmp::Ordering::Greater => Sign::Plus,
+        };
+        BigInt::from_slice(sign, &self.to_digits::<u32>(rug::integer::Order::Lsf))
+    }
+
+    fn pow10(n: u32) -> Self {
+        rug::Integer::from(rug::Integer::u_pow_u(10, n))
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        rug::Integer::from(self * other)
+    }
+
+    fn div(&self, other: &Self) -> Self {
+        rug::Integer::from(self / other)
+    }
+
+    fn isqrt(&self) -> Self {
+        rug::Integer::from(self.sqrt_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn int(s: &str) -> Backend {
+        Backend::from_bigint(&BigInt::from_str(s).unwrap())
+    }
+
+    fn text(n: Backend) -> String {
+        n.into_bigint().to_string()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let big = "-123456789012345678901234567890123456789";
+        assert_eq!(text(int(big)), big);
+        assert_eq!(text(int("0")), "0");
+        assert_eq!(text(Backend::pow10(20)), "100000000000000000000");
+        assert_eq!(text(int("-12").mul(&int("34"))), "-408");
+        // Towards zero, whatever the signs.
+        assert_eq!(text(int("7").div(&int("2"))), "3");
+        assert_eq!(text(int("-7").div(&int("2"))), "-3");
+        assert_eq!(text(int("7").div(&int("-2"))), "-3");
+        assert_eq!(text(int("15").isqrt()), "3");
+        assert_eq!(text(int("16").isqrt()), "4");
+    }
+}
@@ -0,0 +1,454 @@
+//! Compatibility with [GNU dc](https://www.gnu.org/software/bc/manual/dc-1.05/html_mono/dc.html)
+//! programs, so that existing one-liners run unchanged: dc's command
+//! characters are translated into operations on the stack.
+//!
+//! The stack only holds numbers, so strings (`[...]`) are limited to what
+//! one-liners do with them: store them into a register as a macro, print
+//! them, or execute them with `x` and the conditionals such as `<a`.
+use bigdecimal::{num_bigint::BigInt, BigDecimal, ToPrimitive};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+use thiserror::Error;
+
+use crate::format::format_number;
+use crate::math;
+use crate::ops::builtin;
+use crate::stack::{apply_on_stack, InstantStack, Op, Stack, StackError};
+
+// Bound the work of a program, so that an endless loop can't freeze the
+// calculator.
+const MAX_COMMANDS: u64 = 10_000_000;
+// Macros calling themselves other than as their last command nest.
+const MAX_DEPTH: usize = 10_000;
+// The width of the printed numbers, which have no layout to fit.
+const WIDTH: u64 = 1000;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DcError {
+    #[error("{0}")]
+    Stack(#[from] StackError),
+    #[error("unsupported dc command '{0}'")]
+    Unsupported(char),
+    #[error("missing register after '{0}'")]
+    MissingRegister(char),
+    #[error("unterminated string")]
+    Unterminated,
+    #[error("strings can only be stored, printed or executed")]
+    String,
+    #[error("the input base must be between 2 and 16")]
+    InputBase,
+    #[error("the program runs too long")]
+    TooLong,
+    #[error("macros nested too deeply")]
+    TooDeep,
+}
+
+/// The state of dc programs run in turn: the macros stored in registers,
+/// the input base and what was printed.
+pub struct Dc {
+    macros: HashMap<char, Text>,
+    input_base: u32,
+    output: String,
+}
+
+/// A string, shared between the registers and the macros being executed.
+type Text = Rc<[char]>;
+
+/// A program or macro being executed.
+struct Frame {
+    chars: Text,
+    pos: usize,
+}
+
+impl Frame {
+    fn new(chars: Text) -> Self {
+        Frame { chars, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    /// The register named after the command.
+    fn register(&mut self, command: char) -> Result<char, DcError> {
+        self.next().ok_or(DcError::MissingRegister(command))
+    }
+
+    /// The rest of a string after its opening bracket, which may nest.
+    fn string(&mut self) -> Result<Text, DcError> {
+        let mut depth = 0;
+        let mut string = vec![];
+        loop {
+            match self.next().ok_or(DcError::Unterminated)? {
+                ']' if depth == 0 => return Ok(string.into()),
+                c => {
+                    depth += match c {
+                        '[' => 1,
+                        ']' => -1,
+                        _ => 0,
+                    };
+                    string.push(c);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Dc {
+    fn default() -> Self {
+        Dc {
+            macros: HashMap::new(),
+            input_base: 10,
+            output: String::new(),
+        }
+    }
+}
+
+impl Dc {
+    /// What the programs printed so far, which is then cleared.
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Run a program on the stack, as a single step of its undo history. It
+    /// stops at the first error, with the effects of the commands before it
+    /// kept.
+    pub fn run(&mut self, stack: &mut Stack, program: &str) -> Result<(), DcError> {
+        stack.change(|s| self.run_on(s, program))
+    }
+
+    fn run_on(&mut self, stack: &mut InstantStack, program: &str) -> Result<(), DcError> {
+        let mut frames = vec![Frame::new(program.chars().collect())];
+        // A string just pushed, waiting to be stored, printed or executed.
+        let mut string: Option<Text> = None;
+        let mut count = 0;
+        while let Some(frame) = frames.last_mut() {
+            let Some(c) = frame.next() else {
+                frames.pop();
+                continue;
+            };
+            count += 1;
+            if count > MAX_COMMANDS {
+                return Err(DcError::TooLong);
+            }
+            if string.is_some() && !matches!(c, 's' | 'x' | 'p' | 'n' | 'P' | '#') {
+                if !c.is_whitespace() {
+                    return Err(DcError::String);
+                }
+                continue;
+            }
+            // The macro to execute next, if any.
+            let mut execute = None;
+            match c {
+                c if c.is_whitespace() => {}
+                '#' => while frame.next().is_some_and(|c| c != '\n') {},
+                '0'..='9' | 'A'..='F' | '.' | '_' => {
+                    frame.pos -= 1;
+                    let n = self.number(frame, stack.precision);
+                    apply(stack, Op::Push(n))?;
+                }
+                '[' => string = Some(frame.string()?),
+                's' => {
+                    let register = frame.register(c)?;
+                    match string.take() {
+                        Some(string) => {
+                            self.macros.insert(register, string);
+                        }
+                        None => {
+                            apply(stack, Op::Save(register))?;
+                            self.macros.remove(&register);
+                        }
+                    }
+                }
+                'l' => {
+                    let register = frame.register(c)?;
+                    match self.macros.get(&register) {
+                        Some(m) => string = Some(m.clone()),
+                        None => apply(stack, Op::Load(register))?,
+                    }
+                }
+                // Executing a number leaves it as is.
+                'x' => execute = string.take(),
+                // Printing a string drops it, as it can't stay on the stack.
+                'p' => match string.take() {
+                    Some(string) => {
+                        self.output.extend(string.iter());
+                        self.output.push('\n');
+                    }
+                    None => {
+                        let top = self.top(stack)?;
+                        writeln!(self.output, "{top}").unwrap();
+                    }
+                },
+                'n' | 'P' => match string.take() {
+                    Some(string) => self.output.extend(string.iter()),
+                    None if c == 'n' => {
+                        let top = self.top(stack)?;
+                        self.output.push_str(&top);
+                        pop(stack)?;
+                    }
+                    None => return Err(DcError::Unsupported(c)),
+                },
+                'f' => {
+                    for v in stack.values() {
+                        let v = format(&v, stack.output_base);
+                        writeln!(self.output, "{v}").unwrap();
+                    }
+                }
+                'z' => {
+                    let depth = stack.stack.len();
+                    apply(stack, Op::Push((depth as u64).into()))?;
+                }
+                'K' | 'O' | 'I' => {
+                    let n = match c {
+                        'K' => stack.precision,
+                        'O' => stack.output_base.into(),
+                        _ => self.input_base.into(),
+                    };
+                    apply(stack, Op::Push(n.into()))?;
+                }
+                'i' => {
+                    let base = pop(stack)?;
+                    self.input_base = base
+                        .to_u32()
+                        .filter(|b| base.is_integer() && (2..=16).contains(b))
+                        .ok_or(DcError::InputBase)?;
+                }
+                '<' | '>' | '=' => {
+                    let register = frame.register(c)?;
+                    if compare(stack, c)? {
+                        execute = self.macros.get(&register).cloned();
+                    }
+                }
+                '!' => {
+                    // dc runs a shell command on anything else.
+                    let c = frame.next().filter(|c| "<>=".contains(*c));
+                    let c = c.ok_or(DcError::Unsupported('!'))?;
+                    let register = frame.register(c)?;
+                    if !compare(stack, c)? {
+                        execute = self.macros.get(&register).cloned();
+                    }
+                }
+                // From a macro, exit it and the one which called it, and
+                // otherwise stop the program.
+                'q' => match frames.len() {
+                    0..=2 => return Ok(()),
+                    n => frames.truncate(n - 2),
+                },
+                // Exit that many levels of macros, but never the program.
+                'Q' => {
+                    let levels = pop(stack)?;
+                    let levels = levels.to_usize().unwrap_or(usize::MAX);
+                    frames.truncate(frames.len().saturating_sub(levels).max(1));
+                }
+                c => match arithmetic(c) {
+                    Some(name) => apply(stack, builtin(name).expect("dc commands are built-in"))?,
+                    None => return Err(DcError::Unsupported(c)),
+                },
+            }
+            if let Some(m) = execute {
+                // A macro executed last replaces its caller, so that loops
+                // written as tail calls don't nest.
+                if frames.last().is_some_and(Frame::is_done) {
+                    frames.pop();
+                }
+                if frames.len() >= MAX_DEPTH {
+                    return Err(DcError::TooDeep);
+                }
+                frames.push(Frame::new(m));
+            }
+        }
+        Ok(())
+    }
+
+    /// A number in the input base, starting at the current position: digits,
+    /// which are uppercase beyond 9, at most one dot and a leading `_` for
+    /// negative numbers.
+    fn number(&self, frame: &mut Frame, precision: u64) -> BigDecimal {
+        let negative = frame.peek() == Some('_');
+        if negative {
+            frame.pos += 1;
+        }
+        let base = self.input_base;
+        let mut digits = BigInt::from(0);
+        let mut fraction_digits: Option<i64> = None;
+        while let Some(c) = frame.peek() {
+            match (c, fraction_digits) {
+                ('.', None) => fraction_digits = Some(0),
+                ('0'..='9' | 'A'..='F', _) => {
+                    digits = digits * base + c.to_digit(16).unwrap();
+                    fraction_digits = fraction_digits.map(|n| n + 1);
+                }
+                _ => break,
+            }
+            frame.pos += 1;
+        }
+        let fraction_digits = fraction_digits.unwrap_or(0);
+        let n = match base {
+            10 => BigDecimal::new(digits, fraction_digits),
+            _ => math::div(
+                &BigDecimal::from(digits),
+                &BigDecimal::from(BigInt::from(base).pow(fraction_digits as u32)),
+                precision,
+            ),
+        };
+        match negative {
+            true => -n,
+            false => n,
+        }
+    }
+
+    /// The top of the stack, as dc prints it.
+    fn top(&self, stack: &InstantStack) -> Result<String, DcError> {
+        let top = stack.values().next().ok_or(StackError::MissingValue(1))?;
+        Ok(format(&top, stack.output_base))
+    }
+}
+
+/// A number as dc prints it, with uppercase digits beyond 9.
+fn format(n: &BigDecimal, base: u32) -> String {
+    format_number(n, WIDTH, false, base)
+        .to_string()
+        .to_uppercase()
+}
+
+/// The built-in operation of an arithmetic or stack command.
+fn arithmetic(c: char) -> Option<&'static str> {
+    Some(match c {
+        '+' => "add",
+        '-' => "sub",
+        '*' => "mul",
+        '/' => "div",
+        '%' => "mod",
+        '^' => "pow",
+        'v' => "sqrt",
+        'd' => "dup",
+        'r' => "swap",
+        'c' => "clear",
+        'k' => "precision",
+        'o' => "base",
+        _ => return None,
+    })
+}
+
+/// Apply the operation, leaving the stack untouched if it fails: one
+/// consuming a pinned entry is only found out once it's done, on a copy.
+fn apply(stack: &mut InstantStack, op: Op) -> Result<(), StackError> {
+    if stack.pinned.is_empty() {
+        return apply_on_stack(stack, op);
+    }
+    let mut copy = stack.clone();
+    apply_on_stack(&mut copy, op)?;
+    *stack = copy;
+    Ok(())
+}
+
+/// Remove the top of the stack.
+fn pop(stack: &mut InstantStack) -> Result<BigDecimal, DcError> {
+    let top = stack
+        .stack
+        .front()
+        .cloned()
+        .ok_or(StackError::MissingValue(1))?;
+    apply(stack, builtin("drop").expect("drop is built-in"))?;
+    Ok(top)
+}
+
+/// Pop the top two values, and compare the first one to the second one as
+/// the command says.
+fn compare(stack: &mut InstantStack, command: char) -> Result<bool, DcError> {
+    let a = pop(stack)?;
+    let b = pop(stack)?;
+    Ok(match command {
+        '<' => a < b,
+        '>' => a > b,
+        _ => a == b,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the program on an empty stack, and return what it printed.
+    fn dc(program: &str) -> Result<String, DcError> {
+        let mut dc = Dc::default();
+        dc.run(&mut Stack::new(), program)?;
+        Ok(dc.take_output())
+    }
+
+    #[test]
+    fn arithmetic_and_printing() -> Result<(), DcError> {
+        assert_eq!(dc("2 3+p")?, "5\n");
+        assert_eq!(dc("_5 2*p 3 f")?, "-10\n3\n-10\n");
+        assert_eq!(dc("10 3/ 2kp")?, "3.33\n");
+        assert_eq!(dc("2 10^n 1 2 zp")?, "10242\n");
+        assert_eq!(dc("16o 255p 16i FFp # comment p")?, "FF\nFF\n");
+        assert_eq!(dc("[hello]p")?, "hello\n");
+        assert_eq!(dc("1 2 3 c z p")?, "0\n");
+        assert_eq!(dc("p"), Err(DcError::Stack(StackError::MissingValue(1))));
+        assert_eq!(dc("1 !a"), Err(DcError::Unsupported('!')));
+        assert_eq!(dc("[a] 1"), Err(DcError::String));
+        Ok(())
+    }
+
+    #[test]
+    fn registers_and_macros() -> Result<(), DcError> {
+        assert_eq!(dc("42 sa la la + p")?, "84\n");
+        // Count down from 3 with a loop.
+        assert_eq!(dc("[p 1- d 0<a]sa 3 lax")?, "3\n2\n1\n");
+        assert_eq!(dc("[[nested]p]x")?, "nested\n");
+        assert_eq!(dc("1 2 [[yes]p]sa >a [no]p")?, "yes\nno\n");
+        assert_eq!(dc("1 2 [[yes]p]sa !>a [no]p")?, "no\n");
+        assert_eq!(dc("[1p q 2p]x 3p")?, "1\n");
+        assert_eq!(dc("[lax]sa lax"), Err(DcError::TooLong));
+        assert_eq!(dc("[la x 1]sa la x"), Err(DcError::TooDeep));
+        Ok(())
+    }
+
+    #[test]
+    fn one_undo_step() -> Result<(), DcError> {
+        let mut stack = Stack::from(vec![BigDecimal::from(1)], None);
+        let mut dc = Dc::default();
+        dc.run(&mut stack, "[d1+ d5>a]sa lax")?;
+        assert_eq!(stack.values().count(), 5);
+        // What ran before an error is kept.
+        assert!(dc.run(&mut stack, "c 2 0/").is_err());
+        assert_eq!(
+            stack.snapshot(),
+            vec![BigDecimal::from(0), BigDecimal::from(2)]
+        );
+        stack.apply(Op::Undo)?;
+        stack.apply(Op::Undo)?;
+        assert_eq!(stack.snapshot(), vec![BigDecimal::from(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn pinned_entries_are_kept() -> Result<(), DcError> {
+        let mut stack = Stack::from(vec![BigDecimal::from(5)], None);
+        stack.apply(builtin("pin").unwrap())?;
+        let mut dc = Dc::default();
+        let pinned = Err(DcError::Stack(StackError::Pinned));
+        assert_eq!(dc.run(&mut stack, "c"), pinned);
+        // What ran before the error is kept, as usual.
+        assert_eq!(dc.run(&mut stack, "3 c"), pinned);
+        assert_eq!(
+            stack.snapshot(),
+            vec![BigDecimal::from(3), BigDecimal::from(5)]
+        );
+        assert!(stack.is_pinned(1));
+        Ok(())
+    }
+}
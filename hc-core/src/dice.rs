@@ -0,0 +1,98 @@
+//! Dice notation, e.g. `3d6+2` for the total of three six-sided dice plus 2.
+use bigdecimal::BigDecimal;
+
+use crate::random;
+
+/// The most dice rolled at once.
+pub const MAX_DICE: u64 = 1000;
+
+/// A number of dice with the same number of sides, and a modifier added to
+/// their total.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dice {
+    pub count: u64,
+    pub sides: u64,
+    pub modifier: i64,
+}
+
+/// The outcome of rolling dice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    /// Each die, in order.
+    pub rolls: Vec<u64>,
+    /// The sum of the dice and the modifier.
+    pub total: BigDecimal,
+}
+
+/// Parse dice notation: `[count]d<sides>[(+|-)<modifier>]`, with one die if
+/// the count is omitted.
+pub fn parse(text: &str) -> Option<Dice> {
+    let (count, rest) = text.trim().split_once('d')?;
+    let (sides, modifier) = match rest.find(['+', '-']) {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, ""),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !(count.is_empty() || digits(count)) || !digits(sides) {
+        return None;
+    }
+    let dice = Dice {
+        count: match count {
+            "" => 1,
+            _ => count.parse().ok()?,
+        },
+        sides: sides.parse().ok()?,
+        modifier: match modifier {
+            "" => 0,
+            _ if digits(&modifier[1..]) => modifier.parse().ok()?,
+            _ => return None,
+        },
+    };
+    (dice.count >= 1 && dice.count <= MAX_DICE && dice.sides >= 1).then_some(dice)
+}
+
+impl Dice {
+    pub fn roll(&self) -> Roll {
+        let rolls: Vec<u64> = (0..self.count)
+            .map(|_| 1 + random::below(self.sides))
+            .collect();
+        let sum: u128 = rolls.iter().map(|&r| r as u128).sum();
+        Roll {
+            rolls,
+            total: BigDecimal::from(sum as i128 + self.modifier as i128),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notation() {
+        let dice = |count, sides, modifier| {
+            Some(Dice {
+                count,
+                sides,
+                modifier,
+            })
+        };
+        assert_eq!(parse("3d6+2"), dice(3, 6, 2));
+        assert_eq!(parse("d20"), dice(1, 20, 0));
+        assert_eq!(parse(" 2d10-1 "), dice(2, 10, -1));
+        for invalid in [
+            "3d", "d", "3x6", "0d6", "3d0", "3d6+", "3d6+-1", "1001d6", "3", "2h",
+        ] {
+            assert_eq!(parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn roll() {
+        let roll = parse("4d6+2").unwrap().roll();
+        assert_eq!(roll.rolls.len(), 4);
+        assert!(roll.rolls.iter().all(|&r| (1..=6).contains(&r)));
+        let sum: u64 = roll.rolls.iter().sum();
+        assert_eq!(roll.total, BigDecimal::from(sum + 2));
+    }
+}
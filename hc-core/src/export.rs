@@ -0,0 +1,223 @@
+//! Tables of the stack, to paste into documents.
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The markup of a table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableFormat {
+    Markdown,
+    Latex,
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("unknown table format {0}, expected markdown or latex")]
+pub struct UnknownFormat(String);
+
+impl FromStr for TableFormat {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(TableFormat::Markdown),
+            "latex" | "tex" => Ok(TableFormat::Latex),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// The values of a stack as a table, in the order they were pushed, with
+/// optionally their index in the stack and a label for each of them.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    values: Vec<String>,
+    indices: bool,
+    labels: Vec<String>,
+}
+
+impl Table {
+    /// A table of the values, given top first as in the stack.
+    pub fn new(values: Vec<String>) -> Table {
+        Table {
+            values,
+            ..Default::default()
+        }
+    }
+
+    /// Add a column with the index of each value in the stack.
+    pub fn with_indices(mut self) -> Table {
+        self.indices = true;
+        self
+    }
+
+    /// Add a column labelling the values in the order of the table, i.e.
+    /// from the bottom of the stack. Values past the labels have none.
+    pub fn with_labels(mut self, labels: Vec<String>) -> Table {
+        self.labels = labels;
+        self
+    }
+
+    /// The header and the rows, as cells, along with whether each column
+    /// holds numbers to align on the right.
+    fn cells(&self) -> (Vec<bool>, Vec<Vec<String>>) {
+        let mut numeric = vec![];
+        let mut header = vec![];
+        if self.indices {
+            numeric.push(true);
+            header.push("#".to_string());
+        }
+        if !self.labels.is_empty() {
+            numeric.push(false);
+            header.push("Label".to_string());
+        }
+        numeric.push(true);
+        header.push("Value".to_string());
+        let mut rows = vec![header];
+        for (row, value) in self.values.iter().rev().enumerate() {
+            let mut cells = vec![];
+            if self.indices {
+                cells.push((self.values.len() - row).to_string());
+            }
+            if !self.labels.is_empty() {
+                cells.push(self.labels.get(row).cloned().unwrap_or_default());
+            }
+            cells.push(value.clone());
+            rows.push(cells);
+        }
+        (numeric, rows)
+    }
+
+    pub fn render(&self, format: TableFormat) -> String {
+        match format {
+            TableFormat::Markdown => self.markdown(),
+            TableFormat::Latex => self.latex(),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let (numeric, mut rows) = self.cells();
+        for cell in rows.iter_mut().flatten() {
+            *cell = cell.replace('|', "\\|");
+        }
+        let widths: Vec<usize> = (0..numeric.len())
+            // At least 3, for the delimiter row to be valid.
+            .map(|c| {
+                rows.iter()
+                    .map(|r| r[c].chars().count())
+                    .fold(3, usize::max)
+            })
+            .collect();
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let mut out = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            out += &line(
+                row.iter()
+                    .zip(&widths)
+                    .zip(&numeric)
+                    .map(|((cell, &width), &right)| match right {
+                        true => format!("{cell:>width$}"),
+                        false => format!("{cell:<width$}"),
+                    })
+                    .collect(),
+            );
+            if i == 0 {
+                out += &line(
+                    widths
+                        .iter()
+                        .zip(&numeric)
+                        .map(|(&width, &right)| match right {
+                            true => format!("{}:", "-".repeat(width - 1)),
+                            false => "-".repeat(width),
+                        })
+                        .collect(),
+                );
+            }
+        }
+        out
+    }
+
+    fn latex(&self) -> String {
+        let (numeric, rows) = self.cells();
+        let columns: String = numeric
+            .iter()
+            .map(|&right| if right { 'r' } else { 'l' })
+            .collect();
+        let mut out = format!("\\begin{{tabular}}{{{columns}}}\n");
+        for (i, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = row.iter().map(|cell| latex_escape(cell)).collect();
+            out += &format!("{} \\\\\n", cells.join(" & "));
+            if i == 0 {
+                out += "\\hline\n";
+            }
+        }
+        out += "\\end{tabular}\n";
+        out
+    }
+}
+
+/// The text with the characters special to LaTeX escaped.
+fn latex_escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '~' => out += "\\textasciitilde{}",
+            '^' => out += "\\textasciicircum{}",
+            '\\' => out += "\\textbackslash{}",
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Vec<String> {
+        vec!["1234.5".to_string(), "-2".to_string()]
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            Table::new(values()).render(TableFormat::Markdown),
+            "|  Value |\n| -----: |\n|     -2 |\n| 1234.5 |\n"
+        );
+        assert_eq!(
+            Table::new(values())
+                .with_indices()
+                .with_labels(vec!["a|b".to_string()])
+                .render(TableFormat::Markdown),
+            "|   # | Label |  Value |\n\
+             | --: | ----- | -----: |\n\
+             |   2 | a\\|b  |     -2 |\n\
+             |   1 |       | 1234.5 |\n"
+        );
+    }
+
+    #[test]
+    fn latex() {
+        assert_eq!(
+            Table::new(values())
+                .with_indices()
+                .with_labels(vec!["rent & food".to_string(), "100%".to_string()])
+                .render(TableFormat::Latex),
+            "\\begin{tabular}{rlr}\n\
+             \\# & Label & Value \\\\\n\
+             \\hline\n\
+             2 & rent \\& food & -2 \\\\\n\
+             1 & 100\\% & 1234.5 \\\\\n\
+             \\end{tabular}\n"
+        );
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!("md".parse(), Ok(TableFormat::Markdown));
+        assert_eq!("latex".parse(), Ok(TableFormat::Latex));
+        assert!("html".parse::<TableFormat>().is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Infix expressions, e.g. `(2+3)*4^2`.
+//!
+//! Expressions are compiled down to the very same operations as the ones
+//! applied interactively on the stack, so they share their semantics and
+//! error messages.
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::ops;
+use crate::stack::{Op, Stack, StackError};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ExprError {
+    #[error("{0} at position {1}")]
+    Syntax(String, usize),
+    #[error("{0}")]
+    StackError(#[from] StackError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(BigDecimal),
+    Operator(char),
+    Open,
+    Close,
+    Name(String),
+    Comma,
+}
+
+/// The variables and functions which expressions may refer to by name.
+pub(crate) trait Scope {
+    /// The value of the variable, if defined.
+    fn variable(&self, name: &str) -> Option<BigDecimal>;
+
+    /// The operation computing the function from its arguments, pushed in
+    /// order, or why it can't.
+    fn function(&self, name: &str, args: usize) -> Result<Op, String>;
+}
+
+/// Compile an expression into the sequence of stack operations computing it.
+pub fn compile(expr: &str) -> Result<Vec<Op>, ExprError> {
+    Parser::new(tokenize(expr, false)?, expr, None).compile()
+}
+
+/// Compile an expression which may refer to the variables and functions of
+/// the scope, the variables being replaced by their current value.
+pub(crate) fn compile_in(expr: &str, scope: &dyn Scope) -> Result<Vec<Op>, ExprError> {
+    Parser::new(tokenize(expr, true)?, expr, Some(scope)).compile()
+}
+
+/// Evaluate an expression on a scratch stack with the given precision.
+pub fn evaluate(expr: &str, precision: u64) -> Result<BigDecimal, ExprError> {
+    let mut stack = Stack::from(vec![], Some(precision));
+    for op in compile(expr)? {
+        stack.apply(op)?;
+    }
+    Ok(stack.top().cloned().unwrap_or_default())
+}
+
+/// Evaluate an expression as typed so far: without the trailing operators,
+/// and with the open parentheses closed, e.g. `(2+3)*` gives 5.
+pub fn evaluate_partial(expr: &str, precision: u64) -> Option<BigDecimal> {
+    if let Ok(v) = evaluate(expr, precision) {
+        return Some(v);
+    }
+    let expr = expr.trim_end_matches(|c: char| c.is_whitespace() || "+-*/^(".contains(c));
+    let open = expr
+        .matches('(')
+        .count()
+        .saturating_sub(expr.matches(')').count());
+    evaluate(&format!("{expr}{}", ")".repeat(open)), precision).ok()
+}
+
+/// Whether the input is meant as an expression rather than a number.
+pub fn is_expression(input: &str) -> bool {
+    input.contains(['(', ')', '+', '-', '*', '/', '%', '^'])
+}
+
+fn syntax(msg: &str, pos: usize) -> ExprError {
+    ExprError::Syntax(msg.to_owned(), pos)
+}
+
+// Split the expression into tokens, each with its 1-based position. Names
+// are only expected when there is a scope to look them up in.
+fn tokenize(expr: &str, names: bool) -> Result<Vec<(usize, Token)>, ExprError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let pos = i + 1;
+        match chars[i] {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push((pos, Token::Open)),
+            ')' => tokens.push((pos, Token::Close)),
+            ',' if names => tokens.push((pos, Token::Comma)),
+            c if names && (c.is_ascii_alphabetic() || c == '_') => {
+                let start = i;
+                while i + 1 < chars.len()
+                    && (chars[i + 1].is_ascii_alphanumeric() || chars[i + 1] == '_')
+                {
+                    i += 1;
+                }
+                tokens.push((pos, Token::Name(chars[start..=i].iter().collect())));
+            }
+            c @ ('+' | '-' | '*' | '/' | '%' | '^') => tokens.push((pos, Token::Operator(c))),
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i + 1 < chars.len() && matches!(chars[i + 1], '0'..='9' | '.' | '_') {
+                    i += 1;
+                }
+                // Optional exponent, e.g. 1e-3.
+                if i + 1 < chars.len() && (chars[i + 1] == 'e' || chars[i + 1] == 'E') {
+                    let mut j = i + 2;
+                    if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i + 1 < chars.len() && chars[i + 1].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let literal: String = chars[start..=i].iter().filter(|&&c| c != '_').collect();
+                let n = BigDecimal::from_str(&literal)
+                    .map_err(|_| syntax(&format!("invalid number '{literal}'"), pos))?;
+                tokens.push((pos, Token::Number(n)));
+            }
+            c => return Err(syntax(&format!("unexpected '{c}'"), pos)),
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// A Pratt parser, emitting the operations in postfix order as it goes.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize, // Position reported for errors at the end of the input.
+    ops: Vec<Op>,
+    scope: Option<&'a dyn Scope>,
+}
+
+// Binding powers (left, right) of the infix operators. `^` is right-associative.
+fn infix_power(op: char) -> (u8, u8) {
+    match op {
+        '+' | '-' => (1, 2),
+        '*' | '/' | '%' => (3, 4),
+        _ => (7, 6),
+    }
+}
+
+// Unary minus binds tighter than multiplication but looser than `^`, so
+// that -2^2 is -4.
+const PREFIX_POWER: u8 = 5;
+
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<(usize, Token)>, expr: &str, scope: Option<&'a dyn Scope>) -> Self {
+        Parser {
+            tokens,
+            next: 0,
+            end: expr.chars().count() + 1,
+            ops: vec![],
+            scope,
+        }
+    }
+
+    fn compile(mut self) -> Result<Vec<Op>, ExprError> {
+        self.expression(0)?;
+        if let Some((pos, _)) = self.tokens.get(self.next) {
+            return Err(syntax("unexpected token", *pos));
+        }
+        Ok(self.ops)
+    }
+
+    fn advance(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn expression(&mut self, min_power: u8) -> Result<(), ExprError> {
+        match self.advance() {
+            Some((_, Token::Number(n))) => self.ops.push(Op::Push(n)),
+            Some((_, Token::Operator('-'))) => {
+                self.expression(PREFIX_POWER)?;
+                self.ops.push(Op::Push(BigDecimal::from(-1)));
+                self.ops.push(operator('*'));
+            }
+            Some((pos, Token::Open)) => {
+                self.expression(0)?;
+                match self.advance() {
+                    Some((_, Token::Close)) => {}
+                    _ => return Err(syntax("unclosed parenthesis", pos)),
+                }
+            }
+            Some((pos, Token::Name(name))) => self.name(&name, pos)?,
+            Some((pos, _)) => return Err(syntax("unexpected token", pos)),
+            None => return Err(syntax("missing operand", self.end)),
+        }
+        while let Some((_, Token::Operator(op))) = self.tokens.get(self.next) {
+            let op = *op;
+            if op == '%' && !self.operand_at(self.next + 1) {
+                // A percentage of the operand just parsed, e.g. 200*15%,
+                // binding tighter than anything else.
+                self.next += 1;
+                self.ops.push(Op::Push(BigDecimal::from(100)));
+                self.ops.push(operator('/'));
+                continue;
+            }
+            let (left, right) = infix_power(op);
+            if left < min_power {
+                break;
+            }
+            self.next += 1;
+            self.expression(right)?;
+            self.ops.push(operator(op));
+        }
+        Ok(())
+    }
+
+    /// Whether an operand starts with the token at the index, telling a
+    /// modulo from a percentage.
+    fn operand_at(&self, index: usize) -> bool {
+        matches!(
+            self.tokens.get(index),
+            Some((_, Token::Number(_) | Token::Open | Token::Name(_)))
+        )
+    }
+
+    /// A variable, or a function call with its arguments in parentheses.
+    fn name(&mut self, name: &str, pos: usize) -> Result<(), ExprError> {
+        let scope = self.scope.expect("names are only tokenized with a scope");
+        if self.tokens.get(self.next).map(|(_, t)| t) != Some(&Token::Open) {
+            let value = scope
+                .variable(name)
+                .ok_or_else(|| syntax(&format!("unknown variable '{name}'"), pos))?;
+            self.ops.push(Op::Push(value));
+            return Ok(());
+        }
+        self.next += 1;
+        let mut args = 0;
+        if self.tokens.get(self.next).map(|(_, t)| t) == Some(&Token::Close) {
+            self.next += 1;
+        } else {
+            loop {
+                self.expression(0)?;
+                args += 1;
+                match self.advance() {
+                    Some((_, Token::Comma)) => {}
+                    Some((_, Token::Close)) => break,
+                    _ => return Err(syntax("unclosed parenthesis", pos + name.len())),
+                }
+            }
+        }
+        let op = scope
+            .function(name, args)
+            .map_err(|msg| syntax(&msg, pos))?;
+        self.ops.push(op);
+        Ok(())
+    }
+}
+
+/// The operators are the keys of the corresponding operations.
+fn operator(c: char) -> Op {
+    ops::by_key(c).expect("operators are built-in operations")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str) -> Result<String, ExprError> {
+        Ok(evaluate(expr, 12)?.normalized().to_string())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("(2+3)*4^2"), Ok("80".into()));
+        assert_eq!(eval("2+3*4"), Ok("14".into()));
+        assert_eq!(eval("2^3^2"), Ok("512".into()));
+        assert_eq!(eval("10-4-3"), Ok("3".into()));
+        assert_eq!(eval("-2^2"), Ok("-4".into()));
+        assert_eq!(eval("1.5e1 / 3"), Ok("5".into()));
+        assert_eq!(eval("1_000*2"), Ok("2000".into()));
+        assert_eq!(eval("10%4"), Ok("2".into()));
+        assert_eq!(eval("200*15%"), Ok("30".into()));
+        assert_eq!(eval("(50+50)%-4%"), Ok("0.96".into()));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            eval("(2+3"),
+            Err(ExprError::Syntax("unclosed parenthesis".into(), 1))
+        );
+        assert_eq!(
+            eval("2+"),
+            Err(ExprError::Syntax("missing operand".into(), 3))
+        );
+        assert_eq!(
+            eval("2x"),
+            Err(ExprError::Syntax("unexpected 'x'".into(), 2))
+        );
+        assert_eq!(
+            eval("2 3"),
+            Err(ExprError::Syntax("unexpected token".into(), 3))
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let partial = |expr| evaluate_partial(expr, 12).map(|v| v.to_string());
+        assert_eq!(partial("(2+3)*"), Some("5".into()));
+        assert_eq!(partial("2*(3+"), Some("6".into()));
+        assert_eq!(partial("3*2-"), Some("6".into()));
+        assert_eq!(partial("200*15%+"), Some("30.00".into()));
+        assert_eq!(partial("1/(2-2"), None);
+        assert_eq!(partial("("), None);
+    }
+
+    #[test]
+    fn stack_errors() {
+        assert_eq!(
+            eval("1/(2-2)"),
+            Err(ExprError::StackError(StackError::InvalidArgument(
+                "element 1 must be non-zero".into()
+            )))
+        );
+    }
+}
@@ -0,0 +1,723 @@
+//! Number formatting, independent of how the result is eventually displayed.
+use bigdecimal::{
+    num_bigint::{BigInt, BigUint},
+    BigDecimal, RoundingMode, Signed, ToPrimitive, Zero,
+};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::{cmp::min, fmt};
+use thiserror::Error;
+
+/// How a part of a formatted number should stand out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Plain,
+    /// Marks digits left out of the representation, e.g. `~12~`.
+    Elided,
+    /// The number can't be represented at all in the available width.
+    Overflow,
+}
+
+/// A part of a formatted number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Span {
+    pub fn raw<S: Into<String>>(text: S) -> Span {
+        Span {
+            text: text.into(),
+            style: Style::Plain,
+        }
+    }
+
+    pub fn elided<S: Into<String>>(text: S) -> Span {
+        Span {
+            text: text.into(),
+            style: Style::Elided,
+        }
+    }
+
+    pub fn overflow<S: Into<String>>(text: S) -> Span {
+        Span {
+            text: text.into(),
+            style: Style::Overflow,
+        }
+    }
+}
+
+/// A formatted number, as a sequence of spans.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Formatted(pub Vec<Span>);
+
+impl Formatted {
+    pub fn raw<S: Into<String>>(text: S) -> Formatted {
+        Formatted(vec![Span::raw(text)])
+    }
+}
+
+impl fmt::Display for Formatted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for span in &self.0 {
+            f.write_str(&span.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Number formatting. Takes into consideration the actual width of the display,
+/// the required base and whether the user wants additional spacing between groups
+/// of digits for readability.
+pub fn format_number(n: &BigDecimal, width: u64, separator: bool, base: u32) -> Formatted {
+    if base != 10 {
+        format_number_in_base(n, width, separator, base)
+    } else {
+        format_number_in_base_10(n, width, separator)
+    }
+}
+
+/// The negative integer as an unsigned word of the size in bits, i.e. its
+/// two's complement, e.g. 4294967295 (ffffffff) for -1 in 32 bits. None for
+/// other numbers, and negatives which don't fit the word.
+pub fn twos_complement(n: &BigDecimal, bits: u32) -> Option<BigDecimal> {
+    if !n.is_integer() || !n.is_negative() || bits == 0 {
+        return None;
+    }
+    let (int, _) = n.with_scale(0).into_bigint_and_exponent();
+    let word = BigInt::from(1) << bits;
+    match -&int <= &word >> 1 {
+        true => Some(BigDecimal::from(word + int)),
+        false => None,
+    }
+}
+
+/// Format a number of seconds as H:MM:SS, falling back to the regular
+/// representation when it doesn't fit.
+pub fn format_duration(n: &BigDecimal, width: u64, separator: bool) -> Formatted {
+    let seconds = n.abs();
+    let whole = seconds.with_scale(0);
+    let fraction = (&seconds - &whole).normalized();
+    let whole = whole.as_bigint_and_scale().0.into_owned();
+    let fraction = if fraction.is_zero() {
+        String::new()
+    } else {
+        // Drop the leading 0 of "0.xxx".
+        fraction.to_plain_string()[1..].to_string()
+    };
+    let repr = format!(
+        "{}{}:{:02}:{:02}{}",
+        if n < &BigDecimal::zero() { "-" } else { "" },
+        &whole / 3600,
+        &whole / 60 % 60,
+        &whole % 60,
+        fraction
+    );
+    if repr.len() as u64 <= width {
+        Formatted::raw(repr)
+    } else {
+        format_number(n, width, separator, 10)
+    }
+}
+
+/// Format an integer as an IP address: IPv4 up to 2^32 - 1, IPv6 above,
+/// falling back to the regular representation for other numbers or when it
+/// doesn't fit.
+pub fn format_ip(n: &BigDecimal, width: u64, separator: bool) -> Formatted {
+    let repr = match n.is_integer().then(|| n.to_u128()).flatten() {
+        Some(address) => match u32::try_from(address) {
+            Ok(v4) => Ipv4Addr::from(v4).to_string(),
+            Err(_) => Ipv6Addr::from(address).to_string(),
+        },
+        None => String::new(),
+    };
+    match !repr.is_empty() && repr.len() as u64 <= width {
+        true => Formatted::raw(repr),
+        false => format_number(n, width, separator, 10),
+    }
+}
+
+// The SI prefixes shown, by power of 1000 from 10^-30.
+const SI_PREFIXES: [&str; 21] = [
+    "q", "r", "y", "z", "a", "f", "p", "n", "µ", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+    "R", "Q",
+];
+
+/// Format a number with the SI prefix bringing it between 1 and 1000, e.g.
+/// 4.7k or 22µ, falling back to the regular representation out of their
+/// range.
+pub fn format_si(n: &BigDecimal, width: u64, separator: bool) -> Formatted {
+    if n.is_zero() {
+        return format_number(n, width, separator, 10);
+    }
+    let (digits, scale) = n.normalized().into_bigint_and_scale();
+    let magnitude = digits.magnitude().to_string().len() as i64 - 1 - scale;
+    let index = magnitude.div_euclid(3) + 10;
+    let Some(prefix) = usize::try_from(index).ok().and_then(|i| SI_PREFIXES.get(i)) else {
+        return format_number(n, width, separator, 10);
+    };
+    let mantissa = n * BigDecimal::new(1.into(), (index - 10) * 3);
+    let width = width.saturating_sub(prefix.chars().count() as u64);
+    let mut formatted = format_number(&mantissa, width, separator, 10);
+    formatted.0.push(Span::raw(*prefix));
+    formatted
+}
+
+/// Format in base 10: unlike other bases, actual digits after the decimal point are shown,
+/// truncated with `~` only when necessary.
+fn format_number_in_base_10(n: &BigDecimal, width: u64, separator: bool) -> Formatted {
+    let repr = n.normalized().to_plain_string();
+    let total = repr.len() as u64;
+    // Trivial case: the representation already fits the display.
+    if total <= width {
+        if !separator {
+            return Formatted::raw(repr);
+        }
+        let separated_repr = add_separators(&repr, 3);
+        // It's probably still better to remove the separators than to switch to
+        // extended representation if the size is a bit tight.
+        if separated_repr.len() as u64 <= width {
+            return Formatted::raw(separated_repr);
+        }
+        return Formatted::raw(repr);
+    }
+
+    let digits_after_dot = if let Some(idx) = repr.find('.') {
+        (total - idx as u64 - 1) as i64
+    } else {
+        0
+    };
+    // digits_to_dot: length of the string up to and including the '.', e.g. 9 for "12345678."
+    let digits_to_dot = total as i64 - digits_after_dot;
+    let digits_before_dot = digits_to_dot - if digits_after_dot > 0 { 1 } else { 0 };
+
+    // Simple case: the integer part fits; just truncate decimal digits and append '~'.
+    let extra_precision = width as i64 - digits_to_dot - 1;
+    if let Some(tiny) = format_tiny(&repr, width, extra_precision) {
+        return tiny;
+    }
+    if digits_after_dot > 0 && extra_precision >= 0 {
+        return Formatted(vec![
+            Span::raw(repr[..(digits_to_dot + extra_precision) as usize].to_string()),
+            Span::elided("~"),
+        ]);
+    }
+
+    // Complex case: show [sign][MSB]~<magnitude>~[LSB][.decimal?] so that both the
+    // order-of-magnitude and the fine detail are visible.
+    let sign_len = if n < &BigDecimal::zero() { 1i64 } else { 0i64 };
+    let mut budget = width as i64 - sign_len;
+    let mut parts = 2i64;
+    if digits_after_dot > 0 {
+        parts += 1; // LSB section straddles the decimal point
+        budget -= 1; // the '.' itself occupies one character
+    }
+    let pow = format!("~{}~", digits_before_dot - sign_len);
+    budget -= pow.len() as i64;
+
+    let Some((msb, lsb)) = split_budget(budget, parts) else {
+        return Formatted(vec![Span::overflow("~")]);
+    };
+    let lsb_str = if digits_after_dot > 0 {
+        &repr[digits_to_dot as usize - lsb - 1..min(digits_to_dot as usize + lsb, total as usize)]
+    } else {
+        &repr[total as usize - lsb..]
+    };
+    assemble_truncated(
+        repr[..msb + sign_len as usize].to_string(),
+        pow,
+        lsb_str,
+        vec![],
+    )
+}
+
+/// Format a number below 1 whose first significant digit would be truncated
+/// away with the count of its leading zeros, e.g. `0.0{12}437` for
+/// 0.000000000000437, as long as one significant digit fits.
+fn format_tiny(repr: &str, width: u64, extra_precision: i64) -> Option<Formatted> {
+    let (sign, unsigned) = match repr.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", repr),
+    };
+    let decimals = unsigned.strip_prefix("0.")?;
+    let significant = decimals.trim_start_matches('0');
+    let zeros = decimals.len() - significant.len();
+    // The simple truncation shows the first significant digit already.
+    if zeros < 2 || (zeros as i64) < extra_precision {
+        return None;
+    }
+    let prefix = format!("{sign}0.0");
+    let count = format!("{{{zeros}}}");
+    let budget = (width as usize).saturating_sub(prefix.len() + count.len());
+    let mut spans = vec![Span::raw(prefix), Span::elided(count)];
+    if significant.len() <= budget {
+        spans.push(Span::raw(significant));
+    } else if budget >= 2 {
+        spans.push(Span::raw(&significant[..budget - 1]));
+        spans.push(Span::elided("~"));
+    } else {
+        return None;
+    }
+    Some(Formatted(spans))
+}
+
+/// Format in an arbitrary base: the fractional part (if any) is always shown as `.~` because
+/// the base conversion only handles the integer portion.
+fn format_number_in_base(n: &BigDecimal, width: u64, separator: bool, base: u32) -> Formatted {
+    let repr = n.normalized().to_plain_string();
+    let (sign, unsigned_repr) = if let Some(s) = repr.strip_prefix('-') {
+        ("-", s)
+    } else {
+        ("", repr.as_str())
+    };
+    let (int_str, has_fraction) = if let Some(idx) = unsigned_repr.find('.') {
+        (&unsigned_repr[..idx], true)
+    } else {
+        (unsigned_repr, false)
+    };
+
+    // Parse the decimal integer string and re-encode in the target base.
+    let int_val: BigUint = int_str.parse().unwrap_or_default();
+    let base_repr = int_val.to_str_radix(base);
+
+    let group_size: usize = match base {
+        2 | 16 => 4,
+        _ => 3,
+    };
+
+    let sign_len = sign.len() as u64;
+    let base_len = base_repr.len() as u64;
+    let frac_extra: u64 = if has_fraction { 2 } else { 0 }; // for the trailing '.~'
+    let total = sign_len + base_len + frac_extra;
+
+    let trailing_tilde = || Span::elided(".~");
+
+    if total <= width {
+        let base_with_sign = format!("{}{}", sign, base_repr);
+        if separator {
+            let separated = add_separators(&base_with_sign, group_size);
+            if separated.len() as u64 + frac_extra <= width {
+                if has_fraction {
+                    return Formatted(vec![Span::raw(separated), trailing_tilde()]);
+                }
+                return Formatted::raw(separated);
+            }
+        }
+        if has_fraction {
+            return Formatted(vec![Span::raw(base_with_sign), trailing_tilde()]);
+        }
+        return Formatted::raw(base_with_sign);
+    }
+
+    // Truncation: [sign][MSB]~<digit_count>~[LSB][.~]
+    let pow = format!("~{}~", base_len);
+    let budget = width as i64 - sign_len as i64 - pow.len() as i64 - frac_extra as i64;
+
+    let Some((msb, lsb)) = split_budget(budget, 2) else {
+        return Formatted(vec![Span::overflow("~")]);
+    };
+    let suffix = if has_fraction {
+        vec![trailing_tilde()]
+    } else {
+        vec![]
+    };
+    assemble_truncated(
+        format!("{}{}", sign, &base_repr[..msb]),
+        pow,
+        &base_repr[base_repr.len() - lsb..],
+        suffix,
+    )
+}
+
+/// Split a display budget into MSB and LSB character counts.
+/// MSB receives any remainder so it shows the most significant information.
+/// Returns `None` when the budget is too small to fill all parts.
+fn split_budget(budget: i64, parts: i64) -> Option<(usize, usize)> {
+    if budget < parts {
+        return None;
+    }
+    Some((
+        (budget / parts + budget % parts) as usize,
+        (budget / parts) as usize,
+    ))
+}
+
+/// Assemble the `[sign+MSB][~magnitude~][LSB][suffix…]` spans used when a number is truncated.
+fn assemble_truncated(
+    sign_and_msb: String,
+    magnitude: String,
+    lsb: &str,
+    suffix: Vec<Span>,
+) -> Formatted {
+    let mut spans = vec![
+        Span::raw(sign_and_msb),
+        Span::elided(magnitude),
+        Span::raw(lsb.to_string()),
+    ];
+    spans.extend(suffix);
+    Formatted(spans)
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("the format has no digit placeholder (# or 0)")]
+    NoDigits,
+    #[error("the format has more than one number")]
+    TwoNumbers,
+    #[error("the format has an unclosed quote")]
+    UnclosedQuote,
+}
+
+/// A user-defined format in the style of spreadsheets, e.g. `#,##0.00 "€"`:
+/// `0` is a digit always shown, `#` one shown if significant, `,` groups
+/// the thousands and `.` is the decimal point. Anything else, or quoted, is
+/// shown as is around the number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    prefix: String,
+    suffix: String,
+    grouped: bool,
+    min_digits: usize,   // Of the integer part.
+    min_decimals: usize, // Shown even if zero.
+    max_decimals: usize, // The number is rounded to them.
+}
+
+impl Template {
+    pub fn parse(pattern: &str) -> Result<Template, TemplateError> {
+        let (mut prefix, mut number, mut suffix) = (String::new(), String::new(), String::new());
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            let literal = match c {
+                '"' => {
+                    let mut quoted = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break quoted,
+                            Some(c) => quoted.push(c),
+                            None => return Err(TemplateError::UnclosedQuote),
+                        }
+                    }
+                }
+                '#' | '0' | ',' | '.' if suffix.is_empty() => {
+                    number.push(c);
+                    continue;
+                }
+                '#' | '0' | ',' | '.' => return Err(TemplateError::TwoNumbers),
+                c => c.to_string(),
+            };
+            match number.is_empty() {
+                true => prefix.push_str(&literal),
+                false => suffix.push_str(&literal),
+            }
+        }
+        let (integer, decimals) = number.split_once('.').unwrap_or((&number, ""));
+        if !number.contains(['#', '0']) {
+            return Err(TemplateError::NoDigits);
+        }
+        if decimals.contains(['.', ',']) {
+            return Err(TemplateError::TwoNumbers);
+        }
+        Ok(Template {
+            prefix,
+            suffix,
+            grouped: integer.contains(','),
+            min_digits: integer.matches('0').count(),
+            min_decimals: decimals.matches('0').count(),
+            max_decimals: decimals.len(),
+        })
+    }
+
+    pub fn format(&self, n: &BigDecimal) -> String {
+        let rounded = n.with_scale_round(self.max_decimals as i64, RoundingMode::HalfUp);
+        let repr = rounded.abs().to_plain_string();
+        let (integer, decimals) = repr.split_once('.').unwrap_or((&repr, ""));
+        let integer = integer.trim_start_matches('0');
+        let integer = format!("{integer:0>0$}", self.min_digits);
+        let integer = match self.grouped {
+            true => add_separators(&integer, 3).replace(' ', ","),
+            false => integer,
+        };
+        let mut decimals = decimals.to_string();
+        while decimals.len() > self.min_decimals && decimals.ends_with('0') {
+            decimals.pop();
+        }
+        let sign = if rounded < BigDecimal::zero() {
+            "-"
+        } else {
+            ""
+        };
+        let dot = if decimals.is_empty() { "" } else { "." };
+        format!(
+            "{sign}{}{integer}{dot}{decimals}{}",
+            self.prefix, self.suffix
+        )
+    }
+}
+
+fn add_separators(repr: &str, group: usize) -> String {
+    let (sign, rest) = if let Some(number) = repr.strip_prefix('-') {
+        ("-", number)
+    } else {
+        ("", repr)
+    };
+    let (digits, rest) = if let Some(idx) = rest.find('.') {
+        (&rest[..idx], &rest[idx..])
+    } else {
+        (rest, "")
+    };
+    let mut result = String::new();
+    let len = digits.len();
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (len - i) % group == 0 {
+            result.push(' ');
+        }
+        result.push(ch);
+    }
+    format!("{}{}{}", sign, result, rest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_regular_number() {
+        let n: BigDecimal = "12345".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 10).to_string(), "12345");
+    }
+
+    #[test]
+    fn format_regular_number_with_separators() {
+        let n: BigDecimal = "12345".parse().unwrap();
+        assert_eq!(format_number(&n, 10, true, 10).to_string(), "12 345");
+    }
+
+    #[test]
+    fn negative_number_with_separators() {
+        let n: BigDecimal = "-12345".parse().unwrap();
+        assert_eq!(format_number(&n, 10, true, 10).to_string(), "-12 345");
+    }
+
+    #[test]
+    fn negative_number_with_separators_and_decimals() {
+        let n: BigDecimal = "-12345.6789".parse().unwrap();
+        assert_eq!(format_number(&n, 15, true, 10).to_string(), "-12 345.6789");
+    }
+
+    #[test]
+    fn drop_separators_under_pressure() {
+        let n: BigDecimal = "123456789".parse().unwrap();
+        assert_eq!(format_number(&n, 10, true, 10).to_string(), "123456789");
+    }
+
+    #[test]
+    fn format_long_number() {
+        let n: BigDecimal = "123456789098".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 10).to_string(), "123~12~098");
+        assert_eq!(format_number(&n, 11, false, 10).to_string(), "1234~12~098");
+    }
+
+    #[test]
+    fn format_long_negative_number() {
+        let n: BigDecimal = "-123456789098".parse().unwrap();
+        assert_eq!(format_number(&n, 8, false, 10).to_string(), "-12~12~8");
+        assert_eq!(format_number(&n, 7, false, 10).to_string(), "-1~12~8");
+        // We need at least 7 characters for this...
+        assert_eq!(format_number(&n, 6, false, 10).to_string(), "~");
+    }
+
+    #[test]
+    fn format_long_decimal_number() {
+        let n: BigDecimal = "12345678.34567".parse().unwrap();
+        assert_eq!(format_number(&n, 7, false, 10).to_string(), "1~8~8.3");
+    }
+
+    #[test]
+    fn format_dont_overflow_decimal() {
+        let n: BigDecimal = "12345678909876543.21".parse().unwrap();
+        assert_eq!(
+            format_number(&n, 18, false, 10).to_string(),
+            "12345~17~6543.21"
+        );
+    }
+
+    #[test]
+    fn format_long_negative_decimal_number() {
+        let n: BigDecimal = "-12345678.34567".parse().unwrap();
+        assert_eq!(format_number(&n, 8, false, 10).to_string(), "-1~8~8.3");
+    }
+
+    #[test]
+    fn truncate_decimal_part() {
+        let n: BigDecimal = "0.123456789".parse().unwrap();
+        assert_eq!(format_number(&n, 4, false, 10).to_string(), "0.1~");
+        let n: BigDecimal = "10.12345678".parse().unwrap();
+        assert_eq!(format_number(&n, 4, false, 10).to_string(), "10.~");
+    }
+
+    #[test]
+    fn templates() -> Result<(), TemplateError> {
+        let format = |pattern, n: &str| Ok(Template::parse(pattern)?.format(&n.parse().unwrap()));
+        assert_eq!(format(r#"#,##0.00 "€""#, "1234567.891")?, "1,234,567.89 €");
+        assert_eq!(format(r#"#,##0.00 "€""#, "-0.5")?, "-0.50 €");
+        assert_eq!(format("$#,##0.##", "1000")?, "$1,000");
+        assert_eq!(format("$#,##0.##", "0.125")?, "$0.13");
+        assert_eq!(format("#.#", "1.25")?, "1.3");
+        assert_eq!(format("000", "7")?, "007");
+        assert_eq!(format(r#"0 "kg""#, "2.5")?, "3 kg");
+        assert_eq!(Template::parse("EUR"), Err(TemplateError::NoDigits));
+        assert_eq!(Template::parse("0.0 / 0.0"), Err(TemplateError::TwoNumbers));
+        assert_eq!(
+            Template::parse(r#"0 "kg"#),
+            Err(TemplateError::UnclosedQuote)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn count_leading_zeros() {
+        let n: BigDecimal = "0.000000000000437".parse().unwrap();
+        assert_eq!(format_number(&n, 12, false, 10).to_string(), "0.0{12}437");
+        assert_eq!(format_number(&n, 9, false, 10).to_string(), "0.0{12}4~");
+        let n: BigDecimal = "-0.000000123456".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 10).to_string(), "-0.0{6}12~");
+        // Only when the first significant digit wouldn't show otherwise.
+        assert_eq!(format_number(&n, 12, false, 10).to_string(), "-0.00000012~");
+        // Or when nothing significant would fit anyway.
+        assert_eq!(format_number(&n, 7, false, 10).to_string(), "-0.000~");
+    }
+
+    #[test]
+    fn handle_negative_scale() {
+        let n: BigDecimal = "100000000000".parse().unwrap();
+        let n = n.normalized();
+        assert_eq!(format_number(&n, 10, false, 10).to_string(), "100~12~000");
+    }
+
+    #[test]
+    fn trim_unneeded_zeros() {
+        let n: BigDecimal = "0.000100000".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 10).to_string(), "0.0001");
+        let n: BigDecimal = "1e100".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 10).to_string(), "100~101~00");
+    }
+
+    #[test]
+    fn format_durations() {
+        let n: BigDecimal = "3723.5".parse().unwrap();
+        assert_eq!(format_duration(&n, 10, false).to_string(), "1:02:03.5");
+        let n: BigDecimal = "-59".parse().unwrap();
+        assert_eq!(format_duration(&n, 10, false).to_string(), "-0:00:59");
+        let n: BigDecimal = "1e5".parse().unwrap();
+        assert_eq!(format_duration(&n, 10, false).to_string(), "27:46:40");
+        // Too long, fall back to seconds.
+        let n: BigDecimal = "3723.25".parse().unwrap();
+        assert_eq!(format_duration(&n, 8, false).to_string(), "3723.25");
+    }
+
+    #[test]
+    fn format_hex() {
+        let n: BigDecimal = "255".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 16).to_string(), "ff");
+    }
+
+    #[test]
+    fn format_binary() {
+        let n: BigDecimal = "10".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 2).to_string(), "1010");
+    }
+
+    #[test]
+    fn format_octal() {
+        let n: BigDecimal = "8".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 8).to_string(), "10");
+    }
+
+    #[test]
+    fn format_base_truncates_fraction() {
+        let n: BigDecimal = "255.5".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 16).to_string(), "ff.~");
+    }
+
+    #[test]
+    fn format_base_with_separators() {
+        let n: BigDecimal = "65535".parse().unwrap();
+        // ffff with group-of-4 separator -> "ff ff" but only 4 digits so no separator
+        assert_eq!(format_number(&n, 10, true, 16).to_string(), "ffff");
+        let n: BigDecimal = "16711935".parse().unwrap(); // 0xff_00ff
+        assert_eq!(format_number(&n, 10, true, 16).to_string(), "ff 00ff");
+    }
+
+    #[test]
+    fn format_binary_with_separators() {
+        let n: BigDecimal = "255".parse().unwrap(); // 1111 1111
+        assert_eq!(format_number(&n, 12, true, 2).to_string(), "1111 1111");
+    }
+
+    #[test]
+    fn format_long_hex() {
+        // 256^4 = 2^32 = 0x1_0000_0000 (9 hex digits)
+        let n: BigDecimal = "4294967296".parse().unwrap();
+        assert_eq!(format_number(&n, 8, false, 16).to_string(), "100~9~00");
+    }
+
+    #[test]
+    fn format_long_hex_with_decimals() {
+        // 256^4 = 2^32 = 0x1_0000_0000 (9 hex digits)
+        let n: BigDecimal = "4294967296.333".parse().unwrap();
+        assert_eq!(format_number(&n, 8, false, 16).to_string(), "10~9~0.~");
+    }
+
+    #[test]
+    fn format_si_prefixes() {
+        let si = |n: &str| format_si(&n.parse().unwrap(), 10, false).to_string();
+        assert_eq!(si("4700"), "4.7k");
+        assert_eq!(si("0.000022"), "22µ");
+        assert_eq!(si("-3000000"), "-3M");
+        assert_eq!(si("999.5"), "999.5");
+        assert_eq!(si("0.001"), "1m");
+        assert_eq!(si("0"), "0");
+        // Beyond the prefixes.
+        let huge = "1e40".parse().unwrap();
+        assert_eq!(si("1e40"), format_number(&huge, 10, false, 10).to_string());
+        assert_eq!(si("1234567.891"), "1.234567~M");
+    }
+
+    #[test]
+    fn format_negative_hex() {
+        let n: BigDecimal = "-255".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 16).to_string(), "-ff");
+    }
+
+    #[test]
+    fn format_twos_complement() {
+        let word = |n: &str, bits| {
+            let n: BigDecimal = n.parse().unwrap();
+            twos_complement(&n, bits).map(|w| format_number(&w, 20, true, 16).to_string())
+        };
+        assert_eq!(word("-1", 32).as_deref(), Some("ffff ffff"));
+        assert_eq!(word("-128", 8).as_deref(), Some("80"));
+        assert_eq!(word("-2", 16).as_deref(), Some("fffe"));
+        // Out of the word, positive or not an integer.
+        assert_eq!(word("-129", 8), None);
+        assert_eq!(word("5", 8), None);
+        assert_eq!(word("-1.5", 8), None);
+    }
+
+    #[test]
+    fn format_ip_addresses() {
+        let ip = |n: &str, width| format_ip(&n.parse().unwrap(), width, false).to_string();
+        assert_eq!(ip("3232235786", 20), "192.168.1.10");
+        assert_eq!(ip("4294967296", 20), "::1:0:0");
+        // Not an address, or too wide.
+        assert_eq!(ip("1.5", 20), "1.5");
+        assert_eq!(ip("-1", 20), "-1");
+        assert_eq!(ip("3232235786", 10), "3232235786");
+    }
+
+    #[test]
+    fn format_decimal_hex() {
+        let n: BigDecimal = "255.333".parse().unwrap();
+        assert_eq!(format_number(&n, 10, false, 16).to_string(), "ff.~");
+    }
+}
@@ -0,0 +1,37 @@
+//! The engine of helix calc, independent of any user interface.
+//!
+//! - [`stack`] holds the numbers and applies [`stack::Op`]s to them, with
+//!   undo / redo.
+//! - [`ops`] defines the operations, built-in or not.
+//! - [`number`] parses the numbers typed by the user, and [`dice`] the dice
+//!   notation such as `3d6+2`, rolled with [`random`].
+//! - [`expr`] evaluates infix expressions such as `(1 + 2) * 3`, [`bc`] runs
+//!   bc-like scripts built on them, and [`dc`] runs GNU dc programs.
+//! - [`rpn::evaluate`] applies a whole RPN program such as `1 2 +` at once.
+//! - [`format`] renders numbers to fit a given width, and [`export`] the
+//!   stack as a table for documents.
+//! - [`plugin`] lets third parties add their own operations, and `script`
+//!   lets users write them in Rhai (with the `scripting` feature).
+//! - [`state`] persists the stacks between sessions.
+//!
+//! The crate has no dependency on the terminal, and builds for
+//! `wasm32-unknown-unknown` so that it can power a front-end in a browser,
+//! with its own [`state::Storage`].
+pub mod bc;
+mod bignum;
+pub mod dc;
+pub mod dice;
+pub mod export;
+pub mod expr;
+pub mod format;
+mod math;
+pub mod number;
+pub mod ops;
+pub mod plugin;
+pub mod random;
+pub mod rpn;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod stack;
+pub mod state;
+pub mod units;
@@ -0,0 +1,631 @@
+//! Inexact operations, computed to the precision of the stack.
+//!
+//! BigDecimal's own division and square root stop at a fixed number of
+//! significant digits, which is not enough for large precisions (`k`). These
+//! compute results correct to `precision` digits after the dot, with a few
+//! extra guard digits so that rounding for display doesn't suffer from the
+//! truncation of the last ones.
+//!
+//! The big integers holding their digits do the heavy lifting, see
+//! [`crate::bignum`].
+use bigdecimal::{
+    num_bigint::{BigInt, Sign},
+    BigDecimal, RoundingMode, ToPrimitive, Zero,
+};
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::bignum::{Backend, Integer};
+
+/// Extra digits computed beyond the precision.
+const GUARD_DIGITS: u64 = 10;
+
+/// The number of digits before the dot of `a`, at least 1.
+fn integer_digits(a: &BigDecimal) -> u64 {
+    (a.digits() as i64 - a.fractional_digit_count()).max(1) as u64
+}
+
+thread_local! {
+    // Set when the job computing on this thread is cancelled, see
+    // `watch_cancel`.
+    static CANCEL: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Make the iterative methods on this thread give up once the flag is set,
+/// e.g. by a job cancelled, whose result is discarded anyway. None stops
+/// watching a flag.
+pub(crate) fn watch_cancel(flag: Option<Arc<AtomicBool>>) {
+    CANCEL.with(|cancel| *cancel.borrow_mut() = flag);
+}
+
+/// Whether the computation on this thread was cancelled, in which case the
+/// iterative methods stop early with a meaningless result.
+pub(crate) fn cancelled() -> bool {
+    CANCEL.with(|cancel| {
+        cancel
+            .borrow()
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    })
+}
+
+/// Give up on iterative methods after this many steps.
+pub(crate) const MAX_ITERATIONS: usize = 200;
+
+/// Drop the digits of an intermediate result beyond the ones computed.
+pub(crate) fn round(a: BigDecimal, precision: u64) -> BigDecimal {
+    let scale = (precision + GUARD_DIGITS) as i64;
+    match a.fractional_digit_count() > scale {
+        true => a.with_scale(scale),
+        false => a,
+    }
+}
+
+/// The tolerance of iterative methods, leaving half the guard digits for
+/// rounding errors.
+pub(crate) fn epsilon(precision: u64) -> BigDecimal {
+    BigDecimal::new(1.into(), (precision + GUARD_DIGITS / 2) as i64)
+}
+
+/// The step of numerical derivatives: central differences are then exact to
+/// about the square of it.
+pub(crate) fn step(precision: u64) -> BigDecimal {
+    BigDecimal::new(1.into(), ((precision + GUARD_DIGITS) / 2) as i64)
+}
+
+/// Round the result of an iterative method to the digits it is accurate to,
+/// so that 0.0999…9 becomes 0.1.
+pub(crate) fn settle(a: BigDecimal, precision: u64) -> BigDecimal {
+    let scale = (precision + GUARD_DIGITS / 2) as i64;
+    trim(a.with_scale_round(scale, RoundingMode::HalfEven))
+}
+
+/// Drop the trailing zeros of a result, without going into negative scales
+/// (100 rather than 1E+2).
+pub(crate) fn trim(a: BigDecimal) -> BigDecimal {
+    let a = a.normalized();
+    match a.fractional_digit_count() < 0 {
+        true => a.with_scale(0),
+        false => a,
+    }
+}
+
+/// `a * b`, exactly.
+fn product(a: &BigDecimal, b: &BigDecimal) -> BigDecimal {
+    let (an, ascale) = a.as_bigint_and_scale();
+    let (bn, bscale) = b.as_bigint_and_scale();
+    let digits = Backend::from_bigint(&an).mul(&Backend::from_bigint(&bn));
+    BigDecimal::new(digits.into_bigint(), ascale + bscale)
+}
+
+/// `a * b`, without the digits beyond the ones computed.
+pub(crate) fn mul(a: &BigDecimal, b: &BigDecimal, precision: u64) -> BigDecimal {
+    round(product(a, b), precision)
+}
+
+/// The most digits `div` and `sqrt` shift the digits of their operand by,
+/// about 4 billion: more would take gigabytes.
+const MAX_SHIFT: i128 = u32::MAX as i128;
+
+/// The number of digits before the dot of `a`, negative for the zeros after
+/// it, e.g. -2 for 0.001.
+fn exponent(a: &BigDecimal) -> i128 {
+    a.digits() as i128 - a.fractional_digit_count() as i128
+}
+
+/// The power of ten the digits of `a` are multiplied by for `a / b`.
+fn div_shift(a: &BigDecimal, b: &BigDecimal, precision: u64) -> i128 {
+    (precision + GUARD_DIGITS) as i128 + b.fractional_digit_count() as i128
+        - a.fractional_digit_count() as i128
+}
+
+/// The power of ten the digits of `a` are multiplied by for `√a`.
+fn sqrt_shift(a: &BigDecimal, precision: u64) -> i128 {
+    2 * (precision + GUARD_DIGITS) as i128 - a.fractional_digit_count() as i128
+}
+
+/// The number of bits of the integer part of `a / b` roughly, and whether
+/// it can be computed at all. `b` must not be zero.
+pub(crate) fn div_bits(a: &BigDecimal, b: &BigDecimal, precision: u64) -> (f64, bool) {
+    let bits = (exponent(a) - exponent(b)) as f64 * std::f64::consts::LOG2_10;
+    (bits, a.is_zero() || div_shift(a, b, precision) <= MAX_SHIFT)
+}
+
+/// The number of bits of the integer part of `√a` roughly, and whether it
+/// can be computed at all.
+pub(crate) fn sqrt_bits(a: &BigDecimal, precision: u64) -> (f64, bool) {
+    let bits = exponent(a) as f64 / 2.0 * std::f64::consts::LOG2_10;
+    (bits, a.is_zero() || sqrt_shift(a, precision) <= MAX_SHIFT)
+}
+
+/// `n * 10^shift / divisor`, rounded towards zero, computing nothing once
+/// the powers of ten are beyond `n`. The shift must be at most
+/// [`MAX_SHIFT`], see `div_bits` and `sqrt_bits`.
+fn shifted(n: &BigInt, shift: i128, divisor: &Backend) -> Backend {
+    let power = |shift: i128| {
+        let shift = u32::try_from(shift).expect("the operations check the size of the result");
+        Backend::pow10(shift)
+    };
+    match shift >= 0 {
+        _ if n.is_zero() => Backend::from_bigint(n),
+        true => Backend::from_bigint(n).mul(&power(shift)).div(divisor),
+        // 10^-shift is then over n, so nothing is left of it.
+        false if -shift > n.bits() as i128 => Backend::from_bigint(&BigInt::zero()),
+        false => Backend::from_bigint(n).div(&divisor.mul(&power(-shift))),
+    }
+}
+
+/// `a / b` to `precision` digits after the dot. `b` must not be zero.
+pub(crate) fn div(a: &BigDecimal, b: &BigDecimal, precision: u64) -> BigDecimal {
+    let scale = (precision + GUARD_DIGITS) as i64;
+    let (an, _) = a.as_bigint_and_scale();
+    let (bn, _) = b.as_bigint_and_scale();
+    // a / b = an / bn * 10^(bscale - ascale), so the digits of the result at
+    // the wanted scale are an * 10^shift / bn.
+    let q = shifted(&an, div_shift(a, b, precision), &Backend::from_bigint(&bn));
+    trim(BigDecimal::new(q.into_bigint(), scale))
+}
+
+/// The square root of `a` to `precision` digits after the dot, if `a` is
+/// positive.
+pub(crate) fn sqrt(a: &BigDecimal, precision: u64) -> Option<BigDecimal> {
+    if a.sign() == Sign::Minus {
+        return None;
+    }
+    let scale = (precision + GUARD_DIGITS) as i64;
+    let (an, _) = a.as_bigint_and_scale();
+    // √a = √(an * 10^(2 * scale - ascale)) / 10^scale, so the digits of the
+    // result at the wanted scale are the integer square root of the former.
+    let one = Backend::from_bigint(&BigInt::from(1));
+    let n = shifted(&an, sqrt_shift(a, precision), &one);
+    Some(trim(BigDecimal::new(n.isqrt().into_bigint(), scale)))
+}
+
+/// The number of bits of the integer part of `a^n`, roughly.
+pub(crate) fn pow_bits(a: &BigDecimal, n: i64) -> f64 {
+    a.to_f64()
+        .map_or(f64::INFINITY, |a| (a.abs().log2() * n as f64).abs())
+}
+
+/// `a` to the power `n`, to `precision` digits after the dot. `a` must not
+/// be zero if `n` is negative.
+pub(crate) fn powi(a: &BigDecimal, n: i64, precision: u64) -> BigDecimal {
+    // Keep enough significant digits for both the result and its inverse.
+    let magnitude = (pow_bits(a, n) / std::f64::consts::LOG2_10).ceil() as u64;
+    let digits = magnitude + 1 + precision + GUARD_DIGITS;
+    let mut result = BigDecimal::from(1);
+    let mut square = a.clone();
+    let mut m = n.unsigned_abs();
+    while m > 0 && !cancelled() {
+        if m & 1 == 1 {
+            result = product(&result, &square).with_prec(digits);
+        }
+        square = product(&square, &square).with_prec(digits);
+        m >>= 1;
+    }
+    match n < 0 {
+        true => div(&BigDecimal::from(1), &result, precision),
+        false => trim(result.with_scale((precision + GUARD_DIGITS) as i64)),
+    }
+}
+
+/// The value and derivative at `x` of the polynomial with the given
+/// coefficients, highest degree first.
+pub(crate) fn horner<'a, I>(
+    coefficients: I,
+    x: &BigDecimal,
+    precision: u64,
+) -> (BigDecimal, BigDecimal)
+where
+    I: IntoIterator<Item = &'a BigDecimal>,
+{
+    let mut value = BigDecimal::zero();
+    let mut derivative = BigDecimal::zero();
+    for c in coefficients {
+        derivative = round(derivative * x + &value, precision);
+        value = round(value * x + c, precision);
+    }
+    (value, derivative)
+}
+
+/// A root of `f` found with Newton's method starting from `x`, where `f`
+/// returns both its value and its derivative. None if the derivative
+/// vanishes or the method doesn't converge.
+pub(crate) fn newton<F>(f: F, mut x: BigDecimal, precision: u64) -> Option<BigDecimal>
+where
+    F: Fn(&BigDecimal) -> (BigDecimal, BigDecimal),
+{
+    let epsilon = epsilon(precision);
+    for _ in 0..MAX_ITERATIONS {
+        if cancelled() {
+            return None;
+        }
+        let (y, dy) = f(&x);
+        if dy.is_zero() {
+            return None;
+        }
+        let step = div(&y, &dy, precision);
+        x = round(x - &step, precision);
+        if step.abs() < epsilon {
+            return Some(settle(x, precision));
+        }
+    }
+    None
+}
+
+/// The distinct real roots, in increasing order, of the polynomial with the
+/// given coefficients, highest degree first and non-zero.
+pub(crate) fn real_roots(coefficients: &[BigDecimal], precision: u64) -> Vec<BigDecimal> {
+    let degree = coefficients.len() - 1;
+    if degree == 0 {
+        return vec![];
+    }
+    if degree == 1 {
+        return vec![-div(&coefficients[1], &coefficients[0], precision)];
+    }
+    // Between two consecutive roots of the derivative the polynomial is
+    // monotonic, so that it has at most one root there, and all the roots
+    // are within the Cauchy bound.
+    let derivative: Vec<BigDecimal> = coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * BigDecimal::from((degree - i) as u64))
+        .collect();
+    let bound = coefficients[1..]
+        .iter()
+        .map(|c| div(&c.abs(), &coefficients[0].abs(), precision))
+        .max()
+        .unwrap()
+        + 1;
+    let mut points = vec![-&bound];
+    points.extend(real_roots(&derivative, precision));
+    points.push(bound);
+
+    let epsilon = epsilon(precision);
+    let value = |x: &BigDecimal| horner(coefficients, x, precision).0;
+    let mut roots: Vec<BigDecimal> = vec![];
+    let mut add = |root: BigDecimal| {
+        if roots.last().is_none_or(|r| (&root - r).abs() > epsilon) {
+            roots.push(root);
+        }
+    };
+    for pair in points.windows(2) {
+        let (mut lo, mut hi) = (pair[0].clone(), pair[1].clone());
+        let (vlo, vhi) = (value(&lo), value(&hi));
+        // Roots of the derivative can be (multiple) roots too.
+        if vlo.abs() <= epsilon {
+            add(settle(lo.clone(), precision));
+        }
+        if vlo.abs() <= epsilon || vhi.abs() <= epsilon || vlo.sign() == vhi.sign() {
+            continue;
+        }
+        // Bisect, for robustness, down to the last digit computed.
+        let half = BigDecimal::new(5.into(), 1);
+        let resolution = BigDecimal::new(1.into(), (precision + GUARD_DIGITS) as i64);
+        while &hi - &lo > resolution && !cancelled() {
+            let mid = round((&lo + &hi) * &half, precision);
+            match value(&mid).sign() == vlo.sign() {
+                true => lo = mid,
+                false => hi = mid,
+            }
+        }
+        add(settle(lo, precision));
+    }
+    if let Some(last) = points.last() {
+        if value(last).abs() <= epsilon {
+            add(settle(last.clone(), precision));
+        }
+    }
+    roots
+}
+
+/// π to `precision` digits after the dot, by Machin's formula
+/// π = 16·atan(1/5) - 4·atan(1/239).
+pub(crate) fn pi(precision: u64) -> BigDecimal {
+    // Fixed point, with a few more digits for the errors of the terms.
+    let scale = precision + GUARD_DIGITS + 5;
+    let unity = BigInt::from(10).pow(scale as u32);
+    let atan_inverse = |n: u64| {
+        let mut power = &unity / n;
+        let mut sum = power.clone();
+        let mut k = 1u64;
+        while !power.is_zero() && !cancelled() {
+            power /= n * n;
+            let term = &power / (2 * k + 1);
+            match k % 2 {
+                0 => sum += term,
+                _ => sum -= term,
+            }
+            k += 1;
+        }
+        sum
+    };
+    let pi = 16 * atan_inverse(5) - 4 * atan_inverse(239);
+    round(BigDecimal::new(pi, scale as i64), precision)
+}
+
+/// The sine and cosine of `x` radians, to `precision` digits after the dot.
+pub(crate) fn sin_cos(x: &BigDecimal, precision: u64) -> (BigDecimal, BigDecimal) {
+    // Take off the nearest multiple of 2π, with as many more digits of it
+    // as the multiple has.
+    let inner = precision + integer_digits(x);
+    let two_pi = pi(inner) * 2;
+    let turns = div(x, &two_pi, 0).with_scale_round(0, RoundingMode::HalfEven);
+    let x = round(x - turns * two_pi, precision);
+    // Taylor series, the terms being xⁿ / n!.
+    let (mut sin, mut cos) = (BigDecimal::zero(), BigDecimal::zero());
+    let mut term = BigDecimal::from(1);
+    let mut n = 0u64;
+    while !term.is_zero() && !cancelled() {
+        match n % 4 {
+            0 => cos += &term,
+            1 => sin += &term,
+            2 => cos -= &term,
+            _ => sin -= &term,
+        }
+        n += 1;
+        term = div(&mul(&term, &x, precision), &BigDecimal::from(n), precision);
+    }
+    (sin, cos)
+}
+
+/// The arctangent of `x`, in radians between -π/2 and π/2, to `precision`
+/// digits after the dot.
+pub(crate) fn atan(x: &BigDecimal, precision: u64) -> BigDecimal {
+    let one = BigDecimal::from(1);
+    if x.abs() > one {
+        // atan(x) = ±π/2 - atan(1/x)
+        let half_pi = pi(precision) / BigDecimal::from(2);
+        let rest = atan(&div(&one, x, precision), precision);
+        return match x.sign() {
+            Sign::Minus => -half_pi - rest,
+            _ => half_pi - rest,
+        };
+    }
+    // atan(x) = 2·atan(x / (1 + √(1 + x²))), until the series converges
+    // quickly.
+    let mut x = x.clone();
+    let mut factor = 1u64;
+    let small = BigDecimal::new(1.into(), 1);
+    while x.abs() > small && !cancelled() {
+        let root = sqrt(&(&one + &x * &x), precision).unwrap();
+        x = div(&x, &(&one + root), precision);
+        factor *= 2;
+    }
+    // x - x³/3 + x⁵/5 - …
+    let square = mul(&x, &x, precision);
+    let mut power = x.clone();
+    let mut sum = BigDecimal::zero();
+    let mut k = 0u64;
+    while !power.is_zero() && !cancelled() {
+        let term = div(&power, &BigDecimal::from(2 * k + 1), precision);
+        match k % 2 {
+            0 => sum += term,
+            _ => sum -= term,
+        }
+        power = mul(&power, &square, precision);
+        k += 1;
+    }
+    sum * BigDecimal::from(factor)
+}
+
+/// The natural logarithm of `a`, to `precision` digits after the dot. `a`
+/// must be positive.
+pub(crate) fn ln(a: &BigDecimal, precision: u64) -> BigDecimal {
+    // a = m·10^e with 1 ≤ m < 10, so ln(a) = ln(m) + e·ln(10), with as many
+    // more digits of ln(10) as e has.
+    let (digits, scale) = a.as_bigint_and_scale();
+    let e = a.digits() as i64 - 1 - scale;
+    let m = BigDecimal::new(digits.into_owned(), a.digits() as i64 - 1);
+    let inner = precision + e.unsigned_abs().to_string().len() as u64;
+    let ln = ln_near_one(m, inner) + BigDecimal::from(e) * ln_near_one(10.into(), inner);
+    round(ln, precision)
+}
+
+/// 10 to the power `y`, to `precision` digits after the dot.
+pub(crate) fn pow10(y: &BigDecimal, precision: u64) -> BigDecimal {
+    // 10^y = 10^n · e^(f·ln(10)), with n the integer part of y rounded down
+    // and 0 ≤ f < 1: a shift of the digits, then a small exponential.
+    let n = y.with_scale_round(0, RoundingMode::Floor);
+    let f = y - &n;
+    let n = n.to_i64().unwrap();
+    let inner = precision + n.max(0) as u64;
+    let power = exp(&(f * ln_near_one(10.into(), inner)), inner);
+    let (digits, scale) = power.into_bigint_and_scale();
+    round(trim(BigDecimal::new(digits, scale - n)), precision)
+}
+
+/// e to the power `x`, for `x` between 0 and about 2.3.
+fn exp(x: &BigDecimal, precision: u64) -> BigDecimal {
+    // e^x = (e^(x/2^k))^(2^k), with x/2^k small, each squaring doubling the
+    // error.
+    let precision = precision + 3;
+    let small = BigDecimal::new(1.into(), 2);
+    let mut x = x.clone();
+    let mut squarings = 0;
+    while x.abs() > small {
+        x = x / BigDecimal::from(2);
+        squarings += 1;
+    }
+    // 1 + x + x²/2! + …
+    let mut sum = BigDecimal::zero();
+    let mut term = BigDecimal::from(1);
+    let mut n = 0u64;
+    while !term.is_zero() && !cancelled() {
+        sum += &term;
+        n += 1;
+        term = div(&mul(&term, &x, precision), &BigDecimal::from(n), precision);
+    }
+    for _ in 0..squarings {
+        if cancelled() {
+            break;
+        }
+        sum = mul(&sum, &sum, precision);
+    }
+    sum
+}
+
+/// The natural logarithm of `x`, positive, by taking square roots until it
+/// is close to 1, where the series converges quickly.
+fn ln_near_one(mut x: BigDecimal, precision: u64) -> BigDecimal {
+    // Each square root doubles the error of the result.
+    let precision = precision + 3;
+    let one = BigDecimal::from(1);
+    let near = BigDecimal::new(1.into(), 2);
+    let mut factor = 2u64;
+    while (&x - &one).abs() > near && !cancelled() {
+        x = sqrt(&x, precision).unwrap();
+        factor *= 2;
+    }
+    // ln(x) = 2·atanh(z) = 2·(z + z³/3 + z⁵/5 + …), with z = (x - 1)/(x + 1).
+    let z = div(&(&x - &one), &(&x + &one), precision);
+    let square = mul(&z, &z, precision);
+    let mut power = z;
+    let mut sum = BigDecimal::zero();
+    let mut k = 0u64;
+    while !power.is_zero() && !cancelled() {
+        sum += div(&power, &BigDecimal::from(2 * k + 1), precision);
+        power = mul(&power, &square, precision);
+        k += 1;
+    }
+    sum * BigDecimal::from(factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(div(&dec("6"), &dec("2"), 5), dec("3"));
+        assert_eq!(div(&dec("1"), &dec("0.01"), 5).to_string(), "100");
+        assert_eq!(div(&dec("-1"), &dec("8"), 5), dec("-0.125"));
+        let third = div(&dec("1"), &dec("3"), 200);
+        assert_eq!(third.fractional_digit_count(), 210);
+        assert!(third.to_string().ends_with("3333"));
+    }
+
+    #[test]
+    fn integer_power() {
+        assert_eq!(powi(&dec("2"), 10, 5), dec("1024"));
+        assert_eq!(powi(&dec("2"), -2, 5), dec("0.25"));
+        assert_eq!(powi(&dec("7"), 0, 5), dec("1"));
+        assert_eq!(powi(&dec("1.1"), 2, 5), dec("1.21"));
+        // Tiny intermediate values keep their significant digits.
+        let error = powi(&dec("0.5"), -100, 0) - powi(&dec("2"), 100, 0);
+        assert!(error.abs() < dec("1e-5"));
+    }
+
+    #[test]
+    fn polynomials() {
+        // x² - 2, at 3.
+        let p = [dec("1"), dec("0"), dec("-2")];
+        assert_eq!(horner(&p, &dec("3"), 5), (dec("7"), dec("6")));
+        let root = newton(|x| horner(&p, x, 50), dec("1"), 50).unwrap();
+        assert_eq!(
+            root.with_scale(50),
+            sqrt(&dec("2"), 50).unwrap().with_scale(50)
+        );
+        // x² + 1 has no real root.
+        let p = [dec("1"), dec("0"), dec("1")];
+        assert_eq!(newton(|x| horner(&p, x, 5), dec("1"), 5), None);
+    }
+
+    #[test]
+    fn roots() {
+        let roots = |p: &[&str]| {
+            let p: Vec<BigDecimal> = p.iter().map(|c| dec(c)).collect();
+            real_roots(&p, 10)
+        };
+        // (x - 1)(x - 2)(x + 3)
+        assert_eq!(
+            roots(&["1", "0", "-7", "6"]),
+            vec![dec("-3"), dec("1"), dec("2")]
+        );
+        // (x - 1)², x² + 1 and x³ - 2.
+        assert_eq!(roots(&["1", "-2", "1"]), vec![dec("1")]);
+        assert!(roots(&["1", "0", "1"]).is_empty());
+        assert_eq!(roots(&["1", "0", "0", "-2"]).len(), 1);
+        assert_eq!(roots(&["2", "-1"]), vec![dec("0.5")]);
+    }
+
+    #[test]
+    fn trigonometry() {
+        let pi = "3.14159265358979323846264338327950288419716939937510";
+        assert_eq!(self::pi(50).with_scale(50), dec(pi));
+        let (sin, cos) = sin_cos(&dec("1"), 20);
+        assert_eq!(sin.with_scale(20), dec("0.84147098480789650665"));
+        assert_eq!(cos.with_scale(20), dec("0.54030230586813971740"));
+        // Far from 0, sin(2πk + 1) is still sin(1).
+        let far = self::pi(40) * BigDecimal::from(2_000_000) + 1;
+        assert_eq!(sin_cos(&far, 20).0.with_scale(20), sin.with_scale(20));
+        assert_eq!(sin_cos(&dec("0"), 20), (dec("0"), dec("1")));
+
+        let quarter = self::pi(30) / BigDecimal::from(4);
+        assert_eq!(atan(&dec("1"), 30).with_scale(30), quarter.with_scale(30));
+        assert_eq!(
+            atan(&dec("-3"), 20).with_scale(20),
+            dec("-1.24904577239825442582")
+        );
+    }
+
+    #[test]
+    fn logarithm() {
+        let ln2 = "0.69314718055994530941723212145817656807550013436025";
+        assert_eq!(ln(&dec("2"), 50).with_scale(50), dec(ln2));
+        assert_eq!(ln(&dec("1"), 20).with_scale(20), dec("0"));
+        assert_eq!(
+            ln(&dec("1e100"), 20).with_scale(20),
+            dec("230.25850929940456840179")
+        );
+        assert_eq!(
+            ln(&dec("0.001"), 20).with_scale(20),
+            dec("-6.90775527898213705205")
+        );
+    }
+
+    #[test]
+    fn powers_of_ten() {
+        assert_eq!(pow10(&dec("3"), 20), dec("1000"));
+        assert_eq!(pow10(&dec("-2"), 20), dec("0.01"));
+        let root = "3.16227766016837933199";
+        assert_eq!(pow10(&dec("0.5"), 20).with_scale(20), dec(root));
+        assert_eq!(
+            pow10(&dec("-1.5"), 20).with_scale(20),
+            dec("0.03162277660168379331")
+        );
+    }
+
+    #[test]
+    fn square_root() {
+        assert_eq!(sqrt(&dec("16"), 5), Some(dec("4")));
+        assert_eq!(sqrt(&dec("-1"), 5), None);
+        let root = sqrt(&dec("2"), 150).unwrap();
+        assert!(root.fractional_digit_count() >= 150);
+        let error = (&root * &root - dec("2")).abs();
+        assert!(error < dec("1e-150"));
+    }
+
+    #[test]
+    fn cancelled_computations_stop_early() {
+        let flag = Arc::new(AtomicBool::new(false));
+        watch_cancel(Some(flag.clone()));
+        let e = dec("2.71828182845904523536");
+        assert_eq!(exp(&dec("1"), 20).with_scale(20), e);
+        flag.store(true, Ordering::Relaxed);
+        assert!(exp(&dec("1"), 20).is_zero());
+        watch_cancel(None);
+        assert_eq!(exp(&dec("1"), 20).with_scale(20), e);
+    }
+}
@@ -0,0 +1,512 @@
+//! Parsing of the numbers typed by the user, e.g. `_1_000`, `0xff`,
+//! `1 3/4`, `15%`, `2h30m`, `4.7k`, `1:30`, `192.168.1.10` or `#ff8800`.
+use crate::math;
+use crate::units::{self, Dimension};
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, Zero};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum InputError {
+    #[error("Input is empty")]
+    Empty,
+    #[error("invalid character '{0}' at position {1}")]
+    InvalidChar(char, usize),
+    #[error("two decimal points at position {0}")]
+    TwoDecimalPoints(usize),
+    #[error("exponent missing digits at position {0}")]
+    MissingExponent(usize),
+    #[error("missing digits at position {0}")]
+    MissingDigits(usize),
+    #[error("zero denominator at position {0}")]
+    ZeroDenominator(usize),
+    #[error("unknown unit '{0}' at position {1}")]
+    UnknownUnit(String, usize),
+    #[error("missing unit at position {0}")]
+    MissingUnit(usize),
+    #[error("minutes and seconds must be below 60 at position {0}")]
+    SexagesimalRange(usize),
+    #[error("number out of range")]
+    OutOfRange,
+}
+
+impl InputError {
+    /// The 1-based position of the offending character, if any.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            InputError::InvalidChar(_, pos)
+            | InputError::TwoDecimalPoints(pos)
+            | InputError::MissingExponent(pos)
+            | InputError::MissingDigits(pos)
+            | InputError::ZeroDenominator(pos)
+            | InputError::UnknownUnit(_, pos)
+            | InputError::MissingUnit(pos)
+            | InputError::SexagesimalRange(pos) => Some(*pos),
+            InputError::Empty | InputError::OutOfRange => None,
+        }
+    }
+}
+
+/// Parse a single number, fractions and percentages being computed to
+/// `precision` digits after the dot.
+pub fn parse(text: &str, precision: u64) -> Result<BigDecimal, InputError> {
+    if text.is_empty() {
+        return Err(InputError::Empty);
+    }
+    let chars: Vec<char> = text.chars().collect();
+    parse_number(&chars, 0, precision)
+}
+
+/// Parse all the whitespace-separated numbers of the text, as [`parse`].
+pub fn parse_all(text: &str, precision: u64) -> Result<Vec<BigDecimal>, InputError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in chars.iter().chain([' '].iter()).enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                words.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    let mut values = vec![];
+    let mut words = words.into_iter().peekable();
+    while let Some(word) = words.next() {
+        let mut value = parse_number(&chars[word.clone()], word.start, precision)?;
+        // A whole number followed by a single space and a fraction is a
+        // mixed number, e.g. 1 3/4.
+        if let Some(next) = words.next_if(|next| {
+            next.start == word.end + 1
+                && is_whole(&chars[word.clone()])
+                && is_fraction(&chars[next.clone()])
+        }) {
+            let fraction = parse_number(&chars[next.clone()], next.start, precision)?;
+            value = if chars[word.start] == '_' {
+                value - fraction
+            } else {
+                value + fraction
+            };
+        }
+        values.push(value);
+    }
+    if values.is_empty() {
+        return Err(InputError::Empty);
+    }
+    Ok(values)
+}
+
+/// A cursor over the characters of a number, keeping track of positions
+/// for error reporting.
+struct Scanner<'a> {
+    chars: &'a [char],
+    next: usize,
+    offset: usize, // Position of the first character in the whole input.
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.next).copied()
+    }
+
+    // Consume the next character if it's one of the given ones.
+    fn eat(&mut self, candidates: &[char]) -> bool {
+        match self.peek() {
+            Some(c) if candidates.contains(&c) => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn letters(&mut self) -> String {
+        let start = self.next;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == 'µ')
+        {
+            self.next += 1;
+        }
+        self.chars[start..self.next].iter().collect()
+    }
+
+    // Consume digits, ignoring the `_` separators that may follow the first one.
+    fn digits(&mut self, radix: u32) -> String {
+        let start = self.next;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_digit(radix) || (c == '_' && self.next > start))
+        {
+            self.next += 1;
+        }
+        self.chars[start..self.next]
+            .iter()
+            .filter(|&&c| c != '_')
+            .collect()
+    }
+
+    // 1-based position of the next character in the whole input.
+    fn pos(&self) -> usize {
+        self.offset + self.next + 1
+    }
+
+    fn done(&self) -> bool {
+        self.next >= self.chars.len()
+    }
+
+    // The error for whatever comes next when it's not what we expected.
+    fn unexpected(&self) -> InputError {
+        match self.peek() {
+            Some(c) => InputError::InvalidChar(c, self.pos()),
+            None => InputError::MissingDigits(self.pos()),
+        }
+    }
+}
+
+/// Parse a single number, `offset` being its position in the whole input.
+fn parse_number(chars: &[char], offset: usize, precision: u64) -> Result<BigDecimal, InputError> {
+    let mut s = Scanner {
+        chars,
+        next: 0,
+        offset,
+    };
+    if let Some(address) = parse_ip(chars) {
+        return Ok(address);
+    }
+    // Colors, as in CSS.
+    if s.eat(&['#']) {
+        return parse_radix_int(&mut s, 16).and_then(|color| match s.done() {
+            true => Ok(color),
+            false => Err(s.unexpected()),
+        });
+    }
+    let negative = s.eat(&['_']);
+    let radix = if s.peek() == Some('0') && s.chars.len() > s.next + 1 {
+        match s.chars[s.next + 1] {
+            'x' | 'X' => Some(16),
+            'b' | 'B' => Some(2),
+            'o' | 'O' => Some(8),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let result = match radix {
+        Some(radix) => {
+            s.next += 2;
+            parse_radix_int(&mut s, radix)?
+        }
+        None if chars.contains(&':') => return parse_sexagesimal(&mut s, negative),
+        None => parse_decimal(&mut s)?,
+    };
+    let result = if radix.is_none() && s.eat(&['/']) {
+        let pos = s.pos();
+        let denominator = s.digits(10);
+        if denominator.is_empty() {
+            return Err(s.unexpected());
+        }
+        let denominator = BigDecimal::from_str(&denominator).map_err(|_| InputError::OutOfRange)?;
+        if denominator.is_zero() {
+            return Err(InputError::ZeroDenominator(pos));
+        }
+        divide(&result, &denominator, precision)?
+    } else {
+        result
+    };
+    // Percentages are just scaled down, so that e.g. `200 15% *` is 30.
+    let result = if s.eat(&['%']) {
+        divide(&result, &BigDecimal::from(100), precision)?
+    } else if radix.is_none() {
+        parse_units(&mut s, result)?
+    } else {
+        result
+    };
+    if !s.done() {
+        return Err(s.unexpected());
+    }
+    Ok(if negative { -result } else { result })
+}
+
+// `a / b` to `precision` digits after the dot, unless the digits of `a` are
+// too far from the dot for that.
+fn divide(a: &BigDecimal, b: &BigDecimal, precision: u64) -> Result<BigDecimal, InputError> {
+    match math::div_bits(a, b, precision) {
+        (_, true) => Ok(math::div(a, b, precision)),
+        (_, false) => Err(InputError::OutOfRange),
+    }
+}
+
+// Parse H:MM or H:MM:SS(.s) as a number of seconds.
+fn parse_sexagesimal(s: &mut Scanner, negative: bool) -> Result<BigDecimal, InputError> {
+    let hours = s.digits(10);
+    if hours.is_empty() {
+        return Err(s.unexpected());
+    }
+    let mut total =
+        BigDecimal::from_str(&hours).map_err(|_| InputError::OutOfRange)? * BigDecimal::from(3600);
+    for (i, factor) in [60u32, 1].into_iter().enumerate() {
+        if !s.eat(&[':']) {
+            // Seconds are optional, minutes aren't.
+            if i == 0 || !s.done() {
+                return Err(s.unexpected());
+            }
+            break;
+        }
+        let pos = s.pos();
+        let component = if factor == 1 {
+            parse_decimal(s)?
+        } else {
+            let minutes = s.digits(10);
+            if minutes.is_empty() {
+                return Err(s.unexpected());
+            }
+            BigDecimal::from_str(&minutes).map_err(|_| InputError::OutOfRange)?
+        };
+        if component >= 60 {
+            return Err(InputError::SexagesimalRange(pos));
+        }
+        total += component * factor;
+    }
+    if !s.done() {
+        return Err(s.unexpected());
+    }
+    Ok(if negative { -total } else { total })
+}
+
+// An IPv4 or IPv6 address as the integer it stands for. Neither can be
+// mistaken for another number, e.g. H:MM:SS lacks the groups of IPv6.
+fn parse_ip(chars: &[char]) -> Option<BigDecimal> {
+    let text: String = chars.iter().collect();
+    if let Ok(address) = Ipv4Addr::from_str(&text) {
+        return Some(u32::from(address).into());
+    }
+    Ipv6Addr::from_str(&text)
+        .ok()
+        .map(|address| u128::from(address).into())
+}
+
+// Convert the value to the base unit of its suffix, if any. Durations can
+// be chained, e.g. 2h30m.
+fn parse_units(s: &mut Scanner, value: BigDecimal) -> Result<BigDecimal, InputError> {
+    let mut pos = s.pos();
+    let mut unit = s.letters();
+    if unit.is_empty() {
+        return Ok(value);
+    }
+    let chained = |s: &Scanner| s.peek().is_some_and(|c| c.is_ascii_digit() || c == '.');
+    let (dimension, factor) = units::lookup(&unit, chained(s))
+        .ok_or_else(|| InputError::UnknownUnit(unit.clone(), pos))?;
+    let mut total = value * factor;
+    while dimension == Dimension::Time && chained(s) {
+        let value = parse_decimal(s)?;
+        pos = s.pos();
+        unit = s.letters();
+        if unit.is_empty() {
+            return Err(InputError::MissingUnit(pos));
+        }
+        match units::lookup(&unit, true) {
+            Some((Dimension::Time, factor)) => total += value * factor,
+            _ => return Err(InputError::UnknownUnit(unit, pos)),
+        }
+    }
+    Ok(total)
+}
+
+// Whether the word is an integer written in decimal, e.g. `_12`.
+fn is_whole(word: &[char]) -> bool {
+    let digits = word.strip_prefix(&['_']).unwrap_or(word);
+    digits.first().is_some_and(|c| c.is_ascii_digit())
+        && digits.iter().all(|&c| c.is_ascii_digit() || c == '_')
+}
+
+// Whether the word is a simple fraction, e.g. `3/4`.
+fn is_fraction(word: &[char]) -> bool {
+    word.contains(&'/') && word.iter().all(|&c| c.is_ascii_digit() || c == '/')
+}
+
+fn parse_radix_int(s: &mut Scanner, radix: u32) -> Result<BigDecimal, InputError> {
+    let digits = s.digits(radix);
+    if digits.is_empty() {
+        return Err(s.unexpected());
+    }
+    let n = BigInt::parse_bytes(digits.as_bytes(), radix).ok_or(InputError::OutOfRange)?;
+    Ok(BigDecimal::from(n))
+}
+
+fn parse_decimal(s: &mut Scanner) -> Result<BigDecimal, InputError> {
+    let mut repr = s.digits(10);
+    if s.eat(&['.']) {
+        let decimals = s.digits(10);
+        if s.peek() == Some('.') {
+            return Err(InputError::TwoDecimalPoints(s.pos()));
+        }
+        if !decimals.is_empty() {
+            repr = format!("{}.{}", if repr.is_empty() { "0" } else { &repr }, decimals);
+        }
+    }
+    if repr.is_empty() {
+        return Err(s.unexpected());
+    }
+    let exponent_pos = s.pos();
+    if s.eat(&['e', 'E']) {
+        let sign = if s.eat(&['-']) {
+            "-"
+        } else {
+            s.eat(&['+']);
+            ""
+        };
+        let exponent = s.digits(10);
+        if exponent.is_empty() {
+            return Err(InputError::MissingExponent(exponent_pos));
+        }
+        repr = format!("{repr}e{sign}{exponent}");
+    }
+    BigDecimal::from_str(&repr).map_err(|_| InputError::OutOfRange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack::DEFAULT_PRECISION;
+
+    #[test]
+    fn test_diagnostics() {
+        let error = |text| parse(text, DEFAULT_PRECISION);
+        assert_eq!(error("1.2.3"), Err(InputError::TwoDecimalPoints(4)));
+        assert_eq!(error("12e"), Err(InputError::MissingExponent(3)));
+        assert_eq!(error("12e+"), Err(InputError::MissingExponent(3)));
+        assert_eq!(error("0xfg"), Err(InputError::InvalidChar('g', 4)));
+        assert_eq!(error("_"), Err(InputError::MissingDigits(2)));
+        assert_eq!(error("."), Err(InputError::MissingDigits(2)));
+        assert_eq!(
+            InputError::InvalidChar('x', 4).to_string(),
+            "invalid character 'x' at position 4"
+        );
+    }
+
+    #[test]
+    fn test_ip_addresses() {
+        let value = |text| parse(text, DEFAULT_PRECISION).map(|n| n.to_string());
+        assert_eq!(value("192.168.1.10"), Ok("3232235786".into()));
+        assert_eq!(value("0.0.0.0"), Ok("0".into()));
+        assert_eq!(value("::1"), Ok("1".into()));
+        assert_eq!(
+            value("2001:db8::ff"),
+            Ok("42540766411282592856903984951653826815".into())
+        );
+        // Not quite addresses.
+        assert_eq!(value("1.2.3"), Err(InputError::TwoDecimalPoints(4)));
+        assert!(value("256.0.0.1").is_err());
+        assert_eq!(value("1:30:15"), Ok("5415".into()));
+    }
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(parse("#ff8800", DEFAULT_PRECISION), Ok(0xff8800.into()));
+        assert_eq!(
+            parse("#", DEFAULT_PRECISION),
+            Err(InputError::MissingDigits(2))
+        );
+        assert_eq!(
+            parse("#ffg", DEFAULT_PRECISION),
+            Err(InputError::InvalidChar('g', 4))
+        );
+    }
+
+    #[test]
+    fn test_decimal_forms() {
+        let value = |text| parse(text, DEFAULT_PRECISION);
+        assert_eq!(value(".5"), Ok("0.5".parse().unwrap()));
+        assert_eq!(value("5."), Ok(BigDecimal::from(5)));
+        assert_eq!(value("1.5e3"), Ok(BigDecimal::from(1500)));
+        assert_eq!(value("1E-2"), Ok("0.01".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_fractions() {
+        let values = |text| parse_all(text, DEFAULT_PRECISION);
+        assert_eq!(values("3/4"), Ok(vec!["0.75".parse().unwrap()]));
+        assert_eq!(values("_3/4"), Ok(vec!["-0.75".parse().unwrap()]));
+        assert_eq!(values("1 3/4"), Ok(vec!["1.75".parse().unwrap()]));
+        assert_eq!(values("_1 3/4"), Ok(vec!["-1.75".parse().unwrap()]));
+        assert_eq!(
+            values("1  3/4 2"),
+            Ok(vec![
+                BigDecimal::from(1),
+                "0.75".parse().unwrap(),
+                BigDecimal::from(2)
+            ])
+        );
+        assert_eq!(values("1/0"), Err(InputError::ZeroDenominator(3)));
+        assert_eq!(values("1/"), Err(InputError::MissingDigits(3)));
+        assert_eq!(values("1/2/3"), Err(InputError::InvalidChar('/', 4)));
+        // To the precision, however large.
+        let third = parse("1/3", 200_000).unwrap().to_string();
+        assert!(third.starts_with("0.333") && third.len() >= 200_002);
+        assert!(third.chars().skip(2).all(|c| c == '3'));
+        assert_eq!(values("1e5000000000/3"), Err(InputError::OutOfRange));
+    }
+
+    #[test]
+    fn test_percent() {
+        let value = |text| parse(text, DEFAULT_PRECISION);
+        assert_eq!(value("15%"), Ok("0.15".parse().unwrap()));
+        assert_eq!(value("_2.5%"), Ok("-0.025".parse().unwrap()));
+        assert_eq!(value("1/2%"), Ok("0.005".parse().unwrap()));
+        assert_eq!(value("15%%"), Err(InputError::InvalidChar('%', 4)));
+        assert_eq!(value("%"), Err(InputError::InvalidChar('%', 1)));
+    }
+
+    #[test]
+    fn test_units() {
+        let value = |text| parse(text, DEFAULT_PRECISION);
+        assert_eq!(value("5km"), Ok(BigDecimal::from(5000)));
+        assert_eq!(value("3.5GiB"), Ok(BigDecimal::from(3758096384u64)));
+        assert_eq!(value("2h30m"), Ok(BigDecimal::from(9000)));
+        assert_eq!(value("1d2h3m4s"), Ok(BigDecimal::from(93784)));
+        assert_eq!(value("_1.5min"), Ok(BigDecimal::from(-90)));
+        assert_eq!(value("5m"), Ok("0.005".parse().unwrap()));
+        assert_eq!(value("4.7k"), Ok(BigDecimal::from(4700)));
+        assert_eq!(value("22u"), Ok("0.000022".parse().unwrap()));
+        assert_eq!(value("22µ"), Ok("0.000022".parse().unwrap()));
+        assert_eq!(value("3M"), Ok(BigDecimal::from(3000000)));
+        assert_eq!(value("5xy"), Err(InputError::UnknownUnit("xy".into(), 2)));
+        assert_eq!(value("2h30"), Err(InputError::MissingUnit(5)));
+        assert_eq!(
+            value("2h30km"),
+            Err(InputError::UnknownUnit("km".into(), 5))
+        );
+    }
+
+    #[test]
+    fn test_sexagesimal() {
+        let value = |text| parse(text, DEFAULT_PRECISION);
+        assert_eq!(value("1:30"), Ok(BigDecimal::from(5400)));
+        assert_eq!(value("01:02:03.5"), Ok("3723.5".parse().unwrap()));
+        assert_eq!(value("_0:01"), Ok(BigDecimal::from(-60)));
+        assert_eq!(value("1:60"), Err(InputError::SexagesimalRange(3)));
+        assert_eq!(value("1:00:60"), Err(InputError::SexagesimalRange(6)));
+        assert_eq!(value("1:"), Err(InputError::MissingDigits(3)));
+        assert_eq!(value("1:00:"), Err(InputError::MissingDigits(6)));
+        assert_eq!(value("1:00:00:00"), Err(InputError::InvalidChar(':', 8)));
+        assert_eq!(value("1.5:00"), Err(InputError::InvalidChar('.', 2)));
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let value = |text| parse(text, DEFAULT_PRECISION);
+        assert_eq!(value("1_000_000"), Ok(BigDecimal::from(1000000)));
+        assert_eq!(value("_1_000"), Ok(BigDecimal::from(-1000)));
+        assert_eq!(value("0xff_ff"), Ok(BigDecimal::from(65535)));
+        assert_eq!(value("1_000.000_5"), Ok("1000.0005".parse().unwrap()));
+        assert_eq!(value("__1"), Err(InputError::InvalidChar('_', 2)));
+        assert_eq!(value("1._5"), Err(InputError::InvalidChar('_', 3)));
+        let values = |text| parse_all(text, DEFAULT_PRECISION);
+        assert_eq!(values("1_000 1/2"), Ok(vec!["1000.5".parse().unwrap()]));
+    }
+}
@@ -0,0 +1,636 @@
+//! The operations on the stack.
+//!
+//! Every operation, built-in or provided by a plugin, implements
+//! [`Operation`], which holds both its behavior and the metadata shown to the
+//! user (name, key, description).
+mod algebra;
+mod checks;
+mod checksum;
+mod color;
+mod decibel;
+mod fast;
+mod finance;
+mod net;
+mod numbers;
+mod series;
+mod solve;
+pub(crate) mod time;
+mod trig;
+
+pub use solve::Solve;
+pub use time::seconds;
+pub use trig::Angle;
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal, Pow, RoundingMode, ToPrimitive, Zero};
+use std::{fmt, sync::Arc};
+
+use crate::stack::{InstantStack, Op, StackError, DEFAULT_BASE, DEFAULT_PRECISION};
+use crate::{dice, math, random};
+
+/// An operation on the stack.
+pub trait Operation: Send + Sync {
+    /// Unique name of the operation, e.g. "hypot".
+    fn name(&self) -> &str;
+
+    /// Key triggering the operation, if any. Keys already used by the
+    /// calculator are ignored.
+    fn key(&self) -> Option<char> {
+        None
+    }
+
+    /// One-line description, shown in the help.
+    fn description(&self) -> &str;
+
+    /// Number of values taken off the stack.
+    fn arity(&self) -> usize;
+
+    /// Check the arguments before anything is taken off the stack. The
+    /// arguments are in their natural order, i.e. S1 last.
+    fn validate(&self, _args: &[BigDecimal]) -> Result<(), StackError> {
+        Ok(())
+    }
+
+    /// Compute the values replacing the arguments on the stack. Both the
+    /// arguments and the results are in their natural order, i.e. S1 last.
+    /// By default, the arguments are left untouched.
+    fn apply(&self, args: &[BigDecimal]) -> Result<Vec<BigDecimal>, StackError> {
+        Ok(args.to_vec())
+    }
+
+    /// Run the operation on the whole stack. Operations needing more than
+    /// their arguments, e.g. access to the registers, override this instead.
+    fn execute(&self, s: &mut InstantStack) -> Result<(), StackError> {
+        let args = arguments(self.arity(), s)?;
+        self.validate(&args)?;
+        let results = self.apply(&args)?;
+        s.stack.drain(0..args.len());
+        for v in results {
+            s.push_front(v);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for dyn Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operation({})", self.name())
+    }
+}
+
+/// The top `arity` values of the stack, in their natural order.
+fn arguments(arity: usize, s: &InstantStack) -> Result<Vec<BigDecimal>, StackError> {
+    if s.stack.len() < arity {
+        return Err(StackError::MissingValue(arity));
+    }
+    Ok(s.stack.range(0..arity).rev().cloned().collect())
+}
+
+/// The built-in operations, described by a table rather than one type each.
+#[derive(Clone, Copy)]
+struct Builtin {
+    name: &'static str,
+    key: Option<char>,
+    description: &'static str,
+    // Also sees the stack, for its settings.
+    validate: fn(&InstantStack, &[BigDecimal]) -> Result<(), StackError>,
+    run: Run,
+}
+
+/// The implementation of a built-in, given its validated arguments already
+/// popped off the stack. Its variant sets the arity.
+#[derive(Clone, Copy)]
+enum Run {
+    Nullary(fn(&mut InstantStack)),
+    Unary(fn(&mut InstantStack, BigDecimal)),
+    Binary(fn(&mut InstantStack, BigDecimal, BigDecimal)),
+    Ternary(fn(&mut InstantStack, BigDecimal, BigDecimal, BigDecimal)),
+    /// Works on the whole stack, and may fail.
+    Stack(fn(&mut InstantStack) -> Result<(), StackError>),
+}
+
+impl Operation for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn key(&self) -> Option<char> {
+        self.key
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn arity(&self) -> usize {
+        match self.run {
+            Run::Nullary(_) | Run::Stack(_) => 0,
+            Run::Unary(_) => 1,
+            Run::Binary(_) => 2,
+            Run::Ternary(_) => 3,
+        }
+    }
+
+    fn execute(&self, s: &mut InstantStack) -> Result<(), StackError> {
+        let args = arguments(self.arity(), s)?;
+        (self.validate)(s, &args)?;
+        if let Some(result) = s.fast.then(|| fast::run(s, self.name, &args)).flatten() {
+            let v = result?;
+            s.stack.drain(0..args.len());
+            s.push_front(v);
+            return Ok(());
+        }
+        s.stack.drain(0..args.len());
+        let mut args = args.into_iter();
+        let mut arg = || args.next().unwrap();
+        match self.run {
+            Run::Nullary(f) => f(s),
+            Run::Unary(f) => f(s, arg()),
+            Run::Binary(f) => {
+                let a = arg();
+                f(s, a, arg())
+            }
+            Run::Ternary(f) => {
+                let (a, b) = (arg(), arg());
+                f(s, a, b, arg())
+            }
+            Run::Stack(f) => return f(s),
+        }
+        Ok(())
+    }
+}
+
+fn any(_: &InstantStack, _: &[BigDecimal]) -> Result<(), StackError> {
+    Ok(())
+}
+
+fn invalid(message: &str) -> Result<(), StackError> {
+    Err(StackError::InvalidArgument(message.into()))
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "add",
+        key: Some('+'),
+        description: "add S2 and S1",
+        validate: any,
+        run: Run::Binary(|s, a, b| s.push_front(a + b)),
+    },
+    Builtin {
+        name: "sub",
+        key: Some('-'),
+        description: "subtract S1 from S2",
+        validate: any,
+        run: Run::Binary(|s, a, b| s.push_front(a - b)),
+    },
+    Builtin {
+        name: "mul",
+        key: Some('*'),
+        description: "multiply S2 by S1",
+        validate: any,
+        run: Run::Binary(|s, a, b| s.push_front(a * b)),
+    },
+    Builtin {
+        name: "div",
+        key: Some('/'),
+        description: "divide S2 by S1",
+        validate: |s, args| match args[1].is_zero() {
+            true => invalid("element 1 must be non-zero"),
+            false => validate_size(s, math::div_bits(&args[0], &args[1], s.precision)),
+        },
+        run: Run::Binary(|s, a, b| s.push_front(math::div(&a, &b, s.precision))),
+    },
+    Builtin {
+        name: "mod",
+        key: Some('%'),
+        description: "modulo of S2 divided by S1",
+        validate: any,
+        run: Run::Binary(|s, a, b| s.push_front(a % b)),
+    },
+    Builtin {
+        name: "sqrt",
+        key: Some('v'),
+        description: "square root of S1",
+        validate: |s, args| match args[0] < BigDecimal::zero() {
+            true => invalid("element 1 must be positive"),
+            false => validate_size(s, math::sqrt_bits(&args[0], s.precision)),
+        },
+        run: Run::Unary(|s, a| s.push_front(math::sqrt(&a, s.precision).unwrap())),
+    },
+    Builtin {
+        name: "pow",
+        key: Some('^'),
+        description: "raise S2 to the power of S1",
+        validate: validate_pow,
+        run: Run::Binary(|s, a, b| {
+            // This is the only operation that needs to crack open the representation.
+            // Careful, BigDecimal's scale works not only as the number of digits after
+            // the dot, it's really a generalized
+            //     int_value . 10^-scale
+            // We know the numbers are integers, but we still need to flush all
+            // the digits into the bigint where we can express the Pow operation.
+            let a = a.with_scale(0).as_bigint_and_scale().0.into_owned();
+            let b = b.with_scale(0).as_bigint_and_scale().0.into_owned();
+            let result = a.pow(b.to_biguint().unwrap());
+            // Normalization ensures the exponent representation is simplified.
+            // For instance 10^100 -> (1, -100) after normalization instead of
+            // (1e100, 0).
+            s.push_front(BigDecimal::from_bigint(result, 0));
+        }),
+    },
+    Builtin {
+        name: "log",
+        key: None,
+        description: "logarithm of S2 in base S1",
+        validate: |_, args| {
+            let [x, base] = args else { unreachable!() };
+            if *x <= BigDecimal::zero() {
+                return invalid("element 2 must be positive");
+            }
+            match *base <= BigDecimal::zero() || *base == 1 {
+                true => invalid("the base must be positive and other than 1"),
+                false => Ok(()),
+            }
+        },
+        run: Run::Binary(|s, x, base| {
+            let (x, base) = (math::ln(&x, s.precision), math::ln(&base, s.precision));
+            s.push_front(math::settle(math::div(&x, &base, s.precision), s.precision));
+        }),
+    },
+    Builtin {
+        name: "ph",
+        key: None,
+        description: "pH of the concentration S1, i.e. its negative logarithm in base 10",
+        validate: |_, args| match args[0] <= BigDecimal::zero() {
+            true => invalid("element 1 must be positive"),
+            false => Ok(()),
+        },
+        run: Run::Unary(|s, x| {
+            let (x, ten) = (math::ln(&x, s.precision), math::ln(&10.into(), s.precision));
+            s.push_front(math::settle(-math::div(&x, &ten, s.precision), s.precision));
+        }),
+    },
+    Builtin {
+        name: "round_uncertainty",
+        key: None,
+        description: "round S2 to the first significant digit of its uncertainty S1, rounded too",
+        validate: |_, args| match args[1] <= BigDecimal::zero() {
+            true => invalid("the uncertainty must be positive"),
+            false => Ok(()),
+        },
+        run: Run::Binary(|s, x, uncertainty| {
+            // The uncertainty may round up to one more digit, e.g. 0.096 to 0.1.
+            let uncertainty =
+                uncertainty.with_scale_round(-leading_digit(&uncertainty), RoundingMode::HalfUp);
+            let scale = -leading_digit(&uncertainty);
+            s.push_front(math::trim(x.with_scale_round(scale, RoundingMode::HalfUp)));
+            s.push_front(math::trim(uncertainty));
+        }),
+    },
+    Builtin {
+        name: "dup",
+        key: Some('d'),
+        description: "duplicate S1",
+        validate: any,
+        run: Run::Unary(|s, a| {
+            s.push_front(a.clone());
+            s.push_front(a);
+        }),
+    },
+    Builtin {
+        name: "drop",
+        key: Some('P'),
+        description: "pop S1 off the stack",
+        validate: any,
+        run: Run::Unary(|_, _| {}),
+    },
+    Builtin {
+        name: "precision",
+        key: Some('k'),
+        description: "pop S1 and use it to set the precision",
+        validate: |_, args| {
+            let a = &args[0];
+            match *a <= BigDecimal::zero() || *a > i64::MAX || !a.is_integer() {
+                true => invalid("element 1 must be a positive integer"),
+                false => Ok(()),
+            }
+        },
+        run: Run::Unary(|s, a| s.precision = a.to_u64().unwrap()),
+    },
+    Builtin {
+        name: "base",
+        key: Some('o'),
+        description: "pop S1 and use it to set the output base (2–36)",
+        validate: |_, args| {
+            let a = &args[0];
+            match !a.is_integer() || *a < 2 || *a > 36 {
+                true => invalid("base must be an integer between 2 and 36"),
+                false => Ok(()),
+            }
+        },
+        run: Run::Unary(|s, a| s.output_base = a.to_u32().unwrap()),
+    },
+    Builtin {
+        name: "word",
+        key: None,
+        description:
+            "pop S1 and show negatives in bases 2, 8 and 16 as words of S1 bits (0 for none)",
+        validate: |_, args| {
+            let a = &args[0];
+            match !a.is_integer() || *a < 0 || *a > 4096 {
+                true => invalid("word size must be an integer between 0 and 4096"),
+                false => Ok(()),
+            }
+        },
+        run: Run::Unary(|s, a| s.word_size = a.to_u32().filter(|&bits| bits > 0)),
+    },
+    Builtin {
+        name: "pin",
+        key: Some('p'),
+        description: "pin S1 so that operations can't consume it, or unpin it",
+        validate: any,
+        run: Run::Stack(|s| {
+            let Some(bottom) = s.stack.len().checked_sub(1) else {
+                return Err(StackError::MissingValue(1));
+            };
+            if !s.pinned.remove(&bottom) {
+                s.pinned.insert(bottom);
+            }
+            Ok(())
+        }),
+    },
+    Builtin {
+        name: "swap",
+        key: Some('r'),
+        description: "swap S1 and S2",
+        validate: any,
+        run: Run::Binary(|s, a, b| {
+            s.push_front(b);
+            s.push_front(a);
+        }),
+    },
+    Builtin {
+        name: "clear",
+        key: Some('c'),
+        description: "clear the stack",
+        validate: any,
+        run: Run::Nullary(|s| s.stack.clear()),
+    },
+    Builtin {
+        name: "clear_registers",
+        key: Some('C'),
+        description: "clear the registers",
+        validate: any,
+        run: Run::Nullary(|s| s.registers.clear()),
+    },
+    Builtin {
+        name: "defaults",
+        key: Some('n'),
+        description: "reset precision, output base and word size",
+        validate: any,
+        run: Run::Nullary(|s| {
+            s.precision = DEFAULT_PRECISION;
+            s.output_base = DEFAULT_BASE;
+            s.word_size = None;
+        }),
+    },
+    Builtin {
+        name: "rotate",
+        key: Some('y'),
+        description: "rotate stack forward (S1→S2→S3→…→S1)",
+        validate: any,
+        run: Run::Nullary(|s| {
+            if s.stack.len() >= 2 {
+                let top = s.stack.pop_front().unwrap();
+                s.stack.push_back(top);
+            }
+        }),
+    },
+    Builtin {
+        name: "rotate_back",
+        key: Some('Y'),
+        description: "rotate stack backward (S1→…→S3→S2→S1)",
+        validate: any,
+        run: Run::Nullary(|s| {
+            if s.stack.len() >= 2 {
+                let bottom = s.stack.pop_back().unwrap();
+                s.stack.push_front(bottom);
+            }
+        }),
+    },
+    Builtin {
+        name: "last_args",
+        key: Some('a'),
+        description: "push back the values consumed by the latest operation",
+        validate: any,
+        run: Run::Nullary(|s| {
+            for v in s.last_args.clone() {
+                s.push_front(v);
+            }
+        }),
+    },
+    Builtin {
+        name: "roll",
+        key: None,
+        description: "roll S2 dice with S1 sides, pushing each die",
+        validate: |_, args| {
+            let [count, sides] = args else { unreachable!() };
+            if !count.is_integer() || *count < 1 || *count > dice::MAX_DICE {
+                return invalid("the number of dice must be an integer between 1 and 1000");
+            }
+            match sides.is_integer() && *sides >= 1 && sides.to_u64().is_some() {
+                true => Ok(()),
+                false => invalid("the number of sides must be a positive integer"),
+            }
+        },
+        run: Run::Binary(|s, count, sides| {
+            let dice = dice::Dice {
+                count: count.to_u64().unwrap(),
+                sides: sides.to_u64().unwrap(),
+                modifier: 0,
+            };
+            for r in dice.roll().rolls {
+                s.push_front(r.into());
+            }
+        }),
+    },
+    Builtin {
+        name: "seed",
+        key: None,
+        description: "pop S1 and use it to seed the random numbers",
+        validate: |_, args| match args[0].is_integer() && args[0].to_u64().is_some() {
+            true => Ok(()),
+            false => invalid("the seed must be a non-negative integer"),
+        },
+        run: Run::Unary(|_, a| random::seed(a.to_u64().unwrap())),
+    },
+];
+
+/// The power of 10 of the first significant digit of `x`, non-zero, e.g.
+/// -2 for 0.02.
+fn leading_digit(x: &BigDecimal) -> i64 {
+    let x = x.normalized();
+    x.digits() as i64 - 1 - x.fractional_digit_count()
+}
+
+fn validate_pow(s: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    let [a, b] = args else { unreachable!() };
+    if !(b.is_integer() && b > &BigDecimal::zero() && b < &BigDecimal::from(u64::MAX)) {
+        return invalid("element 1 must be a positive integer");
+    }
+    if !a.is_integer() {
+        return invalid("element 2 must be an integer");
+    }
+    let Some(max_bits) = s.max_bits else {
+        return Ok(());
+    };
+    let a = a.with_scale(0).as_bigint_and_scale().0.into_owned();
+    let b = b.with_scale(0).as_bigint_and_scale().0.into_owned();
+    if BigInt::from(a.bits()) * &b > BigInt::from(max_bits) {
+        return Err(StackError::InvalidArgument(format!(
+            "result over {max_bits} bits"
+        )));
+    }
+    Ok(())
+}
+
+/// Check the bits of a result and whether it can be computed at all, from
+/// `math::div_bits` or `math::sqrt_bits`.
+fn validate_size(s: &InstantStack, (bits, computable): (f64, bool)) -> Result<(), StackError> {
+    if !computable {
+        return invalid("too many digits to compute");
+    }
+    match s.max_bits {
+        Some(max_bits) if bits > max_bits as f64 => Err(StackError::InvalidArgument(format!(
+            "result over {max_bits} bits"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The tables of built-in operations.
+fn all() -> impl Iterator<Item = &'static Builtin> {
+    BUILTINS
+        .iter()
+        .chain(finance::FINANCE)
+        .chain(algebra::ALGEBRA)
+        .chain(numbers::NUMBERS)
+        .chain(trig::TRIG)
+        .chain(fast::FAST)
+        .chain(time::TIME)
+        .chain(net::NET)
+        .chain(color::COLOR)
+        .chain(checksum::CHECKSUM)
+        .chain(checks::CHECKS)
+        .chain(decibel::DECIBEL)
+        .chain(series::SERIES)
+}
+
+/// All the built-in operations.
+pub fn builtins() -> impl Iterator<Item = Arc<dyn Operation>> {
+    all().map(|&b| Arc::new(b) as Arc<dyn Operation>)
+}
+
+/// The built-in operation with the given name, if any.
+pub fn builtin(name: &str) -> Option<Op> {
+    all()
+        .find(|b| b.name == name)
+        .map(|&b| Op::Run(Arc::new(b)))
+}
+
+/// The built-in operation bound to the given key, if any.
+pub fn by_key(key: char) -> Option<Op> {
+    all()
+        .find(|b| b.key == Some(key))
+        .map(|&b| Op::Run(Arc::new(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack::Stack;
+    use std::collections::HashSet;
+
+    #[test]
+    fn names_and_keys_are_unique() {
+        let names: HashSet<_> = all().map(|b| b.name).collect();
+        assert_eq!(names.len(), all().count());
+        let keys: Vec<_> = all().filter_map(|b| b.key).collect();
+        assert_eq!(keys.iter().collect::<HashSet<_>>().len(), keys.len());
+    }
+
+    #[test]
+    fn roll() -> Result<(), StackError> {
+        let mut s = Stack::from(vec![BigDecimal::from(6), BigDecimal::from(3)], None);
+        s.apply(builtin("roll").unwrap())?;
+        let rolls = s.snapshot();
+        assert_eq!(rolls.len(), 3);
+        assert!(rolls.iter().all(|r| *r >= 1 && *r <= 6));
+        let mut s = Stack::from(vec![BigDecimal::from(6), BigDecimal::from(0)], None);
+        assert!(s.apply(builtin("roll").unwrap()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn logarithms() -> Result<(), StackError> {
+        let log = |x: &str, base: &str| -> Result<String, StackError> {
+            let mut s = Stack::from(vec![base.parse().unwrap(), x.parse().unwrap()], Some(10));
+            s.apply(builtin("log").unwrap())?;
+            Ok(s.snapshot()[0].to_string())
+        };
+        assert_eq!(log("1000", "10")?, "3");
+        assert_eq!(log("0.125", "2")?, "-3");
+        assert_eq!(log("2", "10")?, "0.3010299956");
+        assert_eq!(log("9", "0.5")?, "-3.1699250014");
+        assert!(log("0", "10").is_err());
+        assert!(log("5", "1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn uncertainties() -> Result<(), StackError> {
+        let round = |x: &str, uncertainty: &str| -> Result<Vec<String>, StackError> {
+            let mut s = Stack::from(vec![uncertainty.parse().unwrap(), x.parse().unwrap()], None);
+            s.apply(builtin("round_uncertainty").unwrap())?;
+            Ok(s.snapshot().iter().rev().map(|v| v.to_string()).collect())
+        };
+        assert_eq!(round("12.3456", "0.02")?, ["12.35", "0.02"]);
+        assert_eq!(round("12.3456", "0.0149")?, ["12.35", "0.01"]);
+        assert_eq!(round("12.3456", "0.096")?, ["12.3", "0.1"]);
+        assert_eq!(round("1234.5", "27")?, ["1230", "30"]);
+        assert!(round("1", "0").is_err());
+
+        let mut s = Stack::from(vec!["1e-7".parse().unwrap()], None);
+        s.apply(builtin("ph").unwrap())?;
+        assert_eq!(s.snapshot(), [BigDecimal::from(7)]);
+        Ok(())
+    }
+
+    #[test]
+    fn huge_exponents() -> Result<(), StackError> {
+        let apply = |values: &[&str], op: &str, max_bits: Option<u64>| {
+            let values = values.iter().map(|v| v.parse().unwrap()).collect();
+            let mut s = Stack::from(values, None);
+            s.set_max_bits(max_bits);
+            s.apply(builtin(op).unwrap())?;
+            Ok::<_, StackError>(s.snapshot()[0].to_string())
+        };
+        let err = |values, op, max_bits| apply(values, op, max_bits).err().unwrap().to_string();
+        assert!(err(&["1e-4000000000", "1"], "div", Some(1000)).contains("result over 1000 bits"));
+        assert!(err(&["1e-5000000000", "1"], "div", None).contains("too many digits"));
+        assert!(err(&["1e4000000000"], "sqrt", Some(1000)).contains("result over 1000 bits"));
+        assert!(err(&["1e5000000000"], "sqrt", None).contains("too many digits"));
+        // Tiny results are fine, and quick.
+        assert_eq!(apply(&["1", "1e-4000000000"], "div", None)?, "0");
+        assert_eq!(apply(&["1", "0"], "div", None)?, "0");
+        assert_eq!(apply(&["1e-4000000000"], "sqrt", None)?, "0");
+        assert_eq!(apply(&["1e-5000000000", "0"], "div", None)?, "0");
+        Ok(())
+    }
+
+    #[test]
+    fn lookup() {
+        assert!(matches!(builtin("add"), Some(Op::Run(op)) if op.key() == Some('+')));
+        assert!(matches!(by_key('^'), Some(Op::Run(op)) if op.name() == "pow"));
+        assert!(builtin("nope").is_none());
+    }
+}
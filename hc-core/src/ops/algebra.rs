@@ -0,0 +1,168 @@
+//! Polynomials.
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+
+use super::{any, invalid, Builtin, Run};
+use crate::math;
+use crate::stack::{InstantStack, StackError};
+
+/// The highest degree of the polynomials taken from the stack.
+const MAX_DEGREE: usize = 20;
+
+pub(super) const ALGEBRA: &[Builtin] = &[
+    Builtin {
+        name: "quadratic",
+        key: None,
+        description: "real roots of S3·x² + S2·x + S1",
+        validate: validate_quadratic,
+        run: Run::Ternary(|s, a, b, c| {
+            for root in math::real_roots(&[a, b, c], s.precision) {
+                s.push_front(root);
+            }
+        }),
+    },
+    Builtin {
+        name: "roots",
+        key: None,
+        description: "real roots of the polynomial of degree S1, coefficients below it",
+        validate: any,
+        run: Run::Stack(|s| {
+            let args = polynomial(s, 0)?;
+            if args[0].is_zero() {
+                return invalid("the leading coefficient must be non-zero");
+            }
+            let roots = math::real_roots(&args, s.precision);
+            if roots.is_empty() {
+                return invalid("no real roots");
+            }
+            for root in roots {
+                s.push_front(root);
+            }
+            Ok(())
+        }),
+    },
+    Builtin {
+        name: "poly",
+        key: None,
+        description: "value at S1 of the polynomial of degree S2, coefficients below it",
+        validate: any,
+        run: Run::Stack(|s| {
+            let x = s.stack.front().ok_or(StackError::MissingValue(2))?.clone();
+            let args = polynomial(s, 1)?;
+            let (value, _) = math::horner(&args, &x, s.precision);
+            s.push_front(math::trim(value));
+            Ok(())
+        }),
+    },
+];
+
+/// Take a polynomial off the stack: its degree, `skip` values below the top,
+/// and its coefficients below, the highest degree first (deepest). The
+/// values above the degree are dropped too.
+fn polynomial(s: &mut InstantStack, skip: usize) -> Result<Vec<BigDecimal>, StackError> {
+    let degree = s
+        .stack
+        .get(skip)
+        .ok_or(StackError::MissingValue(skip + 1))?;
+    let Some(degree) = degree
+        .to_usize()
+        .filter(|d| degree.is_integer() && (1..=MAX_DEGREE).contains(d))
+    else {
+        return Err(StackError::InvalidArgument(format!(
+            "the degree must be an integer between 1 and {MAX_DEGREE}"
+        )));
+    };
+    let len = skip + degree + 2;
+    if s.stack.len() < len {
+        return Err(StackError::MissingValue(len));
+    }
+    Ok(s.stack.drain(0..len).skip(skip + 1).rev().collect())
+}
+
+fn validate_quadratic(s: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    let [a, b, c] = args else { unreachable!() };
+    if a.is_zero() {
+        return invalid("element 3 must be non-zero");
+    }
+    let discriminant = b * b - BigDecimal::from(4) * a * c;
+    if discriminant >= BigDecimal::zero() {
+        return Ok(());
+    }
+    // Flag complex roots with their value, as they can't go on the stack.
+    let show = |x: BigDecimal| {
+        math::trim(x.with_scale_round(s.precision as i64, RoundingMode::HalfEven)).to_string()
+    };
+    let twice = BigDecimal::from(2) * a;
+    let re = math::div(&-b, &twice, s.precision);
+    let im = math::div(
+        &math::sqrt(&-discriminant, s.precision).unwrap(),
+        &twice.abs(),
+        s.precision,
+    );
+    Err(StackError::InvalidArgument(format!(
+        "complex roots {} ± {}i",
+        show(re),
+        show(im)
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    /// Run the operation on the arguments, given in their natural order, and
+    /// return the stack, top first.
+    fn run(name: &str, args: &[&str]) -> Result<Vec<String>, StackError> {
+        let values = args.iter().rev().map(|a| BigDecimal::from_str(a).unwrap());
+        let mut s = Stack::from(values.collect(), Some(4));
+        s.apply(builtin(name).unwrap())?;
+        Ok(s.snapshot().iter().map(|v| v.to_string()).collect())
+    }
+
+    #[test]
+    fn quadratic() -> Result<(), StackError> {
+        assert_eq!(run("quadratic", &["1", "-3", "2"])?, vec!["2", "1"]);
+        assert_eq!(run("quadratic", &["1", "2", "1"])?, vec!["-1"]);
+        assert_eq!(
+            run("quadratic", &["1", "0", "-2"])?,
+            vec!["1.4142", "-1.4142"]
+        );
+        assert_eq!(
+            run("quadratic", &["1", "1", "1"]),
+            Err(StackError::InvalidArgument(
+                "complex roots -0.5 ± 0.866i".into()
+            ))
+        );
+        assert!(run("quadratic", &["0", "1", "1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn evaluation() -> Result<(), StackError> {
+        // 2x² - 3x + 1 at 2 and 0.5, 9 stays below.
+        assert_eq!(
+            run("poly", &["9", "2", "-3", "1", "2", "2"])?,
+            vec!["3", "9"]
+        );
+        assert_eq!(run("poly", &["2", "-3", "1", "2", "0.5"])?, vec!["0"]);
+        assert_eq!(run("poly", &["4", "1", "1", "0.5"])?, vec!["3"]);
+        assert!(run("poly", &["1", "-3", "1", "1.5", "2"]).is_err());
+        assert_eq!(run("poly", &["1", "2"]), Err(StackError::MissingValue(4)));
+        Ok(())
+    }
+
+    #[test]
+    fn polynomial() -> Result<(), StackError> {
+        // 7 stays below the coefficients of x³ - 7x + 6.
+        let roots = run("roots", &["7", "1", "0", "-7", "6", "3"])?;
+        assert_eq!(roots, vec!["2", "1", "-3", "7"]);
+        assert_eq!(run("roots", &["2", "-1", "1"])?, vec!["0.5"]);
+        assert!(run("roots", &["1", "0", "1", "2"]).is_err());
+        assert!(run("roots", &["1", "2", "2"]).is_err());
+        assert!(run("roots", &["0", "1", "1"]).is_err());
+        assert!(run("roots", &["1", "1", "1.5"]).is_err());
+        Ok(())
+    }
+}
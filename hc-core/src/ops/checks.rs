@@ -0,0 +1,93 @@
+//! Check digits, e.g. of credit card numbers or IBANs: whether a number is
+//! valid, and the check digits it should have.
+use bigdecimal::{num_bigint::BigInt, BigDecimal, Signed};
+
+use super::{invalid, Builtin, Run};
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const CHECKS: &[Builtin] = &[
+    Builtin {
+        name: "luhn",
+        key: None,
+        description: "1 if the last digit of S1 is its Luhn check digit, else 0, then that digit",
+        validate: validate_natural,
+        run: Run::Unary(|s, n| {
+            let n = integer(&n);
+            let check = luhn(&n / 10);
+            s.push_front(BigDecimal::from(u32::from(n % 10 == check.into())));
+            s.push_front(BigDecimal::from(check));
+        }),
+    },
+    Builtin {
+        name: "mod97",
+        key: None,
+        description:
+            "1 if S1 is valid under ISO 7064 mod 97-10 (IBAN), else 0, then its check digits",
+        validate: validate_natural,
+        run: Run::Unary(|s, n| {
+            let n = integer(&n);
+            let valid = &n % 97u32 == BigInt::from(1);
+            // The check digits are the last two.
+            let check = 98 - (&n - &n % 100u32) % 97u32;
+            s.push_front(BigDecimal::from(u32::from(valid)));
+            s.push_front(BigDecimal::from(check));
+        }),
+    },
+];
+
+fn integer(n: &BigDecimal) -> BigInt {
+    n.with_scale(0).into_bigint_and_exponent().0
+}
+
+/// The digit to append to `payload` for it to pass the Luhn check: from the
+/// right, every other digit is doubled, the digits of the doubles summed.
+fn luhn(payload: BigInt) -> u32 {
+    let digits = payload.to_string();
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .map(|b| (b - b'0') as u32)
+        .enumerate()
+        .map(|(i, d)| match i % 2 {
+            0 if d > 4 => d * 2 - 9,
+            0 => d * 2,
+            _ => d,
+        })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+fn validate_natural(_: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    match args[0].is_integer() && !args[0].is_negative() {
+        true => Ok(()),
+        false => invalid("element 1 must be a non-negative integer"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::BigDecimal;
+
+    fn check(n: &str, op: &str) -> Result<Vec<BigDecimal>, StackError> {
+        let mut s = Stack::from(vec![n.parse().unwrap()], None);
+        s.apply(builtin(op).unwrap())?;
+        Ok(s.snapshot())
+    }
+
+    #[test]
+    fn check_digits() -> Result<(), StackError> {
+        // Check digit first, validity below.
+        assert_eq!(check("79927398713", "luhn")?, [3, 1].map(BigDecimal::from));
+        assert_eq!(check("79927398710", "luhn")?, [3, 0].map(BigDecimal::from));
+        assert_eq!(check("0", "luhn")?, [0, 1].map(BigDecimal::from));
+        // GB82 WEST 1234 5698 7654 32, rearranged with its letters as numbers.
+        let iban = "3214282912345698765432161182";
+        assert_eq!(check(iban, "mod97")?, [82, 1].map(BigDecimal::from));
+        let iban = "3214282912345698765432161100";
+        assert_eq!(check(iban, "mod97")?, [82, 0].map(BigDecimal::from));
+        assert!(check("-1", "luhn").is_err());
+        Ok(())
+    }
+}
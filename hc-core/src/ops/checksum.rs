@@ -0,0 +1,99 @@
+//! Checksums of the bytes of an integer, most significant first, to
+//! spot-check values while debugging binary protocols.
+use bigdecimal::{BigDecimal, Signed};
+
+use super::{invalid, Builtin, Run};
+use crate::format::twos_complement;
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const CHECKSUM: &[Builtin] = &[
+    Builtin {
+        name: "crc32",
+        key: None,
+        description: "CRC-32 of the bytes of the integer S1, in the word size if any",
+        validate: validate_integer,
+        run: Run::Unary(|s, n| s.push_front(crc32(&bytes(&n, s.word_size)).into())),
+    },
+    Builtin {
+        name: "adler32",
+        key: None,
+        description: "Adler-32 of the bytes of the integer S1, in the word size if any",
+        validate: validate_integer,
+        run: Run::Unary(|s, n| s.push_front(adler32(&bytes(&n, s.word_size)).into())),
+    },
+];
+
+/// The bytes of the integer, most significant first: as few as hold it, or
+/// those of the word. Negatives are in two's complement.
+fn bytes(n: &BigDecimal, word_size: Option<u32>) -> Vec<u8> {
+    let (int, _) = n.with_scale(0).into_bigint_and_exponent();
+    // A sign bit is needed for negatives.
+    let needed = match int.is_negative() {
+        true => (-&int - 1u32).bits() + 1,
+        false => int.bits(),
+    };
+    let bytes = needed.max(word_size.unwrap_or(0) as u64).max(1).div_ceil(8) as usize;
+    let int = match int.is_negative() {
+        true => {
+            twos_complement(n, bytes as u32 * 8)
+                .expect("the word holds the integer")
+                .with_scale(0)
+                .into_bigint_and_exponent()
+                .0
+        }
+        false => int,
+    };
+    let (_, digits) = int.to_bytes_be();
+    let mut padded = vec![0; bytes.saturating_sub(digits.len())];
+    padded.extend(digits);
+    padded
+}
+
+/// CRC-32 as in zip, PNG or Ethernet.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| match crc & 1 {
+            1 => crc >> 1 ^ 0xedb8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+/// Adler-32 as in zlib.
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), &byte| {
+        let a = (a + byte as u32) % MOD;
+        (a, (b + a) % MOD)
+    });
+    b << 16 | a
+}
+
+fn validate_integer(_: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    match args[0].is_integer() {
+        true => Ok(()),
+        false => invalid("element 1 must be an integer"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::num_bigint::{BigInt, Sign};
+
+    #[test]
+    fn checksums() {
+        // "123456789", the usual check value.
+        let check = BigDecimal::from(BigInt::from_bytes_be(Sign::Plus, b"123456789"));
+        assert_eq!(crc32(&bytes(&check, None)), 0xcbf4_3926);
+        assert_eq!(adler32(&bytes(&check, None)), 0x091e_01de);
+        // Padded to the word, negatives in two's complement.
+        assert_eq!(bytes(&BigDecimal::from(0), None), [0]);
+        assert_eq!(
+            bytes(&BigDecimal::from(0x1234), Some(32)),
+            [0, 0, 0x12, 0x34]
+        );
+        assert_eq!(bytes(&BigDecimal::from(-2), None), [0xfe]);
+        assert_eq!(bytes(&BigDecimal::from(-2), Some(16)), [0xff, 0xfe]);
+    }
+}
@@ -0,0 +1,81 @@
+//! 24-bit RGB colors, e.g. `#ff8800`, split into their channels for quick
+//! color math, e.g. blending or scaling, then put back together.
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive};
+
+use super::{invalid, Builtin, Run};
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const COLOR: &[Builtin] = &[
+    Builtin {
+        name: "rgb",
+        key: None,
+        description: "color of the red S3, green S2 and blue S1 channels (0–255)",
+        validate: |_, args| match args.iter().all(|c| *c >= 0 && *c <= 255) {
+            true => Ok(()),
+            false => invalid("channels must be between 0 and 255"),
+        },
+        run: Run::Ternary(|s, r, g, b| {
+            let color = [r, g, b].iter().fold(0, |color, c| color << 8 | channel(c));
+            s.push_front(BigDecimal::from(color));
+        }),
+    },
+    Builtin {
+        name: "unrgb",
+        key: None,
+        description: "split the color S1 into its red, green and blue channels",
+        validate: validate_color,
+        run: Run::Unary(|s, color| {
+            let color = color.to_u32().unwrap();
+            for shift in [16, 8, 0] {
+                s.push_front(BigDecimal::from(color >> shift & 0xff));
+            }
+        }),
+    },
+];
+
+/// The channel as a byte, rounded to the nearest, e.g. after scaling.
+fn channel(c: &BigDecimal) -> u32 {
+    c.with_scale_round(0, RoundingMode::HalfUp)
+        .to_u32()
+        .unwrap()
+}
+
+fn validate_color(_: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    let color = &args[0];
+    match color.is_integer() && *color >= 0 && *color <= 0xffffff {
+        true => Ok(()),
+        false => invalid("the color must be an integer between 0 and 0xffffff"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::number::parse;
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError, DEFAULT_PRECISION};
+    use bigdecimal::BigDecimal;
+
+    #[test]
+    fn channels() -> Result<(), StackError> {
+        let mut s = Stack::from(vec![parse("#ff8800", DEFAULT_PRECISION).unwrap()], None);
+        s.apply(builtin("unrgb").unwrap())?;
+        assert_eq!(s.snapshot(), [0, 136, 255].map(BigDecimal::from));
+        // Half the green, rounded.
+        let mut s = Stack::from(
+            vec![
+                0.into(),
+                parse("68.5", DEFAULT_PRECISION).unwrap(),
+                255.into(),
+            ],
+            None,
+        );
+        s.apply(builtin("rgb").unwrap())?;
+        assert_eq!(
+            s.snapshot(),
+            [parse("0xff4500", DEFAULT_PRECISION).unwrap()]
+        );
+        let mut s = Stack::from(vec![256.into(), 0.into(), 0.into()], None);
+        assert!(s.apply(builtin("rgb").unwrap()).is_err());
+        Ok(())
+    }
+}
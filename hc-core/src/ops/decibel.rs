@@ -0,0 +1,101 @@
+//! Decibels, for audio and RF work: 10·log10 of power ratios, 20·log10 of
+//! amplitude ratios, e.g. voltages.
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+
+use super::{invalid, Builtin, Run};
+use crate::math;
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const DECIBEL: &[Builtin] = &[
+    Builtin {
+        name: "ratio_to_db",
+        key: None,
+        description: "power ratio S1 in decibels",
+        validate: validate_ratio,
+        run: Run::Unary(|s, ratio| s.push_front(to_db(s, &ratio, 10))),
+    },
+    Builtin {
+        name: "db_to_ratio",
+        key: None,
+        description: "power ratio of S1 decibels",
+        validate: |s, args| validate_db(s, &args[0], 10),
+        run: Run::Unary(|s, db| s.push_front(from_db(s, &db, 10))),
+    },
+    Builtin {
+        name: "amplitude_to_db",
+        key: None,
+        description: "amplitude ratio S1, e.g. of voltages, in decibels",
+        validate: validate_ratio,
+        run: Run::Unary(|s, ratio| s.push_front(to_db(s, &ratio, 20))),
+    },
+    Builtin {
+        name: "db_to_amplitude",
+        key: None,
+        description: "amplitude ratio, e.g. of voltages, of S1 decibels",
+        validate: |s, args| validate_db(s, &args[0], 20),
+        run: Run::Unary(|s, db| s.push_front(from_db(s, &db, 20))),
+    },
+];
+
+/// `factor`·log10(ratio).
+fn to_db(s: &InstantStack, ratio: &BigDecimal, factor: u32) -> BigDecimal {
+    let log = math::div(
+        &math::ln(ratio, s.precision + 2),
+        &math::ln(&10.into(), s.precision + 2),
+        s.precision + 2,
+    );
+    math::settle(log * BigDecimal::from(factor), s.precision)
+}
+
+/// 10^(db / `factor`).
+fn from_db(s: &InstantStack, db: &BigDecimal, factor: u32) -> BigDecimal {
+    let exponent = db / BigDecimal::from(factor);
+    math::settle(math::pow10(&exponent, s.precision), s.precision)
+}
+
+fn validate_ratio(_: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    match args[0] > BigDecimal::zero() {
+        true => Ok(()),
+        false => invalid("element 1 must be positive"),
+    }
+}
+
+/// Check the result, of about ±db / `factor` · 3.32 bits, fits.
+fn validate_db(s: &InstantStack, db: &BigDecimal, factor: u32) -> Result<(), StackError> {
+    let bits =
+        db.to_f64().map_or(f64::INFINITY, f64::abs) / factor as f64 * std::f64::consts::LOG2_10;
+    // Even without a cap, the exponent must fit a machine integer.
+    let max_bits = s.max_bits.unwrap_or(u32::MAX as u64);
+    match bits > max_bits as f64 {
+        true => Err(StackError::InvalidArgument(format!(
+            "result over {max_bits} bits"
+        ))),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+
+    fn run(op: &str, x: &str) -> Result<String, StackError> {
+        let mut s = Stack::from(vec![x.parse().unwrap()], Some(6));
+        s.apply(builtin(op).unwrap())?;
+        Ok(s.snapshot()[0].to_string())
+    }
+
+    #[test]
+    fn decibels() -> Result<(), StackError> {
+        assert_eq!(run("ratio_to_db", "100")?, "20");
+        assert_eq!(run("ratio_to_db", "2")?, "3.010299");
+        assert_eq!(run("amplitude_to_db", "0.1")?, "-20");
+        assert_eq!(run("db_to_ratio", "30")?, "1000");
+        assert_eq!(run("db_to_ratio", "-3")?, "0.501187");
+        assert_eq!(run("db_to_amplitude", "6")?, "1.995262");
+        assert!(run("ratio_to_db", "0").is_err());
+        assert!(run("db_to_ratio", "1e9").is_err());
+        assert!(run("db_to_ratio", "-1e30").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+//! Fast mode: the common operations computed with f64 rather than exactly,
+//! for instant results when about 16 significant digits are plenty.
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::{f64::consts::PI, str::FromStr};
+
+use super::{any, Angle, Builtin, Run};
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const FAST: &[Builtin] = &[Builtin {
+    name: "fast",
+    key: None,
+    description: "toggle fast mode: f64 arithmetic, to about 16 digits",
+    validate: any,
+    run: Run::Nullary(|s| s.fast = !s.fast),
+}];
+
+/// The result of the operation in f64, or None if it has no fast version.
+/// The arguments are in their natural order, i.e. S1 last.
+pub(super) fn run(
+    s: &InstantStack,
+    name: &str,
+    args: &[BigDecimal],
+) -> Option<Result<BigDecimal, StackError>> {
+    let args: Vec<f64> = args
+        .iter()
+        .map(|a| a.to_f64().unwrap_or(f64::NAN))
+        .collect();
+    // Radians per unit of angle.
+    let unit = match s.angle {
+        Angle::Radians => 1.0,
+        Angle::Degrees => PI / 180.0,
+        Angle::Gradians => PI / 200.0,
+    };
+    let result = match (name, args.as_slice()) {
+        ("add", &[a, b]) => a + b,
+        ("sub", &[a, b]) => a - b,
+        ("mul", &[a, b]) => a * b,
+        ("div", &[a, b]) => a / b,
+        ("pow", &[a, b]) => a.powf(b),
+        ("sqrt", &[a]) => a.sqrt(),
+        ("sin", &[a]) => (a * unit).sin(),
+        ("cos", &[a]) => (a * unit).cos(),
+        ("tan", &[a]) => (a * unit).tan(),
+        ("asin", &[a]) => a.asin() / unit,
+        ("acos", &[a]) => a.acos() / unit,
+        ("atan", &[a]) => a.atan() / unit,
+        ("pi", &[]) => PI,
+        _ => return None,
+    };
+    Some(match result.is_finite() {
+        // The shortest decimal which reads back as the same f64.
+        true => Ok(BigDecimal::from_str(&result.to_string()).expect("finite floats are decimals")),
+        false => Err(StackError::InvalidArgument(
+            "the result is out of the range of fast mode".into(),
+        )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    /// Run the operations in fast mode on the values, and return the top of
+    /// the stack.
+    fn run(values: &[&str], ops: &[&str]) -> Result<String, StackError> {
+        let values = values
+            .iter()
+            .rev()
+            .map(|v| BigDecimal::from_str(v).unwrap());
+        let mut s = Stack::from(values.collect(), None);
+        s.apply(builtin("fast").unwrap())?;
+        for op in ops {
+            s.apply(builtin(op).unwrap())?;
+        }
+        Ok(s.snapshot()[0].to_string())
+    }
+
+    #[test]
+    fn floats() -> Result<(), StackError> {
+        assert_eq!(run(&["0.1", "0.2"], &["add"])?, "0.300000000000");
+        assert_eq!(run(&["2"], &["sqrt"])?, "1.414213562373");
+        assert_eq!(run(&["7", "2"], &["sub"])?, "5");
+        assert_eq!(run(&["30"], &["deg", "sin"])?, "0.499999999999");
+        assert_eq!(run(&["1", "3"], &["div", "fast", "pi"])?, "3.141592653589");
+        // Operations without a fast version are computed exactly.
+        assert_eq!(run(&["10"], &["fib"])?, "55");
+        assert!(run(&["10", "400"], &["pow"]).is_err());
+        assert!(run(&["1", "0"], &["div"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+//! Time value of money, and pricing. Rates are fractions so that they can be
+//! typed as percentages, e.g. `5%`, and are per period where it applies.
+//!
+//! Cash flows are read from the whole stack, the first one (at period 0)
+//! being the deepest.
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+
+use super::{any, invalid, Builtin, Run};
+use crate::math;
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const FINANCE: &[Builtin] = &[
+    Builtin {
+        name: "fv",
+        key: None,
+        description: "future value of S3 at rate S2 per period over S1 periods",
+        validate: validate_growth,
+        run: Run::Ternary(|s, pv, rate, n| {
+            let growth = growth(s, &rate, &n);
+            s.push_front(math::trim(pv * growth));
+        }),
+    },
+    Builtin {
+        name: "pv",
+        key: None,
+        description: "present value of S3 at rate S2 per period over S1 periods",
+        validate: validate_growth,
+        run: Run::Ternary(|s, fv, rate, n| {
+            let growth = growth(s, &rate, &n);
+            let pv = math::div(&fv, &growth, s.precision);
+            s.push_front(pv);
+        }),
+    },
+    Builtin {
+        name: "pmt",
+        key: None,
+        description: "payment per period of a loan of S3 at rate S2 over S1 periods",
+        validate: validate_loan,
+        run: Run::Ternary(|s, principal, rate, n| {
+            let pmt = payment(s, &principal, &rate, &n);
+            s.push_front(pmt);
+        }),
+    },
+    Builtin {
+        name: "balance",
+        key: None,
+        description: "balance of a loan of S4 at rate S3 over S2 periods after S1 payments",
+        validate: any,
+        run: Run::Stack(|s| {
+            if s.stack.len() < 4 {
+                return Err(StackError::MissingValue(4));
+            }
+            let args: Vec<BigDecimal> = s.stack.drain(0..4).rev().collect();
+            let [principal, rate, n, k] = &args[..] else {
+                unreachable!()
+            };
+            validate_loan(s, &args[..3])?;
+            if !k.is_integer() || k < &BigDecimal::zero() || k > n {
+                return invalid("the number of payments must be an integer between 0 and S2");
+            }
+            // What is owed after k periods, minus the payments and their
+            // interest.
+            let pmt = payment(s, principal, rate, n);
+            let growth = growth(s, rate, k);
+            let paid = match rate.is_zero() {
+                true => pmt * k,
+                false => math::div(&(pmt * (&growth - 1)), rate, s.precision),
+            };
+            s.push_front(math::settle(principal * growth - paid, s.precision));
+            Ok(())
+        }),
+    },
+    Builtin {
+        name: "add_tax",
+        key: None,
+        description: "add tax at rate S1 to S2",
+        validate: validate_rate,
+        run: Run::Binary(|s, price, rate| s.push_front(math::trim(price * (1 + rate)))),
+    },
+    Builtin {
+        name: "remove_tax",
+        key: None,
+        description: "remove tax at rate S1 included in S2",
+        validate: validate_rate,
+        run: Run::Binary(|s, price, rate| {
+            let net = math::div(&price, &(1 + rate), s.precision);
+            s.push_front(net);
+        }),
+    },
+    Builtin {
+        name: "markup_to_margin",
+        key: None,
+        description: "margin for the markup S1",
+        validate: validate_rate,
+        run: Run::Unary(|s, markup| {
+            let margin = math::div(&markup, &(1 + &markup), s.precision);
+            s.push_front(margin);
+        }),
+    },
+    Builtin {
+        name: "margin_to_markup",
+        key: None,
+        description: "markup for the margin S1",
+        validate: |_, args| match args[0] < 1 {
+            true => Ok(()),
+            false => invalid("the margin must be under 100%"),
+        },
+        run: Run::Unary(|s, margin| {
+            let markup = math::div(&margin, &(1 - &margin), s.precision);
+            s.push_front(markup);
+        }),
+    },
+    Builtin {
+        name: "npv",
+        key: None,
+        description: "net present value at rate S1 of the cash flows on the stack",
+        validate: any,
+        run: Run::Stack(|s| {
+            let rate = s.pop_front().ok_or(StackError::MissingValue(2))?;
+            if s.stack.is_empty() {
+                return Err(StackError::MissingValue(2));
+            }
+            if rate <= -1 {
+                return invalid("the rate must be over -100%");
+            }
+            let x = math::div(&BigDecimal::from(1), &(1 + rate), s.precision);
+            let (npv, _) = math::horner(&s.stack, &x, s.precision);
+            s.stack.clear();
+            s.push_front(math::trim(npv));
+            Ok(())
+        }),
+    },
+    Builtin {
+        name: "irr",
+        key: None,
+        description: "internal rate of return of the cash flows on the stack",
+        validate: any,
+        run: Run::Stack(|s| {
+            if s.stack.len() < 2 {
+                return Err(StackError::MissingValue(2));
+            }
+            let zero = BigDecimal::zero();
+            if !(s.stack.iter().any(|v| *v > zero) && s.stack.iter().any(|v| *v < zero)) {
+                return invalid("the cash flows must change sign");
+            }
+            // Solve for x = 1 / (1 + rate), the root of the polynomial whose
+            // coefficients are the cash flows, starting from a zero rate.
+            let x = math::newton(
+                |x| math::horner(&s.stack, x, s.precision),
+                BigDecimal::from(1),
+                s.precision,
+            );
+            let Some(x) = x.filter(|x| *x > zero) else {
+                return invalid("no internal rate of return found");
+            };
+            let irr = math::div(&BigDecimal::from(1), &x, s.precision) - 1;
+            s.stack.clear();
+            s.push_front(math::settle(irr, s.precision));
+            Ok(())
+        }),
+    },
+];
+
+/// The growth factor (1 + rate)^n.
+fn growth(s: &InstantStack, rate: &BigDecimal, n: &BigDecimal) -> BigDecimal {
+    let base = 1 + rate;
+    math::powi(&base, n.to_i64().unwrap(), s.precision)
+}
+
+/// The payment per period repaying the principal over n periods.
+fn payment(
+    s: &InstantStack,
+    principal: &BigDecimal,
+    rate: &BigDecimal,
+    n: &BigDecimal,
+) -> BigDecimal {
+    if rate.is_zero() {
+        return math::div(principal, n, s.precision);
+    }
+    // principal × rate / (1 - (1 + rate)^-n)
+    let discount = 1 - math::div(&BigDecimal::from(1), &growth(s, rate, n), s.precision);
+    math::settle(
+        math::div(&(principal * rate), &discount, s.precision),
+        s.precision,
+    )
+}
+
+/// Check the principal, rate and number of periods of a loan.
+fn validate_loan(s: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    validate_growth(s, args)?;
+    match args[2] > BigDecimal::zero() {
+        true => Ok(()),
+        false => invalid("the number of periods must be positive"),
+    }
+}
+
+/// Check the rate, the last argument.
+fn validate_rate(_: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    match args[args.len() - 1] > -1 {
+        true => Ok(()),
+        false => invalid("the rate must be over -100%"),
+    }
+}
+
+/// Check the rate and number of periods, the last two arguments.
+fn validate_growth(s: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    let [.., rate, n] = args else { unreachable!() };
+    validate_rate(s, &args[..args.len() - 1])?;
+    let Some(periods) = n.to_i64().filter(|_| n.is_integer()) else {
+        return invalid("the number of periods must be an integer");
+    };
+    let base = 1 + rate;
+    match s.max_bits {
+        Some(max_bits) if math::pow_bits(&base, periods) > max_bits as f64 => Err(
+            StackError::InvalidArgument(format!("result over {max_bits} bits")),
+        ),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    /// Run the operation on the arguments, given in their natural order, and
+    /// return the result displayed with 2 digits.
+    fn run(name: &str, args: &[&str]) -> Result<String, StackError> {
+        let values = args.iter().rev().map(|a| BigDecimal::from_str(a).unwrap());
+        let mut s = Stack::from(values.collect(), Some(2));
+        s.apply(builtin(name).unwrap())?;
+        Ok(s.snapshot()[0].to_string())
+    }
+
+    #[test]
+    fn future_and_present_value() -> Result<(), StackError> {
+        assert_eq!(run("fv", &["1000", "0.05", "2"])?, "1102.5");
+        assert_eq!(run("pv", &["1102.5", "0.05", "2"])?, "1000");
+        assert_eq!(run("fv", &["1000", "0", "12"])?, "1000");
+        assert_eq!(run("pv", &["100", "0.1", "1"])?, "90.90");
+        assert_eq!(run("fv", &["100", "0.1", "-1"])?, "90.90");
+        Ok(())
+    }
+
+    #[test]
+    fn loans() -> Result<(), StackError> {
+        assert_eq!(run("pmt", &["200000", "0.005", "360"])?, "1199.10");
+        assert_eq!(run("pmt", &["1200", "0", "12"])?, "100");
+        assert_eq!(run("balance", &["1200", "0", "12", "3"])?, "900");
+        assert_eq!(run("balance", &["200000", "0.005", "360", "0"])?, "200000");
+        assert_eq!(run("balance", &["200000", "0.005", "360", "360"])?, "0");
+        assert_eq!(
+            run("balance", &["200000", "0.005", "360", "12"])?,
+            "197543.97"
+        );
+        assert!(run("pmt", &["1000", "0.01", "0"]).is_err());
+        assert!(run("balance", &["1000", "0.01", "12", "13"]).is_err());
+        assert!(run("balance", &["0.01", "12", "1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pricing() -> Result<(), StackError> {
+        assert_eq!(run("add_tax", &["100", "0.2"])?, "120");
+        assert_eq!(run("remove_tax", &["120", "0.2"])?, "100");
+        assert_eq!(run("markup_to_margin", &["0.25"])?, "0.2");
+        assert_eq!(run("margin_to_markup", &["0.2"])?, "0.25");
+        assert!(run("margin_to_markup", &["1"]).is_err());
+        assert!(run("remove_tax", &["120", "-1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cash_flows() -> Result<(), StackError> {
+        assert_eq!(run("npv", &["-100", "60", "60", "0.1"])?, "4.13");
+        assert_eq!(run("npv", &["100", "0"])?, "100");
+        assert_eq!(run("irr", &["-100", "60", "60"])?, "0.13");
+        assert_eq!(run("irr", &["-100", "110"])?, "0.1");
+        assert!(run("irr", &["100", "110"]).is_err());
+        assert!(run("npv", &["0.1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(run("fv", &["1000", "0.05", "2.5"]).is_err());
+        assert!(run("fv", &["1000", "-1", "2"]).is_err());
+        assert!(run("fv", &["1", "1000000", "1000000"]).is_err());
+        assert_eq!(
+            Stack::new().apply(builtin("pv").unwrap()),
+            Err(StackError::MissingValue(3))
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Network address arithmetic, on IP addresses entered as `192.168.1.10` or
+//! `2001:db8::1` and kept as integers: addresses up to 2^32 - 1 are IPv4,
+//! larger ones IPv6. `I` shows the integers back as addresses.
+use bigdecimal::{BigDecimal, ToPrimitive};
+
+use super::{invalid, Builtin, Run};
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const NET: &[Builtin] = &[
+    Builtin {
+        name: "network",
+        key: None,
+        description: "network address of the IP address S2 with the prefix length S1",
+        validate: validate_cidr,
+        run: Run::Binary(|s, address, prefix| {
+            let (address, _, mask) = cidr(&address, &prefix);
+            s.push_front(BigDecimal::from(address & mask));
+        }),
+    },
+    Builtin {
+        name: "broadcast",
+        key: None,
+        description: "last address of the network of the IP address S2 with the prefix length S1",
+        validate: validate_cidr,
+        run: Run::Binary(|s, address, prefix| {
+            let (address, bits, mask) = cidr(&address, &prefix);
+            s.push_front(BigDecimal::from(address | (!mask & ones(bits))));
+        }),
+    },
+    Builtin {
+        name: "netmask",
+        key: None,
+        description: "IPv4 netmask of the prefix length S1, e.g. 255.255.255.0 for 24",
+        validate: |_, args| prefix(&args[0], 32).map(|_| ()),
+        run: Run::Unary(|s, prefix| {
+            s.push_front(BigDecimal::from(mask(prefix.to_u32().unwrap(), 32)));
+        }),
+    },
+];
+
+/// The address, its width in bits and the mask of the prefix.
+fn cidr(address: &BigDecimal, prefix: &BigDecimal) -> (u128, u32, u128) {
+    let address = address.to_u128().unwrap();
+    let bits = width(address);
+    (address, bits, mask(prefix.to_u32().unwrap(), bits))
+}
+
+/// The width of the address in bits: 32 for IPv4, 128 for IPv6.
+fn width(address: u128) -> u32 {
+    match address <= u32::MAX as u128 {
+        true => 32,
+        false => 128,
+    }
+}
+
+/// The lowest `bits` bits set.
+fn ones(bits: u32) -> u128 {
+    u128::MAX >> (128 - bits)
+}
+
+/// The `prefix` highest of the `bits` bits set.
+fn mask(prefix: u32, bits: u32) -> u128 {
+    match prefix {
+        0 => 0,
+        _ => ones(bits) & !(ones(bits) >> prefix),
+    }
+}
+
+/// The prefix length, if valid for addresses of `bits` bits.
+fn prefix(prefix: &BigDecimal, bits: u32) -> Result<u32, StackError> {
+    match prefix.is_integer() && *prefix >= 0 && *prefix <= bits {
+        true => Ok(prefix.to_u32().unwrap()),
+        false => Err(StackError::InvalidArgument(format!(
+            "the prefix length must be an integer between 0 and {bits}"
+        ))),
+    }
+}
+
+fn validate_cidr(_: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    let [address, length] = args else {
+        unreachable!()
+    };
+    let Some(address) = address.is_integer().then(|| address.to_u128()).flatten() else {
+        return invalid("element 2 must be an IP address");
+    };
+    prefix(length, width(address)).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::number::parse;
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError, DEFAULT_PRECISION};
+
+    fn run(address: &str, prefix: u32, op: &str) -> Result<String, StackError> {
+        let mut s = Stack::from(
+            vec![prefix.into(), parse(address, DEFAULT_PRECISION).unwrap()],
+            None,
+        );
+        s.apply(builtin(op).unwrap())?;
+        Ok(s.snapshot()[0].to_string())
+    }
+
+    #[test]
+    fn networks() -> Result<(), StackError> {
+        // 192.168.1.0 and 192.168.1.255.
+        assert_eq!(run("192.168.1.10", 24, "network")?, "3232235776");
+        assert_eq!(run("192.168.1.10", 24, "broadcast")?, "3232236031");
+        assert_eq!(run("10.1.2.3", 0, "network")?, "0");
+        assert_eq!(run("10.1.2.3", 32, "broadcast")?, "167838211");
+        // 2001:db8:: and 2001:db8::ffff:ffff:ffff:ffff.
+        assert_eq!(
+            run("2001:db8::1", 64, "network")?,
+            "42540766411282592856903984951653826560"
+        );
+        assert_eq!(
+            run("2001:db8::1", 64, "broadcast")?,
+            "42540766411282592875350729025363378175"
+        );
+        assert!(run("10.1.2.3", 33, "network").is_err());
+        assert!(run("2001:db8::1", 129, "network").is_err());
+
+        let mut s = Stack::from(vec![24.into()], None);
+        s.apply(builtin("netmask").unwrap())?;
+        assert_eq!(
+            s.snapshot()[0],
+            parse("255.255.255.0", DEFAULT_PRECISION).unwrap()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! Number theory.
+use bigdecimal::{num_bigint::BigInt, BigDecimal, Signed, ToPrimitive, Zero};
+
+use super::{any, invalid, Builtin, Run};
+use crate::stack::{InstantStack, StackError};
+
+/// The most terms of a continued fraction.
+const MAX_TERMS: usize = 50;
+
+/// The most digits split off an integer, i.e. in base 2.
+const MAX_DIGITS: u64 = 10_000;
+
+pub(super) const NUMBERS: &[Builtin] = &[
+    Builtin {
+        name: "cf",
+        key: None,
+        description: "expand S1 into the terms of its continued fraction",
+        validate: any,
+        run: Run::Unary(|s, x| {
+            for term in continued_fraction(&x, s.precision) {
+                s.push_front(BigDecimal::from(term));
+            }
+        }),
+    },
+    Builtin {
+        name: "fib",
+        key: None,
+        description: "S1-th Fibonacci number",
+        validate: validate_index,
+        run: Run::Unary(|s, n| {
+            let (f, _) = fibonacci(n.to_u64().unwrap());
+            s.push_front(BigDecimal::from(f));
+        }),
+    },
+    Builtin {
+        name: "lucas",
+        key: None,
+        description: "S1-th Lucas number",
+        validate: validate_index,
+        run: Run::Unary(|s, n| {
+            // L(n) = 2·F(n + 1) - F(n)
+            let (f, f1) = fibonacci(n.to_u64().unwrap());
+            s.push_front(BigDecimal::from(2 * f1 - f));
+        }),
+    },
+    Builtin {
+        name: "digits",
+        key: None,
+        description: "split S2 into its digits in base S1, then push their count",
+        validate: |_, args| {
+            let [n, base] = args else { unreachable!() };
+            if !n.is_integer() || n.is_negative() {
+                return invalid("element 2 must be a non-negative integer");
+            }
+            if n.with_scale(0).into_bigint_and_exponent().0.bits() > MAX_DIGITS {
+                return Err(StackError::InvalidArgument(format!(
+                    "only up to {MAX_DIGITS} digits can be pushed"
+                )));
+            }
+            validate_base(base)
+        },
+        run: Run::Binary(|s, n, base| {
+            let digits = digits(n.with_scale(0).into_bigint_and_exponent().0, &base);
+            let count = digits.len();
+            for d in digits {
+                s.push_front(BigDecimal::from(d));
+            }
+            s.push_front(BigDecimal::from(count as u64));
+        }),
+    },
+    Builtin {
+        name: "undigits",
+        key: None,
+        description: "join the S2 digits below into a number in base S1",
+        validate: any,
+        run: Run::Stack(|s| {
+            if s.stack.len() < 2 {
+                return Err(StackError::MissingValue(2));
+            }
+            validate_base(&s.stack[0])?;
+            let count = &s.stack[1];
+            let Some(count) = count.to_usize().filter(|_| count.is_integer()) else {
+                return invalid("element 2 must be a non-negative integer");
+            };
+            if s.stack.len() < count + 2 {
+                return Err(StackError::MissingValue(count + 2));
+            }
+            let base = s.stack[0].clone();
+            let n = s
+                .stack
+                .drain(0..count + 2)
+                .skip(2)
+                .rev()
+                .fold(BigDecimal::zero(), |n, d| n * &base + d);
+            s.push_front(n);
+            Ok(())
+        }),
+    },
+];
+
+fn validate_base(base: &BigDecimal) -> Result<(), StackError> {
+    match base.is_integer() && *base >= 2 {
+        true => Ok(()),
+        false => invalid("the base must be an integer of at least 2"),
+    }
+}
+
+/// The digits of `n` in the base, most significant first.
+fn digits(mut n: BigInt, base: &BigDecimal) -> Vec<BigInt> {
+    let base = base.with_scale(0).into_bigint_and_exponent().0;
+    let mut digits = vec![];
+    while !n.is_zero() {
+        digits.push(&n % &base);
+        n /= &base;
+    }
+    if digits.is_empty() {
+        digits.push(BigInt::zero());
+    }
+    digits.reverse();
+    digits
+}
+
+/// Check the index of a Fibonacci or Lucas number, which have about
+/// 0.69·n bits.
+fn validate_index(s: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    let n = &args[0];
+    let Some(n) = n.to_u64().filter(|_| n.is_integer()) else {
+        return invalid("element 1 must be a non-negative integer");
+    };
+    match s.max_bits {
+        Some(max_bits) if n as f64 * 0.6943 > max_bits as f64 => Err(StackError::InvalidArgument(
+            format!("result over {max_bits} bits"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// F(n) and F(n + 1), by fast doubling:
+///     F(2k) = F(k)·(2·F(k + 1) - F(k))
+///     F(2k + 1) = F(k)² + F(k + 1)²
+fn fibonacci(n: u64) -> (BigInt, BigInt) {
+    if n == 0 {
+        return (BigInt::zero(), BigInt::from(1));
+    }
+    let (a, b) = fibonacci(n / 2);
+    let c = &a * (2 * &b - &a);
+    let d = &a * &a + &b * &b;
+    match n % 2 {
+        0 => (c, d),
+        _ => (d.clone(), c + d),
+    }
+}
+
+/// The terms of the continued fraction of `x`, up to the first convergent
+/// within the precision of it.
+fn continued_fraction(x: &BigDecimal, precision: u64) -> Vec<BigInt> {
+    let x = match x.fractional_digit_count() < 0 {
+        true => x.with_scale(0),
+        false => x.clone(),
+    };
+    let (n, scale) = x.as_bigint_and_scale();
+    // x = n / d, expanded with Euclid's algorithm.
+    let (mut n, mut d) = (n.into_owned(), BigInt::from(10).pow(scale as u32));
+    let tolerance = BigDecimal::new(1.into(), precision as i64);
+    // The last two convergents h / k.
+    let (mut h, mut h1) = (BigInt::from(1), BigInt::zero());
+    let (mut k, mut k1) = (BigInt::zero(), BigInt::from(1));
+    let mut terms = vec![];
+    while !d.is_zero() && terms.len() < MAX_TERMS {
+        let mut a = &n / &d;
+        if n.is_negative() && !(&n % &d).is_zero() {
+            a -= 1;
+        }
+        (n, d) = (d.clone(), n - &a * d);
+        (h, h1) = (&a * &h + h1, h);
+        (k, k1) = (&a * &k + k1, k);
+        terms.push(a);
+        // |x - h / k| < tolerance
+        let error = (&x * BigDecimal::from(k.clone()) - BigDecimal::from(h.clone())).abs();
+        if error < &tolerance * BigDecimal::from(k.clone()) {
+            break;
+        }
+    }
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::builtin;
+    use crate::stack::Stack;
+    use std::str::FromStr;
+
+    fn cf(x: &str, precision: u64) -> Vec<i64> {
+        let x = BigDecimal::from_str(x).unwrap();
+        let terms = continued_fraction(&x, precision);
+        terms
+            .iter()
+            .map(|t| t.to_string().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fibonacci_and_lucas() {
+        let fib: Vec<String> = (0..10).map(|n| fibonacci(n).0.to_string()).collect();
+        assert_eq!(fib, ["0", "1", "1", "2", "3", "5", "8", "13", "21", "34"]);
+        assert_eq!(fibonacci(100).0.to_string(), "354224848179261915075");
+
+        let mut s = Stack::from(vec![BigDecimal::from(10)], None);
+        s.apply(builtin("lucas").unwrap()).unwrap();
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(123)]);
+        let mut s = Stack::from(vec![BigDecimal::from(10_000_000)], None);
+        assert!(s.apply(builtin("fib").unwrap()).is_err());
+    }
+
+    #[test]
+    fn digits_and_back() {
+        let mut s = Stack::from(vec![BigDecimal::from(10), BigDecimal::from(1987)], None);
+        s.apply(builtin("digits").unwrap()).unwrap();
+        assert_eq!(s.snapshot(), [4, 7, 8, 9, 1].map(BigDecimal::from));
+        // In base 16, 0x1987.
+        let mut s = Stack::from([16, 4, 7, 8, 9, 1].map(BigDecimal::from).to_vec(), None);
+        s.apply(builtin("undigits").unwrap()).unwrap();
+        assert_eq!(s.snapshot(), [BigDecimal::from(0x1987)]);
+
+        let mut s = Stack::from(vec![BigDecimal::from(2), BigDecimal::from(0)], None);
+        s.apply(builtin("digits").unwrap()).unwrap();
+        assert_eq!(s.snapshot(), [1, 0].map(BigDecimal::from));
+        let mut s = Stack::from(vec![BigDecimal::from(1), BigDecimal::from(5)], None);
+        assert!(s.apply(builtin("digits").unwrap()).is_err());
+        let mut s = Stack::from(vec![BigDecimal::from(10), BigDecimal::from(3)], None);
+        assert!(s.apply(builtin("undigits").unwrap()).is_err());
+    }
+
+    #[test]
+    fn continued_fractions() {
+        assert_eq!(cf("3.245", 12), vec![3, 4, 12, 4]);
+        assert_eq!(cf("-2.5", 12), vec![-3, 2]);
+        assert_eq!(cf("7", 12), vec![7]);
+        assert_eq!(cf("0.3333333333333333", 12), vec![0, 3]);
+        assert_eq!(cf("3.14159265358979", 4), vec![3, 7, 15]);
+        // √2, cut at the term cap.
+        let root = "1.4142135623730950488016887242096980785696718753769480731766797379907324784621070388503875343276415727";
+        let terms = cf(root, 100);
+        assert_eq!(terms.len(), MAX_TERMS);
+        assert!(terms[1..].iter().all(|&t| t == 2));
+    }
+}
@@ -0,0 +1,103 @@
+//! The whole stack as a series, the deepest value first, or as a dataset,
+//! e.g. for quick sanity checks of pasted time series or invoice amounts.
+use bigdecimal::BigDecimal;
+use std::collections::BTreeSet;
+
+use super::{any, Builtin, Run};
+use crate::stack::StackError;
+
+pub(super) const SERIES: &[Builtin] = &[
+    Builtin {
+        name: "cumsum",
+        key: None,
+        description: "replace the values on the stack with their running totals",
+        validate: any,
+        run: Run::Stack(|s| {
+            if s.stack.is_empty() {
+                return Err(StackError::MissingValue(1));
+            }
+            let mut total = BigDecimal::from(0);
+            for v in s.stack.iter_mut().rev() {
+                total += &*v;
+                v.clone_from(&total);
+            }
+            Ok(())
+        }),
+    },
+    Builtin {
+        name: "diff",
+        key: None,
+        description: "replace the values on the stack with the differences between them",
+        validate: any,
+        run: Run::Stack(|s| {
+            if s.stack.len() < 2 {
+                return Err(StackError::MissingValue(2));
+            }
+            let values: Vec<BigDecimal> = s.stack.drain(..).rev().collect();
+            for pair in values.windows(2) {
+                s.push_front(&pair[1] - &pair[0]);
+            }
+            Ok(())
+        }),
+    },
+    Builtin {
+        name: "dedup",
+        key: None,
+        description: "sort the values on the stack, the greatest on top, and drop duplicates",
+        validate: any,
+        run: Run::Stack(|s| {
+            let mut values: Vec<BigDecimal> = s.stack.drain(..).collect();
+            values.sort();
+            values.dedup();
+            s.stack.extend(values.into_iter().rev());
+            Ok(())
+        }),
+    },
+    Builtin {
+        name: "count_unique",
+        key: None,
+        description: "push the number of distinct values on the stack",
+        validate: any,
+        run: Run::Stack(|s| {
+            let distinct: BTreeSet<&BigDecimal> = s.stack.iter().collect();
+            let count = distinct.len();
+            s.push_front(BigDecimal::from(count as u64));
+            Ok(())
+        }),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::BigDecimal;
+
+    #[test]
+    fn totals_and_differences() -> Result<(), StackError> {
+        // 1, 2, 3, 4 from the bottom.
+        let mut s = Stack::from([4, 3, 2, 1].map(BigDecimal::from).to_vec(), None);
+        s.apply(builtin("cumsum").unwrap())?;
+        assert_eq!(s.snapshot(), [10, 6, 3, 1].map(BigDecimal::from));
+        s.apply(builtin("diff").unwrap())?;
+        assert_eq!(s.snapshot(), [4, 3, 2].map(BigDecimal::from));
+        let mut s = Stack::from(vec![BigDecimal::from(1)], None);
+        assert!(s.apply(builtin("diff").unwrap()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn distinct_values() -> Result<(), StackError> {
+        let values = ["12.5", "3", "12.50", "7", "3"].map(|v| v.parse().unwrap());
+        let mut s = Stack::from(values.to_vec(), None);
+        s.apply(builtin("count_unique").unwrap())?;
+        assert_eq!(s.snapshot()[0], BigDecimal::from(3));
+        let mut s = Stack::from(values.to_vec(), None);
+        s.apply(builtin("dedup").unwrap())?;
+        assert_eq!(
+            s.snapshot(),
+            ["12.5", "7", "3"].map(|v| v.parse::<BigDecimal>().unwrap())
+        );
+        Ok(())
+    }
+}
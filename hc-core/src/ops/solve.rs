@@ -0,0 +1,146 @@
+//! Root finding for any operation.
+use bigdecimal::{BigDecimal, Zero};
+use std::sync::Arc;
+
+use super::Operation;
+use crate::math;
+use crate::stack::{InstantStack, StackError};
+
+/// Finds a root of the function computed by another operation, which takes
+/// x from S1 and leaves f(x) in its place, with Newton's method starting from
+/// the guess in S1.
+pub struct Solve {
+    f: Arc<dyn Operation>,
+    description: String,
+}
+
+impl Solve {
+    pub fn new(f: Arc<dyn Operation>) -> Self {
+        let description = format!("root of {} near S1", f.name());
+        Solve { f, description }
+    }
+
+    /// f(x), computed on a copy of the stack so that f can use the values
+    /// below x and the registers.
+    fn eval(&self, s: &InstantStack, x: &BigDecimal) -> Result<BigDecimal, StackError> {
+        let mut scratch = s.clone();
+        scratch.push_front(x.clone());
+        self.f.execute(&mut scratch)?;
+        match scratch.pop_front() {
+            Some(y) => Ok(math::round(y, s.precision)),
+            None => Err(StackError::InvalidArgument(format!(
+                "{} left no value",
+                self.f.name()
+            ))),
+        }
+    }
+}
+
+impl Operation for Solve {
+    fn name(&self) -> &str {
+        "solve"
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn execute(&self, s: &mut InstantStack) -> Result<(), StackError> {
+        let mut x = s.pop_front().ok_or(StackError::MissingValue(1))?;
+        let (h, epsilon) = (math::step(s.precision), math::epsilon(s.precision));
+        for _ in 0..math::MAX_ITERATIONS {
+            let y = self.eval(s, &x)?;
+            if y.is_zero() {
+                break;
+            }
+            let slope = self.eval(s, &(&x + &h))? - self.eval(s, &(&x - &h))?;
+            if slope.is_zero() {
+                return Err(StackError::InvalidArgument(
+                    "the derivative vanishes, try another guess".into(),
+                ));
+            }
+            let delta = math::div(&(y * BigDecimal::from(2) * &h), &slope, s.precision);
+            x = math::round(x - &delta, s.precision);
+            if delta.abs() < epsilon {
+                s.push_front(math::settle(x, s.precision));
+                return Ok(());
+            }
+        }
+        if self.eval(s, &x)?.is_zero() {
+            s.push_front(math::settle(x, s.precision));
+            return Ok(());
+        }
+        Err(StackError::InvalidArgument("no root found".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::builtin;
+    use crate::stack::{Op, Stack};
+
+    /// x² - 2, as an operation.
+    struct Square;
+
+    impl Operation for Square {
+        fn name(&self) -> &str {
+            "square"
+        }
+
+        fn description(&self) -> &str {
+            "x² - 2"
+        }
+
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn apply(&self, args: &[BigDecimal]) -> Result<Vec<BigDecimal>, StackError> {
+            Ok(vec![&args[0] * &args[0] - 2])
+        }
+    }
+
+    #[test]
+    fn newton() -> Result<(), StackError> {
+        let solve = Op::Run(Arc::new(Solve::new(Arc::new(Square))));
+        let mut s = Stack::from(vec![BigDecimal::from(1)], Some(30));
+        s.apply(solve.clone())?;
+        assert_eq!(
+            s.snapshot()[0].to_string(),
+            "1.414213562373095048801688724209"
+        );
+        s.apply(Op::Push((-5).into()))?;
+        s.apply(solve.clone())?;
+        assert_eq!(
+            s.snapshot()[0].to_string(),
+            "-1.414213562373095048801688724209"
+        );
+        assert_eq!(s.snapshot().len(), 2);
+
+        // The derivative of x² - 2 is 0 at 0.
+        s.apply(Op::Push(0.into()))?;
+        assert!(s.apply(solve).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn errors_of_the_function() {
+        let solve = |name| {
+            Op::Run(Arc::new(Solve::new(match builtin(name) {
+                Some(Op::Run(op)) => op,
+                _ => unreachable!(),
+            })))
+        };
+        // Taking the square root of a negative number fails, and clearing
+        // leaves no value.
+        let mut s = Stack::from(vec![BigDecimal::from(-1)], None);
+        assert!(s.apply(solve("sqrt")).is_err());
+        assert!(s.apply(solve("clear")).is_err());
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(-1)]);
+    }
+}
@@ -0,0 +1,90 @@
+//! Timestamps and elapsed times, in seconds, e.g. for quick benchmarking or
+//! billing. `T` shows the durations as H:MM:SS.
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use std::time::Duration;
+
+use super::{any, invalid, Builtin, Run};
+use crate::stack::{InstantStack, StackError};
+
+pub(super) const TIME: &[Builtin] = &[
+    Builtin {
+        name: "now",
+        key: None,
+        description: "push the current time, in seconds since 1970",
+        validate: validate_clock,
+        run: Run::Nullary(|s| s.push_front(now().unwrap())),
+    },
+    Builtin {
+        name: "since",
+        key: None,
+        description: "replace the time S1 with the seconds elapsed since",
+        validate: validate_clock,
+        run: Run::Unary(|s, start| s.push_front(now().unwrap() - start)),
+    },
+    Builtin {
+        name: "elapsed",
+        key: None,
+        description: "seconds elapsed from the time S2 to the time S1",
+        validate: any,
+        run: Run::Binary(|s, start, end| s.push_front(end - start)),
+    },
+];
+
+/// The current time, to the millisecond, if the platform has a clock.
+fn now() -> Option<BigDecimal> {
+    let elapsed = clock()?;
+    Some(BigDecimal::new(BigInt::from(elapsed.as_millis()), 3).normalized())
+}
+
+/// The current time, in whole seconds since 1970, if the platform has a
+/// clock.
+pub fn seconds() -> Option<u64> {
+    clock().map(|elapsed| elapsed.as_secs())
+}
+
+/// The time since the epoch. Browsers have no system clock to read.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn clock() -> Option<Duration> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).ok()
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn clock() -> Option<Duration> {
+    None
+}
+
+fn validate_clock(_: &InstantStack, _: &[BigDecimal]) -> Result<(), StackError> {
+    match clock() {
+        Some(_) => Ok(()),
+        None => invalid("no clock on this platform"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
+    #[test]
+    fn stopwatch() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(builtin("now").unwrap())?;
+        assert!(s.snapshot()[0] > 1_700_000_000);
+        s.apply(builtin("since").unwrap())?;
+        let lap = s.snapshot()[0].clone();
+        assert!((BigDecimal::from(0)..BigDecimal::from(60)).contains(&lap));
+
+        let mut s = Stack::from(
+            vec![
+                BigDecimal::from_f64(1700003600.5).unwrap(),
+                BigDecimal::from(1700000000),
+            ],
+            None,
+        );
+        s.apply(builtin("elapsed").unwrap())?;
+        assert_eq!(s.snapshot(), [BigDecimal::from_f64(3600.5).unwrap()]);
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+//! Trigonometry, with angles in the mode of the stack.
+use bigdecimal::{num_bigint::Sign, BigDecimal, ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::{any, invalid, Builtin, Run};
+use crate::math;
+use crate::stack::{InstantStack, StackError};
+
+/// The unit of angles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Angle {
+    #[default]
+    #[serde(rename = "rad")]
+    Radians,
+    #[serde(rename = "deg")]
+    Degrees,
+    /// A right angle is 100 gradians, as used in surveying.
+    #[serde(rename = "grad")]
+    Gradians,
+}
+
+impl Angle {
+    /// A full turn, if it is a whole number of the unit.
+    fn turn(self) -> Option<BigDecimal> {
+        match self {
+            Angle::Radians => None,
+            Angle::Degrees => Some(360.into()),
+            Angle::Gradians => Some(400.into()),
+        }
+    }
+}
+
+impl fmt::Display for Angle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Angle::Radians => "rad",
+            Angle::Degrees => "deg",
+            Angle::Gradians => "grad",
+        })
+    }
+}
+
+pub(super) const TRIG: &[Builtin] = &[
+    Builtin {
+        name: "sin",
+        key: None,
+        description: "sine of S1",
+        validate: any,
+        run: Run::Unary(|s, x| {
+            let (sin, _) = sin_cos(s, &x);
+            s.push_front(math::settle(sin, s.precision));
+        }),
+    },
+    Builtin {
+        name: "cos",
+        key: None,
+        description: "cosine of S1",
+        validate: any,
+        run: Run::Unary(|s, x| {
+            let (_, cos) = sin_cos(s, &x);
+            s.push_front(math::settle(cos, s.precision));
+        }),
+    },
+    Builtin {
+        name: "tan",
+        key: None,
+        description: "tangent of S1",
+        validate: validate_tan,
+        run: Run::Unary(|s, x| {
+            let (sin, cos) = sin_cos(s, &x);
+            s.push_front(math::settle(
+                math::div(&sin, &cos, s.precision),
+                s.precision,
+            ));
+        }),
+    },
+    Builtin {
+        name: "asin",
+        key: None,
+        description: "arcsine of S1",
+        validate: validate_unit,
+        run: Run::Unary(|s, x| {
+            let angle = asin(&x, s.precision);
+            s.push_front(from_radians(s, angle));
+        }),
+    },
+    Builtin {
+        name: "acos",
+        key: None,
+        description: "arccosine of S1",
+        validate: validate_unit,
+        run: Run::Unary(|s, x| {
+            // acos(x) = π/2 - asin(x)
+            let angle = quarter_turn(s.precision) - asin(&x, s.precision);
+            s.push_front(from_radians(s, angle));
+        }),
+    },
+    Builtin {
+        name: "atan",
+        key: None,
+        description: "arctangent of S1",
+        validate: any,
+        run: Run::Unary(|s, x| {
+            let angle = math::atan(&x, s.precision);
+            s.push_front(from_radians(s, angle));
+        }),
+    },
+    Builtin {
+        name: "polar",
+        key: None,
+        description: "convert the point x S2, y S1 to its distance S2 and angle S1",
+        validate: any,
+        run: Run::Binary(|s, x, y| {
+            let r = math::sqrt(&(&x * &x + &y * &y), s.precision).unwrap();
+            let angle = atan2(&y, &x, s.precision);
+            s.push_front(math::settle(r, s.precision));
+            s.push_front(from_radians(s, angle));
+        }),
+    },
+    Builtin {
+        name: "rect",
+        key: None,
+        description: "convert the distance S2 and angle S1 to the point x S2, y S1",
+        validate: any,
+        run: Run::Binary(|s, r, angle| {
+            let (sin, cos) = sin_cos(s, &angle);
+            s.push_front(math::settle(&r * cos, s.precision));
+            s.push_front(math::settle(r * sin, s.precision));
+        }),
+    },
+    Builtin {
+        name: "pi",
+        key: None,
+        description: "push π",
+        validate: any,
+        run: Run::Nullary(|s| s.push_front(math::settle(math::pi(s.precision), s.precision))),
+    },
+    Builtin {
+        name: "rad",
+        key: None,
+        description: "use radians for angles",
+        validate: any,
+        run: Run::Nullary(|s| s.angle = Angle::Radians),
+    },
+    Builtin {
+        name: "deg",
+        key: None,
+        description: "use degrees for angles",
+        validate: any,
+        run: Run::Nullary(|s| s.angle = Angle::Degrees),
+    },
+    Builtin {
+        name: "grad",
+        key: None,
+        description: "use gradians for angles",
+        validate: any,
+        run: Run::Nullary(|s| s.angle = Angle::Gradians),
+    },
+];
+
+/// π/2, a quarter turn in radians.
+fn quarter_turn(precision: u64) -> BigDecimal {
+    math::pi(precision) / BigDecimal::from(2)
+}
+
+/// The arcsine of `x` in radians: atan(x / √(1 - x²)), or ±π/2 at ±1.
+fn asin(x: &BigDecimal, precision: u64) -> BigDecimal {
+    match math::sqrt(&(BigDecimal::from(1) - x * x), precision) {
+        Some(root) if !root.is_zero() => math::atan(&math::div(x, &root, precision), precision),
+        _ => quarter_turn(precision) * x,
+    }
+}
+
+/// The angle in radians of the point (x, y), from -π (excluded) to π.
+fn atan2(y: &BigDecimal, x: &BigDecimal, precision: u64) -> BigDecimal {
+    let half_turn = || math::pi(precision);
+    match (x.sign(), y.sign()) {
+        (Sign::NoSign, Sign::NoSign) => BigDecimal::zero(),
+        (Sign::NoSign, Sign::Plus) => quarter_turn(precision),
+        (Sign::NoSign, Sign::Minus) => -quarter_turn(precision),
+        (Sign::Plus, _) => math::atan(&math::div(y, x, precision), precision),
+        (Sign::Minus, Sign::Minus) => {
+            math::atan(&math::div(y, x, precision), precision) - half_turn()
+        }
+        (Sign::Minus, _) => math::atan(&math::div(y, x, precision), precision) + half_turn(),
+    }
+}
+
+/// The sine and cosine of `x` in the angle mode of the stack, before
+/// settling. Multiples of a quarter turn are exact in degrees and gradians,
+/// e.g. sin(180°) is 0.
+fn sin_cos(s: &InstantStack, x: &BigDecimal) -> (BigDecimal, BigDecimal) {
+    let Some(turn) = s.angle.turn() else {
+        return math::sin_cos(x, s.precision);
+    };
+    let x = x % &turn;
+    let quarter = &turn / BigDecimal::from(4);
+    if (&x % &quarter).is_zero() {
+        let quarters = (&x / &quarter).to_i64().unwrap().rem_euclid(4);
+        let (sin, cos) = [(0, 1), (1, 0), (0, -1), (-1, 0)][quarters as usize];
+        return (sin.into(), cos.into());
+    }
+    let half = turn / BigDecimal::from(2);
+    // A few more digits of π, for the up to 3 digits of x before the dot.
+    let radians = math::div(&(x * math::pi(s.precision + 3)), &half, s.precision);
+    math::sin_cos(&radians, s.precision)
+}
+
+/// An angle in radians, converted to the angle mode of the stack.
+fn from_radians(s: &InstantStack, x: BigDecimal) -> BigDecimal {
+    let x = match s.angle.turn() {
+        None => x,
+        Some(turn) => {
+            let half = turn / BigDecimal::from(2);
+            math::div(&(x * half), &math::pi(s.precision), s.precision)
+        }
+    };
+    math::settle(x, s.precision)
+}
+
+fn validate_tan(s: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    let Some(turn) = s.angle.turn() else {
+        return Ok(());
+    };
+    // The cosine is zero at odd multiples of a quarter turn.
+    let half = &turn / BigDecimal::from(2);
+    match (&args[0] % &half).abs() == turn / BigDecimal::from(4) {
+        true => invalid("the tangent is infinite there"),
+        false => Ok(()),
+    }
+}
+
+fn validate_unit(_: &InstantStack, args: &[BigDecimal]) -> Result<(), StackError> {
+    match args[0].abs() > 1 {
+        true => invalid("element 1 must be between -1 and 1"),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::builtin;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    /// Run the operations on the value in the angle mode, and return the
+    /// top of the stack.
+    fn run(mode: &str, ops: &[&str], x: &str) -> Result<String, StackError> {
+        let mut s = Stack::from(vec![BigDecimal::from_str(x).unwrap()], Some(10));
+        s.apply(builtin(mode).unwrap())?;
+        for op in ops {
+            s.apply(builtin(op).unwrap())?;
+        }
+        Ok(s.snapshot()[0].to_string())
+    }
+
+    #[test]
+    fn angle_modes() -> Result<(), StackError> {
+        assert_eq!(run("deg", &["sin"], "30")?, "0.5");
+        assert_eq!(run("deg", &["cos"], "-540")?, "-1");
+        assert_eq!(run("grad", &["sin"], "100")?, "1");
+        assert_eq!(run("grad", &["cos"], "50")?, "0.7071067811");
+        assert_eq!(run("rad", &["cos"], "0")?, "1");
+        assert_eq!(run("rad", &["sin"], "1")?, "0.8414709848");
+        assert_eq!(run("deg", &["tan"], "45")?, "1");
+        assert!(run("deg", &["tan"], "270").is_err());
+        assert!(run("grad", &["tan"], "-100").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn polar_and_rectangular() -> Result<(), StackError> {
+        let convert = |mode, op, a: &str, b: &str| -> Result<Vec<String>, StackError> {
+            let mut s = Stack::from(
+                vec![
+                    BigDecimal::from_str(b).unwrap(),
+                    BigDecimal::from_str(a).unwrap(),
+                ],
+                Some(10),
+            );
+            s.apply(builtin(mode).unwrap())?;
+            s.apply(builtin(op).unwrap())?;
+            Ok(s.snapshot().iter().rev().map(|v| v.to_string()).collect())
+        };
+        assert_eq!(convert("deg", "polar", "3", "4")?, ["5", "53.1301023541"]);
+        assert_eq!(
+            convert("deg", "polar", "-1", "-1")?,
+            ["1.4142135623", "-135"]
+        );
+        assert_eq!(convert("deg", "polar", "-2", "0")?, ["2", "180"]);
+        assert_eq!(convert("grad", "polar", "0", "-2")?, ["2", "-100"]);
+        assert_eq!(convert("rad", "polar", "0", "0")?, ["0", "0"]);
+        assert_eq!(convert("deg", "rect", "2", "90")?, ["0", "2"]);
+        assert_eq!(convert("deg", "rect", "10", "30")?, ["8.6602540378", "5"]);
+        assert_eq!(convert("rad", "rect", "1", "0")?, ["1", "0"]);
+        Ok(())
+    }
+
+    #[test]
+    fn inverses() -> Result<(), StackError> {
+        assert_eq!(run("deg", &["asin"], "0.5")?, "30");
+        assert_eq!(run("deg", &["acos"], "-1")?, "180");
+        assert_eq!(run("grad", &["asin"], "1")?, "100");
+        assert_eq!(run("grad", &["atan"], "1")?, "50");
+        assert_eq!(run("rad", &["acos"], "0")?, "1.5707963267");
+        let round_trip = run("deg", &["sin", "asin"], "12.5")?;
+        assert!(round_trip.starts_with("12.5000000") || round_trip.starts_with("12.4999999"));
+        assert!(run("deg", &["asin"], "1.5").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! User-provided operations.
+//!
+//! Third parties add operations by implementing [`Operation`] and registering
+//! them in a [`Registry`], either at compile time or, with the `dynamic`
+//! feature, by loading a shared library exporting a registration function.
+//! Registered operations are applied through [`Op::Run`], and benefit from
+//! the same undo / redo as the built-in ones.
+use std::sync::Arc;
+
+pub use crate::ops::Operation;
+use crate::stack::Op;
+
+/// The signature of the function a dynamic plugin exports as
+/// `hc_plugin_register`.
+pub type RegisterFn = fn(&mut Registry);
+
+/// The registered operations.
+#[derive(Default)]
+pub struct Registry {
+    ops: Vec<Arc<dyn Operation>>,
+    // Declared last so that the operations are dropped before their code.
+    #[cfg(feature = "dynamic")]
+    libraries: Vec<libloading::Library>,
+}
+
+impl Registry {
+    /// Register an operation, replacing any previous one with the same name.
+    pub fn register<O: Operation + 'static>(&mut self, op: O) {
+        self.ops.retain(|o| o.name() != op.name());
+        self.ops.push(Arc::new(op));
+    }
+
+    /// The operation with the given name, if any.
+    pub fn get(&self, name: &str) -> Option<Op> {
+        self.ops
+            .iter()
+            .find(|o| o.name() == name)
+            .map(|o| Op::Run(o.clone()))
+    }
+
+    /// All the registered operations, in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Operation>> {
+        self.ops.iter()
+    }
+
+    /// Load a shared library and let it register its operations.
+    ///
+    /// # Safety
+    ///
+    /// The library must export `hc_plugin_register` as a [`RegisterFn`], and
+    /// be built with the same compiler and version of this crate, as Rust
+    /// doesn't provide a stable ABI.
+    #[cfg(feature = "dynamic")]
+    pub unsafe fn load<P: AsRef<std::ffi::OsStr>>(&mut self, path: P) -> anyhow::Result<()> {
+        let library = libloading::Library::new(path)?;
+        let register = *library.get::<RegisterFn>(b"hc_plugin_register\0")?;
+        register(self);
+        self.libraries.push(library);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack::{Stack, StackError};
+    use bigdecimal::BigDecimal;
+
+    struct Average;
+
+    impl Operation for Average {
+        fn name(&self) -> &str {
+            "avg"
+        }
+
+        fn key(&self) -> Option<char> {
+            Some('a')
+        }
+
+        fn description(&self) -> &str {
+            "average of S2 and S1"
+        }
+
+        fn arity(&self) -> usize {
+            2
+        }
+
+        fn apply(&self, args: &[BigDecimal]) -> Result<Vec<BigDecimal>, StackError> {
+            Ok(vec![(&args[0] + &args[1]) / 2])
+        }
+    }
+
+    #[test]
+    fn registry() {
+        let mut registry = Registry::default();
+        registry.register(Average);
+        registry.register(Average);
+        assert_eq!(registry.iter().count(), 1);
+        assert!(registry.get("avg").is_some());
+        assert!(registry.get("max").is_none());
+    }
+
+    #[test]
+    fn apply() -> Result<(), StackError> {
+        let mut registry = Registry::default();
+        registry.register(Average);
+        let avg = registry.get("avg").unwrap();
+
+        let values: Vec<BigDecimal> = vec![4.into(), 2.into(), 7.into()];
+        let mut s = Stack::from(values.clone(), None);
+        s.apply(avg.clone())?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(3), BigDecimal::from(7)]);
+        s.apply(Op::Undo)?;
+        assert_eq!(s.snapshot(), values);
+
+        let mut s = Stack::from(vec![BigDecimal::from(1)], None);
+        assert_eq!(s.apply(avg), Err(StackError::MissingValue(2)));
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1)]);
+        Ok(())
+    }
+}
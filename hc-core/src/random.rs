@@ -0,0 +1,70 @@
+//! Random numbers, for the operations that need them.
+//!
+//! The generator is shared by the whole process so that undoing a roll and
+//! rolling again gives a new result, rather than replaying the same one. It
+//! is a SplitMix64, which is plenty for dice and doesn't need any system
+//! support beyond a seed (fixed where the platform has no randomness, e.g.
+//! on the web, unless [`seed`] is called).
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Once,
+    },
+};
+
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+static STATE: AtomicU64 = AtomicU64::new(0);
+static INIT: Once = Once::new();
+
+/// Seed the generator, to make the following numbers reproducible.
+pub fn seed(seed: u64) {
+    INIT.call_once(|| {});
+    STATE.store(seed, Ordering::Relaxed);
+}
+
+/// The next random number of the generator.
+pub fn next_u64() -> u64 {
+    // The hasher of a new RandomState is randomly keyed by the system.
+    INIT.call_once(|| {
+        let seed = RandomState::new().build_hasher().finish();
+        STATE.store(seed, Ordering::Relaxed);
+    });
+    let mut z = STATE
+        .fetch_add(GAMMA, Ordering::Relaxed)
+        .wrapping_add(GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A random number uniformly distributed in `0..n`, for `n` > 0.
+pub fn below(n: u64) -> u64 {
+    // Reject the numbers of the last incomplete range of n values, which
+    // would otherwise make the first values more likely.
+    let zone = u64::MAX - u64::MAX % n;
+    loop {
+        let r = next_u64();
+        if r < zone {
+            return r % n;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform() {
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            counts[below(6) as usize] += 1;
+        }
+        // Loose bounds, this only catches gross errors.
+        assert!(counts.iter().all(|&c| c > 800 && c < 1200), "{counts:?}");
+        assert_eq!(below(1), 0);
+    }
+}
@@ -0,0 +1,64 @@
+//! Evaluation of RPN programs in one go, e.g. `1 2 + 3 *`, for programs
+//! that just want the answer and for fuzzing.
+use bigdecimal::BigDecimal;
+use thiserror::Error;
+
+use crate::{
+    number, ops,
+    stack::{Op, Stack, StackError},
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum EvalError {
+    #[error("unknown word '{0}'")]
+    UnknownWord(String),
+    #[error("{1} at '{0}'")]
+    Stack(String, StackError),
+}
+
+/// Apply the whitespace-separated words to an empty stack, and return it,
+/// top first. A word is a number, or an operation by name or by key. This
+/// has no side effect beyond its result.
+pub fn evaluate(program: &str) -> Result<Vec<BigDecimal>, EvalError> {
+    let mut stack = Stack::new();
+    for word in program.split_whitespace() {
+        let op = match number::parse(word, stack.precision()) {
+            Ok(v) => Op::Push(v),
+            Err(_) => ops::builtin(word)
+                .or_else(|| {
+                    let mut chars = word.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => ops::by_key(c),
+                        _ => None,
+                    }
+                })
+                .ok_or_else(|| EvalError::UnknownWord(word.to_string()))?,
+        };
+        stack
+            .apply(op)
+            .map_err(|err| EvalError::Stack(word.to_string(), err))?;
+    }
+    Ok(stack.snapshot())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(program: &str) -> Result<Vec<String>, EvalError> {
+        Ok(evaluate(program)?.iter().map(|v| v.to_string()).collect())
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(eval(""), Ok(vec![]));
+        assert_eq!(eval("1 2 + 3 *"), Ok(vec!["9".to_string()]));
+        assert_eq!(
+            eval("2 3 ^ 1.5 d"),
+            Ok(vec!["1.5".into(), "1.5".into(), "8".into()])
+        );
+        assert_eq!(eval("4k 1 -"), Ok(vec!["3999".to_string()]));
+        assert_eq!(eval("1 nope"), Err(EvalError::UnknownWord("nope".into())));
+        assert!(matches!(eval("1 0 /"), Err(EvalError::Stack(word, _)) if word == "/"));
+    }
+}
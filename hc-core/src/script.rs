@@ -0,0 +1,327 @@
+//! Operations written in [Rhai](https://rhai.rs) scripts.
+//!
+//! Every function without parameters defined in the script becomes an
+//! operation of the same name. Functions work on the stack through:
+//!
+//! - `push(x)`, `pop()`, `peek()` and `depth()`,
+//! - `load(r)` and `save(r, x)` for the registers, e.g. `load('a')`,
+//! - `dec("1.5")` to build a number, which then supports the usual
+//!   arithmetic and comparison operators, mixed with integers too.
+//!
+//! ```text
+//! // Hypotenuse of the right triangle with sides S2 and S1.
+//! fn hyp() {
+//!     let b = pop();
+//!     let a = pop();
+//!     push(sqrt(a * a + b * b));
+//! }
+//! ```
+use bigdecimal::{BigDecimal, Zero};
+use rhai::{Engine, EvalAltResult, Scope, AST, INT};
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+use crate::math;
+use crate::plugin::{Operation, Registry};
+use crate::stack::{InstantStack, StackError};
+
+// Bound the work of a script, so that an accidental endless loop can't
+// freeze the calculator.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("{0}")]
+    Parse(#[from] rhai::ParseError),
+}
+
+type Slot = Arc<Mutex<InstantStack>>;
+type RhaiResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// A compiled script, and the stack its functions currently work on.
+struct Script {
+    engine: Engine,
+    ast: AST,
+    slot: Slot,
+    // Held while a function runs: functions run on other threads at the
+    // same time, e.g. by a watch, would otherwise swap their stacks.
+    running: Mutex<()>,
+}
+
+/// A function of a script, as an operation.
+struct ScriptOp {
+    name: String,
+    script: Arc<Script>,
+}
+
+impl Operation for ScriptOp {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "defined in the script"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn apply(&self, _: &[BigDecimal]) -> Result<Vec<BigDecimal>, StackError> {
+        Ok(vec![])
+    }
+
+    fn execute(&self, s: &mut InstantStack) -> Result<(), StackError> {
+        let script = &self.script;
+        let _running = script.running.lock().unwrap();
+        std::mem::swap(s, &mut script.slot.lock().unwrap());
+        let result = script
+            .engine
+            .call_fn::<()>(&mut Scope::new(), &script.ast, &self.name, ());
+        std::mem::swap(s, &mut script.slot.lock().unwrap());
+        result.map_err(|e| StackError::InvalidArgument(format!("{}: {e}", self.name)))
+    }
+}
+
+/// Compile the script and register its functions as operations.
+pub fn load(registry: &mut Registry, source: &str) -> Result<(), ScriptError> {
+    let slot: Slot = Arc::new(Mutex::new(InstantStack::new(VecDeque::new(), 0)));
+    let engine = engine(&slot);
+    let ast = engine.compile(source)?;
+    let mut names: Vec<String> = ast
+        .iter_functions()
+        .filter(|f| f.params.is_empty())
+        .map(|f| f.name.to_owned())
+        .collect();
+    names.sort();
+    let script = Arc::new(Script {
+        engine,
+        ast,
+        slot,
+        running: Mutex::new(()),
+    });
+    for name in names {
+        registry.register(ScriptOp {
+            name,
+            script: script.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn error<T>(e: StackError) -> RhaiResult<T> {
+    Err(e.to_string().into())
+}
+
+fn engine(slot: &Slot) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.register_type_with_name::<BigDecimal>("Decimal");
+
+    // Numbers.
+    engine.register_fn("dec", |s: &str| -> RhaiResult<BigDecimal> {
+        BigDecimal::from_str(s).map_err(|e| e.to_string().into())
+    });
+    engine.register_fn("dec", |i: INT| BigDecimal::from(i));
+    engine.register_fn("to_string", |a: &mut BigDecimal| a.to_string());
+    engine.register_fn("to_debug", |a: &mut BigDecimal| a.to_string());
+    engine.register_fn("abs", |a: BigDecimal| a.abs());
+    // Inexact operations honor the precision of the stack.
+    let s = slot.clone();
+    engine.register_fn("sqrt", move |a: BigDecimal| -> RhaiResult<BigDecimal> {
+        match math::sqrt(&a, s.lock().unwrap().precision) {
+            Some(r) => Ok(r),
+            None => error(StackError::InvalidArgument(
+                "cannot compute the square root of a negative number".to_owned(),
+            )),
+        }
+    });
+    let s = slot.clone();
+    let quotient = move |a: BigDecimal, b: BigDecimal| {
+        divide(a, b, |a, b| math::div(&a, &b, s.lock().unwrap().precision))
+    };
+    let q = quotient.clone();
+    engine.register_fn("/", move |a: BigDecimal, b: BigDecimal| q(a, b));
+    let q = quotient.clone();
+    engine.register_fn("/", move |a: BigDecimal, b: INT| q(a, b.into()));
+    engine.register_fn("/", move |a: INT, b: BigDecimal| quotient(a.into(), b));
+    engine.register_fn("-", |a: BigDecimal| -a);
+    macro_rules! arithmetic {
+        ($($op:tt)*) => {$(
+            engine.register_fn(stringify!($op), |a: BigDecimal, b: BigDecimal| a $op b);
+            engine.register_fn(stringify!($op), |a: BigDecimal, b: INT| a $op BigDecimal::from(b));
+            engine.register_fn(stringify!($op), |a: INT, b: BigDecimal| BigDecimal::from(a) $op b);
+        )*};
+    }
+    arithmetic!(+ - *);
+    macro_rules! division {
+        ($($op:tt)*) => {$(
+            engine.register_fn(stringify!($op), |a: BigDecimal, b: BigDecimal| divide(a, b, |a, b| a $op b));
+            engine.register_fn(stringify!($op), |a: BigDecimal, b: INT| divide(a, b.into(), |a, b| a $op b));
+            engine.register_fn(stringify!($op), |a: INT, b: BigDecimal| divide(a.into(), b, |a, b| a $op b));
+        )*};
+    }
+    division!(%);
+    macro_rules! comparison {
+        ($($op:tt)*) => {$(
+            engine.register_fn(stringify!($op), |a: BigDecimal, b: BigDecimal| a $op b);
+            engine.register_fn(stringify!($op), |a: BigDecimal, b: INT| {
+                let b = BigDecimal::from(b);
+                a $op b
+            });
+            engine.register_fn(stringify!($op), |a: INT, b: BigDecimal| {
+                let a = BigDecimal::from(a);
+                a $op b
+            });
+        )*};
+    }
+    comparison!(== != < <= > >=);
+
+    // The stack.
+    let s = slot.clone();
+    engine.register_fn("push", move |v: BigDecimal| {
+        s.lock().unwrap().push_front(v);
+    });
+    let s = slot.clone();
+    engine.register_fn("push", move |v: INT| {
+        s.lock().unwrap().push_front(v.into());
+    });
+    let s = slot.clone();
+    engine.register_fn("pop", move || -> RhaiResult<BigDecimal> {
+        match s.lock().unwrap().pop_front() {
+            Some(v) => Ok(v),
+            None => error(StackError::MissingValue(1)),
+        }
+    });
+    let s = slot.clone();
+    engine.register_fn("peek", move || -> RhaiResult<BigDecimal> {
+        match s.lock().unwrap().stack.front() {
+            Some(v) => Ok(v.clone()),
+            None => error(StackError::MissingValue(1)),
+        }
+    });
+    let s = slot.clone();
+    engine.register_fn("depth", move || s.lock().unwrap().stack.len() as INT);
+
+    // The registers.
+    let s = slot.clone();
+    engine.register_fn("load", move |r: char| -> RhaiResult<BigDecimal> {
+        match s.lock().unwrap().registers.get(&r) {
+            Some(v) => Ok(v.clone()),
+            None => error(StackError::InvalidArgument(format!(
+                "register '{r}' is empty"
+            ))),
+        }
+    });
+    let s = slot.clone();
+    engine.register_fn("save", move |r: char, v: BigDecimal| {
+        s.lock().unwrap().registers.insert(r, v);
+    });
+    engine
+}
+
+fn divide<F>(a: BigDecimal, b: BigDecimal, f: F) -> RhaiResult<BigDecimal>
+where
+    F: Fn(BigDecimal, BigDecimal) -> BigDecimal,
+{
+    if b.is_zero() {
+        return error(StackError::InvalidArgument("division by zero".to_owned()));
+    }
+    Ok(f(a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack::{Op, Stack};
+
+    fn run(source: &str, name: &str, values: Vec<BigDecimal>) -> Result<Stack, StackError> {
+        let mut registry = Registry::default();
+        load(&mut registry, source).unwrap();
+        let mut s = Stack::from(values, None);
+        s.apply(registry.get(name).unwrap())?;
+        Ok(s)
+    }
+
+    #[test]
+    fn functions_become_operations() {
+        let mut registry = Registry::default();
+        load(&mut registry, "fn a() {} fn b(x) {} fn c() {}").unwrap();
+        let names: Vec<&str> = registry.iter().map(|o| o.name()).collect();
+        assert_eq!(names, vec!["a", "c"]);
+        assert!(load(&mut registry, "fn (").is_err());
+    }
+
+    #[test]
+    fn stack_access() -> Result<(), StackError> {
+        let script = r#"
+            fn sum() {
+                let total = dec(0);
+                while depth() > 0 { total += pop(); }
+                push(total);
+            }
+            fn half() { push(pop() / 2); }
+        "#;
+        let s = run(script, "sum", vec![1.into(), 2.into(), 3.into()])?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(6)]);
+        let s = run(script, "half", vec![BigDecimal::from(3)])?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from_str("1.5").unwrap()]);
+        Ok(())
+    }
+
+    #[test]
+    fn registers() -> Result<(), StackError> {
+        let script = "fn keep() { save('a', peek()); push(load('a') * 2); }";
+        let mut s = run(script, "keep", vec![BigDecimal::from(21)])?;
+        assert_eq!(
+            s.snapshot(),
+            vec![BigDecimal::from(42), BigDecimal::from(21)]
+        );
+        assert_eq!(s.registers().get(&'a'), Some(&BigDecimal::from(21)));
+        s.apply(Op::Undo)?;
+        assert!(s.registers().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn errors_leave_the_stack_untouched() {
+        let script = "fn twice() { pop(); pop(); } fn forever() { loop {} }";
+        assert!(run(script, "twice", vec![BigDecimal::from(1)]).is_err());
+        assert!(run(script, "forever", vec![]).is_err());
+        let mut registry = Registry::default();
+        load(&mut registry, script).unwrap();
+        let mut s = Stack::from(vec![BigDecimal::from(1)], None);
+        assert!(s.apply(registry.get("twice").unwrap()).is_err());
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1)]);
+    }
+
+    #[test]
+    fn functions_run_at_once_keep_their_stacks() {
+        let mut registry = Registry::default();
+        // Slow enough for the calls on both threads to overlap.
+        let script = "fn inc() { let x = pop(); for i in 0..1000 {} push(x + 1); }";
+        load(&mut registry, script).unwrap();
+        let threads: Vec<_> = [0, 1000]
+            .map(|start| {
+                let inc = registry.get("inc").unwrap();
+                std::thread::spawn(move || {
+                    let mut s = Stack::from(vec![BigDecimal::from(start)], None);
+                    for _ in 0..200 {
+                        s.apply(inc.clone()).unwrap();
+                    }
+                    s.snapshot()
+                })
+            })
+            .into_iter()
+            .collect();
+        let stacks: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(
+            stacks,
+            [vec![BigDecimal::from(200)], vec![BigDecimal::from(1200)]]
+        );
+    }
+}
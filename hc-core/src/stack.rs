@@ -0,0 +1,1475 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use bigdecimal::{BigDecimal, ParseBigDecimalError};
+use thiserror::Error;
+
+use crate::math;
+use crate::ops::{time, Angle, Operation};
+use crate::state::State;
+
+/// Stack represents the internal state of the calculator.
+#[derive(Clone)]
+pub struct Stack {
+    stack: Undoable<InstantStack>,
+    // Named copies of the stack, kept outside of the undo history.
+    bookmarks: BTreeMap<String, InstantStack>,
+}
+
+/// An entry added, removed or changed since a bookmark.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    /// The position from the top in the current stack, or where a removed
+    /// entry would be.
+    pub index: usize,
+    pub before: Option<BigDecimal>,
+    pub after: Option<BigDecimal>,
+}
+
+/// An Undoable keeps track of a sequence of states, and allows
+/// to undo/redo them, in the most simple way: it clones the old
+/// state into the new one for further manipulation, and keeps
+/// an index on the currently active one.
+#[derive(Clone)]
+pub struct Undoable<T>
+where
+    T: Clone,
+{
+    history: Vec<T>,
+    // The estimated size in bytes of each state, and their sum.
+    weights: Vec<usize>,
+    weight: usize,
+    current: usize,
+    limits: UndoLimits,
+    // Whether old states were dropped to stay within the limits.
+    truncated: bool,
+    // Changes with the current state, see Undoable::generation.
+    generation: u64,
+}
+
+// The next generation of any Undoable, so that they never share one unless
+// one is a copy of the other.
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// How much undo history to keep, the oldest states being dropped first.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UndoLimits {
+    /// The most steps which can be undone, if capped.
+    pub steps: Option<usize>,
+    /// The most memory the states can take, in bytes, if capped. The
+    /// current state is always kept.
+    pub bytes: Option<usize>,
+}
+
+impl<T> Undoable<T>
+where
+    T: Clone,
+{
+    pub fn new(start: T) -> Undoable<T> {
+        Undoable {
+            history: vec![start],
+            weights: vec![0],
+            weight: 0,
+            current: 0,
+            limits: UndoLimits::default(),
+            truncated: false,
+            generation: next_generation(),
+        }
+    }
+
+    /// Introduce a new state, identical to the current one.
+    pub fn add(&mut self, v: T) -> &mut T {
+        self.add_weighted(v, 0)
+    }
+
+    /// Introduce a new state of the given size in bytes, dropping the oldest
+    /// ones beyond the limits.
+    pub fn add_weighted(&mut self, v: T, weight: usize) -> &mut T {
+        self.history.truncate(self.current + 1);
+        for w in self.weights.drain(self.current + 1..) {
+            self.weight -= w;
+        }
+        self.history.push(v);
+        self.weights.push(weight);
+        self.weight += weight;
+        self.current += 1;
+        self.generation = next_generation();
+        self.enforce();
+        &mut (self.history[self.current])
+    }
+
+    /// Change the limits, dropping the oldest states beyond them.
+    pub fn set_limits(&mut self, limits: UndoLimits) {
+        self.limits = limits;
+        self.enforce();
+    }
+
+    /// Whether old states were dropped to stay within the limits.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn enforce(&mut self) {
+        let over = |u: &Self| {
+            u.limits.steps.is_some_and(|steps| u.current > steps)
+                || u.limits.bytes.is_some_and(|bytes| u.weight > bytes)
+        };
+        let mut dropped = 0;
+        while self.current > 0 && over(self) {
+            self.weight -= self.weights[dropped];
+            self.current -= 1;
+            dropped += 1;
+        }
+        if dropped > 0 {
+            self.history.drain(..dropped);
+            self.weights.drain(..dropped);
+            self.truncated = true;
+        }
+    }
+
+    /// Undo to the previous state if there is one, returns false if not.
+    pub fn undo(&mut self) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        self.current -= 1;
+        self.generation = next_generation();
+        true
+    }
+
+    /// Redo to the next state if there is one, returns false if not.
+    pub fn redo(&mut self) -> bool {
+        if self.current >= self.history.len() - 1 {
+            return false;
+        }
+        self.current += 1;
+        self.generation = next_generation();
+        true
+    }
+
+    pub fn cur(&self) -> &T {
+        &(self.history[self.current])
+    }
+
+    pub fn cur_mut(&mut self) -> &mut T {
+        self.generation = next_generation();
+        &mut (self.history[self.current])
+    }
+
+    /// All the states, to change them regardless of the history.
+    pub fn all_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.generation = next_generation();
+        self.history.iter_mut()
+    }
+
+    /// A number which changes whenever the current state may have, and which
+    /// no other Undoable has unless copied from this one.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+/// Instantaneous stack, without undo/redo support. This is the
+/// representation of what's seen by the user at a given point in
+/// time.
+#[derive(Clone, Debug)]
+pub struct InstantStack {
+    pub stack: VecDeque<BigDecimal>,
+    // Precision when taking a snapshot (not of internal representation).
+    pub precision: u64,
+    // Base for displaying numbers (2-36, default 10).
+    pub output_base: u32,
+    // Named registers (single-char key).
+    pub registers: HashMap<char, BigDecimal>,
+    // Cap on the size of exponentiation results, if any.
+    pub max_bits: Option<u64>,
+    // Unit of the angles of trigonometric operations.
+    pub angle: Angle,
+    // Whether the common operations are computed with f64, see ops::fast.
+    pub fast: bool,
+    // Size in bits of the words negative integers are shown in, in bases
+    // which are powers of two, if any.
+    pub word_size: Option<u32>,
+    // Entries operations can't consume, by position from the bottom.
+    pub pinned: BTreeSet<usize>,
+    // When each entry was pushed, in seconds since 1970, by position from
+    // the bottom, if known.
+    pub pushed: Vec<Option<u64>>,
+    // How each entry was computed, e.g. "12.5 3 mul", by position from the
+    // bottom, if by an operation.
+    pub origins: Vec<Option<String>>,
+    // The values consumed by the latest operation taking any, bottom first.
+    pub last_args: Vec<BigDecimal>,
+}
+
+impl InstantStack {
+    pub fn new(stack: VecDeque<BigDecimal>, precision: u64) -> InstantStack {
+        InstantStack {
+            stack,
+            precision,
+            output_base: DEFAULT_BASE,
+            registers: HashMap::new(),
+            max_bits: Some(DEFAULT_MAX_BITS),
+            angle: Angle::default(),
+            fast: false,
+            word_size: None,
+            pinned: BTreeSet::new(),
+            pushed: vec![],
+            origins: vec![],
+            last_args: vec![],
+        }
+    }
+
+    /// An estimate of the memory taken by the values, in bytes.
+    pub fn footprint(&self) -> usize {
+        let size = |v: &BigDecimal| {
+            std::mem::size_of::<BigDecimal>()
+                + v.as_bigint_and_scale().0.bits().div_ceil(8) as usize
+        };
+        let values = self.stack.iter().chain(&self.last_args);
+        values.chain(self.registers.values()).map(size).sum()
+    }
+
+    pub fn push_front(&mut self, v: BigDecimal) {
+        self.stack.push_front(v);
+    }
+
+    pub fn pop_front(&mut self) -> Option<BigDecimal> {
+        self.stack.pop_front()
+    }
+
+    /// The values, top first, rounded to the precision when they have more
+    /// decimals.
+    pub fn values(&self) -> impl Iterator<Item = Cow<'_, BigDecimal>> {
+        // Ensure the scale does not exceed the precision, but don't force
+        // it on all numbers as displaying 1.0000000000 is annoying.
+        self.stack.iter().map(|v| self.rounded(v))
+    }
+
+    fn rounded<'a>(&self, v: &'a BigDecimal) -> Cow<'a, BigDecimal> {
+        let (_, scale) = v.as_bigint_and_scale();
+        if (v.digits() as i64) < scale - self.precision as i64 {
+            // All the digits are cut, without dividing by a huge power of ten.
+            Cow::Owned(BigDecimal::new(0.into(), self.precision as i64))
+        } else if scale > self.precision as i64 {
+            Cow::Owned(v.with_scale(self.precision as i64))
+        } else {
+            Cow::Borrowed(v)
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum StackError {
+    #[error("operation requires {0} elements")]
+    MissingValue(usize),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("the operation would consume a pinned entry")]
+    Pinned,
+}
+
+/// An operation running in the background, see [`Stack::spawn`]. Dropping
+/// it cancels the operation: its result is discarded, and the worker thread
+/// stops at the next step of the iterative methods, or else once the
+/// computation is over. On wasm32, which has no threads, the operation is
+/// over by the time the job is returned.
+pub struct Job {
+    receiver: mpsc::Receiver<Result<InstantStack, StackError>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The outcome of a [`Job`].
+pub struct Outcome(Result<InstantStack, StackError>);
+
+impl Outcome {
+    /// The top of the stack left by the operations, if any, without keeping
+    /// the rest, e.g. when they ran on a copy of the stack.
+    pub fn top(self) -> Result<Option<BigDecimal>, StackError> {
+        Ok(self.0?.pop_front())
+    }
+}
+
+impl Job {
+    /// Wait up to the given duration for the outcome of the operation.
+    pub fn poll(&self, timeout: Duration) -> Option<Outcome> {
+        match receive(&self.receiver, timeout) {
+            Ok(result) => Some(Outcome(result)),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Some(Outcome(Err(
+                StackError::InvalidArgument("the operation failed".into()),
+            ))),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn receive<T>(
+    receiver: &mpsc::Receiver<T>,
+    timeout: Duration,
+) -> Result<T, mpsc::RecvTimeoutError> {
+    receiver.recv_timeout(timeout)
+}
+
+/// The outcome is already there without threads, and there is no clock to
+/// time out with anyway.
+#[cfg(target_arch = "wasm32")]
+fn receive<T>(receiver: &mpsc::Receiver<T>, _: Duration) -> Result<T, mpsc::RecvTimeoutError> {
+    receiver.try_recv().map_err(|err| match err {
+        mpsc::TryRecvError::Empty => mpsc::RecvTimeoutError::Timeout,
+        mpsc::TryRecvError::Disconnected => mpsc::RecvTimeoutError::Disconnected,
+    })
+}
+
+/// What can be done to a stack: run an operation, or the few actions taking
+/// their parameter from elsewhere than the stack.
+#[derive(Debug, Clone)]
+pub enum Op {
+    Push(BigDecimal),
+    Save(char),
+    Load(char),
+    Undo,
+    Redo,
+    Run(Arc<dyn Operation>),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Push(v) => write!(f, "push {v}"),
+            Op::Save(c) => write!(f, "save {c}"),
+            Op::Load(c) => write!(f, "load {c}"),
+            Op::Undo => f.write_str("undo"),
+            Op::Redo => f.write_str("redo"),
+            Op::Run(op) => f.write_str(op.name()),
+        }
+    }
+}
+
+/// Exponentiation is capped by default to that number of bits (about 300k
+/// digits) to avoid accidental memory blowups when pressing ^ too many times.
+pub const DEFAULT_MAX_BITS: u64 = 1 << 20;
+
+pub(crate) const DEFAULT_PRECISION: u64 = 12;
+pub(crate) const DEFAULT_BASE: u32 = 10;
+
+// The most characters of each argument in the origin of an entry.
+const MAX_ORIGIN_ARG: usize = 16;
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stack {
+    /// An empty stack with the default precision.
+    pub fn new() -> Stack {
+        Stack {
+            stack: Undoable::new(InstantStack::new(VecDeque::new(), DEFAULT_PRECISION)),
+            bookmarks: BTreeMap::new(),
+        }
+    }
+
+    /// A stack holding the given values, top first.
+    pub fn from(values: Vec<BigDecimal>, precision: Option<u64>) -> Stack {
+        Stack {
+            stack: Undoable::new(InstantStack::new(
+                values.into(),
+                precision.unwrap_or(DEFAULT_PRECISION),
+            )),
+            bookmarks: BTreeMap::new(),
+        }
+    }
+
+    /// Apply an operation, recording it in the undo history on success.
+    pub fn apply(&mut self, op: Op) -> Result<(), StackError> {
+        log::debug!("apply {op}");
+        match op {
+            Op::Undo => match self.stack.undo() {
+                true => Ok(()),
+                false => Err(StackError::InvalidArgument("Nothing to undo.".to_owned())),
+            },
+            Op::Redo => match self.stack.redo() {
+                true => Ok(()),
+                false => Err(StackError::InvalidArgument("Nothing to redo.".to_owned())),
+            },
+            op => {
+                let mut s = self.stack.cur().clone();
+                match apply_on_stack(&mut s, op) {
+                    Ok(_) => {
+                        self.commit(s);
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+
+    /// Apply operations in sequence as a single step of the undo history,
+    /// e.g. to take in a batch of values. Nothing changes if one fails.
+    pub fn apply_all(&mut self, ops: Vec<Op>) -> Result<(), StackError> {
+        log::debug!(
+            "apply {}",
+            ops.iter().map(Op::to_string).collect::<Vec<_>>().join(", ")
+        );
+        let mut s = self.stack.cur().clone();
+        for op in ops {
+            apply_on_stack(&mut s, op)?;
+        }
+        self.commit(s);
+        Ok(())
+    }
+
+    /// Change the stack in place as a single step of the undo history, which
+    /// keeps the changes made before an error, if any.
+    pub(crate) fn change<E>(
+        &mut self,
+        f: impl FnOnce(&mut InstantStack) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut s = self.stack.cur().clone();
+        let result = f(&mut s);
+        self.commit(s);
+        result
+    }
+
+    /// Record the new state in the undo history, with the times its entries
+    /// were pushed: the entries already there keep theirs, even moved, and
+    /// the others are new.
+    fn commit(&mut self, mut s: InstantStack) {
+        let cur = self.stack.cur();
+        let now = time::seconds();
+        // The entries below the change are untouched.
+        let common = cur
+            .stack
+            .iter()
+            .rev()
+            .zip(s.stack.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let stamp = |i: usize| cur.pushed.get(i).copied().flatten();
+        let mut moved: HashMap<&BigDecimal, Vec<Option<u64>>> = HashMap::new();
+        for (i, v) in cur.stack.iter().rev().enumerate().skip(common) {
+            moved.entry(v).or_default().push(stamp(i));
+        }
+        let above = s.stack.iter().rev().skip(common).map(|v| {
+            moved
+                .get_mut(v)
+                .and_then(|stamps| stamps.pop())
+                .unwrap_or(now)
+        });
+        let pushed = (0..common).map(stamp).chain(above).collect();
+        s.pushed = pushed;
+        let weight = s.footprint();
+        self.stack.add_weighted(s, weight);
+    }
+
+    /// Start applying an operation on a worker thread, leaving the stack
+    /// untouched until the outcome is passed to [`Stack::complete`].
+    pub fn spawn(&self, op: Op) -> Job {
+        self.spawn_all(vec![op])
+    }
+
+    /// Start applying operations in sequence on a worker thread, as
+    /// [`Stack::spawn`] does for one. On wasm32, they are applied right away.
+    pub fn spawn_all(&self, ops: Vec<Op>) -> Job {
+        log::debug!(
+            "spawn {}",
+            ops.iter().map(Op::to_string).collect::<Vec<_>>().join(", ")
+        );
+        let mut s = self.stack.cur().clone();
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = cancel.clone();
+        let run = move || {
+            math::watch_cancel(Some(flag.clone()));
+            let result = ops
+                .into_iter()
+                .try_for_each(|op| match flag.load(Ordering::Relaxed) {
+                    true => Err(StackError::InvalidArgument("cancelled".into())),
+                    false => apply_on_stack(&mut s, op),
+                })
+                .map(|_| s);
+            // Not to cancel what runs next on the thread, without threads.
+            math::watch_cancel(None);
+            // Nobody is listening anymore if the job was cancelled.
+            let _ = sender.send(result);
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(run);
+        #[cfg(target_arch = "wasm32")]
+        run();
+        Job { receiver, cancel }
+    }
+
+    /// Record the outcome of a job in the undo history, or return its error.
+    pub fn complete(&mut self, outcome: Outcome) -> Result<(), StackError> {
+        if let Err(err) = &outcome.0 {
+            log::debug!("failed: {err}");
+        }
+        self.commit(outcome.0?);
+        Ok(())
+    }
+
+    /// The current values, top first, borrowed from the stack. Values are
+    /// only copied when they need rounding to the precision, which makes this
+    /// cheap enough to call on every frame.
+    pub fn values(&self) -> impl Iterator<Item = Cow<'_, BigDecimal>> {
+        self.stack.cur().values()
+    }
+
+    /// At most `count` of the current values from the `start`th, top first,
+    /// in the same time however deep the stack is, e.g. to show them.
+    pub fn window(&self, start: usize, count: usize) -> impl Iterator<Item = Cow<'_, BigDecimal>> {
+        let s = self.stack.cur();
+        let start = start.min(s.stack.len());
+        let end = (start + count).min(s.stack.len());
+        s.stack.range(start..end).map(|v| s.rounded(v))
+    }
+
+    /// A copy of the current values, top first, e.g. to save them.
+    pub fn snapshot(&self) -> Vec<BigDecimal> {
+        self.values().map(Cow::into_owned).collect()
+    }
+
+    /// Remove the top of the stack to edit it, if any.
+    pub fn edit_top(&mut self) -> Option<BigDecimal> {
+        if self.is_pinned(0) {
+            return None;
+        }
+        // TODO: this is actually a bit subboptimal, as we introduce a new
+        // state with the edited item being removed, which is then visible
+        // in the history.
+        let s = self.stack.cur().clone();
+        let weight = s.footprint();
+        let cur = self.stack.add_weighted(s, weight);
+        cur.pop_front()
+    }
+
+    /// The top of the stack at full internal precision, if any.
+    pub fn top(&self) -> Option<&BigDecimal> {
+        self.stack.cur().stack.front()
+    }
+
+    /// The number of decimals kept by divisions and square roots.
+    pub fn precision(&self) -> u64 {
+        self.stack.cur().precision
+    }
+
+    /// The base in which numbers are displayed.
+    pub fn output_base(&self) -> u32 {
+        self.stack.cur().output_base
+    }
+
+    /// Cap the size of exponentiation results, or remove the cap with None.
+    pub fn set_max_bits(&mut self, max_bits: Option<u64>) {
+        for s in self.stack.all_mut().chain(self.bookmarks.values_mut()) {
+            s.max_bits = max_bits;
+        }
+    }
+
+    /// A number which changes whenever the values or the settings of the
+    /// stack may have, and which no other stack has unless copied from this
+    /// one: comparing it tells cheaply whether anything changed.
+    pub fn generation(&self) -> u64 {
+        self.stack.generation()
+    }
+
+    /// Limit the undo history, dropping the oldest steps beyond the limits.
+    pub fn set_undo_limits(&mut self, limits: UndoLimits) {
+        self.stack.set_limits(limits);
+    }
+
+    /// Whether the oldest steps of the undo history were dropped to stay
+    /// within its limits.
+    pub fn undo_truncated(&self) -> bool {
+        self.stack.truncated()
+    }
+
+    /// Save a copy of the stack under the name, replacing any previous one.
+    /// Bookmarks are independent of the undo history.
+    pub fn bookmark(&mut self, name: &str) {
+        self.bookmarks
+            .insert(name.to_owned(), self.stack.cur().clone());
+    }
+
+    /// Go back to the stack saved under the name, as a new step of the undo
+    /// history.
+    pub fn restore(&mut self, name: &str) -> Result<(), StackError> {
+        let saved = self.saved(name)?.clone();
+        let weight = saved.footprint();
+        self.stack.add_weighted(saved, weight);
+        Ok(())
+    }
+
+    /// How the stack changed since the bookmark, top first. The entries
+    /// matching at the top and at the bottom are skipped, and those left in
+    /// between are paired from the bottom up.
+    pub fn diff(&self, name: &str) -> Result<Vec<Change>, StackError> {
+        let before = &self.saved(name)?.stack;
+        let after = &self.stack.cur().stack;
+        let common = before.len().min(after.len());
+        let top = (0..common).take_while(|&i| before[i] == after[i]).count();
+        let bottom = (0..common - top)
+            .take_while(|&i| before[before.len() - 1 - i] == after[after.len() - 1 - i])
+            .count();
+        let (before_len, after_len) = (before.len() - top - bottom, after.len() - top - bottom);
+        let mut changes: Vec<Change> = (0..before_len.max(after_len))
+            .map(|i| Change {
+                index: top + after_len.saturating_sub(i + 1),
+                before: (i < before_len).then(|| before[top + before_len - 1 - i].clone()),
+                after: (i < after_len).then(|| after[top + after_len - 1 - i].clone()),
+            })
+            .collect();
+        changes.reverse();
+        Ok(changes)
+    }
+
+    fn saved(&self, name: &str) -> Result<&InstantStack, StackError> {
+        self.bookmarks.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.bookmarks.keys().map(String::as_str).collect();
+            StackError::InvalidArgument(match names.is_empty() {
+                true => format!("no bookmark '{name}'"),
+                false => format!("no bookmark '{name}' (only {})", names.join(", ")),
+            })
+        })
+    }
+
+    /// The bookmarks, each as a stack of its own, e.g. to save them.
+    pub fn bookmarks(&self) -> impl Iterator<Item = (&str, Stack)> {
+        self.bookmarks.iter().map(|(name, s)| {
+            let stack = Stack {
+                stack: Undoable::new(s.clone()),
+                bookmarks: BTreeMap::new(),
+            };
+            (name.as_str(), stack)
+        })
+    }
+
+    /// Whether the entry at the given index, from the top, is pinned.
+    pub fn is_pinned(&self, index: usize) -> bool {
+        let cur = self.stack.cur();
+        index < cur.stack.len() && cur.pinned.contains(&(cur.stack.len() - 1 - index))
+    }
+
+    /// How the entry at the given index, from the top, was computed, e.g.
+    /// `12.5 3 mul`, if by an operation.
+    pub fn origin(&self, index: usize) -> Option<&str> {
+        let cur = self.stack.cur();
+        let bottom = cur.stack.len().checked_sub(index + 1)?;
+        cur.origins.get(bottom)?.as_deref()
+    }
+
+    /// When the entry at the given index, from the top, was pushed, in
+    /// seconds since 1970, if known.
+    pub fn pushed_at(&self, index: usize) -> Option<u64> {
+        let cur = self.stack.cur();
+        let bottom = cur.stack.len().checked_sub(index + 1)?;
+        cur.pushed.get(bottom).copied().flatten()
+    }
+
+    /// The unit of angles.
+    pub fn angle(&self) -> Angle {
+        self.stack.cur().angle
+    }
+
+    /// Whether the common operations are computed with f64 rather than
+    /// exactly.
+    pub fn fast(&self) -> bool {
+        self.stack.cur().fast
+    }
+
+    /// The size in bits of the words negative integers are shown in, in
+    /// bases which are powers of two.
+    pub fn word_size(&self) -> Option<u32> {
+        self.stack.cur().word_size
+    }
+
+    /// The values saved in registers.
+    pub fn registers(&self) -> &HashMap<char, BigDecimal> {
+        &self.stack.cur().registers
+    }
+}
+
+impl TryFrom<State> for Stack {
+    type Error = ParseBigDecimalError;
+
+    fn try_from(mut value: State) -> Result<Self, Self::Error> {
+        let mut bookmarks = BTreeMap::new();
+        for (name, state) in std::mem::take(&mut value.bookmarks) {
+            let saved: Stack = state.try_into()?;
+            bookmarks.insert(name, saved.stack.cur().clone());
+        }
+        let mut values = vec![];
+        for v in value.stack {
+            values.push(BigDecimal::from_str(&v)?);
+        }
+        let mut stack = Stack::from(values, value.precision);
+        let cur = stack.stack.cur_mut();
+        if let Some(base) = value.output_base {
+            cur.output_base = base;
+        }
+        if let Some(angle) = value.angle {
+            cur.angle = angle;
+        }
+        cur.fast = value.fast;
+        cur.word_size = value.word_size;
+        let len = cur.stack.len();
+        cur.pinned = value
+            .pinned
+            .iter()
+            .filter(|&&i| i < len)
+            .map(|i| len - 1 - i)
+            .collect();
+        cur.pushed = (0..len)
+            .map(|i| value.pushed.get(len - 1 - i).copied().flatten())
+            .collect();
+        cur.origins = (0..len)
+            .map(|i| value.origins.get(len - 1 - i).cloned().flatten())
+            .collect();
+        for (k, v) in value.registers {
+            cur.registers.insert(k, BigDecimal::from_str(&v)?);
+        }
+        stack.bookmarks = bookmarks;
+        Ok(stack)
+    }
+}
+
+pub(crate) fn apply_on_stack(s: &mut InstantStack, op: Op) -> Result<(), StackError> {
+    // The pinned entries, and the ones below them, must come out untouched.
+    let protected = s.pinned.last().map_or(0, |b| b + 1);
+    let consumed = match &op {
+        Op::Save(_) => 1,
+        Op::Run(op) => op.arity(),
+        _ => 0,
+    };
+    if s.stack.len() >= consumed && s.stack.len() - consumed < protected {
+        return Err(StackError::Pinned);
+    }
+    let kept: Vec<BigDecimal> = s.stack.iter().rev().take(protected).cloned().collect();
+    // The entries the operation may move or replace: its arguments, or all
+    // of them for operations on the whole stack.
+    let below = match &op {
+        Op::Run(_) if consumed == 0 => 0,
+        _ => s.stack.len().saturating_sub(consumed),
+    };
+    let before: Vec<BigDecimal> = s.stack.iter().rev().skip(below).cloned().collect();
+    let origin = match &op {
+        Op::Run(run) if consumed > 0 => Some(describe(s, &before, run.name())),
+        Op::Run(run) => Some(run.name().to_owned()),
+        Op::Load(_) => Some(op.to_string()),
+        _ => None,
+    };
+    let run = matches!(op, Op::Run(_));
+    apply_unpinned(s, op)?;
+    if run && consumed > 0 {
+        s.last_args = before.clone();
+    }
+    track_origins(s, below, before, origin);
+    match s.stack.len() >= protected && s.stack.iter().rev().take(protected).eq(&kept) {
+        true => Ok(()),
+        false => Err(StackError::Pinned),
+    }
+}
+
+/// The arguments of the operation, followed by its name, e.g. `12.5 3 mul`.
+/// Long arguments are cut short.
+fn describe(s: &InstantStack, args: &[BigDecimal], name: &str) -> String {
+    let mut words: Vec<String> = args
+        .iter()
+        .map(|v| {
+            let v = s.rounded(v);
+            let (digits, scale) = v.as_bigint_and_scale();
+            // Only the zeros of huge numbers which show before the cut.
+            let v = match scale < 0 {
+                true => {
+                    let zeros = (-scale).min(MAX_ORIGIN_ARG as i64) as usize;
+                    format!("{digits}{}", "0".repeat(zeros))
+                }
+                false => v.to_plain_string(),
+            };
+            match v.chars().count() > MAX_ORIGIN_ARG {
+                true => v.chars().take(MAX_ORIGIN_ARG - 1).chain(['…']).collect(),
+                false => v,
+            }
+        })
+        .collect();
+    words.push(name.to_owned());
+    words.join(" ")
+}
+
+/// Give the entries above the `below` deepest ones their origin: those which
+/// were already there, even moved, keep theirs, and the others are new.
+fn track_origins(
+    s: &mut InstantStack,
+    below: usize,
+    before: Vec<BigDecimal>,
+    origin: Option<String>,
+) {
+    // Changes made without operations may have left them out of step.
+    s.origins.resize(below + before.len(), None);
+    let mut moved: HashMap<&BigDecimal, Vec<Option<String>>> = HashMap::new();
+    for (v, o) in before.iter().zip(s.origins.drain(below..)) {
+        moved.entry(v).or_default().push(o);
+    }
+    let above: Vec<Option<String>> = s
+        .stack
+        .iter()
+        .rev()
+        .skip(below)
+        .map(|v| {
+            moved
+                .get_mut(v)
+                .and_then(|origins| origins.pop())
+                .unwrap_or_else(|| origin.clone())
+        })
+        .collect();
+    s.origins.extend(above);
+}
+
+fn apply_unpinned(s: &mut InstantStack, op: Op) -> Result<(), StackError> {
+    match op {
+        // Undo & Redo are meta-operations handled above.
+        Op::Undo | Op::Redo => {}
+        Op::Push(v) => {
+            s.push_front(v);
+        }
+        Op::Save(reg) => {
+            let a = s.pop_front().ok_or(StackError::MissingValue(1))?;
+            s.registers.insert(reg, a);
+        }
+        Op::Load(reg) => match s.registers.get(&reg).cloned() {
+            Some(v) => s.push_front(v),
+            None => {
+                return Err(StackError::InvalidArgument(format!(
+                    "register '{reg}' is empty"
+                )))
+            }
+        },
+        Op::Run(op) => op.execute(s)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod undoable_tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let mut u: Undoable<i32> = Undoable::new(0);
+        assert!(!u.undo());
+        assert!(!u.redo());
+    }
+
+    #[test]
+    fn add() {
+        let mut u: Undoable<i32> = Undoable::new(0);
+        let new = u.add(1);
+        assert_eq!(1, *new);
+        assert_eq!(1, *u.cur());
+        let new = u.add(2);
+        assert_eq!(2, *new);
+        assert_eq!(2, *u.cur());
+    }
+
+    #[test]
+    fn undo() {
+        let mut u: Undoable<i32> = Undoable::new(0);
+        u.add(1);
+        assert_eq!(1, *u.cur());
+        // Undo leads to the previous value.
+        assert!(u.undo());
+        assert_eq!(0, *u.cur());
+        // ...and fwd from there ignores the previous value.
+        u.add(2);
+        assert_eq!(2, *u.cur());
+    }
+
+    #[test]
+    fn limits() {
+        let mut u: Undoable<i32> = Undoable::new(0);
+        u.set_limits(UndoLimits {
+            steps: Some(2),
+            bytes: None,
+        });
+        for i in 1..=4 {
+            u.add(i);
+        }
+        assert!(u.truncated());
+        assert!(u.undo() && u.undo());
+        assert_eq!(2, *u.cur());
+        assert!(!u.undo());
+        // Memory is capped too, the current state being always kept.
+        let mut u: Undoable<i32> = Undoable::new(0);
+        u.set_limits(UndoLimits {
+            steps: None,
+            bytes: Some(10),
+        });
+        u.add_weighted(1, 4);
+        u.add_weighted(2, 4);
+        assert!(!u.truncated());
+        u.add_weighted(3, 4);
+        assert!(u.truncated());
+        assert!(u.undo());
+        assert!(!u.undo());
+        u.add_weighted(4, 20);
+        assert_eq!(4, *u.cur());
+        assert!(!u.undo());
+    }
+
+    #[test]
+    fn redo() {
+        let mut u: Undoable<i32> = Undoable::new(0);
+        u.add(1);
+        // Undo leads to the previous value.
+        assert!(u.undo());
+        assert_eq!(0, *u.cur());
+        // ...and redo brings back the most recent one.
+        assert!(u.redo());
+        assert_eq!(1, *u.cur());
+    }
+}
+
+#[cfg(test)]
+mod stack_tests {
+    use bigdecimal::num_bigint::{self};
+
+    use super::*;
+    use bigdecimal::num_bigint::BigInt;
+
+    fn op(name: &str) -> Op {
+        crate::ops::builtin(name).unwrap()
+    }
+
+    #[test]
+    fn addition() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(10.into()))?;
+        s.apply(Op::Push(20.into()))?;
+        s.apply(op("add"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(30)]);
+        Ok(())
+    }
+
+    #[test]
+    fn apply_all_is_one_step() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.apply_all(vec![
+            Op::Push(2.into()),
+            op("add"),
+            Op::Push(3.into()),
+            op("add"),
+        ])?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(6)]);
+        let failing = vec![Op::Push(0.into()), op("div"), Op::Push(1.into())];
+        assert!(s.apply_all(failing).is_err());
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(6)]);
+        s.apply(Op::Undo)?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn pinned_entries_are_kept() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(7.into()))?;
+        s.apply(op("pin"))?;
+        s.apply(Op::Push(1.into()))?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(op("add"))?;
+        assert_eq!(s.apply(op("add")), Err(StackError::Pinned));
+        assert_eq!(s.apply(op("clear")), Err(StackError::Pinned));
+        assert_eq!(s.apply(op("rotate")), Err(StackError::Pinned));
+        assert_eq!(s.apply(Op::Save('a')), Ok(()));
+        assert_eq!(s.apply(Op::Save('a')), Err(StackError::Pinned));
+        assert_eq!(s.edit_top(), None);
+        assert!(s.is_pinned(0));
+        s.apply(op("pin"))?;
+        s.apply(op("clear"))?;
+        assert!(s.snapshot().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn push_times() -> Result<(), StackError> {
+        let mut s = Stack::from(vec![2.into(), 1.into()], None);
+        assert_eq!(s.pushed_at(0), None);
+        s.apply(Op::Push(3.into()))?;
+        let pushed = s.pushed_at(0);
+        assert!(pushed.is_some() && s.pushed_at(1).is_none());
+        // Moved entries keep their time, and undoing brings back the former.
+        s.apply(op("swap"))?;
+        assert_eq!((s.pushed_at(0), s.pushed_at(1)), (None, pushed));
+        s.apply(Op::Undo)?;
+        assert_eq!(s.pushed_at(0), pushed);
+
+        let s: Stack = State::from(&s).try_into().unwrap();
+        assert_eq!((s.pushed_at(0), s.pushed_at(2)), (pushed, None));
+        assert_eq!(s.pushed_at(3), None);
+        Ok(())
+    }
+
+    #[test]
+    fn generations() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        let first = s.generation();
+        s.apply(Op::Push(1.into()))?;
+        assert_ne!(s.generation(), first);
+        let copy = s.clone();
+        assert_eq!(copy.generation(), s.generation());
+        assert_ne!(Stack::new().generation(), s.generation());
+        s.apply(Op::Undo)?;
+        assert_ne!(s.generation(), copy.generation());
+
+        // Operations run on a copy of the current state.
+        let job = copy.spawn_all(vec![Op::Push(2.into()), op("add")]);
+        let top = job.poll(Duration::from_secs(10)).unwrap().top()?;
+        assert_eq!(top, Some(BigDecimal::from(3)));
+        assert_eq!(copy.snapshot(), [BigDecimal::from(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn origins() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push("12.5".parse().unwrap()))?;
+        s.apply(Op::Push(3.into()))?;
+        s.apply(op("mul"))?;
+        assert_eq!(s.origin(0), Some("12.5 3 mul"));
+        s.apply(op("pi"))?;
+        s.apply(Op::Push(1.into()))?;
+        assert_eq!(
+            [s.origin(0), s.origin(1), s.origin(2)],
+            [None, Some("pi"), Some("12.5 3 mul")]
+        );
+        // Moved entries keep theirs.
+        s.apply(op("rotate"))?;
+        assert_eq!([s.origin(0), s.origin(2)], [Some("pi"), None]);
+        s.apply(op("drop"))?;
+        s.apply(op("add"))?;
+        assert_eq!(s.origin(0), Some("1 37.5 add"));
+        s.apply(Op::Push("1e30".parse().unwrap()))?;
+        s.apply(op("sqrt"))?;
+        assert_eq!(s.origin(0), Some("100000000000000… sqrt"));
+
+        let s: Stack = State::from(&s).try_into().unwrap();
+        assert_eq!(s.origin(0), Some("100000000000000… sqrt"));
+        assert_eq!(s.origin(1), Some("1 37.5 add"));
+        Ok(())
+    }
+
+    #[test]
+    fn bookmarks() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.bookmark("one");
+        s.apply(Op::Push(2.into()))?;
+        s.apply(op("add"))?;
+        s.restore("one")?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1)]);
+        // Restoring is undoable, and bookmarks outlive the undo history.
+        s.apply(Op::Undo)?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(3)]);
+        s.apply(Op::Undo)?;
+        s.apply(Op::Undo)?;
+        s.apply(Op::Undo)?;
+        s.restore("one")?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1)]);
+        assert_eq!(
+            s.restore("two"),
+            Err(StackError::InvalidArgument(
+                "no bookmark 'two' (only one)".into()
+            ))
+        );
+
+        let state = State::from(&s);
+        assert_eq!(state.bookmarks["one"].stack, vec!["1"]);
+        let mut s: Stack = state.try_into().unwrap();
+        s.apply(op("clear"))?;
+        s.restore("one")?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn bookmark_diff() -> Result<(), StackError> {
+        let change = |index, before: Option<i32>, after: Option<i32>| Change {
+            index,
+            before: before.map(BigDecimal::from),
+            after: after.map(BigDecimal::from),
+        };
+        let mut s = Stack::from((1..=5).map(BigDecimal::from).collect(), None);
+        s.bookmark("start");
+        assert!(s.diff("start")?.is_empty());
+        s.apply(Op::Push(6.into()))?;
+        assert_eq!(s.diff("start")?, vec![change(0, None, Some(6))]);
+        // From the top, 1 2 3 4 5 -> 7 3 2 4 5.
+        s.apply(op("drop"))?;
+        s.apply(op("drop"))?;
+        s.apply(op("swap"))?;
+        s.apply(Op::Push(7.into()))?;
+        assert_eq!(
+            s.diff("start")?,
+            vec![
+                change(0, Some(1), Some(7)),
+                change(1, Some(2), Some(3)),
+                change(2, Some(3), Some(2)),
+            ]
+        );
+        s.apply(op("clear"))?;
+        s.apply(Op::Push(5.into()))?;
+        assert_eq!(
+            s.diff("start")?,
+            vec![
+                change(0, Some(1), None),
+                change(0, Some(2), None),
+                change(0, Some(3), None),
+                change(0, Some(4), None),
+            ]
+        );
+        assert!(s.diff("end").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn subtract() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(10.into()))?;
+        s.apply(Op::Push(20.into()))?;
+        s.apply(op("sub"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(-10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn mumltiply() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(10.into()))?;
+        s.apply(Op::Push(20.into()))?;
+        s.apply(op("mul"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(200)]);
+        Ok(())
+    }
+
+    #[test]
+    fn divide() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(20.into()))?;
+        s.apply(Op::Push(10.into()))?;
+        s.apply(op("div"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn divide_by_zero() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(20.into()))?;
+        s.apply(Op::Push(0.into()))?;
+        assert_eq!(
+            s.apply(op("div")),
+            Err(StackError::InvalidArgument(
+                "element 1 must be non-zero".into()
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rem() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(7.into()))?;
+        s.apply(Op::Push(3.into()))?;
+        s.apply(op("mod"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn sqrt() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(4.into()))?;
+        s.apply(op("sqrt"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn sqrt_of_negative() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push((-4).into()))?;
+        assert_eq!(
+            s.apply(op("sqrt")),
+            Err(StackError::InvalidArgument(
+                "element 1 must be positive".into()
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pow() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(2.into()))?;
+        s.apply(Op::Push(8.into()))?;
+        s.apply(op("pow"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(256)]);
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.apply(op("dup"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1), BigDecimal::from(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn pop() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.apply(op("drop"))?;
+        assert!(s.snapshot().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn rotate() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(op("swap"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(1), BigDecimal::from(2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn precision() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1234.into()))?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(op("precision"))?;
+        assert_eq!(s.snapshot()[0].to_string(), "1234");
+        s.apply(Op::Push(3.into()))?;
+        s.apply(op("div"))?;
+        assert_eq!(s.snapshot()[0].to_string(), "411.33");
+        Ok(())
+    }
+
+    #[test]
+    fn high_precision() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(200.into()))?;
+        s.apply(op("precision"))?;
+        s.apply(Op::Push(1.into()))?;
+        s.apply(Op::Push(3.into()))?;
+        s.apply(op("div"))?;
+        assert_eq!(
+            s.snapshot()[0].to_string(),
+            format!("0.{}", "3".repeat(200))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn values_borrow_unless_rounded() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.apply(Op::Push(3.into()))?;
+        s.apply(op("div"))?;
+        s.apply(Op::Push(2.into()))?;
+        let values: Vec<_> = s.values().collect();
+        assert!(matches!(values[0], Cow::Borrowed(_)));
+        assert!(matches!(values[1], Cow::Owned(_)));
+        assert_eq!(
+            values.into_iter().map(Cow::into_owned).collect::<Vec<_>>(),
+            s.snapshot()
+        );
+        let window: Vec<_> = s.window(1, 5).map(Cow::into_owned).collect();
+        assert_eq!(window, s.snapshot()[1..]);
+        assert_eq!(s.window(7, 1).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn pow_cap() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(2.into()))?;
+        s.apply(Op::Push(4096.into()))?;
+        s.apply(op("pow"))?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(Op::Push(2_000_000.into()))?;
+        assert_eq!(
+            s.apply(op("pow")),
+            Err(StackError::InvalidArgument(
+                "result over 1048576 bits".into()
+            ))
+        );
+
+        s.set_max_bits(Some(100));
+        s.apply(Op::Undo)?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(Op::Push(200.into()))?;
+        assert_eq!(
+            s.apply(op("pow")),
+            Err(StackError::InvalidArgument("result over 100 bits".into()))
+        );
+        s.set_max_bits(None);
+        s.apply(op("pow"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn save_and_load() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(42.into()))?;
+        s.apply(Op::Save('x'))?;
+        assert!(s.snapshot().is_empty());
+        s.apply(Op::Load('x'))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(42)]);
+        Ok(())
+    }
+
+    #[test]
+    fn load_empty_register() {
+        let mut s = Stack::new();
+        assert_eq!(
+            s.apply(Op::Load('z')),
+            Err(StackError::InvalidArgument("register 'z' is empty".into()))
+        );
+    }
+
+    #[test]
+    fn defaults() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(5.into()))?;
+        s.apply(op("precision"))?;
+        s.apply(Op::Push(16.into()))?;
+        s.apply(op("base"))?;
+        assert_eq!(s.precision(), 5);
+        assert_eq!(s.output_base(), 16);
+        s.apply(op("defaults"))?;
+        assert_eq!(s.precision(), DEFAULT_PRECISION);
+        assert_eq!(s.output_base(), DEFAULT_BASE);
+        Ok(())
+    }
+
+    #[test]
+    fn permutation_forward() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(Op::Push(3.into()))?;
+        s.apply(op("rotate"))?;
+        assert_eq!(
+            s.snapshot(),
+            vec![
+                BigDecimal::from(2),
+                BigDecimal::from(1),
+                BigDecimal::from(3)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn permutation_backward() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(1.into()))?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(Op::Push(3.into()))?;
+        s.apply(op("rotate_back"))?;
+        assert_eq!(
+            s.snapshot(),
+            vec![
+                BigDecimal::from(1),
+                BigDecimal::from(3),
+                BigDecimal::from(2)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn permutation_single_noop() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(42.into()))?;
+        s.apply(op("rotate"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(42)]);
+        Ok(())
+    }
+
+    #[test]
+    fn last_args() -> Result<(), StackError> {
+        let mut s = Stack::from(vec![BigDecimal::from(3), BigDecimal::from(6)], None);
+        s.apply(op("div"))?;
+        s.apply(op("dup"))?;
+        s.apply(op("clear"))?;
+        // Operations without arguments leave the latest ones alone.
+        s.apply(op("last_args"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(2)]);
+        s.apply(Op::Undo)?;
+        s.apply(Op::Undo)?;
+        s.apply(Op::Undo)?;
+        s.apply(op("last_args"))?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(3), 6.into(), 2.into()]);
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_and_complete() -> Result<(), StackError> {
+        let mut s = Stack::from(vec![BigDecimal::from(3), BigDecimal::from(4)], None);
+        let job = s.spawn(op("mul"));
+        let outcome = job.poll(Duration::from_secs(10)).unwrap();
+        assert_eq!(s.snapshot().len(), 2);
+        s.complete(outcome)?;
+        assert_eq!(s.snapshot(), vec![BigDecimal::from(12)]);
+        s.apply(Op::Undo)?;
+        assert_eq!(s.snapshot().len(), 2);
+
+        s.apply(Op::Push(0.into()))?;
+        let job = s.spawn(op("div"));
+        let outcome = job.poll(Duration::from_secs(10)).unwrap();
+        assert!(s.complete(outcome).is_err());
+        assert_eq!(s.snapshot()[0], BigDecimal::from(0));
+        Ok(())
+    }
+
+    #[test]
+    fn pow_representation() -> Result<(), StackError> {
+        let mut s = Stack::new();
+        s.apply(Op::Push(10.into()))?;
+        s.apply(Op::Push(2.into()))?;
+        s.apply(op("pow"))?;
+        let r = s.snapshot()[0].clone();
+        let (bi, s) = r.as_bigint_and_scale();
+
+        assert_eq!(*bi, BigInt::new(num_bigint::Sign::Plus, vec![100]));
+        assert_eq!(s, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+use crate::ops::Angle;
+use crate::stack::Stack;
+
+/// Permanent state of the app.
+#[derive(Serialize, Deserialize, Default)]
+pub struct State {
+    pub stack: Vec<String>,
+    pub precision: Option<u64>,
+    #[serde(default)]
+    pub output_base: Option<u32>,
+    #[serde(default)]
+    pub registers: HashMap<char, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub angle: Option<Angle>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fast: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_size: Option<u32>,
+    // Named copies of the stack, see Stack::bookmark.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bookmarks: BTreeMap<String, State>,
+    // The indexes of the pinned entries, from the top.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pinned: Vec<usize>,
+    // When each entry was pushed, in seconds since 1970, top first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pushed: Vec<Option<u64>>,
+    // How each entry was computed, if by an operation, top first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub origins: Vec<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Additional workspaces, beyond this first one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tabs: Vec<State>,
+    #[serde(default)]
+    pub active_tab: usize,
+    // The inputs and operations committed, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<String>,
+    // The keys of the macros, by name, written as for feeding them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub macros: BTreeMap<String, String>,
+}
+
+impl State {
+    /// Keep at most `max` entries on the top of each stack. Returns the
+    /// entries dropped, in the order they were pushed.
+    pub fn cap(&mut self, max: usize) -> Vec<String> {
+        let mut dropped = vec![];
+        if self.stack.len() > max {
+            dropped.extend(self.stack.drain(max..).rev());
+            self.pinned.retain(|&i| i < max);
+            self.pushed.truncate(max);
+            self.origins.truncate(max);
+        }
+        for tab in &mut self.tabs {
+            dropped.extend(tab.cap(max));
+        }
+        dropped
+    }
+
+    /// The state of the workspace that was active when saving.
+    pub fn active(&self) -> &State {
+        match self.active_tab {
+            0 => self,
+            n => self.tabs.get(n - 1).unwrap_or(self),
+        }
+    }
+}
+
+impl From<&Stack> for State {
+    fn from(stack: &Stack) -> Self {
+        let values: Vec<String> = stack.snapshot().iter().map(|v| v.to_string()).collect();
+        State {
+            pinned: (0..values.len()).filter(|&i| stack.is_pinned(i)).collect(),
+            pushed: match (0..values.len()).any(|i| stack.pushed_at(i).is_some()) {
+                true => (0..values.len()).map(|i| stack.pushed_at(i)).collect(),
+                false => vec![],
+            },
+            origins: match (0..values.len()).any(|i| stack.origin(i).is_some()) {
+                true => (0..values.len())
+                    .map(|i| stack.origin(i).map(str::to_owned))
+                    .collect(),
+                false => vec![],
+            },
+            stack: values,
+            precision: Some(stack.precision()),
+            output_base: Some(stack.output_base()),
+            angle: Some(stack.angle()),
+            fast: stack.fast(),
+            word_size: stack.word_size(),
+            bookmarks: stack
+                .bookmarks()
+                .map(|(name, saved)| (name.to_owned(), State::from(&saved)))
+                .collect(),
+            registers: stack
+                .registers()
+                .iter()
+                .map(|(&k, v)| (k, v.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Where the state is kept between sessions. Front-ends without a file
+/// system, e.g. in a browser, provide their own.
+pub trait Storage {
+    /// Load the state saved by the previous session.
+    fn load(&self) -> anyhow::Result<State>;
+
+    /// Save the state for the next session.
+    fn save(&self, state: &State) -> anyhow::Result<()>;
+}
+
+/// Encryption of the files of a [`FileStorage`], see
+/// [`FileStorage::with_cipher`].
+pub trait Cipher {
+    fn encrypt(&self, plain: &[u8]) -> anyhow::Result<Vec<u8>>;
+
+    /// The plain data, or an error if it wasn't encrypted with this key or
+    /// has been tampered with.
+    fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>>;
+}
+
+/// The start of the encrypted files, telling them from plain ones.
+const ENCRYPTED: &[u8] = b"hc-encrypted-1\n";
+
+/// The file is encrypted, and the storage has no key for it or a wrong one.
+#[derive(Debug, Error)]
+#[error("{} is encrypted, and can't be read without its passphrase or key file", .0.display())]
+pub struct Encrypted(pub PathBuf);
+
+/// The state as a JSON file, encrypted or not.
+#[derive(Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+    cipher: Option<Arc<dyn Cipher>>,
+}
+
+impl FileStorage {
+    pub fn new(path: PathBuf) -> FileStorage {
+        FileStorage { path, cipher: None }
+    }
+
+    /// Encrypt the files written from now on, the state and those next to
+    /// it, and decrypt those read. Plain files are still read as they are,
+    /// so that an existing state is encrypted once saved again.
+    pub fn with_cipher(mut self, cipher: Arc<dyn Cipher>) -> FileStorage {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Whether the file exists and is encrypted.
+    pub fn encrypted(&self) -> bool {
+        fs::read(&self.path).is_ok_and(|data| data.starts_with(ENCRYPTED))
+    }
+
+    /// Another file, with the same cipher.
+    fn sibling(&self, path: PathBuf) -> FileStorage {
+        FileStorage {
+            path,
+            cipher: self.cipher.clone(),
+        }
+    }
+
+    /// The contents of the file, decrypted if need be.
+    fn read(&self) -> anyhow::Result<Vec<u8>> {
+        let data = fs::read(&self.path)?;
+        let Some(sealed) = data.strip_prefix(ENCRYPTED) else {
+            return Ok(data);
+        };
+        let cipher = self.cipher.as_ref().ok_or(Encrypted(self.path.clone()))?;
+        match cipher.decrypt(sealed) {
+            Ok(plain) => Ok(plain),
+            Err(err) => {
+                log::warn!("failed to decrypt {}: {err:#}", self.path.display());
+                Err(Encrypted(self.path.clone()).into())
+            }
+        }
+    }
+
+    /// Replace the contents of the file, encrypted if need be.
+    fn write(&self, plain: &[u8]) -> anyhow::Result<()> {
+        let prefix = self.path.parent().context("incorrect path")?;
+        std::fs::create_dir_all(prefix)?;
+        let data = match &self.cipher {
+            Some(cipher) => [ENCRYPTED, &cipher.encrypt(plain)?].concat(),
+            None => plain.to_vec(),
+        };
+        let mut output = File::create(&self.path)?;
+        output.write_all(&data).context("failed to write")
+    }
+
+    /// The file in the configuration directory of the user.
+    pub fn user() -> anyhow::Result<FileStorage> {
+        Ok(FileStorage::new(config_file()?))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Move the file aside, e.g. when it can't be loaded, so that saving
+    /// doesn't overwrite it. Returns the path of the backup.
+    pub fn back_up(&self) -> anyhow::Result<PathBuf> {
+        let backup = self.path.with_extension("json.bak");
+        fs::rename(&self.path, &backup)
+            .with_context(|| format!("failed to move {}", self.path.display()))?;
+        Ok(backup)
+    }
+
+    /// When the file was last written, if it exists.
+    pub fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Keep other instances from saving the file until the returned lock is
+    /// dropped, waiting for them to be done if they are.
+    pub fn lock(&self) -> anyhow::Result<File> {
+        let path = self.path.with_extension("json.lock");
+        fs::create_dir_all(path.parent().context("incorrect path")?)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        file.lock()
+            .with_context(|| format!("failed to lock {}", path.display()))?;
+        Ok(file)
+    }
+
+    /// Save the state as a new session next to the file, rather than over
+    /// it. Returns the path of the session.
+    pub fn save_new(&self, state: &State) -> anyhow::Result<PathBuf> {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let path = self.path.with_file_name(format!("state-{secs}.json"));
+        self.sibling(path.clone()).save(state)?;
+        Ok(path)
+    }
+
+    /// Append the values to the archive next to the file, one per line.
+    /// Returns the path of the archive.
+    pub fn archive(&self, values: &[String]) -> anyhow::Result<PathBuf> {
+        let path = self.path.with_file_name("archive.txt");
+        let archive = self.sibling(path.clone());
+        if self.cipher.is_some() || archive.encrypted() {
+            // Encrypted as a whole, so rewritten rather than appended to.
+            let mut text = match path.exists() {
+                true => archive.read()?,
+                false => Vec::new(),
+            };
+            for v in values {
+                writeln!(text, "{v}")?;
+            }
+            archive
+                .write(&text)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            return Ok(path);
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        for v in values {
+            writeln!(file, "{v}")?;
+        }
+        Ok(path)
+    }
+
+    /// Save an emergency copy of the state next to the file, e.g. after a
+    /// crash, when the state may not be sound enough to replace the saved
+    /// one. Returns the path of the copy.
+    pub fn save_crash(&self, state: &State) -> anyhow::Result<PathBuf> {
+        let path = self.path.with_extension("json.crash");
+        self.sibling(path.clone()).save(state)?;
+        Ok(path)
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self) -> anyhow::Result<State> {
+        let json = self.read()?;
+        let state: State = serde_json::from_slice(&json)?;
+        Ok(state)
+    }
+
+    fn save(&self, state: &State) -> anyhow::Result<()> {
+        self.write(serde_json::to_string(state)?.as_bytes())?;
+        log::info!("saved the state to {}", self.path.display());
+        Ok(())
+    }
+}
+
+fn config_file() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("state.json"))
+}
+
+/// The directory holding the files of helix calc.
+#[cfg(windows)]
+pub fn config_dir() -> anyhow::Result<PathBuf> {
+    Ok(PathBuf::from(std::env::var("LOCALAPPDATA")?).join("HelixCalc"))
+}
+
+/// The directory holding the files of helix calc.
+#[cfg(unix)]
+pub fn config_dir() -> anyhow::Result<PathBuf> {
+    Ok(PathBuf::from(std::env::var("HOME")?)
+        .join(".config")
+        .join("helix-calc"))
+}
+
+/// There's no such directory elsewhere, e.g. in a browser.
+#[cfg(not(any(unix, windows)))]
+pub fn config_dir() -> anyhow::Result<PathBuf> {
+    anyhow::bail!("no configuration directory on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn cap_the_stacks() {
+        let mut state = State {
+            stack: vec!["3".into(), "2".into(), "1".into()],
+            pinned: vec![0, 2],
+            tabs: vec![State {
+                stack: vec!["5".into(), "4".into()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(state.cap(1), ["1", "2", "4"]);
+        assert_eq!(state.stack, ["3"]);
+        assert_eq!(state.pinned, [0]);
+        assert_eq!(state.tabs[0].stack, ["5"]);
+        assert!(state.cap(1).is_empty());
+    }
+
+    #[test]
+    fn file_storage_round_trip() -> anyhow::Result<()> {
+        let dir = env::temp_dir().join(format!("hc-state-{}", std::process::id()));
+        let storage = FileStorage::new(dir.join("state.json"));
+        assert!(storage.load().is_err());
+        let state = State {
+            stack: vec!["1.5".into()],
+            name: Some("work".into()),
+            ..Default::default()
+        };
+        storage.save(&state)?;
+        let loaded = storage.load()?;
+        assert_eq!(loaded.stack, state.stack);
+        assert_eq!(loaded.name, state.name);
+
+        fs::write(dir.join("state.json"), "{\"stack\": 1")?;
+        assert!(storage.load().is_err());
+        let backup = storage.back_up()?;
+        assert!(!storage.exists());
+        assert_eq!(fs::read_to_string(backup)?, "{\"stack\": 1");
+        let crash = storage.save_crash(&state)?;
+        assert!(!storage.exists());
+        assert_eq!(FileStorage::new(crash).load()?.stack, state.stack);
+        let lock = storage.lock()?;
+        let session = storage.save_new(&state)?;
+        drop(lock);
+        assert!(session
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("state-"));
+        assert_eq!(FileStorage::new(session).load()?.stack, state.stack);
+        storage.archive(&["1".into(), "2".into()])?;
+        let archive = storage.archive(&["3".into()])?;
+        assert_eq!(fs::read_to_string(archive)?, "1\n2\n3\n");
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    /// Flips the bits, and checks the key.
+    struct Flip(u8);
+
+    impl Cipher for Flip {
+        fn encrypt(&self, plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+            let mut data = vec![self.0];
+            data.extend(plain.iter().map(|b| !b));
+            Ok(data)
+        }
+
+        fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+            anyhow::ensure!(data.first() == Some(&self.0), "wrong key");
+            Ok(data[1..].iter().map(|b| !b).collect())
+        }
+    }
+
+    #[test]
+    fn encrypted_file_storage() -> anyhow::Result<()> {
+        let dir = env::temp_dir().join(format!("hc-encrypted-{}", std::process::id()));
+        let path = dir.join("state.json");
+        let state = State {
+            stack: vec!["1.5".into()],
+            ..Default::default()
+        };
+        // A plain state is read as is, and encrypted once saved.
+        FileStorage::new(path.clone()).save(&state)?;
+        let storage = FileStorage::new(path.clone()).with_cipher(Arc::new(Flip(1)));
+        assert!(!storage.encrypted());
+        assert_eq!(storage.load()?.stack, state.stack);
+        storage.save(&state)?;
+        assert!(storage.encrypted());
+        assert!(!fs::read(&path)?.windows(3).any(|w| w == b"1.5"));
+        assert_eq!(storage.load()?.stack, state.stack);
+
+        for storage in [
+            FileStorage::new(path.clone()),
+            FileStorage::new(path.clone()).with_cipher(Arc::new(Flip(2))),
+        ] {
+            let err = storage.load().err().unwrap();
+            assert!(err.is::<Encrypted>(), "{err}");
+        }
+        let crash = storage.save_crash(&state)?;
+        assert!(storage.sibling(crash.clone()).encrypted());
+        assert_eq!(storage.sibling(crash).load()?.stack, state.stack);
+
+        storage.archive(&["1".into()])?;
+        let archive = storage.archive(&["2".into()])?;
+        assert!(storage.sibling(archive.clone()).encrypted());
+        assert_eq!(storage.sibling(archive).read()?, b"1\n2\n");
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Unit suffixes accepted on input, e.g. `5km` or `3.5GiB`, and SI prefixes
+//! alone, e.g. `4.7k`.
+//!
+//! There is no notion of units on the stack: values are converted to the
+//! base unit of their dimension (meters, bytes or seconds) when entered.
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dimension {
+    Length,
+    Data,
+    Time,
+    /// A bare SI prefix, which only scales the number.
+    Prefix,
+}
+
+const UNITS: &[(&str, Dimension, &str)] = &[
+    ("km", Dimension::Length, "1000"),
+    ("cm", Dimension::Length, "0.01"),
+    ("mm", Dimension::Length, "0.001"),
+    ("in", Dimension::Length, "0.0254"),
+    ("ft", Dimension::Length, "0.3048"),
+    ("B", Dimension::Data, "1"),
+    ("kB", Dimension::Data, "1000"),
+    ("KB", Dimension::Data, "1000"),
+    ("MB", Dimension::Data, "1000000"),
+    ("GB", Dimension::Data, "1000000000"),
+    ("TB", Dimension::Data, "1000000000000"),
+    ("KiB", Dimension::Data, "1024"),
+    ("MiB", Dimension::Data, "1048576"),
+    ("GiB", Dimension::Data, "1073741824"),
+    ("TiB", Dimension::Data, "1099511627776"),
+    ("d", Dimension::Time, "86400"),
+    ("h", Dimension::Time, "3600"),
+    ("min", Dimension::Time, "60"),
+    ("s", Dimension::Time, "1"),
+    ("ms", Dimension::Time, "0.001"),
+    ("f", Dimension::Prefix, "1e-15"),
+    ("p", Dimension::Prefix, "1e-12"),
+    ("n", Dimension::Prefix, "1e-9"),
+    ("u", Dimension::Prefix, "1e-6"),
+    ("µ", Dimension::Prefix, "1e-6"),
+    ("m", Dimension::Prefix, "1e-3"),
+    ("k", Dimension::Prefix, "1e3"),
+    ("M", Dimension::Prefix, "1e6"),
+    ("G", Dimension::Prefix, "1e9"),
+    ("T", Dimension::Prefix, "1e12"),
+    ("P", Dimension::Prefix, "1e15"),
+];
+
+/// The dimension of the unit and its factor to the base unit.
+///
+/// `m` is ambiguous on its own (meters? minutes?), so it's milli, as in
+/// `22m`, except within a compound duration such as `2h30m`.
+pub fn lookup(unit: &str, in_duration: bool) -> Option<(Dimension, BigDecimal)> {
+    if in_duration && unit == "m" {
+        return Some((Dimension::Time, BigDecimal::from(60)));
+    }
+    UNITS
+        .iter()
+        .find(|(name, _, _)| *name == unit)
+        .map(|(_, dimension, factor)| (*dimension, BigDecimal::from_str(factor).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minutes_only_in_durations() {
+        assert_eq!(
+            lookup("m", false),
+            Some((Dimension::Prefix, "0.001".parse().unwrap()))
+        );
+        assert_eq!(lookup("m", true), Some((Dimension::Time, 60.into())));
+        assert_eq!(lookup("min", false), Some((Dimension::Time, 60.into())));
+    }
+
+    #[test]
+    fn table_is_valid() {
+        for (name, _, _) in UNITS {
+            assert!(lookup(name, false).is_some());
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Timings of representative work of the engine: deep stacks, huge numbers
+//! and high precisions, through both arithmetic and formatting, to measure
+//! performance regressions as it grows.
+use bigdecimal::BigDecimal;
+use hc_core::{
+    format::format_number,
+    ops,
+    stack::{Op, Stack, StackError},
+};
+use std::{
+    hint::black_box,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+/// How long each case is repeated for, at least once.
+const BUDGET: Duration = Duration::from_millis(500);
+
+/// A piece of work to time.
+struct Case {
+    name: &'static str,
+    run: fn() -> Result<(), StackError>,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "push 1k values",
+        run: || {
+            let mut s = Stack::new();
+            for i in 0..1000 {
+                s.apply(Op::Push(BigDecimal::from(i)))?;
+            }
+            Ok(())
+        },
+    },
+    Case {
+        name: "add 1k values",
+        run: || {
+            let mut s = Stack::from((0..1000).map(BigDecimal::from).collect(), None);
+            for _ in 1..1000 {
+                s.apply(op("add"))?;
+            }
+            Ok(())
+        },
+    },
+    Case {
+        name: "3^200000",
+        run: || run(&[3, 200_000], &["pow"], None),
+    },
+    Case {
+        name: "√2 to 2000 digits",
+        run: || run(&[2], &["sqrt"], Some(2000)),
+    },
+    Case {
+        name: "1/7 to 20000 digits",
+        run: || run(&[1, 7], &["div"], Some(20_000)),
+    },
+    Case {
+        name: "sin 1 to 500 digits",
+        run: || run(&[1], &["sin"], Some(500)),
+    },
+    Case {
+        name: "format 10k values",
+        run: || {
+            for i in 0..10_000 {
+                let v = BigDecimal::from(i) / BigDecimal::from(7);
+                black_box(format_number(&v, 20, true, 10));
+            }
+            Ok(())
+        },
+    },
+    Case {
+        name: "3^100000 in decimal",
+        run: || format_power(3, 10),
+    },
+    Case {
+        name: "3^100000 in hex",
+        run: || format_power(3, 16),
+    },
+];
+
+fn op(name: &str) -> Op {
+    ops::builtin(name).expect("the benchmarks use built-ins")
+}
+
+/// Apply the operations to the values, pushed in order, at the precision.
+fn run(values: &[u32], names: &[&str], precision: Option<u64>) -> Result<(), StackError> {
+    let values = values.iter().rev().map(|&v| v.into()).collect();
+    let mut s = Stack::from(values, precision);
+    for name in names {
+        s.apply(op(name))?;
+    }
+    black_box(s.snapshot());
+    Ok(())
+}
+
+/// Compute n^100000, and format it in the base.
+fn format_power(n: u32, base: u32) -> Result<(), StackError> {
+    let mut s = Stack::from(vec![100_000.into(), n.into()], None);
+    s.apply(op("pow"))?;
+    black_box(format_number(&s.snapshot()[0], 80, true, base));
+    Ok(())
+}
+
+/// The number of runs of the case within the budget, and their mean time.
+fn measure(case: &Case, budget: Duration) -> Result<(u32, Duration), StackError> {
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs == 0 || start.elapsed() < budget {
+        (case.run)()?;
+        runs += 1;
+    }
+    Ok((runs, start.elapsed() / runs))
+}
+
+/// Time the cases whose name contains the filter, a line each.
+pub fn bench(filter: &str, mut out: impl Write) -> anyhow::Result<()> {
+    bench_within(filter, BUDGET, &mut out)
+}
+
+fn bench_within(filter: &str, budget: Duration, out: &mut impl Write) -> anyhow::Result<()> {
+    let cases: Vec<&Case> = CASES.iter().filter(|c| c.name.contains(filter)).collect();
+    if cases.is_empty() {
+        anyhow::bail!("no benchmark matches '{filter}'");
+    }
+    let width = cases
+        .iter()
+        .map(|c| c.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for case in cases {
+        let (runs, mean) = measure(case, budget)?;
+        writeln!(out, "{:width$}  {runs:>6} runs  {mean:>12.3?}", case.name)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn time_the_cases() -> anyhow::Result<()> {
+        let mut out = vec![];
+        bench_within("digits", Duration::ZERO, &mut out)?;
+        let out = String::from_utf8(out)?;
+        let names: Vec<&str> = out.lines().map(|l| l.split("  ").next().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "√2 to 2000 digits",
+                "1/7 to 20000 digits",
+                "sin 1 to 500 digits"
+            ]
+        );
+        assert!(out.lines().all(|l| l.contains("     1 runs")), "{out}");
+        assert!(bench_within("nope", Duration::ZERO, &mut io::sink()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! Bit-field view of an integer: its bits byte by byte, with their indices,
+//! to read flags and masks without expanding hex mentally.
+use bigdecimal::{num_bigint::BigUint, BigDecimal, Signed, ToPrimitive};
+use hc_core::format::twos_complement;
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+use thiserror::Error;
+
+// The widest integer shown, in bits, one byte per line.
+const MAX_BITS: u64 = 128;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BitsError {
+    #[error("only the bits of integers can be shown")]
+    NotInteger,
+    #[error("only up to {MAX_BITS} bits can be shown")]
+    TooWide,
+}
+
+/// The bits of an integer, in as few whole bytes as hold it and the word
+/// size, if any. Negatives are in two's complement.
+pub struct BitField {
+    value: BigUint,
+    bytes: u64,
+    grouped: bool,
+}
+
+impl BitField {
+    /// The bits of the integer, with a space between nibbles if grouped.
+    pub fn new(n: &BigDecimal, word_size: Option<u32>, grouped: bool) -> Result<Self, BitsError> {
+        if !n.is_integer() {
+            return Err(BitsError::NotInteger);
+        }
+        let (int, _) = n.with_scale(0).into_bigint_and_exponent();
+        // A sign bit is needed for negatives.
+        let needed = match int.is_negative() {
+            true => (-&int - 1u32).bits() + 1,
+            false => int.bits(),
+        };
+        let bits = needed.max(word_size.unwrap_or(0) as u64).max(1);
+        if bits > MAX_BITS {
+            return Err(BitsError::TooWide);
+        }
+        let bytes = bits.div_ceil(8);
+        let value = match int.is_negative() {
+            true => twos_complement(n, (bytes * 8) as u32)
+                .expect("the word holds the integer")
+                .with_scale(0)
+                .into_bigint_and_exponent()
+                .0
+                .magnitude()
+                .clone(),
+            false => int.magnitude().clone(),
+        };
+        Ok(BitField {
+            value,
+            bytes,
+            grouped,
+        })
+    }
+
+    /// The bits of the byte, most significant first.
+    fn byte(&self, byte: u64) -> impl Iterator<Item = bool> + '_ {
+        (0..8).rev().map(move |i| self.value.bit(byte * 8 + i))
+    }
+
+    /// A line per byte, most significant first: the indices of its bits, the
+    /// bits, set ones highlighted, then the byte in hex.
+    fn lines(&self) -> Vec<Line<'static>> {
+        let gap = if self.grouped { " " } else { "" };
+        let mut lines = vec![Line::from(format!("        7654{gap}3210  hex")).dark_gray()];
+        for byte in (0..self.bytes).rev() {
+            let mut spans = vec![Span::raw(format!("{:>3}-{:<3} ", byte * 8 + 7, byte * 8))];
+            for (i, bit) in self.byte(byte).enumerate() {
+                if i == 4 {
+                    spans.push(gap.into());
+                }
+                spans.push(match bit {
+                    true => "1".yellow().bold(),
+                    false => "0".into(),
+                });
+            }
+            let hex = (&self.value >> (byte * 8)).to_u64().unwrap_or(0) & 0xff;
+            spans.push(format!("  {hex:02x}").blue());
+            lines.push(Line::from(spans));
+        }
+        lines
+    }
+}
+
+impl Widget for &BitField {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let lines = self.lines();
+        let width = lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 2;
+        let [area] = Layout::vertical([Constraint::Length(lines.len() as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Length(width.max(20))])
+            .flex(Flex::Center)
+            .areas(area);
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .title(format!(" {} bits of S1 ", self.bytes * 8))
+                    .title_bottom("<Any key to close>"),
+            )
+            .bg(Color::Black)
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(n: &str, word_size: Option<u32>) -> Result<Vec<String>, BitsError> {
+        let field = BitField::new(&n.parse().unwrap(), word_size, true)?;
+        Ok(field.lines().iter().map(Line::to_string).collect())
+    }
+
+    #[test]
+    fn bytes_of_the_integer() -> Result<(), BitsError> {
+        assert_eq!(
+            bits("5", None)?,
+            ["        7654 3210  hex", "  7-0   0000 0101  05"]
+        );
+        assert_eq!(
+            bits("4660", None)?,
+            [
+                "        7654 3210  hex",
+                " 15-8   0001 0010  12",
+                "  7-0   0011 0100  34"
+            ]
+        );
+        // Two's complement, in the word if any.
+        assert_eq!(bits("-1", None)?[1], "  7-0   1111 1111  ff");
+        assert_eq!(bits("-128", None)?.len(), 2);
+        assert_eq!(bits("-129", None)?.len(), 3);
+        assert_eq!(bits("-2", Some(16))?[1], " 15-8   1111 1111  ff");
+        assert_eq!(bits("1.5", None).err(), Some(BitsError::NotInteger));
+        assert_eq!(bits("1e40", None).err(), Some(BitsError::TooWide));
+        Ok(())
+    }
+}
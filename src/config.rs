@@ -0,0 +1,217 @@
+//! User preferences, read from `config.json` next to the saved state.
+use anyhow::Context;
+use hc_core::{
+    format::Template,
+    ops::Angle,
+    stack::{UndoLimits, DEFAULT_MAX_BITS},
+    state::{self, State},
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs};
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Cap on the size of exponentiation results, `null` for none.
+    pub max_bits: Option<u64>,
+    /// The most steps which can be undone in each stack, `null` for all.
+    pub max_undo_steps: Option<usize>,
+    /// The most memory the undo history of each stack can take, in
+    /// megabytes, `null` for no cap.
+    pub max_undo_megabytes: Option<usize>,
+    /// Precision of new stacks, when there is no saved state.
+    pub precision: Option<u64>,
+    /// Output base of new stacks, when there is no saved state.
+    pub output_base: Option<u32>,
+    /// Unit of angles of new stacks, when there is no saved state.
+    pub angle: Option<Angle>,
+    /// Show the decimal separator at startup.
+    pub separator: bool,
+    /// Show numbers as H:MM:SS durations at startup.
+    pub durations: bool,
+    /// Show numbers with SI prefixes at startup.
+    pub si_prefixes: bool,
+    /// A format of the numbers to switch to, e.g. `#,##0.00 "€"`.
+    pub format: Option<String>,
+    /// Save at most that many entries of each stack, the deepest ones being
+    /// dropped, `null` for all of them.
+    pub max_saved_entries: Option<usize>,
+    /// Append the entries dropped when saving to `archive.txt`.
+    pub archive_dropped: bool,
+    /// Keep the bookmarks of the stacks from one session to the next.
+    pub save_bookmarks: bool,
+    /// Enter and the operation keys behave as on classic HP calculators.
+    pub hp_mode: bool,
+    /// Operations bound to keys, by name, e.g. `"r": "sqrt"`, in addition
+    /// to or instead of their own key.
+    pub keys: BTreeMap<char, String>,
+    /// Operations bound to Ctrl or Alt chords, by name, e.g. `"A-s": "sqrt"`.
+    pub chords: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_bits: Some(DEFAULT_MAX_BITS),
+            max_undo_steps: None,
+            max_undo_megabytes: None,
+            precision: None,
+            output_base: None,
+            angle: None,
+            separator: false,
+            durations: false,
+            si_prefixes: false,
+            format: None,
+            max_saved_entries: None,
+            archive_dropped: false,
+            save_bookmarks: false,
+            hp_mode: false,
+            keys: BTreeMap::new(),
+            chords: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// How much undo history each stack keeps.
+    pub fn undo_limits(&self) -> UndoLimits {
+        UndoLimits {
+            steps: self.max_undo_steps,
+            bytes: self.max_undo_megabytes.map(|mb| mb.saturating_mul(1 << 20)),
+        }
+    }
+
+    /// The state to start from without a saved one, e.g. for a new tab.
+    pub fn initial_state(&self) -> State {
+        State {
+            precision: self.precision,
+            output_base: self.output_base,
+            angle: self.angle,
+            ..Default::default()
+        }
+    }
+
+    /// The state to start a clean session from: the initial one, with the
+    /// history and the macros of the saved one.
+    pub fn clean_state(&self, saved: State) -> State {
+        State {
+            history: saved.history,
+            macros: saved.macros,
+            ..self.initial_state()
+        }
+    }
+}
+
+/// Load the configuration, which is optional.
+pub fn load() -> anyhow::Result<Config> {
+    let path = state::config_dir()?.join("config.json");
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    parse(&fs::read_to_string(&path)?).with_context(|| format!("invalid {}", path.display()))
+}
+
+/// The lines of the optional startup file, run on the saved state before
+/// anything else.
+pub fn init_script() -> anyhow::Result<Option<String>> {
+    let path = state::config_dir()?.join("init.hc");
+    if !path.exists() {
+        return Ok(None);
+    }
+    let script =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(Some(script))
+}
+
+fn parse(json: &str) -> anyhow::Result<Config> {
+    let config: Config = serde_json::from_str(json)?;
+    anyhow::ensure!(config.precision != Some(0), "precision must be positive");
+    if let Some(format) = &config.format {
+        Template::parse(format).context("invalid format")?;
+    }
+    if let Some(base) = config.output_base {
+        anyhow::ensure!(
+            (2..=36).contains(&base),
+            "output_base must be between 2 and 36"
+        );
+    }
+    for chord in config.chords.keys() {
+        let key = match chord.split_once('-') {
+            Some(("C" | "A", key)) => key,
+            _ => anyhow::bail!("chord {chord} is neither C-x nor A-x"),
+        };
+        anyhow::ensure!(key.chars().count() == 1, "chord {chord} is not on one key");
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_max_bits() -> anyhow::Result<()> {
+        assert_eq!(parse("{}")?, Config::default());
+        assert_eq!(parse(r#"{"max_bits": 8192}"#)?.max_bits, Some(8192));
+        assert_eq!(parse(r#"{"max_bits": null}"#)?.max_bits, None);
+        assert!(parse(r#"{"max_bit": 1}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_undo_limits() -> anyhow::Result<()> {
+        assert_eq!(Config::default().undo_limits(), UndoLimits::default());
+        let config = parse(r#"{"max_undo_steps": 100, "max_undo_megabytes": 2}"#)?;
+        assert_eq!(
+            config.undo_limits(),
+            UndoLimits {
+                steps: Some(100),
+                bytes: Some(2 << 20)
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_startup_settings() -> anyhow::Result<()> {
+        let config =
+            parse(r#"{"precision": 2, "output_base": 16, "separator": true, "angle": "grad"}"#)?;
+        assert_eq!(config.initial_state().angle, Some(Angle::Gradians));
+        assert_eq!(config.initial_state().precision, Some(2));
+        assert_eq!(config.initial_state().output_base, Some(16));
+        assert!(config.separator && !config.durations);
+        assert!(parse(r#"{"precision": 0}"#).is_err());
+        assert!(parse(r#"{"output_base": 37}"#).is_err());
+        assert!(parse(r#"{"format": "EUR"}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn clean_state() -> anyhow::Result<()> {
+        let config = parse(r#"{"precision": 2}"#)?;
+        let saved = State {
+            stack: vec!["1".into()],
+            precision: Some(30),
+            history: vec!["1".into()],
+            macros: [("half".into(), "2<Enter>/".into())].into(),
+            ..Default::default()
+        };
+        let state = config.clean_state(saved);
+        assert!(state.stack.is_empty());
+        assert_eq!(state.precision, Some(2));
+        assert_eq!(state.history, ["1"]);
+        assert_eq!(state.macros.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_chords() -> anyhow::Result<()> {
+        let config = parse(r#"{"chords": {"A-s": "sqrt", "C-e": "npv"}}"#)?;
+        assert_eq!(config.chords["A-s"], "sqrt");
+        assert!(parse(r#"{"chords": {"S-x": "sqrt"}}"#).is_err());
+        assert!(parse(r#"{"chords": {"A-Enter": "sqrt"}}"#).is_err());
+        assert_eq!(parse(r#"{"keys": {"r": "sqrt"}}"#)?.keys[&'r'], "sqrt");
+        assert!(parse(r#"{"keys": {"rr": "sqrt"}}"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! What to do with the state on exit when another instance saved it since
+//! this one loaded it, rather than silently overwriting its save.
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    /// Save over the other one.
+    Mine,
+    /// Keep the other one, dropping this state.
+    Theirs,
+    /// Save next to the other one, as a new session.
+    NewSession,
+}
+
+/// Ask which state to keep until the answer is understood. Without an
+/// answer, e.g. at the end of the input, save a new session: it loses
+/// nothing.
+pub fn ask(mut input: impl BufRead, mut output: impl Write) -> io::Result<Resolution> {
+    writeln!(
+        output,
+        "The state was saved by another instance of hc since this one started."
+    )?;
+    loop {
+        write!(
+            output,
+            "Keep [m]ine, keep [t]heirs, or save as a [n]ew session? "
+        )?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            return Ok(Resolution::NewSession);
+        }
+        match answer.trim() {
+            "m" | "mine" => return Ok(Resolution::Mine),
+            "t" | "theirs" => return Ok(Resolution::Theirs),
+            "n" | "new" => return Ok(Resolution::NewSession),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(input: &str) -> Resolution {
+        ask(input.as_bytes(), io::sink()).unwrap()
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(answer("m\n"), Resolution::Mine);
+        assert_eq!(answer("what?\ntheirs\n"), Resolution::Theirs);
+        assert_eq!(answer("n\n"), Resolution::NewSession);
+        assert_eq!(answer("x\n"), Resolution::NewSession);
+    }
+}
@@ -0,0 +1,72 @@
+//! Encryption of the state with a passphrase or the contents of a key file:
+//! XChaCha20-Poly1305, with the key derived from the secret by Argon2id and
+//! a random salt kept in each file, along with the nonce.
+use anyhow::anyhow;
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use hc_core::state::Cipher;
+
+const SALT: usize = 16;
+const NONCE: usize = 24;
+
+/// The passphrase or key file the state is encrypted with.
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    pub fn new(secret: Vec<u8>) -> Secret {
+        Secret(secret)
+    }
+
+    fn cipher(&self, salt: &[u8]) -> anyhow::Result<XChaCha20Poly1305> {
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(&self.0, salt, &mut key)
+            .map_err(|err| anyhow!("failed to derive the key: {err}"))?;
+        Ok(XChaCha20Poly1305::new(&key))
+    }
+}
+
+impl Cipher for Secret {
+    fn encrypt(&self, plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut salt = [0; SALT];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher(&salt)?
+            .encrypt(&nonce, plain)
+            .map_err(|_| anyhow!("failed to encrypt"))?;
+        Ok([&salt[..], &nonce, &sealed].concat())
+    }
+
+    fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(data.len() >= SALT + NONCE, "truncated");
+        let (salt, data) = data.split_at(SALT);
+        let (nonce, sealed) = data.split_at(NONCE);
+        self.cipher(salt)?
+            .decrypt(XNonce::from_slice(nonce), sealed)
+            .map_err(|_| anyhow!("wrong key, or tampered with"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> anyhow::Result<()> {
+        let secret = Secret::new(b"correct horse".to_vec());
+        let data = secret.encrypt(b"[\"1.5\"]")?;
+        assert_eq!(secret.decrypt(&data)?, b"[\"1.5\"]");
+        // A new salt and nonce each time.
+        assert_ne!(secret.encrypt(b"[\"1.5\"]")?, data);
+        assert!(Secret::new(b"wrong horse".to_vec()).decrypt(&data).is_err());
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(secret.decrypt(&tampered).is_err());
+        assert!(secret.decrypt(&data[..SALT]).is_err());
+        Ok(())
+    }
+}
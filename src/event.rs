@@ -0,0 +1,110 @@
+//! The keyboard and mouse events handled by the app: those of crossterm in a
+//! terminal, or in a browser, where crossterm doesn't build, the same types
+//! filled in by the web front-end.
+#[cfg(not(target_arch = "wasm32"))]
+pub use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers, MouseEvent, MouseEventKind,
+};
+
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
+
+/// The subset of the crossterm event types used by the app, with the same
+/// names and semantics.
+#[cfg(target_arch = "wasm32")]
+mod web {
+    bitflags::bitflags! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct KeyModifiers: u8 {
+            const SHIFT = 0b0001;
+            const CONTROL = 0b0010;
+            const ALT = 0b0100;
+            const NONE = 0b0000;
+        }
+    }
+
+    bitflags::bitflags! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct KeyEventState: u8 {
+            const KEYPAD = 0b0001;
+            const NONE = 0b0000;
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum KeyCode {
+        Backspace,
+        Enter,
+        Left,
+        Right,
+        Up,
+        Down,
+        Home,
+        End,
+        PageUp,
+        PageDown,
+        Tab,
+        BackTab,
+        Delete,
+        F(u8),
+        Char(char),
+        Esc,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum KeyEventKind {
+        Press,
+        Repeat,
+        Release,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct KeyEvent {
+        pub code: KeyCode,
+        pub modifiers: KeyModifiers,
+        pub kind: KeyEventKind,
+        pub state: KeyEventState,
+    }
+
+    impl KeyEvent {
+        pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+            KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: KeyEventState::NONE,
+            }
+        }
+    }
+
+    impl From<KeyCode> for KeyEvent {
+        fn from(code: KeyCode) -> Self {
+            KeyEvent::new(code, KeyModifiers::NONE)
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum MouseEventKind {
+        Moved,
+        ScrollDown,
+        ScrollUp,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct MouseEvent {
+        pub kind: MouseEventKind,
+        pub column: u16,
+        pub row: u16,
+        pub modifiers: KeyModifiers,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub enum Event {
+        FocusGained,
+        FocusLost,
+        Key(KeyEvent),
+        Mouse(MouseEvent),
+        Paste(String),
+        Resize(u16, u16),
+    }
+}
@@ -1,41 +1,236 @@
-use crate::format::format_number;
+use crate::bits::{BitField, BitsError};
+use crate::config::Config;
+use crate::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+use crate::help::{Help, HelpState};
+use crate::histogram::{self, Bin};
+use crate::history::{History, HistoryAction, HistoryState};
 use crate::input::{InputError, InputState, InputWidget};
-use crate::{
-    help::{Help, HelpState},
-    stack::{Op, Stack, StackError},
-    state::State,
+use crate::keys::KeySheet;
+use crate::palette::{self, Palette, PaletteAction, PaletteState};
+use crate::remote::Request;
+use crate::report;
+use bigdecimal::BigDecimal;
+#[cfg(not(target_arch = "wasm32"))]
+use crossterm::{clipboard::CopyToClipboard, event, execute};
+use hc_core::dc::{Dc, DcError};
+use hc_core::dice;
+use hc_core::export::{self, TableFormat, UnknownFormat};
+use hc_core::expr::{self, ExprError};
+use hc_core::format::{
+    self, format_duration, format_ip, format_number, format_si, Formatted, Template,
+};
+use hc_core::{
+    number,
+    ops::{self, Operation},
+    plugin::Registry,
+    stack::{Change, Job, Op, Stack, StackError},
+    state::{FileStorage, State, Storage},
 };
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Flex, Layout, Rect},
-    style::{Color, Stylize},
+    style::{Color, Style, Stylize},
     text::{Line, Text},
-    widgets::{Block, Cell, Clear, Paragraph, Row, StatefulWidget, Table, Widget},
+    widgets::{Block, Cell, Clear, Paragraph, Row, StatefulWidget, Table, Tabs, Widget, Wrap},
+    Frame,
 };
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::stdout;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
+/// How the numbers are shown, other than in the output base.
+#[derive(Clone, Copy, PartialEq)]
+enum Notation {
+    Plain,
+    Durations, // As H:MM:SS.
+    Si,        // With SI prefixes.
+    Ip,        // As IP addresses.
+    Template,  // With the format of the configuration.
+}
+
 #[derive(Clone, Copy)]
 enum PendingReg {
     Load,
     Save,
 }
 
+/// A search through the stack, typed after Ctrl-f.
+#[derive(Default)]
+struct Search {
+    query: String,
+    current: Option<usize>, // The stack index of the selected match.
+    matches: Vec<usize>,    // The stack indexes of the matches, in order.
+}
+
+/// Numbers piped in, reduced with an operation as they arrive.
+struct Feed {
+    lines: Option<Receiver<String>>, // None once the pipe is closed.
+    op: Op,
+    count: usize, // The numbers read so far.
+}
+
+/// The values last shown in the stack, formatted, to only format them again
+/// when they or the way to show them change rather than on every frame.
+#[derive(Default)]
+struct Shown {
+    // The width, notation, separator, base and word size.
+    how: Option<(u64, Notation, bool, u32, Option<u32>)>,
+    values: Vec<BigDecimal>,
+    lines: Vec<Line<'static>>,
+}
+
+/// The state before a block of lines which failed part-way, to go back to.
+struct Rollback {
+    stack: Stack,
+    lines: Vec<String>,
+}
+
+// How long operations may take before the interface shows a spinner.
+const QUICK: Duration = Duration::from_millis(50);
+// The refresh rate of the spinner.
+const TICK: Duration = Duration::from_millis(100);
+const SPINNER: &str = "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏";
+
+// The most lines of a feed taken in at once, to keep the interface responsive.
+const FEED_BATCH: usize = 10_000;
+// The width of the numbers of the plain front-end, which has no layout.
+const PLAIN_WIDTH: u64 = 1000;
+// The command finding a root of the function named after it.
+const SOLVE: &str = "solve";
+// The commands saving the stack under the name after them, and restoring it.
+const BOOKMARK: &str = "bookmark";
+const RESTORE: &str = "restore";
+// The command showing how the stack changed since the bookmark after it.
+const DIFF: &str = "diff";
+// The command running the GNU dc program after it.
+const DC: &str = "dc";
+// The command copying the whole stack to the clipboard.
+const COPY: &str = "copy";
+// The command copying the stack as a table in the format after it.
+const EXPORT: &str = "export";
+// The command writing a report of the session, named after it.
+const REPORT: &str = "report";
+// The command loading the state saved by another instance, when watching it.
+const RELOAD: &str = "reload";
+// The command recording the keys typed next as the macro named after it.
+const RECORD: &str = "record";
+// The command defining the macro named after it as the keys after the name,
+// or deleting it without keys.
+const MACRO: &str = "macro";
+// The command showing the result of the macro named after it in the
+// status line, run on a copy of the stack whenever it changes, or no longer
+// without a name.
+const WATCH: &str = "watch";
+// How deeply macros can run one another.
+const MAX_MACRO_DEPTH: usize = 16;
 const LOAD: char = 'l';
 const SAVE: char = 's';
+// The letters of the Ctrl chords of the interface, which can't be rebound.
+const INTERFACE_CHORDS: &str = "abefgjkmopqrtwyz";
+
+/// A named workspace, with its own stack.
+struct Tab {
+    name: String,
+    stack: Stack,
+}
+
+impl Tab {
+    fn new(name: String, state: State) -> anyhow::Result<Self> {
+        Ok(Tab {
+            name: state.name.clone().unwrap_or(name),
+            stack: state.try_into()?,
+        })
+    }
+
+    /// The tabs of the state, taking its additional workspaces.
+    fn all(mut state: State) -> anyhow::Result<Vec<Self>> {
+        let extra = std::mem::take(&mut state.tabs);
+        let mut tabs = vec![Tab::new("1".into(), state)?];
+        for (i, s) in extra.into_iter().enumerate() {
+            tabs.push(Tab::new((i + 2).to_string(), s)?);
+        }
+        Ok(tabs)
+    }
+}
+
+/// The saved state, watched for the saves of other instances.
+struct Watch {
+    storage: FileStorage,
+    seen: Option<SystemTime>,   // When it was last written, as noticed.
+    synced: Option<SystemTime>, // When the version last loaded was written.
+}
 
 /// Overall state of the app.
 pub struct App {
-    exit: bool,                      // If true, exit.
-    input: InputState,               // The input widget.
-    stack: Stack,                    // The stack of big numbers.
-    help: HelpState,                 // The help widget and its display state.
-    separator: bool,                 // If true, show decimal separator.
-    ops: HashMap<char, Op>,          // The known operations on the stack.
-    op: Option<char>,                // The latest operation.
-    op_status: Result<(), AppError>, // The latest status.
-    pending_reg: Option<PendingReg>, // Waiting for register key after L/S.
+    exit: bool,                                 // If true, exit.
+    input: InputState,                          // The input widget.
+    tabs: Vec<Tab>,                             // The workspaces, each with a stack of big numbers.
+    tab: usize,                                 // The active workspace.
+    help: HelpState,                            // The help widget and its display state.
+    keys: bool,                                 // If true, show the cheat-sheet of the keys.
+    histogram: bool,                            // If true, show the histogram of the values.
+    bins: Option<(u64, usize, Vec<Bin>)>,       // The bins last shown, see refresh_bins.
+    ages: bool,                                 // If true, show how long ago entries were pushed.
+    origins: bool,                              // If true, show how entries were computed.
+    bits: Option<BitField>,                     // The bits of S1, when shown.
+    macros: BTreeMap<String, String>,           // The keys of the macros, by name.
+    recording: Option<(String, String)>,        // The macro being recorded, and its keys so far.
+    playing: usize,                             // How many macros are running.
+    watcher: Option<Watcher>,                   // The macro watching the stack, if any.
+    diff: Option<(String, Vec<Change>)>,        // The changes since a bookmark, when shown.
+    history: HistoryState,                      // The inputs and operations, across sessions.
+    palette: PaletteState,                      // The operations, to find and run.
+    separator: bool,                            // If true, show decimal separator.
+    notation: Notation,                         // How to show the numbers.
+    template: Option<Template>,                 // The format of the configuration, if any.
+    ops: HashMap<char, Op>,                     // The known operations on the stack.
+    commands: HashMap<String, Op>,              // The operations run by name, as in ':name'.
+    conflicts: Vec<String>,                     // The key bindings which were ignored.
+    op: Option<char>,                           // The latest operation.
+    op_status: Result<(), AppError>,            // The latest status.
+    pending_reg: Option<PendingReg>,            // Waiting for register key after L/S.
+    title: String,                              // The terminal title last set.
+    job: Option<(Job, Instant)>,                // The operation running in the background.
+    config: Config,                             // The user preferences.
+    search: Option<Search>,                     // The search through the stack, if any.
+    notice: Option<String>,                     // A startup message to acknowledge.
+    feed: Option<Feed>,                         // The numbers piped in, if any.
+    rollback: Option<Rollback>,                 // Undoes a block of lines which failed.
+    dc: Dc,                                     // The macros of the dc programs run so far.
+    lift_disabled: bool,                        // In HP mode, the next number replaces S1.
+    negated: Option<String>,                    // The number typed before `-` negated it.
+    preview_mode: bool,                         // If true, operation keys show the result first.
+    preview: Option<Preview>,                   // The operation waiting to be confirmed.
+    live: Option<(String, Option<BigDecimal>)>, // The expression typed and its value.
+    clipboard: Option<String>,                  // Text to copy, on the next redraw.
+    read_only: bool,                            // If true, the state won't be saved.
+    encrypted: bool,                            // If true, nothing is written in plain text.
+    watch: Option<Watch>,                       // The saved state, if watched.
+    remote: Option<Receiver<Request>>,          // The requests of remote control, if any.
+    waiting: Option<Sender<String>>,            // The client waiting for the operation.
+    shown: Shown,                               // The values last shown in the stack.
+}
+
+/// A macro run on a copy of the stack whenever it changes, e.g. to always
+/// show the running sum.
+struct Watcher {
+    name: String,
+    seen: Option<u64>, // The generation of the stack it last ran on.
+    job: Option<Job>,  // Its run on a copy of the stack, until it's over.
+    result: Result<BigDecimal, String>,
+}
+
+/// An operation shown before it runs, with the top of the stack it would
+/// leave, if any.
+struct Preview {
+    key: char,
+    result: Result<Option<BigDecimal>, AppError>,
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -44,72 +239,722 @@ enum AppError {
     InputError(#[from] InputError),
     #[error("{0}")]
     StackError(#[from] StackError),
+    #[error("{0}")]
+    ExprError(#[from] ExprError),
+    #[error("{0}")]
+    DcError(#[from] DcError),
+    #[error("cannot close the last tab")]
+    LastTab,
+    #[error("unknown command '{0}'")]
+    UnknownCommand(String),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("line {0}: {1} (^z to roll back)")]
+    Line(usize, Box<AppError>),
+    #[error("too slow to preview")]
+    TooSlow,
+    #[error("unknown key {0}")]
+    UnknownKey(String),
+    #[error("no format in the configuration")]
+    NoFormat,
+    #[error("{0}")]
+    UnknownFormat(#[from] UnknownFormat),
+    #[error("{0}")]
+    Bits(#[from] BitsError),
+    #[error("failed to write the report: {0}")]
+    Report(String),
+    #[error("reports are in plain text, unlike the encrypted state")]
+    PlainReport,
+    #[error("another instance saved the state, :reload to load it")]
+    Saved,
+    #[error("the saved state is only reloaded with --watch")]
+    NotWatching,
+    #[error("failed to reload the state: {0}")]
+    Reload(String),
+    #[error("'{0}' can't name a macro")]
+    MacroName(String),
+    #[error("not recording a macro, :record NAME to start")]
+    NotRecording,
+    #[error("macros nested too deeply")]
+    MacroDepth,
+    #[error("no macro named '{0}'")]
+    UnknownMacro(String),
+    #[error("{0} acts on the interface, it can't be watched")]
+    Unwatchable(String),
 }
 
 impl App {
-    pub fn new(state: State) -> anyhow::Result<Self> {
-        Ok(App {
+    pub fn new(mut state: State) -> anyhow::Result<Self> {
+        let history = std::mem::take(&mut state.history);
+        let macros = std::mem::take(&mut state.macros);
+        let active = state.active_tab;
+        let tabs = Tab::all(state)?;
+        let app = App {
             exit: false,
             input: InputState::default(),
-            stack: state.try_into()?,
+            tab: active.min(tabs.len() - 1),
+            tabs,
             help: HelpState::default(),
+            keys: false,
+            histogram: false,
+            bins: None,
+            ages: false,
+            origins: false,
+            bits: None,
+            macros,
+            recording: None,
+            playing: 0,
+            watcher: None,
+            diff: None,
+            history: HistoryState::new(history),
+            palette: PaletteState::default(),
             separator: false,
-            ops: HashMap::from([
-                ('+', Op::Add),
-                ('-', Op::Subtract),
-                ('/', Op::Divide),
-                ('*', Op::Multiply),
-                ('%', Op::Modulo),
-                ('^', Op::Pow),
-                ('v', Op::Sqrt),
-                ('d', Op::Duplicate),
-                ('P', Op::Pop),
-                ('k', Op::Precision),
-                ('o', Op::OutputBase),
-                ('r', Op::Swap),
-                ('u', Op::Undo),
-                ('U', Op::Redo),
-                ('c', Op::ClearStack),
-                ('n', Op::Defaults),
-                ('C', Op::ClearRegisters),
-                ('y', Op::Permutation(true)),
-                ('Y', Op::Permutation(false)),
-            ]),
+            notation: Notation::Plain,
+            template: None,
+            ops: HashMap::from([('u', Op::Undo), ('U', Op::Redo)]),
+            commands: HashMap::new(),
+            conflicts: vec![],
             op: None,
             op_status: Ok(()),
             pending_reg: None,
-        })
+            title: String::new(),
+            job: None,
+            config: Config::default(),
+            search: None,
+            notice: None,
+            rollback: None,
+            dc: Dc::default(),
+            feed: None,
+            lift_disabled: false,
+            negated: None,
+            preview_mode: false,
+            preview: None,
+            live: None,
+            clipboard: None,
+            read_only: false,
+            encrypted: false,
+            watch: None,
+            remote: None,
+            waiting: None,
+            shown: Shown::default(),
+        };
+        Ok(app.with_operations(ops::builtins()))
+    }
+
+    /// Mark the session as one whose state won't be saved.
+    pub fn with_read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Mark the session as one whose state is encrypted, which reports would
+    /// give away.
+    pub fn with_encrypted(mut self) -> Self {
+        self.encrypted = true;
+        self
+    }
+
+    /// Tell when the saved state changes, i.e. another instance saved it,
+    /// for `:reload` to load it.
+    pub fn with_watch(mut self, storage: FileStorage) -> Self {
+        let modified = storage.modified();
+        self.watch = Some(Watch {
+            storage,
+            seen: modified,
+            synced: modified,
+        });
+        self
+    }
+
+    /// Serve the requests of remote control, between key presses.
+    pub fn with_remote(mut self, requests: Receiver<Request>) -> Self {
+        self.remote = Some(requests);
+        self
+    }
+
+    /// When the saved state last loaded was written, if watched.
+    pub fn synced(&self) -> Option<Option<SystemTime>> {
+        self.watch.as_ref().map(|w| w.synced)
+    }
+
+    /// Show a message at startup, to continue past or quit.
+    pub fn with_notice(mut self, notice: String) -> Self {
+        self.notice = Some(notice);
+        self
+    }
+
+    /// Reduce the numbers of the lines with the operation, given by key or
+    /// by name, as they arrive. The first number is pushed as is.
+    pub fn with_reduce(mut self, op: &str, lines: Receiver<String>) -> anyhow::Result<Self> {
+        let mut chars = op.chars();
+        let op = match (chars.next(), chars.next()) {
+            (Some(c), None) if self.ops.contains_key(&c) => self.ops[&c].clone(),
+            _ => self.command(op)?,
+        };
+        self.feed = Some(Feed {
+            lines: Some(lines),
+            op,
+            count: 0,
+        });
+        Ok(self)
+    }
+
+    /// Apply the user preferences.
+    pub fn with_config(mut self, config: Config) -> Self {
+        for tab in &mut self.tabs {
+            tab.stack.set_max_bits(config.max_bits);
+            tab.stack.set_undo_limits(config.undo_limits());
+        }
+        self.separator = config.separator;
+        self.notation = match (config.durations, config.si_prefixes) {
+            (true, _) => Notation::Durations,
+            (false, true) => Notation::Si,
+            (false, false) => Notation::Plain,
+        };
+        // The configuration was checked when loaded.
+        self.template = config
+            .format
+            .as_deref()
+            .and_then(|f| Template::parse(f).ok());
+        for (&key, name) in &config.keys {
+            match self.commands.get(name) {
+                _ if reserved(key) => self
+                    .conflicts
+                    .push(format!("key {key} for :{name} is used by the interface")),
+                None => self
+                    .conflicts
+                    .push(format!("key {key} is for unknown :{name}")),
+                Some(op) => {
+                    // The key then runs this one instead, which is worth
+                    // telling unless it's an alias.
+                    match self.ops.insert(key, op.clone()) {
+                        Some(taken) if taken.to_string() != op.to_string() => self
+                            .conflicts
+                            .push(format!("key {key} for :{name} replaces :{taken}")),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        for (chord, name) in &config.chords {
+            let interface = match chord.split_once('-') {
+                Some(("C", key)) => key.len() == 1 && INTERFACE_CHORDS.contains(key),
+                _ => false,
+            };
+            if interface {
+                self.conflicts.push(format!(
+                    "chord {chord} for :{name} is used by the interface"
+                ));
+            } else if !self.commands.contains_key(name) {
+                self.conflicts
+                    .push(format!("chord {chord} is for unknown :{name}"));
+            }
+        }
+        self.config = config;
+        self.refresh_help();
+        self
+    }
+
+    /// The key bindings of the configuration and the plugins which were
+    /// ignored, and why.
+    pub fn conflicts(&self) -> &[String] {
+        &self.conflicts
+    }
+
+    /// Make the plugin operations available as commands, and bind their
+    /// keys unless already taken.
+    pub fn with_plugins(self, registry: &Registry) -> Self {
+        let mut app = self;
+        app.help = HelpState::with_plugins(registry);
+        app.with_operations(registry.iter().cloned())
+    }
+
+    // Run the operations by name, and bind their keys, unless already taken.
+    fn with_operations<I: Iterator<Item = Arc<dyn Operation>>>(mut self, ops: I) -> Self {
+        for op in ops {
+            self.commands
+                .entry(op.name().to_owned())
+                .or_insert(Op::Run(op.clone()));
+            let Some(key) = op.key() else {
+                continue;
+            };
+            match self.ops.get(&key) {
+                _ if reserved(key) => self.conflicts.push(format!(
+                    "key {key} of :{} is used by the interface",
+                    op.name()
+                )),
+                Some(taken) => self
+                    .conflicts
+                    .push(format!("key {key} of :{} is taken by :{taken}", op.name())),
+                None => {
+                    self.ops.insert(key, Op::Run(op));
+                }
+            }
+        }
+        self.refresh_help();
+        self
+    }
+
+    /// List the operations in the help under the keys actually bound.
+    fn refresh_help(&mut self) {
+        self.help.set_keys(&self.op_keys());
     }
 
     /// The app's main loop.
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn run(&mut self, term: &mut ratatui::DefaultTerminal) -> std::io::Result<()> {
+        let mut redraw = true;
         while !self.exit {
-            term.draw(|frame| {
-                if let Some(cursor) = self.render_all(frame.area(), frame.buffer_mut()) {
-                    frame.set_cursor_position(cursor);
+            redraw |= self.refresh_watcher(Duration::ZERO);
+            if redraw {
+                term.draw(|frame| self.draw(frame))?;
+                if let Some(text) = self.take_clipboard() {
+                    // Terminals without OSC 52 support just ignore it.
+                    let _ = execute!(stdout(), CopyToClipboard::to_clipboard_from(text));
                 }
-            })?;
-            self.handle_events()?;
+                let title = self.title();
+                if title != self.title {
+                    // Not all terminals support setting the title, that's fine.
+                    let _ = crate::title::set(&title);
+                    self.title = title;
+                }
+            }
+            if self.feed.as_ref().is_some_and(|f| f.lines.is_some()) && self.job.is_none() {
+                // Keep taking in the numbers piped in between key presses.
+                redraw = event::poll(TICK)? && self.handle_events()?;
+                redraw |= self.receive();
+                redraw |= self.check_watch();
+                redraw |= self.serve();
+            } else if (self.watch.is_some() || self.remote.is_some() || self.watching())
+                && self.job.is_none()
+            {
+                redraw = event::poll(TICK)? && self.handle_events()?;
+                redraw |= self.check_watch();
+                redraw |= self.serve();
+            } else if self.job.is_none() {
+                redraw = self.handle_events()?;
+            } else {
+                // Keep the spinner going while waiting for the operation.
+                if event::poll(TICK)? {
+                    self.handle_events()?;
+                }
+                self.check_job();
+                redraw = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the app on the frame, with the cursor where the user types.
+    pub fn draw(&mut self, frame: &mut Frame) {
+        if let Some(cursor) = self.render_all(frame.area(), frame.buffer_mut()) {
+            frame.set_cursor_position(cursor);
+        }
+    }
+
+    /// The text to copy to the clipboard, if any since the last call.
+    pub fn take_clipboard(&mut self) -> Option<String> {
+        self.clipboard.take()
+    }
+
+    /// Answer the requests of remote control received so far. Returns
+    /// whether there were any.
+    fn serve(&mut self) -> bool {
+        let mut served = false;
+        // One operation at a time: the requests after a slow one wait for it
+        // to be done, as they would if typed.
+        while self.job.is_none() {
+            let Some(request) = self.remote.as_ref().and_then(|r| r.try_recv().ok()) else {
+                break;
+            };
+            match self.answer(request.line.trim()) {
+                // Replied to once done, see `reply`.
+                Ok(_) if self.job.is_some() => self.waiting = Some(request.reply),
+                answer => {
+                    // The client may be gone already.
+                    let _ = request
+                        .reply
+                        .send(answer.unwrap_or_else(|err| format!("error: {err}")));
+                }
+            }
+            served = true;
+        }
+        served
+    }
+
+    /// Reply to the client waiting for the operation just done, if any.
+    fn reply(&mut self, status: &Result<(), AppError>) {
+        if let Some(client) = self.waiting.take() {
+            let _ = client.send(match status {
+                Ok(()) => "ok".into(),
+                Err(err) => format!("error: {err}"),
+            });
+        }
+    }
+
+    /// The reply to a request of remote control, see the remote module.
+    fn answer(&mut self, request: &str) -> Result<String, AppError> {
+        let (command, args) = request.split_once(' ').unwrap_or((request, ""));
+        match command {
+            "stack" => Ok(self.stack_text().replace('\n', " ")),
+            "top" => Ok(self
+                .stack()
+                .values()
+                .next()
+                .ok_or(StackError::MissingValue(1))?
+                .to_plain_string()),
+            "push" => {
+                let mut ops = vec![];
+                for word in args.split_whitespace() {
+                    ops.push(Op::Push(number::parse(word, self.stack().precision())?));
+                }
+                self.stack_mut().apply_all(ops)?;
+                self.history.push(args.trim().to_owned());
+                Ok("ok".into())
+            }
+            "op" => {
+                let name = args.trim();
+                let mut chars = name.chars();
+                let op = match (chars.next(), chars.next()) {
+                    (Some(c), None) if self.ops.contains_key(&c) => self.ops[&c].clone(),
+                    _ => self.command(name)?,
+                };
+                let entry = match &op {
+                    Op::Run(o) => format!(":{}", o.name()),
+                    _ => format!(":{name}"),
+                };
+                self.execute(op)?;
+                self.history.push(entry);
+                Ok("ok".into())
+            }
+            _ => Err(AppError::UnknownCommand(request.to_owned())),
+        }
+    }
+
+    /// Tell if the saved state changed since last noticed. Returns whether
+    /// it did.
+    fn check_watch(&mut self) -> bool {
+        let Some(watch) = &mut self.watch else {
+            return false;
+        };
+        let modified = watch.storage.modified();
+        if modified == watch.seen {
+            return false;
+        }
+        watch.seen = modified;
+        self.op = None;
+        self.op_status = Err(AppError::Saved);
+        true
+    }
+
+    /// Replace the stacks with those of the saved state, keeping the history
+    /// of the inputs.
+    fn reload(&mut self) -> Result<(), AppError> {
+        let watch = self.watch.as_mut().ok_or(AppError::NotWatching)?;
+        let modified = watch.storage.modified();
+        let state = watch
+            .storage
+            .load()
+            .map_err(|err| AppError::Reload(format!("{err:#}")))?;
+        let active = state.active_tab;
+        let mut tabs = Tab::all(state).map_err(|err| AppError::Reload(format!("{err:#}")))?;
+        for tab in &mut tabs {
+            tab.stack.set_max_bits(self.config.max_bits);
+            tab.stack.set_undo_limits(self.config.undo_limits());
         }
+        (watch.seen, watch.synced) = (modified, modified);
+        self.tab = active.min(tabs.len() - 1);
+        self.tabs = tabs;
         Ok(())
     }
 
     pub fn state(&self) -> State {
-        (&self.stack).into()
+        let mut states = self.tabs.iter().map(|t| State {
+            name: Some(t.name.clone()),
+            ..(&t.stack).into()
+        });
+        let mut state = states.next().unwrap();
+        state.tabs = states.collect();
+        state.active_tab = self.tab;
+        state.history = self.history.entries().to_vec();
+        state.macros = self.macros.clone();
+        if !self.config.save_bookmarks {
+            state.bookmarks.clear();
+            for tab in &mut state.tabs {
+                tab.bookmarks.clear();
+            }
+        }
+        state
+    }
+
+    /// The terminal title: the top of the stack, or the tab name if it's empty.
+    pub fn title(&self) -> String {
+        match self.stack().values().next() {
+            Some(top) => format!("hc — {}", self.format(&top, 40)),
+            None => format!("hc — {}", self.tabs[self.tab].name),
+        }
+    }
+
+    /// Format a value according to the display settings.
+    fn format(&self, n: &BigDecimal, width: u64) -> Line<'static> {
+        let formatted = match (self.notation, &self.template) {
+            (Notation::Durations, _) => format_duration(n, width, self.separator),
+            (Notation::Si, _) => format_si(n, width, self.separator),
+            (Notation::Ip, _) => format_ip(n, width, self.separator),
+            (Notation::Template, Some(template)) => {
+                let text = template.format(n);
+                match text.chars().count() as u64 <= width {
+                    true => Formatted::raw(text),
+                    false => format_number(n, width, self.separator, 10),
+                }
+            }
+            _ => {
+                // Negative integers as systems show them, when a word size is set.
+                let base = self.stack().output_base();
+                let word = match self.stack().word_size() {
+                    Some(bits) if base.is_power_of_two() => format::twos_complement(n, bits),
+                    _ => None,
+                };
+                format_number(word.as_ref().unwrap_or(n), width, self.separator, base)
+            }
+        };
+        styled(formatted)
+    }
+
+    /// Switch to the notation, or back to the plain one.
+    fn toggle(&mut self, notation: Notation) {
+        self.notation = match self.notation == notation {
+            true => Notation::Plain,
+            false => notation,
+        };
+    }
+
+    fn stack(&self) -> &Stack {
+        &self.tabs[self.tab].stack
+    }
+
+    fn stack_mut(&mut self) -> &mut Stack {
+        &mut self.tabs[self.tab].stack
     }
 
     pub fn add_extra<S: AsRef<str>>(&mut self, extra: S) -> anyhow::Result<()> {
         for c in extra.as_ref().chars() {
             self.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))?;
+            while self.job.is_some() {
+                self.wait(TICK)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the lines of the startup file, each as if typed and committed,
+    /// leaving them out of the history. Blank lines and those starting with
+    /// `#` are skipped.
+    pub fn run_init(&mut self, script: &str) -> anyhow::Result<()> {
+        let history = std::mem::take(&mut self.history);
+        let result = script.lines().enumerate().try_for_each(|(i, line)| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return Ok(());
+            }
+            self.run_line(line)
+                .map_err(|err| anyhow::anyhow!("line {}: {err}", i + 1))
+        });
+        self.history = history;
+        result
+    }
+
+    /// Press the keys, to script whole sessions without a terminal, e.g.
+    /// `1<Enter>2+<C-z>`. Keys other than characters are named between angle
+    /// brackets: `<Enter>`, `<Esc>`, `<Tab>`, `<Backspace>`, `<Up>`, `<Down>`,
+    /// `<Left>`, `<Right>`, and `<lt>` for `<` itself; `<C-x>` and `<A-x>` add
+    /// Ctrl and Alt. Operations run to completion, and an error stops there.
+    pub fn feed_keys(&mut self, keys: &str) -> anyhow::Result<()> {
+        for k in parse_keys(keys)? {
+            self.handle_key(k)?;
+            while self.job.is_some() {
+                self.wait(TICK)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The interface as it would show in a terminal of the size, as text
+    /// with a line per row.
+    pub fn render_to_string(&mut self, width: u16, height: u16) -> String {
+        // The result of the watching macro is waited for.
+        self.refresh_watcher(Duration::ZERO);
+        while self.watching() {
+            self.refresh_watcher(TICK);
+        }
+        let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+        self.render_all(buf.area, &mut buf);
+        let rows: Vec<String> = (0..height)
+            .map(|y| (0..width).map(|x| buf[(x, y)].symbol()).collect())
+            .collect();
+        rows.join("\n")
+    }
+
+    /// Type a line and commit it, for front-ends without a keyboard of
+    /// their own. Operations run to completion.
+    pub fn submit(&mut self, line: &str) -> anyhow::Result<()> {
+        let result = self.add_extra(line).and_then(|_| {
+            self.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+            while self.job.is_some() {
+                self.wait(TICK)?;
+            }
+            Ok(())
+        });
+        self.input.reset();
+        result
+    }
+
+    pub fn exiting(&self) -> bool {
+        self.exit
+    }
+
+    /// The values of the stack in full, one per line and in the order they
+    /// were pushed, e.g. to paste them elsewhere.
+    pub fn stack_text(&self) -> String {
+        let values: Vec<String> = self.stack().values().map(|v| v.to_plain_string()).collect();
+        values.into_iter().rev().collect::<Vec<_>>().join("\n")
+    }
+
+    /// The stack as a table, with the labels for its values from the bottom
+    /// if there are any, or else their indices.
+    pub fn stack_table(&self, format: TableFormat, labels: Vec<String>) -> String {
+        let values = self.stack().values().map(|v| v.to_plain_string());
+        let table = export::Table::new(values.collect());
+        let table = match labels.is_empty() {
+            true => table.with_indices(),
+            false => table.with_labels(labels),
+        };
+        table.render(format)
+    }
+
+    /// Write the report of the session to `NAME.md`, titled after the file.
+    fn report(&self, name: &str) -> Result<(), AppError> {
+        if self.encrypted {
+            return Err(AppError::PlainReport);
+        }
+        let path = PathBuf::from(format!("{name}.md"));
+        let title = path.file_stem().unwrap_or_default().to_string_lossy();
+        let stack = self.stack_table(TableFormat::Markdown, vec![]);
+        // Not SystemTime::now(), which panics in browsers.
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(ops::seconds().unwrap_or(0));
+        let text = report::report(&title, now, self.history.session(), &stack);
+        std::fs::write(&path, text).map_err(|err| AppError::Report(err.to_string()))
+    }
+
+    /// The values of the stack as plain text, top first, e.g. to be read
+    /// out rather than seen.
+    pub fn plain_values(&self) -> Vec<String> {
+        self.stack()
+            .values()
+            .map(|v| self.format(&v, PLAIN_WIDTH).to_string())
+            .collect()
+    }
+
+    /// Apply an operation on a worker thread, so that the interface stays
+    /// responsive if it takes a while.
+    fn execute(&mut self, op: Op) -> Result<(), AppError> {
+        if !matches!(op, Op::Run(_)) {
+            return Ok(self.stack_mut().apply(op)?);
+        }
+        let job = self.stack().spawn(op);
+        // Most operations are instant, don't even bother with the spinner.
+        // That's all of them in a browser, which has no clock for it anyway.
+        if let Some(outcome) = job.poll(QUICK) {
+            return Ok(self.stack_mut().complete(outcome)?);
         }
+        self.job = Some((job, Instant::now()));
         Ok(())
     }
 
+    /// Wait up to the given duration for the running operation, if any.
+    fn wait(&mut self, timeout: Duration) -> Result<(), AppError> {
+        let Some((job, _)) = &self.job else {
+            return Ok(());
+        };
+        let Some(outcome) = job.poll(timeout) else {
+            return Ok(());
+        };
+        self.job = None;
+        let status = self.stack_mut().complete(outcome).map_err(AppError::from);
+        self.reply(&status);
+        status
+    }
+
+    /// Take in the outcome of the running operation if it's done, which
+    /// is then the latest status. Esc may have cancelled it, with its own.
+    fn check_job(&mut self) {
+        if self.job.is_some() {
+            let status = self.wait(Duration::ZERO);
+            if self.job.is_none() {
+                self.op_status = status;
+            }
+        }
+    }
+
     fn handle_key(&mut self, k: KeyEvent) -> Result<(), AppError> {
+        let negated = self.negated.take();
+        // A failed block of lines can only be rolled back right away.
+        if let Some(rollback) = self.rollback.take() {
+            if (k.code, k.modifiers) == (KeyCode::Char('z'), KeyModifiers::CONTROL) {
+                *self.stack_mut() = rollback.stack;
+                self.input = InputState::default().with_lines(rollback.lines);
+                return Ok(());
+            }
+        }
+        if self.keys || self.diff.is_some() || self.bits.is_some() {
+            self.keys = false;
+            self.diff = None;
+            self.bits = None;
+            return Ok(());
+        }
+        if self.notice.is_some() {
+            match k.code {
+                KeyCode::Enter => self.notice = None,
+                KeyCode::Char('q') | KeyCode::Esc => self.exit = true,
+                _ => {}
+            }
+            return Ok(());
+        }
         if self.help.is_visible() {
             self.help.handle_key(k);
             return Ok(());
         }
+        if self.history.is_visible() {
+            match self.history.handle_key(k) {
+                Some(HistoryAction::Run(entry)) => {
+                    self.input = self.input.clone().with_value(entry);
+                    self.input_consume()?;
+                }
+                Some(HistoryAction::Edit(entry)) => {
+                    self.input = self.input.clone().with_value(entry);
+                }
+                None => {}
+            }
+            return Ok(());
+        }
+        if self.palette.is_visible() {
+            match self.palette.handle_key(k) {
+                Some(PaletteAction::Run(name)) => {
+                    self.input = self.input.clone().with_value(format!(":{name}"));
+                    self.input_consume()?;
+                }
+                Some(PaletteAction::Edit(name)) => {
+                    // Macros are edited by defining them again.
+                    let text = match self.macros.get(&name) {
+                        Some(keys) => format!(":{MACRO} {name} {keys}"),
+                        None => format!(":{name}"),
+                    };
+                    self.input = self.input.clone().with_value(text);
+                }
+                None => {}
+            }
+            return Ok(());
+        }
         if let Some(pending) = self.pending_reg {
             self.pending_reg = None;
             if let KeyCode::Char(c) = k.code {
@@ -117,7 +962,7 @@ impl App {
                     PendingReg::Load => LOAD,
                     PendingReg::Save => SAVE,
                 });
-                self.stack
+                self.stack_mut()
                     .apply(match pending {
                         PendingReg::Load => Op::Load(c),
                         PendingReg::Save => Op::Save(c),
@@ -126,16 +971,38 @@ impl App {
             }
             return Ok(());
         }
+        if self.search.is_some() {
+            self.handle_search_key(k);
+            return Ok(());
+        }
+        if let Some(preview) = self.preview.take() {
+            match (k.code, k.modifiers) {
+                (KeyCode::Enter, KeyModifiers::NONE) => return self.confirm(preview.key),
+                (KeyCode::Char(c), KeyModifiers::NONE) if c == preview.key => {
+                    return self.confirm(c)
+                }
+                (KeyCode::Esc, _) => return Ok(()),
+                // Anything else dismisses it and goes on as usual.
+                _ => {}
+            }
+        }
         let empty = self.input.is_empty();
+        // Only typing the number keeps the stack lift disabled.
+        let lift_disabled = std::mem::take(&mut self.lift_disabled);
         match (k.code, k.modifiers) {
             (KeyCode::Up, KeyModifiers::NONE) => {
                 // Edit the top entry if there is one and the editor is empty.
                 if self.input.is_empty() {
-                    if let Some(n) = self.stack.edit_top() {
+                    if let Some(n) = self.stack_mut().edit_top() {
                         self.input = self.input.clone().with_value(n.to_plain_string());
                     }
                 }
             }
+            (KeyCode::Backspace, KeyModifiers::NONE) if empty => {
+                // Like the drop key of HP calculators, and just as undoable.
+                let drop = ops::builtin("drop").expect("drop is built-in");
+                self.stack_mut().apply(drop)?;
+            }
             (KeyCode::Char('?'), KeyModifiers::NONE) => {
                 self.help.set_visible(true);
             }
@@ -145,24 +1012,161 @@ impl App {
             (KeyCode::Char('\''), KeyModifiers::NONE) => {
                 self.separator = !self.separator;
             }
+            (KeyCode::Char('T'), KeyModifiers::NONE) if empty => {
+                self.toggle(Notation::Durations);
+            }
+            (KeyCode::Char('S'), KeyModifiers::NONE) if empty => {
+                self.toggle(Notation::Si);
+            }
+            (KeyCode::Char('I'), KeyModifiers::NONE) if empty => {
+                self.toggle(Notation::Ip);
+            }
+            (KeyCode::Char('F'), KeyModifiers::NONE) if empty => {
+                if self.template.is_none() {
+                    return Err(AppError::NoFormat);
+                }
+                self.toggle(Notation::Template);
+            }
+            (KeyCode::Char('t'), KeyModifiers::CONTROL) => {
+                let name = (self.tabs.len() + 1).to_string();
+                let mut stack: Stack = self.config.initial_state().try_into().unwrap_or_default();
+                stack.set_max_bits(self.config.max_bits);
+                stack.set_undo_limits(self.config.undo_limits());
+                self.tabs.push(Tab { name, stack });
+                self.tab = self.tabs.len() - 1;
+            }
+            (KeyCode::Char('w'), KeyModifiers::CONTROL) => {
+                if self.tabs.len() == 1 {
+                    return Err(AppError::LastTab);
+                }
+                self.tabs.remove(self.tab);
+                self.tab = self.tab.min(self.tabs.len() - 1);
+            }
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                if self.input.is_empty() {
+                    return Err(AppError::InputError(InputError::Empty));
+                }
+                self.tabs[self.tab].name = self.input.text().to_owned();
+                self.input.reset();
+            }
+            (KeyCode::Char('f'), KeyModifiers::CONTROL) => {
+                self.search = Some(Search::default());
+            }
+            (KeyCode::Char('o'), KeyModifiers::CONTROL) => {
+                self.history.set_visible(true);
+            }
+            (KeyCode::Char('k'), KeyModifiers::CONTROL) => {
+                self.keys = true;
+            }
+            (KeyCode::Char('g'), KeyModifiers::CONTROL) => {
+                self.palette.open(self.palette_entries());
+            }
+            (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                self.preview_mode = !self.preview_mode;
+            }
+            (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
+                self.histogram = !self.histogram;
+            }
+            (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+                self.ages = !self.ages;
+            }
+            (KeyCode::Char('y'), KeyModifiers::CONTROL) => {
+                self.origins = !self.origins;
+            }
+            (KeyCode::Char('q'), KeyModifiers::CONTROL) => {
+                let (name, keys) = self.recording.take().ok_or(AppError::NotRecording)?;
+                self.macros.insert(name, keys);
+            }
+            (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+                let top = self.stack().window(0, 1).next();
+                let top = top.ok_or(StackError::MissingValue(1))?;
+                let bits = BitField::new(&top, self.stack().word_size(), self.separator)?;
+                self.bits = Some(bits);
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => {
+                self.tab = (self.tab + 1) % self.tabs.len();
+            }
+            (KeyCode::BackTab, KeyModifiers::NONE) => {
+                self.tab = (self.tab + self.tabs.len() - 1) % self.tabs.len();
+            }
+            (KeyCode::Enter, KeyModifiers::ALT) => {
+                self.input.new_line();
+            }
+            (KeyCode::Char(_), KeyModifiers::NONE) if self.input.is_multiline() => {
+                // Lines are typed as is, and only run with the whole block.
+                self.input.handle_event(&Event::Key(k));
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE)
+                if self.preview_mode
+                    && matches!(self.ops.get(&c), Some(Op::Run(_)))
+                    && (empty || self.completes(c)) =>
+            {
+                self.lift_disabled = lift_disabled;
+                let result = self.preview(c);
+                self.preview = Some(Preview { key: c, result });
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) if self.config.hp_mode => {
+                self.lift_disabled = lift_disabled;
+                self.hp_enter()?;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE)
+                if self.config.hp_mode && self.ops.contains_key(&c) && self.completes(c) =>
+            {
+                // Like on HP calculators, operations complete the number typed.
+                self.lift_disabled = lift_disabled;
+                self.input_consume()?;
+                self.handle_key(k)?;
+            }
+            (KeyCode::Char(' '), KeyModifiers::NONE)
+                if matches!(
+                    self.input.command(),
+                    Some(SOLVE | BOOKMARK | RESTORE | DIFF | DC | REPORT | RECORD | WATCH)
+                ) || self
+                    .input
+                    .command()
+                    .is_some_and(|c| c.starts_with(EXPORT) || c.starts_with(MACRO)) =>
+            {
+                // Space separates the name following these commands, and the
+                // labels of an export or the keys of a macro.
+                self.input.handle_event(&Event::Key(k));
+            }
             (KeyCode::Enter, KeyModifiers::NONE)
             | (KeyCode::Char(' '), KeyModifiers::NONE)
-            | (KeyCode::Char('m'), KeyModifiers::CONTROL) => {
+            | (KeyCode::Char('m'), KeyModifiers::CONTROL)
+            | (KeyCode::Char('='), KeyModifiers::NONE) => {
+                self.lift_disabled = lift_disabled;
                 self.input_consume()?;
             }
             (KeyCode::Char('-'), KeyModifiers::NONE) if !empty => {
-                if let Ok(v) = self.input.value() {
-                    self.input = self.input.clone().with_value((-v).to_plain_string());
-                } else {
-                    let event = Event::Key(k);
-                    self.input.handle_event(&event);
+                self.lift_disabled = lift_disabled;
+                match self.input.value(self.stack().precision()) {
+                    Ok(v) if !typed_expression(self.input.text()) => {
+                        self.negated = Some(self.input.text().to_owned());
+                        self.input = self.input.clone().with_value((-v).to_plain_string());
+                    }
+                    _ => {
+                        let event = Event::Key(k);
+                        self.input.handle_event(&event);
+                    }
                 }
             }
+            (KeyCode::Char(c), KeyModifiers::NONE)
+                if negated.is_some() && (c.is_ascii_digit() || c == '.' || c == '(') =>
+            {
+                // An operand after `-` makes it a subtraction, e.g. `10-4`,
+                // rather than the negation of the number.
+                self.lift_disabled = lift_disabled;
+                let text = negated.unwrap_or_default();
+                self.input = self.input.clone().with_value(format!("{text}-{c}"));
+            }
             (KeyCode::Char(c), KeyModifiers::NONE) if self.ops.contains_key(&c) && empty => {
-                self.op = Some(c);
-                self.stack
-                    .apply(self.ops[&c].clone())
-                    .map_err(AppError::StackError)?;
+                self.run_key(c)?;
+            }
+            (KeyCode::Char(_), KeyModifiers::CONTROL | KeyModifiers::ALT)
+                if self.chord(k).is_some() =>
+            {
+                self.lift_disabled = lift_disabled;
+                self.run_chord(k)?;
             }
             (KeyCode::Char(LOAD), KeyModifiers::NONE) if empty => {
                 self.pending_reg = Some(PendingReg::Load);
@@ -171,6 +1175,7 @@ impl App {
                 self.pending_reg = Some(PendingReg::Save);
             }
             _ => {
+                self.lift_disabled = lift_disabled;
                 let event = Event::Key(k);
                 self.input.handle_event(&event);
             }
@@ -178,41 +1183,757 @@ impl App {
         Ok(())
     }
 
-    fn handle_events(&mut self) -> std::io::Result<()> {
-        match crossterm::event::read()? {
-            Event::Key(mut key_event) if key_event.kind == KeyEventKind::Press => {
-                self.op = None;
-                // crossterm is doing very inconsistent things with SHIFT between
-                // letters and non-letters, for instance Shift-/ is '?' but is
-                // reported Char('?') + SHIFT.
-                //
-                // As we don't really _care_ about SHIFT as a modifier, let's
-                // filter it out altogether here.
-                key_event.modifiers = key_event.modifiers.difference(KeyModifiers::SHIFT);
-                self.op_status = self.handle_key(key_event);
-            }
-            _ => {}
+    /// Whether the key ends the number typed rather than continuing it, as
+    /// `k` does in `4.7k`.
+    fn completes(&self, c: char) -> bool {
+        let (text, precision) = (self.input.text(), self.stack().precision());
+        self.input.value(precision).is_ok()
+            && number::parse(&format!("{text}{c}"), precision).is_err()
+    }
+
+    /// Run the operation bound to the key.
+    fn run_key(&mut self, c: char) -> Result<(), AppError> {
+        self.op = Some(c);
+        let op = self.ops[&c].clone();
+        let name = match &op {
+            Op::Run(o) => Some(format!(":{}", o.name())),
+            _ => None,
         };
+        self.execute(op)?;
+        if let Some(name) = name {
+            self.history.push(name);
+        }
         Ok(())
     }
 
-    fn input_consume(&mut self) -> Result<(), AppError> {
-        if self.input.is_empty() {
-            return Ok(());
-        }
-        let v = self.input.value()?;
-        self.stack
-            .apply(Op::Push(v))
-            .map_err(AppError::StackError)?;
-        self.input.reset();
+    /// The name of the operation bound to the chord in the configuration.
+    fn chord(&self, k: KeyEvent) -> Option<&str> {
+        self.config
+            .chords
+            .iter()
+            .find(|(chord, _)| parse_key(chord) == Some(k))
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Run the operation bound to the chord, after the input if any.
+    fn run_chord(&mut self, k: KeyEvent) -> Result<(), AppError> {
+        let name = self.chord(k).unwrap_or_default().to_owned();
+        let op = self.command(&name)?;
+        self.input_consume()?;
+        self.execute(op)?;
+        self.history.push(format!(":{name}"));
         Ok(())
     }
 
-    fn render_instructions(&self) -> impl Widget {
-        Line::from(vec![
-            format!(" Helix Calc {} - ", env!("CARGO_PKG_VERSION")).into(),
-            " Help ".into(),
-            "<?> ".blue().bold(),
+    /// The top of the stack after the operation of the key, with the number
+    /// typed pushed first, as computed on a copy of the stack.
+    fn preview(&self, c: char) -> Result<Option<BigDecimal>, AppError> {
+        let mut stack = self.stack().clone();
+        if !self.input.is_empty() {
+            let mut ops = vec![];
+            if self.lift_disabled {
+                ops.push(ops::builtin("drop").expect("drop is built-in"));
+            }
+            ops.extend(
+                self.input
+                    .values(self.stack().precision())?
+                    .into_iter()
+                    .map(Op::Push),
+            );
+            stack.apply_all(ops)?;
+        }
+        let outcome = stack.spawn(self.ops[&c].clone()).poll(QUICK);
+        stack.complete(outcome.ok_or(AppError::TooSlow)?)?;
+        let top = stack.values().next().map(|v| v.into_owned());
+        Ok(top)
+    }
+
+    /// Run the previewed operation, after the number typed if any.
+    fn confirm(&mut self, c: char) -> Result<(), AppError> {
+        self.input_consume()?;
+        self.run_key(c)
+    }
+
+    /// Enter on HP calculators: complete the number typed, if any, and copy
+    /// it to S2, where it stays when the next number replaces it in S1.
+    fn hp_enter(&mut self) -> Result<(), AppError> {
+        if self.input.is_multiline() || self.input.command().is_some() {
+            return self.input_consume();
+        }
+        self.input_consume()?;
+        let dup = ops::builtin("dup").expect("dup is built-in");
+        self.stack_mut().apply(dup)?;
+        self.lift_disabled = true;
+        Ok(())
+    }
+
+    /// The keys and chords bound to operations, with the operation names.
+    fn op_keys(&self) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = self
+            .ops
+            .iter()
+            .filter_map(|(key, op)| {
+                let name = match op {
+                    Op::Run(op) => op.name(),
+                    Op::Undo => "undo",
+                    Op::Redo => "redo",
+                    _ => return None,
+                };
+                Some((key.to_string(), name.to_owned()))
+            })
+            .collect();
+        entries.extend(
+            self.config
+                .chords
+                .iter()
+                .map(|(chord, name)| (chord.clone(), name.clone())),
+        );
+        entries.sort();
+        entries
+    }
+
+    /// The operations runnable by name, with the keys bound to them.
+    fn palette_entries(&self) -> Vec<palette::Entry> {
+        let keys = self.op_keys();
+        let macros = self.macros.iter().map(|(name, keys)| palette::Entry {
+            name: name.clone(),
+            keys: vec![],
+            description: format!("macro {keys}"),
+        });
+        self.commands
+            .iter()
+            .filter_map(|(name, op)| match op {
+                Op::Run(op) => Some(palette::Entry {
+                    name: name.clone(),
+                    keys: keys
+                        .iter()
+                        .filter(|(_, n)| n == name)
+                        .map(|(k, _)| k.clone())
+                        .collect(),
+                    description: op.description().to_owned(),
+                }),
+                _ => None,
+            })
+            .chain(macros)
+            .collect()
+    }
+
+    /// The bound keys and what they do: the operations, then the keys of
+    /// the interface itself.
+    fn key_entries(&self) -> Vec<(String, String)> {
+        let mut entries = self.op_keys();
+        let interface = [
+            (LOAD.to_string(), "load"),
+            (SAVE.to_string(), "save"),
+            ("'".into(), "separator"),
+            ("T".into(), "durations"),
+            ("S".into(), "SI prefixes"),
+            ("I".into(), "IP addresses"),
+            ("F".into(), "format"),
+            ("?".into(), "help"),
+            ("q".into(), "quit"),
+            ("^f".into(), "search"),
+            ("^o".into(), "history"),
+            ("^t".into(), "new tab"),
+            ("^w".into(), "close tab"),
+            ("^r".into(), "rename tab"),
+            ("^k".into(), "keys"),
+            ("^g".into(), "operations"),
+            ("^p".into(), "preview"),
+            ("^b".into(), "histogram"),
+            ("^a".into(), "ages"),
+            ("^y".into(), "origins"),
+            ("^e".into(), "bits"),
+            ("^q".into(), "stop recording"),
+        ];
+        entries.extend(interface.map(|(key, name)| (key, name.to_owned())));
+        entries
+    }
+
+    /// Edit the search query, or move between its matches.
+    fn handle_search_key(&mut self, k: KeyEvent) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let matches = &search.matches;
+        let position = matches.iter().position(|&m| Some(m) == search.current);
+        let n = matches.len();
+        match (k.code, k.modifiers) {
+            (KeyCode::Esc, _) => self.search = None,
+            // Deeper in the stack, i.e. up on the screen.
+            (KeyCode::Enter | KeyCode::Up, KeyModifiers::NONE)
+            | (KeyCode::Char('n'), KeyModifiers::CONTROL)
+                if n > 0 =>
+            {
+                search.current = Some(matches[position.map_or(0, |p| (p + 1) % n)]);
+            }
+            (KeyCode::Down, KeyModifiers::NONE) | (KeyCode::Char('p'), KeyModifiers::CONTROL)
+                if n > 0 =>
+            {
+                search.current = Some(matches[position.map_or(0, |p| (p + n - 1) % n)]);
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                search.query.pop();
+                search.current = None;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) => {
+                search.query.push(c);
+                search.current = None;
+            }
+            _ => {}
+        }
+        // Select the match closest to the top of the stack after an edit.
+        if self.search.as_ref().is_some_and(|s| s.current.is_none()) {
+            self.refresh_matches();
+            let search = self.search.as_mut().unwrap();
+            search.current = search.matches.first().copied();
+        }
+    }
+
+    /// Find the values matching the search, against their decimal digits.
+    /// This goes through the whole stack, so it's only done when the query
+    /// or the stack change, not on every frame.
+    fn refresh_matches(&mut self) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let matches = match search.query.is_empty() {
+            true => vec![],
+            false => self
+                .stack()
+                .values()
+                .enumerate()
+                .filter(|(_, v)| v.to_plain_string().contains(search.query.as_str()))
+                .map(|(i, _)| i)
+                .collect(),
+        };
+        self.search.as_mut().unwrap().matches = matches;
+    }
+
+    /// Reduce the numbers piped in since the last call, as a single step of
+    /// the undo history. Returns whether anything changed.
+    fn receive(&mut self) -> bool {
+        let precision = self.stack().precision();
+        let Some(feed) = &mut self.feed else {
+            return false;
+        };
+        let Some(lines) = &feed.lines else {
+            return false;
+        };
+        let mut ops = vec![];
+        let mut status = Ok(());
+        let mut closed = false;
+        while ops.len() < FEED_BATCH {
+            let line = match lines.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    closed = true;
+                    break;
+                }
+            };
+            match number::parse_all(&line, precision) {
+                Ok(values) => {
+                    for v in values {
+                        ops.push(Op::Push(v));
+                        if feed.count > 0 {
+                            ops.push(feed.op.clone());
+                        }
+                        feed.count += 1;
+                    }
+                }
+                Err(err) => status = Err(AppError::InputError(err)),
+            }
+        }
+        if closed {
+            feed.lines = None;
+        }
+        let changed = closed || !ops.is_empty() || status.is_err();
+        if !ops.is_empty() {
+            if let Err(err) = self.stack_mut().apply_all(ops) {
+                status = Err(err.into());
+            }
+            self.refresh_matches();
+        }
+        if status.is_err() {
+            self.op = None;
+            self.op_status = status;
+        }
+        changed
+    }
+
+    /// Handle the next event, and any other already pending so that bursts
+    /// (e.g. a paste) lead to a single redraw. Returns whether the display
+    /// may have changed.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn handle_events(&mut self) -> std::io::Result<bool> {
+        let mut changed = self.handle_event(event::read()?);
+        while !self.exit && event::poll(Duration::ZERO)? {
+            changed |= self.handle_event(event::read()?);
+        }
+        Ok(changed)
+    }
+
+    /// Handle an event, returning whether the display may have changed.
+    pub fn handle_event(&mut self, event: Event) -> bool {
+        log::debug!("{event:?}");
+        if self.job.is_some() {
+            // Only cancellation is possible while an operation runs.
+            if let Event::Key(k) = event {
+                if k.kind == KeyEventKind::Press && k.code == KeyCode::Esc {
+                    self.job = None;
+                    self.op_status = Err(AppError::Cancelled);
+                    self.reply(&Err(AppError::Cancelled));
+                    return true;
+                }
+            }
+            return false;
+        }
+        match event {
+            Event::Key(mut key_event) if key_event.kind == KeyEventKind::Press => {
+                self.op = None;
+                // crossterm is doing very inconsistent things with SHIFT between
+                // letters and non-letters, for instance Shift-/ is '?' but is
+                // reported Char('?') + SHIFT.
+                //
+                // As we don't really _care_ about SHIFT as a modifier, let's
+                // filter it out altogether here. Shift-Enter is the exception:
+                // it starts a new line, like Alt-Enter in the terminals which
+                // don't report it.
+                if key_event.code == KeyCode::Enter
+                    && key_event.modifiers.contains(KeyModifiers::SHIFT)
+                {
+                    key_event.modifiers.insert(KeyModifiers::ALT);
+                }
+                key_event.modifiers = key_event.modifiers.difference(KeyModifiers::SHIFT);
+                let key_event = normalize_key(key_event);
+                if let Some((_, keys)) = &mut self.recording {
+                    if (key_event.code, key_event.modifiers)
+                        != (KeyCode::Char('q'), KeyModifiers::CONTROL)
+                    {
+                        keys.extend(key_text(key_event));
+                    }
+                }
+                self.op_status = self.handle_key(key_event);
+                if let Err(err) = &self.op_status {
+                    log::debug!("error: {err}");
+                }
+                true
+            }
+            Event::Paste(text)
+                if !self.help.is_visible()
+                    && !self.history.is_visible()
+                    && !self.palette.is_visible() =>
+            {
+                self.op = None;
+                self.op_status = Ok(());
+                self.input.paste(&text);
+                true
+            }
+            Event::Mouse(m) if self.help.is_visible() => self.help.handle_mouse(m),
+            Event::Resize(_, _) => true,
+            // Key releases, focus changes, etc.
+            _ => false,
+        }
+    }
+
+    /// The operation run by a command: a name, or `solve` followed by the
+    /// name of the function to find a root of.
+    fn command(&self, command: &str) -> Result<Op, AppError> {
+        let named = |name: &str| {
+            self.commands
+                .get(name)
+                .cloned()
+                .ok_or_else(|| AppError::UnknownCommand(name.to_owned()))
+        };
+        match command.split_once(' ') {
+            Some((SOLVE, f)) => match named(f.trim())? {
+                Op::Run(f) => Ok(Op::Run(Arc::new(ops::Solve::new(f)))),
+                _ => Err(AppError::UnknownCommand(f.trim().to_owned())),
+            },
+            _ => named(command),
+        }
+    }
+
+    /// The name for a macro, unless it's empty or that of an operation.
+    fn macro_name(&self, name: &str) -> Result<String, AppError> {
+        let name = name.trim();
+        match name.is_empty() || self.commands.contains_key(name) {
+            true => Err(AppError::MacroName(name.to_owned())),
+            false => Ok(name.to_owned()),
+        }
+    }
+
+    /// Type the keys of a macro. Operations run to completion, and an error
+    /// stops there.
+    fn play(&mut self, keys: &str) -> Result<(), AppError> {
+        if self.playing == MAX_MACRO_DEPTH {
+            return Err(AppError::MacroDepth);
+        }
+        // The input running the macro is left for the keys to fill.
+        self.input.reset();
+        self.playing += 1;
+        let result = parse_keys(keys).and_then(|keys| {
+            keys.into_iter().try_for_each(|k| {
+                self.handle_key(k)?;
+                while self.job.is_some() {
+                    self.wait(TICK)?;
+                }
+                Ok(())
+            })
+        });
+        self.playing -= 1;
+        result
+    }
+
+    /// Run the watching macro again on a copy of the stack if the stack
+    /// changed since, and wait up to the timeout for the result. Returns
+    /// whether the result changed.
+    pub fn refresh_watcher(&mut self, timeout: Duration) -> bool {
+        let generation = self.stack().generation();
+        let Some(watcher) = &self.watcher else {
+            return false;
+        };
+        if watcher.seen != Some(generation) {
+            let job = self
+                .watched_ops(&watcher.name, 0)
+                .map(|ops| self.stack().spawn_all(ops));
+            let watcher = self.watcher.as_mut().expect("watching");
+            watcher.seen = Some(generation);
+            match job {
+                Ok(job) => watcher.job = Some(job),
+                Err(err) => {
+                    watcher.job = None;
+                    watcher.result = Err(err.to_string());
+                    return true;
+                }
+            }
+        }
+        let watcher = self.watcher.as_mut().expect("watching");
+        let Some(outcome) = watcher.job.as_ref().and_then(|job| job.poll(timeout)) else {
+            return false;
+        };
+        watcher.job = None;
+        watcher.result = match outcome.top() {
+            Ok(Some(top)) => Ok(top),
+            Ok(None) => Err(StackError::MissingValue(1).to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        true
+    }
+
+    /// Whether the watching macro is still running.
+    fn watching(&self) -> bool {
+        self.watcher.as_ref().is_some_and(|w| w.job.is_some())
+    }
+
+    /// The operations the keys of the macro amount to, to run them on a copy
+    /// of the stack. Only the keys typing the input and those running
+    /// operations can be watched, not those acting on the interface.
+    fn watched_ops(&self, name: &str, depth: usize) -> Result<Vec<Op>, AppError> {
+        if depth == MAX_MACRO_DEPTH {
+            return Err(AppError::MacroDepth);
+        }
+        let keys = self
+            .macros
+            .get(name)
+            .ok_or_else(|| AppError::UnknownMacro(name.to_owned()))?;
+        let unwatchable = |k| AppError::Unwatchable(key_text(k).unwrap_or_default());
+        let mut ops = vec![];
+        let mut text = String::new();
+        let mut lift_disabled = false;
+        let mut pending_reg = None;
+        let precision = self.stack().precision();
+        for k in parse_keys(keys)? {
+            if let Some(pending) = pending_reg.take() {
+                if let KeyCode::Char(c) = k.code {
+                    ops.push(match pending {
+                        PendingReg::Load => Op::Load(c),
+                        PendingReg::Save => Op::Save(c),
+                    });
+                }
+                continue;
+            }
+            let empty = text.is_empty();
+            let completes = number::parse(&text, precision).is_ok();
+            match (k.code, k.modifiers) {
+                (KeyCode::Backspace, KeyModifiers::NONE) if empty => {
+                    ops.push(ops::builtin("drop").expect("drop is built-in"));
+                    lift_disabled = false;
+                }
+                (KeyCode::Backspace, KeyModifiers::NONE) => {
+                    text.pop();
+                }
+                (KeyCode::Char('?' | 'q' | '\''), KeyModifiers::NONE) => {
+                    return Err(unwatchable(k));
+                }
+                (KeyCode::Char('T' | 'S' | 'I' | 'F'), KeyModifiers::NONE) if empty => {
+                    return Err(unwatchable(k));
+                }
+                (KeyCode::Char(' '), KeyModifiers::NONE)
+                    if text.strip_prefix(':') == Some(SOLVE) =>
+                {
+                    text.push(' ');
+                }
+                (KeyCode::Enter, KeyModifiers::NONE) if self.config.hp_mode => {
+                    let command = text.starts_with(':');
+                    ops.extend(self.typed_ops(&std::mem::take(&mut text), lift_disabled, depth)?);
+                    lift_disabled = !command;
+                    if !command {
+                        ops.push(ops::builtin("dup").expect("dup is built-in"));
+                    }
+                }
+                (KeyCode::Enter | KeyCode::Char(' ' | '='), KeyModifiers::NONE)
+                | (KeyCode::Char('m'), KeyModifiers::CONTROL) => {
+                    ops.extend(self.typed_ops(&std::mem::take(&mut text), lift_disabled, depth)?);
+                    lift_disabled = false;
+                }
+                (KeyCode::Char('-'), KeyModifiers::NONE) if !empty => {
+                    match number::parse(&text, precision) {
+                        Ok(v) => text = (-v).to_plain_string(),
+                        Err(_) => text.push('-'),
+                    }
+                }
+                (KeyCode::Char(c), KeyModifiers::NONE)
+                    if self.ops.contains_key(&c)
+                        && (empty
+                            || self.config.hp_mode
+                                && completes
+                                && number::parse(&format!("{text}{c}"), precision).is_err()) =>
+                {
+                    ops.extend(self.typed_ops(&std::mem::take(&mut text), lift_disabled, depth)?);
+                    lift_disabled = false;
+                    match &self.ops[&c] {
+                        Op::Undo | Op::Redo => return Err(unwatchable(k)),
+                        op => ops.push(op.clone()),
+                    }
+                }
+                (KeyCode::Char(_), KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                    let name = self.chord(k).ok_or(unwatchable(k))?;
+                    ops.extend(self.typed_ops(&std::mem::take(&mut text), lift_disabled, depth)?);
+                    lift_disabled = false;
+                    ops.push(self.command(name)?);
+                }
+                (KeyCode::Char(LOAD), KeyModifiers::NONE) if empty => {
+                    pending_reg = Some(PendingReg::Load);
+                }
+                (KeyCode::Char(SAVE), KeyModifiers::NONE) if empty => {
+                    pending_reg = Some(PendingReg::Save);
+                }
+                (KeyCode::Char(c), KeyModifiers::NONE) => text.push(c),
+                _ => return Err(unwatchable(k)),
+            }
+        }
+        Ok(ops)
+    }
+
+    /// The operations of a line typed in a watched macro: those of a command
+    /// or of another macro, or pushing the values typed.
+    fn typed_ops(
+        &self,
+        text: &str,
+        lift_disabled: bool,
+        depth: usize,
+    ) -> Result<Vec<Op>, AppError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(vec![]);
+        }
+        if let Some(command) = text.strip_prefix(':') {
+            let command = command.trim();
+            if !self.commands.contains_key(command) && self.macros.contains_key(command) {
+                return self.watched_ops(command, depth + 1);
+            }
+            return Ok(vec![self.command(command)?]);
+        }
+        let mut ops = vec![];
+        if lift_disabled {
+            ops.push(ops::builtin("drop").expect("drop is built-in"));
+        }
+        ops.extend(self.typed_values(text)?.into_iter().map(Op::Push));
+        Ok(ops)
+    }
+
+    fn input_consume(&mut self) -> Result<(), AppError> {
+        if self.input.is_empty() {
+            return Ok(());
+        }
+        if self.input.is_multiline() {
+            return self.run_lines();
+        }
+        let text = self.input.text().to_owned();
+        self.input_apply()?;
+        self.input.reset();
+        self.history.push(text);
+        Ok(())
+    }
+
+    /// Run the lines of the input in turn, each as if typed and committed.
+    /// On failure, the failing line and those after it are left to fix, and
+    /// the lines already run can be rolled back.
+    fn run_lines(&mut self) -> Result<(), AppError> {
+        let lines = self.input.take_lines();
+        let stack = self.stack().clone();
+        for (i, line) in lines.iter().enumerate() {
+            if let Err(err) = self.run_line(line) {
+                self.input = InputState::default().with_lines(lines[i..].to_vec());
+                self.rollback = Some(Rollback { stack, lines });
+                return Err(AppError::Line(i + 1, Box::new(err)));
+            }
+        }
+        Ok(())
+    }
+
+    fn run_line(&mut self, line: &str) -> Result<(), AppError> {
+        let keys = line
+            .chars()
+            .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        for k in keys.chain([KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)]) {
+            let result = self.handle_key(k).and_then(|_| {
+                while self.job.is_some() {
+                    self.wait(TICK)?;
+                }
+                Ok(())
+            });
+            if result.is_err() {
+                self.input.reset();
+                return result;
+            }
+        }
+        Ok(())
+    }
+
+    /// The values pushed by a line typed: the numbers in it, the roll of
+    /// the dice, or else the value of the expression.
+    fn typed_values(&self, text: &str) -> Result<Vec<BigDecimal>, AppError> {
+        Ok(
+            match (
+                dice::parse(text),
+                number::parse_all(text, self.stack().precision()),
+            ) {
+                (Some(dice), _) => vec![dice.roll().total],
+                (None, Ok(values)) => values,
+                (None, Err(err)) if !expr::is_expression(text) => return Err(err.into()),
+                (None, Err(_)) => vec![expr::evaluate(text, self.stack().precision())?],
+            },
+        )
+    }
+
+    /// Apply the input: run a command, roll dice, push numbers or evaluate
+    /// an expression.
+    fn input_apply(&mut self) -> Result<(), AppError> {
+        let lift_disabled = std::mem::take(&mut self.lift_disabled);
+        if let Some(command) = self.input.command() {
+            match command.trim().split_once(' ') {
+                Some((BOOKMARK, name)) => {
+                    let name = name.trim().to_owned();
+                    self.stack_mut().bookmark(&name);
+                    return Ok(());
+                }
+                Some((RESTORE, name)) => {
+                    let name = name.trim().to_owned();
+                    return Ok(self.stack_mut().restore(&name)?);
+                }
+                Some((DC, program)) => {
+                    let stack = &mut self.tabs[self.tab].stack;
+                    self.dc.run(stack, program)?;
+                    // The stack shows the outcome, what the program printed
+                    // is only of use on the command line.
+                    self.dc.take_output();
+                    return Ok(());
+                }
+                Some((DIFF, name)) => {
+                    let name = name.trim().to_owned();
+                    let changes = self.stack().diff(&name)?;
+                    self.diff = Some((name, changes));
+                    return Ok(());
+                }
+                Some((REPORT, name)) => return self.report(name.trim()),
+                Some((RECORD, name)) => {
+                    let name = self.macro_name(name)?;
+                    self.recording = Some((name, String::new()));
+                    return Ok(());
+                }
+                Some((MACRO, definition)) => {
+                    let (name, keys) = definition
+                        .trim()
+                        .split_once(' ')
+                        .unwrap_or((definition, ""));
+                    let name = self.macro_name(name)?;
+                    match keys.trim() {
+                        "" => self.macros.remove(&name),
+                        keys => {
+                            parse_keys(keys)?;
+                            self.macros.insert(name, keys.to_owned())
+                        }
+                    };
+                    // The watched macro may have changed.
+                    if let Some(watcher) = &mut self.watcher {
+                        watcher.seen = None;
+                    }
+                    return Ok(());
+                }
+                Some((WATCH, name)) => {
+                    let name = name.trim();
+                    self.watched_ops(name, 0)?;
+                    self.watcher = Some(Watcher {
+                        name: name.to_owned(),
+                        seen: None,
+                        job: None,
+                        result: Ok(BigDecimal::from(0)),
+                    });
+                    return Ok(());
+                }
+                Some((EXPORT, args)) => {
+                    let mut args = args.split_whitespace();
+                    let format = args.next().unwrap_or_default().parse()?;
+                    let labels = args.map(str::to_string).collect();
+                    self.clipboard = Some(self.stack_table(format, labels));
+                    return Ok(());
+                }
+                _ => {}
+            }
+            if command.trim() == COPY {
+                self.clipboard = Some(self.stack_text());
+                return Ok(());
+            }
+            if command.trim() == RELOAD {
+                return self.reload();
+            }
+            if command.trim() == WATCH {
+                self.watcher = None;
+                return Ok(());
+            }
+            if !self.commands.contains_key(command.trim()) {
+                if let Some(keys) = self.macros.get(command.trim()).cloned() {
+                    return self.play(&keys);
+                }
+            }
+            let op = self.command(command.trim())?;
+            return self.execute(op);
+        }
+        // A list of numbers (typically pasted) pushes them all, and anything
+        // else is evaluated as an expression.
+        let values = self.typed_values(self.input.text())?;
+        if lift_disabled {
+            // The number replaces the copy left by Enter, in a single step.
+            let drop = ops::builtin("drop").expect("drop is built-in");
+            let pushes = values.into_iter().map(Op::Push);
+            return Ok(self
+                .stack_mut()
+                .apply_all([drop].into_iter().chain(pushes).collect())?);
+        }
+        // A paste is a single step of the undo history.
+        Ok(self
+            .stack_mut()
+            .apply_all(values.into_iter().map(Op::Push).collect())?)
+    }
+
+    fn render_instructions(&self) -> impl Widget {
+        Line::from(vec![
+            format!(" Helix Calc {} - ", env!("CARGO_PKG_VERSION")).into(),
+            " Help ".into(),
+            "<?> ".blue().bold(),
             " Quit ".into(),
             "<Q> ".blue().bold(),
         ])
@@ -220,13 +1941,20 @@ impl App {
         .bg(Color::Black)
     }
 
+    fn render_tabs(&self) -> impl Widget {
+        Tabs::new(self.tabs.iter().map(|t| t.name.clone()))
+            .select(self.tab)
+            .highlight_style(Style::new().blue().bold())
+            .bg(Color::Black)
+    }
+
     fn render_registers(&self, area: &Rect) -> impl Widget {
         let margin = 5; // same column layout as the stack
-        let base = self.stack.output_base();
+
         // inner width after block borders (1 left + 1 right)
         let value_width = (area.width as u64).saturating_sub(margin as u64 + 1 + 2);
         let mut regs: Vec<(char, _)> = self
-            .stack
+            .stack()
             .registers()
             .iter()
             .map(|(&k, v)| (k, v.clone()))
@@ -236,9 +1964,7 @@ impl App {
             .into_iter()
             .map(|(key, val)| {
                 Row::new(vec![
-                    Cell::from(
-                        format_number(&val, value_width, self.separator, base).right_aligned(),
-                    ),
+                    Cell::from(self.format(&val, value_width).right_aligned()),
                     Cell::from(Line::raw(key.to_string()).right_aligned()),
                 ])
             })
@@ -252,42 +1978,193 @@ impl App {
         .bg(Color::Black)
     }
 
-    fn render_stack(&self, area: &Rect) -> impl Widget {
+    /// Count the values in at most `max` bins for the histogram. This goes
+    /// through the whole stack, so it's only done when the stack or the
+    /// space available change, not on every frame.
+    fn refresh_bins(&mut self, max: usize) {
+        let generation = self.stack().generation();
+        if self
+            .bins
+            .as_ref()
+            .is_some_and(|(g, m, _)| (*g, *m) == (generation, max))
+        {
+            return;
+        }
+        let values: Vec<Cow<BigDecimal>> = self.stack().values().collect();
+        let bins = histogram::bins(values.iter().map(Cow::as_ref), max);
+        self.bins = Some((generation, max, bins));
+    }
+
+    /// The values of the stack binned in as many ranges as fit the area,
+    /// each labelled with its low end.
+    fn render_histogram(&mut self, area: &Rect) -> impl Widget + '_ {
+        self.refresh_bins(area.height.saturating_sub(2) as usize);
+        let bins = self.bins.as_ref().map_or(&[][..], |(_, _, bins)| bins);
+        let label_width = 10;
+        histogram::chart(bins, |low| {
+            let label = self.format(low, label_width);
+            let padding = (label_width as usize).saturating_sub(label.width());
+            let mut spans = vec![" ".repeat(padding).into()];
+            spans.extend(label.spans);
+            spans.push(" ".into());
+            Line::from(spans)
+        })
+    }
+
+    /// The `count` values from the `offset`th formatted to the width, only
+    /// formatted again if they changed since the last frame.
+    fn shown(&mut self, offset: usize, count: usize, width: u64) -> Vec<Line<'static>> {
+        let how = Some((
+            width,
+            self.notation,
+            self.separator,
+            self.stack().output_base(),
+            self.stack().word_size(),
+        ));
+        // Only the visible values are needed, however deep the stack.
+        let values: Vec<_> = self.stack().window(offset, count).collect();
+        if self.shown.how != how
+            || !self
+                .shown
+                .values
+                .iter()
+                .eq(values.iter().map(|v| v.as_ref()))
+        {
+            let lines = values.iter().map(|v| self.format(v, width)).collect();
+            let values = values.into_iter().map(Cow::into_owned).collect();
+            self.shown = Shown { how, values, lines };
+        }
+        self.shown.lines.clone()
+    }
+
+    fn render_stack(&mut self, area: &Rect) -> impl Widget {
         let margin = 5; // Size of the margin holding the stack index.
-        let snapshot = self.stack.snapshot();
-        let base = self.stack.output_base();
-        let stack: Vec<Row<'_>> = (1..=area.height)
+
+        // Size of the column of the ages, if shown, e.g. "59m ago".
+        let ages = if self.ages { 8 } else { 0 };
+        // Size of the column of the origins, if shown, e.g. "12.5 3 mul".
+        let origins = if self.origins { area.width * 2 / 5 } else { 0 };
+        let height = area.height as usize;
+        // Scroll up to the selected match, if any.
+        let current = self.search.as_ref().and_then(|s| s.current);
+        let offset = current.map_or(0, |c| (c + 1).saturating_sub(height));
+        let width = area
+            .width
+            .saturating_sub(margin + 1 + ages + ages.min(1) + origins + origins.min(1));
+        let mut values = self.shown(offset, height, width as u64);
+        let now = ops::seconds().unwrap_or(0);
+        let matches = self.search.as_ref().map_or(&[][..], |s| &s.matches);
+        let stack: Vec<Row<'_>> = (1..=height)
             .rev()
-            .map(|index| {
-                let stack_index = (index as usize) - 1;
-                let [val, idx] = if stack_index < snapshot.len() {
+            .map(|row| {
+                let stack_index = offset + row - 1;
+                let [val, idx] = if let Some(value) = values.get_mut(row - 1) {
                     [
-                        format_number(
-                            &snapshot[stack_index],
-                            (area.width - (margin + 1)) as u64,
-                            self.separator,
-                            base,
-                        ),
-                        Line::raw(format!("{}", index)).style(Color::White),
+                        std::mem::take(value),
+                        // Pinned entries are marked, not only colored.
+                        match self.stack().is_pinned(stack_index) {
+                            true => Line::raw(format!("•{}", stack_index + 1)).yellow(),
+                            false => Line::raw(format!("{}", stack_index + 1)).style(Color::White),
+                        },
                     ]
                 } else {
                     [Line::raw(""), Line::raw("")]
                 };
-                Row::new(vec![
-                    Cell::from(val.right_aligned()),
-                    Cell::from(idx.right_aligned()),
-                ])
+                let style = if Some(stack_index) == current {
+                    Style::new().bg(Color::Blue)
+                } else if matches.binary_search(&stack_index).is_ok() {
+                    Style::new().bg(Color::DarkGray)
+                } else {
+                    Style::new()
+                };
+                let mut cells = vec![];
+                if self.origins {
+                    let origin = self.stack().origin(stack_index);
+                    let origin = origin.map(|o| format!("= {o}")).unwrap_or_default();
+                    cells.push(Cell::from(Line::raw(origin).dark_gray()));
+                }
+                cells.push(Cell::from(val.right_aligned()));
+                if self.ages {
+                    let pushed = self.stack().pushed_at(stack_index);
+                    let age = pushed.map(|p| age(p, now)).unwrap_or_default();
+                    cells.push(Cell::from(Line::raw(age).dark_gray().right_aligned()));
+                }
+                cells.push(Cell::from(idx.right_aligned()));
+                Row::new(cells).style(style)
             })
             .collect();
-        Table::new(
-            stack,
-            [Constraint::Percentage(100), Constraint::Length(margin)],
-        )
-        .column_spacing(1)
-        .bg(Color::Black)
+        let mut widths = vec![];
+        if self.origins {
+            widths.push(Constraint::Length(origins));
+        }
+        widths.push(Constraint::Percentage(100));
+        if self.ages {
+            widths.push(Constraint::Length(ages));
+        }
+        widths.push(Constraint::Length(margin));
+        Table::new(stack, widths).column_spacing(1).bg(Color::Black)
     }
 
     fn render_status(&self) -> impl Widget {
+        if let Some(search) = &self.search {
+            let count = match search.matches.len() {
+                0 => "no match".to_string(),
+                1 => "1 match".to_string(),
+                n => format!("{n} matches"),
+            };
+            let mut spans = vec![
+                "/".blue().bold(),
+                format!("{}  ({count}, ", search.query).into(),
+                "<Esc>".blue().bold(),
+                " to close)".into(),
+            ];
+            // How the selected entry was computed, if by an operation.
+            if let Some(origin) = search.current.and_then(|c| self.stack().origin(c)) {
+                spans.push(format!("  = {origin}").dark_gray());
+            }
+            return Text::from(Line::from(spans)).bg(Color::Black);
+        }
+        if let Some((_, start)) = &self.job {
+            let frames: Vec<char> = SPINNER.chars().collect();
+            let frame =
+                frames[(start.elapsed().as_millis() / TICK.as_millis()) as usize % frames.len()];
+            return Text::from(Line::from(vec![
+                format!("{frame} computing, ").into(),
+                "<Esc>".blue().bold(),
+                " to cancel".into(),
+            ]))
+            .bg(Color::Black);
+        }
+        if let Some(preview) = &self.preview {
+            let mut spans = vec![format!("<{}>", preview.key).blue().bold()];
+            match &preview.result {
+                Ok(Some(top)) => {
+                    spans.push(" = ".into());
+                    spans.extend(self.format(top, 20).spans);
+                }
+                Ok(None) => spans.push(" empties the stack".into()),
+                Err(err) => spans.push(format!(": {err}").into()),
+            }
+            spans.extend([", ".into(), "<Enter>".blue().bold(), " to apply".into()]);
+            return Text::from(Line::from(spans)).bg(Color::Black);
+        }
+        if let (Some((name, _)), Ok(_), None) = (&self.recording, &self.op_status, self.op) {
+            return Text::from(Line::from(vec![
+                "● ".red(),
+                format!("recording :{name}, ").into(),
+                "<^q>".blue().bold(),
+                " to stop".into(),
+            ]))
+            .bg(Color::Black);
+        }
+        if let (Some(feed), Ok(_), None) = (&self.feed, &self.op_status, self.op) {
+            let waiting = match feed.lines {
+                Some(_) => ", waiting for more",
+                None => "",
+            };
+            return Text::from(format!("{} numbers piped in{waiting}", feed.count))
+                .bg(Color::Black);
+        }
         let status = match (&self.op_status, self.op) {
             (Ok(_), Some(c)) => Line::from(format!("<{}>", c).blue().bold()),
             (Err(err), Some(c)) => Line::from(vec![
@@ -300,16 +2177,125 @@ impl App {
         Text::from(status).bg(Color::Black)
     }
 
+    /// The name of the watching macro and its result, if any.
+    fn render_watcher(&self) -> Option<Line<'static>> {
+        let watcher = self.watcher.as_ref()?;
+        let mut line = Line::from(vec![format!(" :{}", watcher.name).blue().bold()]);
+        match &watcher.result {
+            Ok(v) => {
+                line.spans.push(" = ".into());
+                line.spans.extend(self.format(v, 20).spans);
+            }
+            Err(err) => line.spans.push(format!(": {err}").red()),
+        }
+        line.spans.push(" ".into());
+        Some(line)
+    }
+
     fn render_precision_base(&self) -> impl Widget {
-        let base = self.stack.output_base();
+        let base = match self.notation {
+            Notation::Durations => "H:M:S".to_string(),
+            Notation::Si => "SI".to_string(),
+            Notation::Ip => "IP".to_string(),
+            Notation::Template => "format".to_string(),
+            Notation::Plain => match self.stack().word_size() {
+                Some(bits) if self.stack().output_base().is_power_of_two() => {
+                    format!("{}/{bits}", self.stack().output_base())
+                }
+                _ => self.stack().output_base().to_string(),
+            },
+        };
         let sep = if self.separator { "on " } else { "off" };
+        let precision = match self.stack().fast() {
+            true => "f64".to_string(),
+            false => self.stack().precision().to_string(),
+        };
         let label = format!(
-            "Precision: {} | Base: {} | Separator: {}",
-            self.stack.precision(),
+            "Precision: {} | Base: {} | Separator: {} | {}",
+            precision,
             base,
-            sep
+            sep,
+            self.stack().angle()
         );
-        Text::from(label.green().into_centered_line()).bg(Color::Black)
+        let mut line = Line::from(label.green());
+        if self.stack().undo_truncated() {
+            line.spans.insert(0, "Undo capped | ".yellow());
+        }
+        if self.read_only {
+            line.spans.insert(0, "Read-only | ".red().bold());
+        }
+        Text::from(line.centered()).bg(Color::Black)
+    }
+
+    /// The value of the expression being typed, if it has one so far. It's
+    /// only computed again once the expression changes.
+    fn live_result(&mut self) -> Option<BigDecimal> {
+        let text = self.input.text();
+        if !expr::is_expression(text)
+            || self.input.command().is_some()
+            || dice::parse(text).is_some()
+            || self.input.values(self.stack().precision()).is_ok()
+        {
+            return None;
+        }
+        if self.live.as_ref().is_none_or(|(typed, _)| typed != text) {
+            let value = expr::evaluate_partial(text, self.stack().precision());
+            self.live = Some((text.to_owned(), value));
+        }
+        self.live.as_ref().and_then(|(_, v)| v.clone())
+    }
+
+    /// A popup with the changes since a bookmark, one per line: the position
+    /// in the stack, then the old and new values.
+    fn render_diff(&self, area: Rect, buf: &mut Buffer) {
+        let Some((name, changes)) = &self.diff else {
+            return;
+        };
+        let width = 19; // Of each value, to fit both in the page.
+        let lines: Vec<Line> = match changes.is_empty() {
+            true => vec![Line::raw(format!("No change since '{name}'."))],
+            false => changes
+                .iter()
+                .map(|change| {
+                    let mut spans = match &change.after {
+                        Some(_) => vec![format!("{:>4}: ", change.index + 1).into()],
+                        None => vec!["   -: ".into()],
+                    };
+                    match (&change.before, &change.after) {
+                        (Some(before), Some(after)) => {
+                            spans.extend(self.format(before, width).spans);
+                            spans.push(" → ".yellow());
+                            spans.extend(self.format(after, width).spans);
+                        }
+                        (None, Some(after)) => {
+                            spans.push("+ ".green());
+                            spans.extend(self.format(after, width).spans);
+                        }
+                        (Some(before), None) => {
+                            spans.push("- ".red());
+                            spans.extend(self.format(before, width).spans);
+                        }
+                        (None, None) => {}
+                    }
+                    Line::from(spans)
+                })
+                .collect(),
+        };
+        let [v_center] = Layout::vertical([Constraint::Length(lines.len() as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup_area] = Layout::horizontal([Constraint::Length(50)])
+            .flex(Flex::Center)
+            .areas(v_center);
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .title(format!(" Since '{name}' "))
+                    .title_bottom("<Any key to close>"),
+            )
+            .bg(Color::Black)
+            .render(popup_area, buf);
     }
 
     fn render_reg_prompt(&self, area: Rect, buf: &mut Buffer) {
@@ -335,18 +2321,45 @@ impl App {
         let [page] = Layout::horizontal([Constraint::Length(50)])
             .flex(Flex::Center)
             .areas(area);
+        // On tiny terminals, drop the decorations first so that the stack
+        // remains visible as long as possible.
+        let instructions_height = if page.height < 7 { 0 } else { 1 };
+        // Multi-line input grows up to a third of the page.
+        let lines = (self.input.lines() as u16).min(page.height / 3).max(1);
+        let input_height = if page.height < 6 { 1 } else { 2 + lines };
         let [instructions_area, stack_area, input_area, status_op_area, status_info_area] =
             Layout::vertical([
-                Constraint::Length(1),
+                Constraint::Length(instructions_height),
                 Constraint::Percentage(100),
-                Constraint::Length(3),
+                Constraint::Length(input_height),
                 Constraint::Length(1),
                 Constraint::Length(1),
             ])
             .areas(page);
 
         self.render_instructions().render(instructions_area, buf);
-        let num_regs = self.stack.registers().len();
+        let stack_area = if self.tabs.len() > 1 && stack_area.height > 1 {
+            let [tabs_area, remaining] =
+                Layout::vertical([Constraint::Length(1), Constraint::Percentage(100)])
+                    .areas(stack_area);
+            self.render_tabs().render(tabs_area, buf);
+            remaining
+        } else {
+            stack_area
+        };
+        // The histogram takes the upper half, as long as a few values remain
+        // visible below.
+        let stack_area = if self.histogram && stack_area.height >= 8 {
+            let [histogram_area, remaining] =
+                Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .areas(stack_area);
+            self.render_histogram(&histogram_area)
+                .render(histogram_area, buf);
+            remaining
+        } else {
+            stack_area
+        };
+        let num_regs = self.stack().registers().len();
         let reg_rows = num_regs.min(stack_area.height as usize / 2) as u16;
         if reg_rows > 0 {
             let [reg_area, remaining_stack] = Layout::vertical([
@@ -360,24 +2373,216 @@ impl App {
         } else {
             self.render_stack(&stack_area).render(stack_area, buf);
         }
-        InputWidget::default().render(input_area, buf, &mut self.input);
+        let mut input = InputWidget::default();
+        if let Some(v) = self.live_result() {
+            let width = input_area.width.saturating_sub(6) as u64;
+            let mut result = Line::raw(" = ");
+            result.spans.extend(self.format(&v, width).spans);
+            result.spans.push(" ".into());
+            input = input.with_result(result);
+        }
+        input.render(input_area, buf, &mut self.input);
+        // The watching macro has its own slot, on the right of the status.
+        let status_op_area = match self.render_watcher() {
+            Some(watched) => {
+                let width = (watched.width() as u16).min(status_op_area.width / 2);
+                let [status_op_area, watcher_area] =
+                    Layout::horizontal([Constraint::Fill(1), Constraint::Length(width)])
+                        .areas(status_op_area);
+                Text::from(watched)
+                    .bg(Color::Black)
+                    .render(watcher_area, buf);
+                status_op_area
+            }
+            None => status_op_area,
+        };
         self.render_status().render(status_op_area, buf);
         self.render_precision_base().render(status_info_area, buf);
         Help::default().render(area, buf, &mut self.help);
+        History::default().render(area, buf, &mut self.history);
+        Palette::default().render(area, buf, &mut self.palette);
+        if self.keys {
+            KeySheet::new(self.key_entries()).render(area, buf);
+        }
+        if self.diff.is_some() {
+            self.render_diff(area, buf);
+        }
+        if let Some(bits) = &self.bits {
+            bits.render(area, buf);
+        }
 
         if self.pending_reg.is_some() {
             self.render_reg_prompt(area, buf);
         }
+        if let Some(notice) = &self.notice {
+            render_notice(notice, area, buf);
+        }
         Some(self.input.cursor())
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// The keys written as for `App::feed_keys`.
+fn parse_keys(keys: &str) -> Result<Vec<KeyEvent>, AppError> {
+    let mut events = vec![];
+    let mut rest = keys;
+    while let Some(c) = rest.chars().next() {
+        if c != '<' {
+            events.push(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let end = rest.find('>').ok_or(AppError::UnknownKey(rest.into()))?;
+        events.push(parse_key(&rest[1..end]).ok_or(AppError::UnknownKey(rest[..=end].into()))?);
+        rest = &rest[end + 1..];
+    }
+    Ok(events)
+}
 
-    #[test]
-    fn validate_display_of_long_numbers() -> anyhow::Result<()> {
+/// Whether the input typed has become an expression, where `-` subtracts
+/// rather than negates: it opens a parenthesis, or has an operator past its
+/// sign other than that of an exponent, e.g. `2*3` or `15%` but not `1e-3`.
+fn typed_expression(text: &str) -> bool {
+    text.starts_with('(')
+        || text.char_indices().skip(1).any(|(i, c)| match c {
+            '+' | '-' => !text[..i].ends_with(['e', 'E']),
+            _ => "()*/%^".contains(c),
+        })
+}
+
+/// Whether the key is used by the interface rather than bound to an
+/// operation: digits and the like start a number or an expression.
+fn reserved(key: char) -> bool {
+    key.is_ascii_digit() || "._(?q'TSIF= ".contains(key) || key == LOAD || key == SAVE
+}
+
+/// The key of the main keyboard doing the same as the key pressed: the
+/// keypad Enter may come as a carriage return or a line feed, and its
+/// decimal key as a comma in some layouts.
+fn normalize_key(k: KeyEvent) -> KeyEvent {
+    let keypad = k.state.contains(KeyEventState::KEYPAD);
+    match (k.code, k.modifiers) {
+        (KeyCode::Char('\r' | '\n'), _) | (KeyCode::Char('j'), KeyModifiers::CONTROL) => KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: k.modifiers.difference(KeyModifiers::CONTROL),
+            ..k
+        },
+        (KeyCode::Char(','), _) if keypad => KeyEvent {
+            code: KeyCode::Char('.'),
+            ..k
+        },
+        _ => k,
+    }
+}
+
+/// How long ago the time was, in seconds since 1970, in its largest unit,
+/// e.g. `2m ago`.
+fn age(pushed: u64, now: u64) -> String {
+    let elapsed = now.saturating_sub(pushed);
+    match elapsed {
+        0..60 => format!("{elapsed}s ago"),
+        60..3600 => format!("{}m ago", elapsed / 60),
+        3600..86400 => format!("{}h ago", elapsed / 3600),
+        _ => format!("{}d ago", elapsed / 86400),
+    }
+}
+
+/// The key written as for `App::feed_keys`, if it can be.
+fn key_text(k: KeyEvent) -> Option<String> {
+    let name = match k.code {
+        KeyCode::Enter => "Enter".to_owned(),
+        KeyCode::Esc => "Esc".to_owned(),
+        KeyCode::Tab => "Tab".to_owned(),
+        KeyCode::Backspace => "Backspace".to_owned(),
+        KeyCode::Up => "Up".to_owned(),
+        KeyCode::Down => "Down".to_owned(),
+        KeyCode::Left => "Left".to_owned(),
+        KeyCode::Right => "Right".to_owned(),
+        KeyCode::Char('<') => "lt".to_owned(),
+        KeyCode::Char(c) => c.to_string(),
+        _ => return None,
+    };
+    match k.modifiers {
+        KeyModifiers::NONE if name.chars().count() == 1 => Some(name),
+        KeyModifiers::NONE => Some(format!("<{name}>")),
+        KeyModifiers::CONTROL => Some(format!("<C-{name}>")),
+        KeyModifiers::ALT => Some(format!("<A-{name}>")),
+        _ => None,
+    }
+}
+
+/// A key named between angle brackets, e.g. `Enter` or `C-z`.
+fn parse_key(name: &str) -> Option<KeyEvent> {
+    let (modifiers, name) = match name.split_once('-') {
+        Some(("C", key)) => (KeyModifiers::CONTROL, key),
+        Some(("A", key)) => (KeyModifiers::ALT, key),
+        _ => (KeyModifiers::NONE, name),
+    };
+    let code = match name {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Tab" => KeyCode::Tab,
+        "Backspace" => KeyCode::Backspace,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "lt" => KeyCode::Char('<'),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// A centered popup with the notice, over everything else.
+fn render_notice(notice: &str, area: Rect, buf: &mut Buffer) {
+    // At least 6 lines of text, more if it wraps to more.
+    let lines: usize = notice.lines().map(|l| l.len().div_ceil(48).max(1)).sum();
+    let height = u16::try_from(lines.max(6) + 2).unwrap_or(u16::MAX);
+    let [v_center] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [popup_area] = Layout::horizontal([Constraint::Length(50)])
+        .flex(Flex::Center)
+        .areas(v_center);
+    Clear.render(popup_area, buf);
+    Paragraph::new(notice)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::bordered()
+                .title(" Notice ")
+                .title_bottom("<Enter> to continue, <q> to quit"),
+        )
+        .bg(Color::Black)
+        .render(popup_area, buf);
+}
+
+/// Highlight the elided digits in yellow and overflows in red.
+fn styled(formatted: Formatted) -> Line<'static> {
+    Line::from(
+        formatted
+            .0
+            .into_iter()
+            .map(|span| match span.style {
+                format::Style::Plain => ratatui::text::Span::raw(span.text),
+                format::Style::Elided => ratatui::text::Span::from(span.text).yellow(),
+                format::Style::Overflow => ratatui::text::Span::from(span.text).red(),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::{MouseEvent, MouseEventKind};
+
+    #[test]
+    fn validate_display_of_long_numbers() -> anyhow::Result<()> {
         let mut app = App::new(State::default())?;
         app.add_extra("10000000 100000000 *")?;
 
@@ -427,13 +2632,1156 @@ mod test {
 
     // Render into a 20-wide buffer of the given height and return the text at the given row.
     fn render_row(app: &mut App, height: u16, row: u16) -> anyhow::Result<String> {
-        let mut buf = Buffer::empty(Rect::new(0, 0, 20, height));
+        let text = app.render_to_string(20, height);
+        Ok(text
+            .lines()
+            .nth(row as usize)
+            .unwrap_or_default()
+            .to_owned())
+    }
+
+    fn ctrl(app: &mut App, c: char) -> Result<(), AppError> {
+        app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    #[test]
+    fn scripted_session() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.feed_keys("1<Enter>2<Enter>+3<Enter>4<Backspace>*u<lt>")?;
+        assert_eq!(app.plain_values(), ["3", "3"]);
+        assert_eq!(app.input.text(), "<");
+        let screen = app.render_to_string(20, 8);
+        let rows: Vec<&str> = screen.lines().collect();
+        assert_eq!(rows.len(), 8);
+        assert_eq!(rows[1..3], ["             3     2", "             3     1"]);
+        assert!(app.feed_keys("<Enter").is_err());
+        assert!(app.feed_keys("<F13>").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn evaluate_expressions() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("(2+3)*4^2=")?;
+        assert_eq!(render(app)?, "            80     1");
+        Ok(())
+    }
+
+    #[test]
+    fn subtract_in_expressions() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("10-4=")?;
+        app.add_extra("2*3-1=")?;
+        app.add_extra("200*15%=")?;
+        app.add_extra("3/4-1=")?;
+        assert_eq!(app.plain_values(), ["-0.25", "30", "5", "6"]);
+        // Without an operand next, `-` still negates the number.
+        app.add_extra("5-=")?;
+        app.add_extra("1e-3-=")?;
+        app.add_extra("8-(1+1)=")?;
+        assert_eq!(&app.plain_values()[..3], ["6", "-0.001", "-5"]);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_expressions_stay_in_input() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        assert_eq!(
+            app.add_extra("(2+3=").err().map(|e| e.to_string()),
+            Some("unclosed parenthesis at position 1".into())
+        );
+        assert_eq!(app.input.text(), "(2+3");
+        Ok(())
+    }
+
+    #[test]
+    fn paste_multiple_numbers() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.handle_event(Event::Paste("1\r\n2\t3\n".into()));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        app.add_extra("+")?;
+        assert_eq!(render_row(&mut app, 8, 1)?, "             1     2");
+        assert_eq!(render_row(&mut app, 8, 2)?, "             5     1");
+        // A paste on one line is undone at once.
+        let before = app.plain_values();
+        app.handle_event(Event::Paste("7\t8 9".into()));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert_eq!(app.plain_values().len(), 5);
+        app.submit("u")?;
+        assert_eq!(app.plain_values(), before);
+        Ok(())
+    }
+
+    #[test]
+    fn toggle_sexagesimal_display() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("1:30:15 T")?;
+        assert_eq!(render(app)?, "       1:30:15     1");
+        Ok(())
+    }
+
+    #[test]
+    fn toggle_si_display() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("4.7k 22u S")?;
+        assert_eq!(render_row(&mut app, 8, 1)?, "          4.7k     2");
+        assert_eq!(render_row(&mut app, 8, 2)?, "           22µ     1");
+        // Durations replace SI prefixes, and the other way around.
+        app.add_extra("T")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "0:00:00.000022     1");
+        Ok(())
+    }
+
+    #[test]
+    fn toggle_ip_display() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("10.1.2.3 24 :network ")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "     167838208     1");
+        app.add_extra("I")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "      10.1.2.0     1");
+        app.add_extra("2001:db8:1::7 32 :network ")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "    2001:db8::     1");
+        Ok(())
+    }
+
+    #[test]
+    fn toggle_format_display() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("1234.5 ")?;
+        assert_eq!(
+            app.handle_key(KeyEvent::new(KeyCode::Char('F'), KeyModifiers::NONE)),
+            Err(AppError::NoFormat)
+        );
+        let config = Config {
+            format: Some(r#"#,##0.00 "€""#.into()),
+            ..Default::default()
+        };
+        let mut app = app.with_config(config);
+        app.add_extra("F")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "    1,234.50 €     1");
+        app.add_extra("F")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "        1234.5     1");
+        Ok(())
+    }
+
+    #[test]
+    fn copy_the_stack() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit("1 2 3 /")?;
+        app.submit(":copy")?;
+        assert_eq!(app.clipboard.as_deref(), Some("1\n0.666666666666"));
+        app.submit(":export md rent")?;
+        assert_eq!(
+            app.clipboard.as_deref(),
+            Some("| Label |          Value |\n| ----- | -------------: |\n| rent  |              1 |\n|       | 0.666666666666 |\n")
+        );
+        assert!(app.submit(":export html").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn report_command() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit("1 2")?;
+        app.add_extra("+")?;
+        let path = std::env::temp_dir().join(format!("hc-report-{}", std::process::id()));
+        app.submit(&format!(":report {}", path.display()))?;
+        let path = path.with_extension("md");
+        let report = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(path)?;
+        assert!(report.starts_with("# hc-report-"));
+        assert!(report.contains("## Tape\n\n    1\n    2\n    :add\n"));
+        assert!(report.ends_with("## Stack\n\n|   # | Value |\n| --: | ----: |\n|   1 |     3 |\n"));
+
+        // Not with an encrypted state, which it would give away.
+        let mut app = App::new(State::default())?.with_encrypted();
+        app.submit("1")?;
+        let path = std::env::temp_dir().join(format!("hc-secret-{}", std::process::id()));
+        assert!(app.submit(&format!(":report {}", path.display())).is_err());
+        assert!(!path.with_extension("md").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn backspace_drops_when_input_is_empty() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let backspace = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+        app.add_extra("1 23")?;
+        // The first one edits the input, the second one drops 2.
+        app.handle_key(backspace)?;
+        app.add_extra(" ")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "             2     1");
+        app.handle_key(backspace)?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "             1     1");
+        app.add_extra("u")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "             2     1");
+        Ok(())
+    }
+
+    struct Negate(char);
+
+    impl Operation for Negate {
+        fn name(&self) -> &str {
+            "neg"
+        }
+
+        fn key(&self) -> Option<char> {
+            Some(self.0)
+        }
+
+        fn description(&self) -> &str {
+            "negate S1"
+        }
+
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn apply(&self, args: &[BigDecimal]) -> Result<Vec<BigDecimal>, StackError> {
+            Ok(vec![-&args[0]])
+        }
+    }
+
+    #[test]
+    fn plugins_bind_free_keys() -> anyhow::Result<()> {
+        let mut registry = Registry::default();
+        registry.register(Negate('N'));
+        let mut app = App::new(State::default())?.with_plugins(&registry);
+        app.add_extra("2 N")?;
+        assert_eq!(app.stack().top(), Some(&BigDecimal::from(-2)));
+
+        // Built-in keys win.
+        let mut registry = Registry::default();
+        registry.register(Negate('d'));
+        let mut app = App::new(State::default())?.with_plugins(&registry);
+        app.add_extra("2 d")?;
+        assert_eq!(app.stack().snapshot().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn plugins_run_as_commands() -> anyhow::Result<()> {
+        let mut registry = Registry::default();
+        registry.register(Negate('N'));
+        let mut app = App::new(State::default())?.with_plugins(&registry);
+        app.add_extra("2 :neg ")?;
+        assert_eq!(app.stack().top(), Some(&BigDecimal::from(-2)));
+        assert!(app.input.is_empty());
+
+        app.add_extra(":nope")?;
+        assert_eq!(
+            app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+            Err(AppError::UnknownCommand("nope".into()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn solve_command() -> anyhow::Result<()> {
+        let mut registry = Registry::default();
+        registry.register(Negate('N'));
+        let mut app = App::new(State::default())?.with_plugins(&registry);
+        app.add_extra("5 :solve neg ")?;
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(0)]);
+        app.add_extra(":solve nope")?;
+        assert_eq!(
+            app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+            Err(AppError::UnknownCommand("nope".into()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn search_the_stack() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("3 120 7 12 5 ")?;
+        ctrl(&mut app, 'f')?;
+        app.add_extra("12")?;
+        assert_eq!(app.search.as_ref().unwrap().matches, vec![1, 3]);
+        assert_eq!(app.search.as_ref().unwrap().current, Some(1));
+        // Typing goes to the search, not to the input.
+        assert!(app.input.is_empty());
+
+        // The selection scrolls into view.
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert_eq!(app.search.as_ref().unwrap().current, Some(3));
+        assert!(render_row(&mut app, 8, 1)?.contains("120"));
+        assert!(render_row(&mut app, 8, 6)?.contains("2 matches"));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert_eq!(app.search.as_ref().unwrap().current, Some(1));
+
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))?;
+        assert!(app.search.is_none());
+        assert!(!app.exit);
+        Ok(())
+    }
+
+    #[test]
+    fn palette_finds_and_runs_operations() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit("2")?;
+        ctrl(&mut app, 'g')?;
+        app.add_extra("square")?;
+        assert!(app.render_to_string(80, 24).contains(":sqrt v : "));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert!(!app.palette.is_visible());
+        assert_eq!(app.plain_values(), ["1.414213562373"]);
+
+        ctrl(&mut app, 'g')?;
+        app.add_extra("fib")?;
+        app.handle_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE))?;
+        assert_eq!(app.input.text(), ":fib");
+        Ok(())
+    }
+
+    #[test]
+    fn history_replays_across_sessions() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("16 v2+3 :swap ")?;
+        assert_eq!(app.history.entries(), ["16", ":sqrt", "2+3", ":swap"]);
+        // A failed input isn't recorded.
+        assert!(app.add_extra(":nope ").is_err());
+        app.input.reset();
+
+        let mut app = App::new(app.state())?;
+        assert_eq!(app.history.entries().len(), 4);
+        // The newest entry matching the filter runs again.
+        ctrl(&mut app, 'o')?;
+        app.add_extra("sw")?;
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert!(!app.history.is_visible());
+        assert_eq!(
+            app.stack().snapshot(),
+            vec![BigDecimal::from(5), BigDecimal::from(4)]
+        );
+        assert_eq!(app.history.entries().last().unwrap(), ":swap");
+        Ok(())
+    }
+
+    #[test]
+    fn notice_waits_for_acknowledgement() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?.with_notice("state lost".into());
+        assert!(render_row(&mut app, 12, 3)?.contains("state lost"));
+        app.add_extra("1 ")?;
+        assert!(app.input.is_empty() && app.stack().snapshot().is_empty());
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        app.add_extra("1 ")?;
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(1)]);
+
+        let mut app = App::new(State::default())?.with_notice("state lost".into());
+        app.add_extra("q")?;
+        assert!(app.exit);
+        Ok(())
+    }
+
+    #[test]
+    fn reduce_piped_numbers() -> anyhow::Result<()> {
+        let (sender, lines) = std::sync::mpsc::channel();
+        let mut app = App::new(State::default())?.with_reduce("+", lines)?;
+        sender.send("1".into())?;
+        sender.send("2 3".into())?;
+        assert!(app.receive());
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(6)]);
+        assert!(!app.receive());
+
+        // Lines that aren't numbers are reported and skipped.
+        sender.send("x".into())?;
+        sender.send("4".into())?;
+        drop(sender);
+        assert!(app.receive());
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(10)]);
+        assert!(app.op_status.is_err());
+        assert!(app.feed.as_ref().unwrap().lines.is_none());
+
+        // Operations also go by name.
+        let (_, lines) = std::sync::mpsc::channel();
+        assert!(App::new(State::default())?
+            .with_reduce("mul", lines)
+            .is_ok());
+        let (_, lines) = std::sync::mpsc::channel();
+        assert!(App::new(State::default())?
+            .with_reduce("nope", lines)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cheat_sheet_lists_bound_keys() -> anyhow::Result<()> {
+        let mut registry = Registry::default();
+        registry.register(Negate('N'));
+        let mut app = App::new(State::default())?.with_plugins(&registry);
+        let entries = app.key_entries();
+        for (key, name) in [("+", "add"), ("v", "sqrt"), ("u", "undo"), ("N", "neg")] {
+            assert!(entries.contains(&(key.into(), name.into())), "{key}");
+        }
+        ctrl(&mut app, 'k')?;
+        assert!(app.keys);
+        app.add_extra("1")?;
+        assert!(!app.keys && app.input.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn reload_the_saves_of_other_instances() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("hc-watch-{}", std::process::id()));
+        let storage = FileStorage::new(dir.join("state.json"));
+        let mut app = App::new(State::default())?;
+        assert!(app.submit(":reload").is_err());
+        app.input.reset();
+        let mut app = app.with_watch(FileStorage::new(storage.path().to_owned()));
+        assert_eq!(app.synced(), Some(None));
+        app.submit("1")?;
+        assert!(!app.check_watch());
+
+        storage.save(&State {
+            stack: vec!["42".into()],
+            ..Default::default()
+        })?;
+        assert!(app.check_watch());
+        assert!(matches!(app.op_status, Err(AppError::Saved)));
+        // Noticed once.
+        assert!(!app.check_watch());
+        assert_eq!(app.plain_values(), ["1"]);
+        app.submit(":reload")?;
+        assert_eq!(app.plain_values(), ["42"]);
+        assert_eq!(app.synced(), Some(storage.modified()));
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn remote_control() -> anyhow::Result<()> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut app = App::new(State::default())?.with_remote(rx);
+        let mut replies = vec![];
+        for line in [
+            "push 9 4k",
+            "op v",
+            "op swap",
+            "stack",
+            "top",
+            "op nope",
+            "pop",
+        ] {
+            let (reply, reply_rx) = std::sync::mpsc::channel();
+            tx.send(Request {
+                line: line.into(),
+                reply,
+            })?;
+            replies.push(reply_rx);
+        }
+        assert!(app.serve());
+        let replies: Vec<String> = replies.iter().map(|r| r.recv().unwrap()).collect();
+        assert_eq!(
+            replies,
+            [
+                "ok",
+                "ok",
+                "ok",
+                "63.245553203367 9",
+                "9",
+                "error: unknown command 'nope'",
+                "error: unknown command 'pop'"
+            ]
+        );
+        assert!(!app.serve());
+        assert_eq!(app.history.entries(), ["9 4k", ":sqrt", ":swap"]);
+        Ok(())
+    }
+
+    #[test]
+    fn remote_control_waits_for_slow_operations() -> anyhow::Result<()> {
+        let mut registry = Registry::default();
+        registry.register(Slow);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut app = App::new(State::default())?
+            .with_plugins(&registry)
+            .with_remote(rx);
+        let mut replies = vec![];
+        for line in ["op slow", "push 2", "op slow"] {
+            let (reply, reply_rx) = std::sync::mpsc::channel();
+            tx.send(Request {
+                line: line.into(),
+                reply,
+            })?;
+            replies.push(reply_rx);
+        }
+        assert!(app.serve());
+        assert!(app.job.is_some());
+        assert!(replies[0].try_recv().is_err());
+        while app.job.is_some() {
+            app.wait(TICK)?;
+        }
+        assert_eq!(replies[0].recv()?, "ok");
+        app.serve();
+        assert_eq!(replies[1].recv()?, "ok");
+        assert!(app.handle_event(Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))));
+        assert_eq!(replies[2].recv()?, "error: cancelled");
+        assert_eq!(app.plain_values(), ["2", "1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn binding_conflicts() -> anyhow::Result<()> {
+        assert!(App::new(State::default())?.conflicts().is_empty());
+        let mut registry = Registry::default();
+        registry.register(Negate('+'));
+        let config = Config {
+            keys: [
+                ('r', "sqrt"),
+                ('o', "sqrt"),
+                ('5', "neg"),
+                ('I', "neg"),
+                ('v', "sqrt"),
+                ('x', "nope"),
+            ]
+            .map(|(key, name)| (key, name.to_owned()))
+            .into(),
+            chords: [("C-k", "dup"), ("A-k", "dup"), ("C-y", "dup")]
+                .map(|(chord, name)| (chord.to_owned(), name.to_owned()))
+                .into(),
+            ..Default::default()
+        };
+        let mut app = App::new(State::default())?
+            .with_plugins(&registry)
+            .with_config(config);
+        assert_eq!(
+            app.conflicts(),
+            [
+                "key + of :neg is taken by :add",
+                "key 5 for :neg is used by the interface",
+                "key I for :neg is used by the interface",
+                "key o for :sqrt replaces :base",
+                "key r for :sqrt replaces :swap",
+                "key x is for unknown :nope",
+                "chord C-k for :dup is used by the interface",
+                "chord C-y for :dup is used by the interface",
+            ]
+        );
+        // Aliases: the operation keeps its own key.
+        app.feed_keys("16<Enter>r81<Enter>o2<Enter>v")?;
+        assert_eq!(app.plain_values(), ["1.414213562373", "9", "4"]);
+        Ok(())
+    }
+
+    #[test]
+    fn chords_run_operations() -> anyhow::Result<()> {
+        let config = Config {
+            chords: [("A-s", "sqrt"), ("C-d", "dup"), ("A-x", "nope")]
+                .map(|(chord, name)| (chord.to_owned(), name.to_owned()))
+                .into(),
+            ..Default::default()
+        };
+        let mut app = App::new(State::default())?.with_config(config);
+        app.feed_keys("16<Enter><A-s>9<A-s><C-d>")?;
+        assert_eq!(app.plain_values(), ["3", "3", "4"]);
+        assert_eq!(
+            app.history.session().last().map(String::as_str),
+            Some(":dup")
+        );
+        assert!(app.feed_keys("<A-x>").is_err());
+        assert!(app.key_entries().contains(&("A-s".into(), "sqrt".into())));
+        Ok(())
+    }
+
+    #[test]
+    fn angle_mode_in_status() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let status = |app: &App| {
+            let mut buf = Buffer::empty(Rect::new(0, 0, 70, 1));
+            app.render_precision_base().render(buf.area, &mut buf);
+            (0..70).map(|x| buf[(x, 0)].symbol()).collect::<String>()
+        };
+        assert!(status(&app).trim_end().ends_with("| rad"));
+        app.submit(":grad")?;
+        assert!(status(&app).trim_end().ends_with("| grad"));
+        let app = app.with_read_only();
+        assert!(status(&app)
+            .trim_start()
+            .starts_with("Read-only | Precision"));
+        Ok(())
+    }
+
+    #[test]
+    fn capped_undo_history() -> anyhow::Result<()> {
+        let config = Config {
+            max_undo_steps: Some(2),
+            ..Default::default()
+        };
+        let mut app = App::new(State::default())?.with_config(config);
+        let status = |app: &App| {
+            let mut buf = Buffer::empty(Rect::new(0, 0, 80, 1));
+            app.render_precision_base().render(buf.area, &mut buf);
+            (0..80).map(|x| buf[(x, 0)].symbol()).collect::<String>()
+        };
+        app.submit("1")?;
+        app.submit("2")?;
+        assert!(!status(&app).contains("Undo capped"));
+        app.submit("3")?;
+        assert!(status(&app).trim_start().starts_with("Undo capped | "));
+        app.submit("u")?;
+        app.submit("u")?;
+        assert!(app.submit("u").is_err());
+        assert_eq!(app.plain_values(), ["1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn pinned_entries() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("3 p1 2 +")?;
+        assert!(render_row(&mut app, 10, 3)?.ends_with("•2"));
+        // The stray + can't eat the pinned 3.
+        assert_eq!(
+            app.add_extra("+").unwrap_err().to_string(),
+            StackError::Pinned.to_string()
+        );
+        let app = App::new(app.state())?;
+        assert!(app.stack().is_pinned(1));
+        Ok(())
+    }
+
+    #[test]
+    fn bookmark_commands() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("2 :bookmark two 3 *")?;
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(6)]);
+        app.submit(":restore two")?;
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(2)]);
+        assert!(app.submit(":restore three").is_err());
+
+        // Only saved when configured to.
+        assert!(app.state().bookmarks.is_empty());
+        let config = Config {
+            save_bookmarks: true,
+            ..Default::default()
+        };
+        let app = app.with_config(config);
+        assert_eq!(app.state().bookmarks["two"].stack, vec!["2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn bookmark_diff_popup() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit("1 2")?;
+        app.submit(":bookmark b")?;
+        app.submit("3 +")?;
+        app.submit(":diff b")?;
+        let mut buf = Buffer::empty(Rect::new(0, 0, 50, 5));
         app.render_all(buf.area, &mut buf);
-        let mut line = String::with_capacity(20);
-        for x in 0..20 {
-            line.push_str(buf[(x, row)].symbol());
+        let rows: Vec<String> = (0..5)
+            .map(|y| (0..50).map(|x| buf[(x, y)].symbol()).collect())
+            .collect();
+        assert!(rows[2].contains("1: 2 → 5"), "{rows:?}");
+        // Any key closes it, without acting.
+        app.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE))?;
+        assert!(app.diff.is_none() && !app.exiting());
+        assert!(app.submit(":diff c").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn hp_mode() -> anyhow::Result<()> {
+        let config = Config {
+            hp_mode: true,
+            ..Default::default()
+        };
+        let mut app = App::new(State::default())?.with_config(config);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        // Enter copies the number, which the next one replaces.
+        app.add_extra("3")?;
+        app.handle_key(enter)?;
+        assert_eq!(app.plain_values(), ["3", "3"]);
+        app.add_extra("4+")?;
+        assert_eq!(app.plain_values(), ["7"]);
+        // Without a new number, the copy is used.
+        app.handle_key(enter)?;
+        app.add_extra("*")?;
+        assert_eq!(app.plain_values(), ["49"]);
+        // Replacing is a single step, and the swap key completes the number.
+        app.add_extra("1")?;
+        app.handle_key(enter)?;
+        app.add_extra("2r")?;
+        assert_eq!(app.plain_values(), ["1", "2", "49"]);
+        app.add_extra("uu")?;
+        assert_eq!(app.plain_values(), ["1", "1", "49"]);
+        // Any other key enables the stack lift again.
+        app.add_extra("r5")?;
+        app.handle_key(enter)?;
+        assert_eq!(app.plain_values(), ["5", "5", "1", "1", "49"]);
+        Ok(())
+    }
+
+    #[test]
+    fn shown_values_follow_changes() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("1234567 ")?;
+        assert_eq!(render_row(&mut app, 7, 1)?, "       1234567     1");
+        assert_eq!(render_row(&mut app, 7, 1)?, "       1234567     1");
+        app.add_extra("'")?;
+        assert_eq!(render_row(&mut app, 7, 1)?, "     1 234 567     1");
+        app.add_extra("2 *")?;
+        assert_eq!(render_row(&mut app, 7, 1)?, "     2 469 134     1");
+        Ok(())
+    }
+
+    #[test]
+    fn live_result() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("(2+3)*")?;
+        assert_eq!(render_row(&mut app, 10, 7)?, "└───────────── = 5 ┘");
+        app.add_extra("4")?;
+        assert_eq!(render_row(&mut app, 10, 7)?, "└──────────── = 20 ┘");
+        // Numbers alone have nothing to show.
+        app.add_extra("=12")?;
+        assert_eq!(render_row(&mut app, 10, 7)?, "└──────────────────┘");
+        Ok(())
+    }
+
+    #[test]
+    fn preview_mode() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        ctrl(&mut app, 'p')?;
+        app.add_extra("6 3/")?;
+        // Nothing happens until confirmed.
+        assert_eq!(app.plain_values(), ["6"]);
+        assert_eq!(app.input.text(), "3");
+        assert!(render_row(&mut app, 10, 8)?.starts_with("</> = 2,"));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert_eq!(app.plain_values(), ["2"]);
+        // Esc or another key dismisses it, and pressing the key again runs it.
+        app.add_extra("v")?;
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))?;
+        app.add_extra("d")?;
+        assert!(render_row(&mut app, 10, 8)?.starts_with("<d> = 2,"));
+        app.add_extra("d+")?;
+        assert!(render_row(&mut app, 10, 8)?.starts_with("<+> = 4,"));
+        app.add_extra("1")?;
+        assert_eq!(app.plain_values(), ["2", "2"]);
+        assert_eq!(app.input.text(), "1");
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_lines() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let alt_enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT);
+        app.add_extra("1")?;
+        app.handle_key(alt_enter)?;
+        app.add_extra("2 + 3")?;
+        app.handle_key(alt_enter)?;
+        app.add_extra("*")?;
+        // Nothing runs before the whole block is committed.
+        assert!(app.stack().snapshot().is_empty());
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(9)]);
+        assert!(app.input.is_empty());
+
+        // The failing line and the next ones are left in the input.
+        app.handle_event(Event::Paste("2 *\n0 /\n1 +\n".into()));
+        assert_eq!(
+            app.input_consume().unwrap_err().to_string(),
+            "line 2: element 1 must be non-zero (^z to roll back)"
+        );
+        assert_eq!(app.input.take_lines(), ["0 /", "1 +"]);
+        assert_eq!(
+            app.stack().snapshot(),
+            vec![BigDecimal::from(0), BigDecimal::from(18)]
+        );
+        ctrl(&mut app, 'z')?;
+        assert_eq!(app.stack().snapshot(), vec![BigDecimal::from(9)]);
+        assert_eq!(app.input.take_lines(), ["2 *", "0 /", "1 +"]);
+        Ok(())
+    }
+
+    #[test]
+    fn dc_command() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit("5")?;
+        app.handle_event(Event::Paste(":dc [d1-d1<a]sa lax".into()));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+        assert_eq!(app.plain_values(), ["1", "2", "3", "4", "5"]);
+        app.submit("u")?;
+        assert_eq!(app.plain_values(), ["5"]);
+        Ok(())
+    }
+
+    #[test]
+    fn dice_notation() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("2d6+100 ")?;
+        let total = app.stack().top().unwrap();
+        assert!((BigDecimal::from(102)..=BigDecimal::from(112)).contains(total));
+        Ok(())
+    }
+
+    struct Slow;
+
+    impl Operation for Slow {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn description(&self) -> &str {
+            "take a while"
+        }
+
+        fn arity(&self) -> usize {
+            0
+        }
+
+        fn apply(&self, _: &[BigDecimal]) -> Result<Vec<BigDecimal>, StackError> {
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(vec![BigDecimal::from(1)])
         }
-        Ok(line)
+    }
+
+    #[test]
+    fn redraw_only_on_change() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let mut key = KeyEvent::new(KeyCode::Char('1'), KeyModifiers::NONE);
+        assert!(app.handle_event(Event::Key(key)));
+        key.kind = KeyEventKind::Release;
+        assert!(!app.handle_event(Event::Key(key)));
+        assert!(!app.handle_event(Event::FocusLost));
+        assert!(app.handle_event(Event::Resize(80, 24)));
+        Ok(())
+    }
+
+    #[test]
+    fn help_scrolls_with_the_wheel() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let wheel = |kind| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column: 0,
+                row: 0,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        // Ignored without the help.
+        assert!(!app.handle_event(wheel(MouseEventKind::ScrollDown)));
+        app.add_extra("?")?;
+        let top = app.render_to_string(80, 24);
+        assert!(app.handle_event(wheel(MouseEventKind::ScrollDown)));
+        assert_ne!(app.render_to_string(80, 24), top);
+        assert!(app.handle_event(wheel(MouseEventKind::ScrollUp)));
+        assert_eq!(app.render_to_string(80, 24), top);
+        assert!(!app.handle_event(wheel(MouseEventKind::Moved)));
+        Ok(())
+    }
+
+    #[test]
+    fn histogram_of_the_values() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit("1 2 3 4 5 6 7 8 10")?;
+        ctrl(&mut app, 'b')?;
+        let screen = app.render_to_string(50, 24);
+        assert!(screen.contains("Histogram"), "{screen}");
+        // From 4 to 7, 3 values.
+        assert!(screen.contains("│         4  3█"), "{screen}");
+        // Counted again once the stack changes.
+        app.submit("5")?;
+        let screen = app.render_to_string(50, 24);
+        assert!(screen.contains("│      3.25  3█"), "{screen}");
+        ctrl(&mut app, 'b')?;
+        assert!(!app.render_to_string(50, 24).contains("Histogram"));
+        Ok(())
+    }
+
+    #[test]
+    fn twos_complement_words() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit("_1 32 :word 16 :base")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "      ffffffff     1");
+        assert!(app.render_to_string(50, 10).contains("Base: 16/32"));
+        // Signed in decimal.
+        app.submit("10 :base")?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "            -1     1");
+        Ok(())
+    }
+
+    #[test]
+    fn bits_of_the_top() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        assert!(ctrl(&mut app, 'e').is_err());
+        app.submit("1.5")?;
+        assert_eq!(
+            ctrl(&mut app, 'e'),
+            Err(AppError::Bits(BitsError::NotInteger))
+        );
+        app.submit("160")?;
+        ctrl(&mut app, 'e')?;
+        assert!(app
+            .render_to_string(50, 10)
+            .contains("  7-0   10100000  a0"));
+        // Any key closes it.
+        app.add_extra("x")?;
+        assert!(!app.render_to_string(50, 10).contains("bits of S1"));
+        Ok(())
+    }
+
+    #[test]
+    fn watch_with_a_macro() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.submit(":macro sum :cumsum<Enter>")?;
+        app.submit("1 2 3")?;
+        assert!(app.submit(":watch nope").is_err());
+        app.submit(":watch sum")?;
+        let history = app.history.entries().len();
+        assert!(app.render_to_string(50, 10).contains(":sum = 6"));
+        // The stack and the history are left alone.
+        assert_eq!(app.plain_values(), ["3", "2", "1"]);
+        assert_eq!(app.history.entries().len(), history);
+        app.submit("4")?;
+        assert!(app.render_to_string(50, 10).contains(":sum = 10"));
+        app.submit("c")?;
+        assert!(app.render_to_string(50, 10).contains(":sum: "));
+        app.submit(":watch")?;
+        assert!(!app.render_to_string(50, 10).contains(":sum"));
+
+        // Macros acting on the interface can't be watched.
+        app.macros.insert("halt".into(), "2<Enter>q".into());
+        app.macros.insert("tab".into(), "<C-t>1<Enter>".into());
+        let err = app.submit(":watch halt").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "q acts on the interface, it can't be watched"
+        );
+        assert!(app.submit(":watch tab").is_err());
+        assert!(!app.exiting() && app.tabs.len() == 1);
+        // Those running other macros and operations by name can.
+        app.submit(":macro twice 2<Enter>*")?;
+        app.submit(":macro four :twice<Enter>:twice<Enter>")?;
+        app.submit("5")?;
+        app.submit(":watch four")?;
+        assert!(app.render_to_string(50, 10).contains(":four = 20"));
+        Ok(())
+    }
+
+    #[test]
+    fn record_and_play_macros() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let typed = |app: &mut App, keys: &str| {
+            for k in parse_keys(keys).unwrap() {
+                app.handle_event(Event::Key(k));
+            }
+        };
+        typed(&mut app, "120<Enter>:record net<Enter>1.2<Enter>/<C-q>");
+        assert_eq!(app.macros["net"], "1.2<Enter>/");
+        typed(&mut app, "240<Enter>:net<Enter>");
+        assert_eq!(app.plain_values(), ["200", "100"]);
+        assert_eq!(ctrl(&mut app, 'q'), Err(AppError::NotRecording));
+
+        // Kept with the state.
+        let mut app = App::new(app.state())?;
+        app.submit("60")?;
+        app.submit(":net")?;
+        assert_eq!(app.plain_values(), ["50", "200", "100"]);
+        // Edited from the palette.
+        ctrl(&mut app, 'g')?;
+        app.feed_keys("net<Tab>")?;
+        assert_eq!(app.input.text(), ":macro net 1.2<Enter>/");
+        app.input.reset();
+        app.submit(":macro half 2<Enter>/")?;
+        app.submit(":half")?;
+        assert_eq!(app.plain_values(), ["25", "200", "100"]);
+        assert!(app.submit(":macro sin 2").is_err());
+        assert!(app.submit(":macro bad <Nope>").is_err());
+        app.submit(":macro loop :loop<Enter>")?;
+        assert!(app.submit(":loop").is_err());
+        app.submit(":macro half")?;
+        assert!(!app.macros.contains_key("half"));
+        Ok(())
+    }
+
+    #[test]
+    fn startup_file() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.run_init("# Preferences\n20 k\n\n  3.14159 sp\n'\n")?;
+        assert_eq!(app.stack().precision(), 20);
+        assert_eq!(app.stack().registers()[&'p'].to_string(), "3.14159");
+        assert!(app.separator);
+        assert!(app.history.entries().is_empty());
+        let err = app.run_init("1 2 +\n:nope").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: unknown command 'nope'");
+        assert_eq!(app.plain_values(), ["3"]);
+        Ok(())
+    }
+
+    #[test]
+    fn ages_of_the_entries() -> anyhow::Result<()> {
+        assert_eq!(age(100, 159), "59s ago");
+        assert_eq!(age(100, 230), "2m ago");
+        assert_eq!(age(0, 7200), "2h ago");
+        assert_eq!(age(0, 200_000), "2d ago");
+        let mut app = App::new(State::default())?;
+        app.submit("42")?;
+        ctrl(&mut app, 'a')?;
+        let row = render_row(&mut app, 8, 2)?;
+        assert!(row.contains("s ago     1") && row.contains("42"), "{row}");
+        ctrl(&mut app, 'a')?;
+        assert!(!render_row(&mut app, 8, 2)?.contains("ago"));
+        Ok(())
+    }
+
+    #[test]
+    fn origins_of_the_entries() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("12.5 3 * 7 ")?;
+        ctrl(&mut app, 'y')?;
+        let text = app.render_to_string(40, 8);
+        let rows: Vec<&str> = text.lines().collect();
+        assert!(
+            rows[1].contains("= 12.5 3 mul") && rows[1].contains("37.5"),
+            "{text}"
+        );
+        assert!(!rows[2].contains('='), "{text}");
+        ctrl(&mut app, 'y')?;
+        assert!(!app.render_to_string(40, 8).contains('='));
+
+        // The origin of the selected match is shown too.
+        ctrl(&mut app, 'f')?;
+        app.add_extra("37")?;
+        let status = app.render_to_string(60, 8);
+        assert!(status.contains("to close)  = 12.5 3 mul"), "{status}");
+        Ok(())
+    }
+
+    #[test]
+    fn keypad_keys() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let keypad = |code, modifiers| {
+            Event::Key(KeyEvent::new_with_kind_and_state(
+                code,
+                modifiers,
+                KeyEventKind::Press,
+                KeyEventState::KEYPAD,
+            ))
+        };
+        for c in "1,5\r2\n".chars() {
+            app.handle_event(keypad(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        app.handle_event(keypad(KeyCode::Char('*'), KeyModifiers::NONE));
+        assert_eq!(app.plain_values(), ["3"]);
+        app.handle_event(keypad(KeyCode::Char('4'), KeyModifiers::NONE));
+        app.handle_event(keypad(KeyCode::Char('j'), KeyModifiers::CONTROL));
+        app.handle_event(keypad(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(app.plain_values(), ["7"]);
+        // Elsewhere, the comma is not a decimal point.
+        app.handle_event(Event::Key(KeyEvent::new(
+            KeyCode::Char(','),
+            KeyModifiers::NONE,
+        )));
+        assert_eq!(app.input.text(), ",");
+        Ok(())
+    }
+
+    #[test]
+    fn slow_operations_can_be_cancelled() -> anyhow::Result<()> {
+        let mut registry = Registry::default();
+        registry.register(Slow);
+        let mut app = App::new(State::default())?.with_plugins(&registry);
+        app.input = app.input.clone().with_value(":slow".into());
+        app.handle_event(Event::Key(KeyEvent::new(
+            KeyCode::Enter,
+            KeyModifiers::NONE,
+        )));
+        assert!(app.job.is_some());
+        assert!(render_row(&mut app, 8, 6)?.contains("computing"));
+        // Keys are ignored, except for Esc.
+        assert!(!app.handle_event(Event::Key(KeyEvent::new(
+            KeyCode::Char('q'),
+            KeyModifiers::NONE,
+        ))));
+        assert!(!app.exit);
+        assert!(app.handle_event(Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))));
+        assert!(app.job.is_none());
+        // As the main loop does next.
+        app.check_job();
+        assert_eq!(app.op_status, Err(AppError::Cancelled));
+        assert!(app.stack().snapshot().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn tiny_terminals_dont_panic() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("1 sa 2 sb 123456789 _1.5")?;
+        ctrl(&mut app, 't')?;
+        app.add_extra("42 ")?;
+        for width in 0..25 {
+            for height in 0..12 {
+                let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+                app.render_all(buf.area, &mut buf);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tiny_terminals_keep_the_stack() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("42 7")?;
+        // height=5 → no instructions, single-line input, 2 rows of stack.
+        assert_eq!(render_row(&mut app, 5, 1)?, "            42     1");
+        assert_eq!(render_row(&mut app, 5, 2)?, "7                   ");
+        Ok(())
+    }
+
+    #[test]
+    fn title_shows_top_of_stack() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        assert_eq!(app.title(), "hc — 1");
+        app.add_extra("255 16 o")?;
+        assert_eq!(app.title(), "hc — ff");
+        Ok(())
+    }
+
+    #[test]
+    fn tabs_have_independent_stacks() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("1 ")?;
+        ctrl(&mut app, 't')?;
+        app.add_extra("2 3 +")?;
+        // height=8 → stack_area=2 rows, the first one holding the tab bar.
+        assert_eq!(render_row(&mut app, 8, 1)?, " 1 │ 2              ");
+        assert_eq!(render_row(&mut app, 8, 2)?, "             5     1");
+        app.handle_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE))?;
+        assert_eq!(render_row(&mut app, 8, 2)?, "             1     1");
+        Ok(())
+    }
+
+    #[test]
+    fn tabs_rename_and_close() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        ctrl(&mut app, 't')?;
+        app.add_extra("work")?;
+        ctrl(&mut app, 'r')?;
+        assert_eq!(render_row(&mut app, 8, 1)?, " 1 │ work           ");
+        ctrl(&mut app, 'w')?;
+        assert_eq!(ctrl(&mut app, 'w'), Err(AppError::LastTab));
+        Ok(())
+    }
+
+    #[test]
+    fn tabs_are_saved() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        app.add_extra("1 ")?;
+        ctrl(&mut app, 't')?;
+        app.add_extra("2 ")?;
+        let state = app.state();
+        assert_eq!(state.tabs.len(), 1);
+        assert_eq!(state.active().stack, vec!["2"]);
+        let app = App::new(state)?;
+        assert_eq!(app.tab, 1);
+        assert_eq!(
+            app.tabs[0].stack.snapshot(),
+            vec![bigdecimal::BigDecimal::from(1)]
+        );
+        Ok(())
     }
 
     #[test]
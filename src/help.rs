@@ -1,5 +1,7 @@
 //! Help popup implementation.
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use hc_core::ops::{self, Operation};
+use hc_core::plugin::Registry;
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Constraint, Flex, Layout, Rect},
@@ -10,6 +12,14 @@ use ratatui::{
         Widget, Wrap,
     },
 };
+use std::sync::Arc;
+
+/// Lines scrolled by a notch of the mouse wheel.
+const WHEEL_LINES: usize = 3;
+
+/// The smallest popup, unless the terminal is even smaller.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
 
 /// The stateful Help widget.
 #[derive(Default)]
@@ -17,6 +27,8 @@ pub struct Help {}
 
 /// State for the Help widget (scrolling, visibility)
 pub struct HelpState {
+    // The operations of the plugins, listed after the built-in ones.
+    plugins: Vec<Arc<dyn Operation>>,
     content: Text<'static>,
     visible: bool,
     vs_state: ScrollbarState,
@@ -41,6 +53,16 @@ impl HelpState {
         }
     }
 
+    /// Scroll with the mouse wheel. Returns whether the event was one.
+    pub fn handle_mouse(&mut self, m: MouseEvent) -> bool {
+        match m.kind {
+            MouseEventKind::ScrollUp => (0..WHEEL_LINES).for_each(|_| self.vs_state.prev()),
+            MouseEventKind::ScrollDown => (0..WHEEL_LINES).for_each(|_| self.vs_state.next()),
+            _ => return false,
+        }
+        true
+    }
+
     pub fn is_visible(&self) -> bool {
         self.visible
     }
@@ -50,57 +72,86 @@ impl HelpState {
     }
 }
 
-/// Generate the full help text.
-fn help() -> Text<'static> {
-    let lines: Vec<Line> = vec![
+/// Generate the full help text, with the operations under the keys bound
+/// to them, given as (key, operation name).
+fn help(plugins: &[Arc<dyn Operation>], keys: &[(String, String)]) -> Text<'static> {
+    let mut lines: Vec<Line> = vec![
         Line::from("Helix Calc is a Reverse Polish Notation calculator."),
         Line::from(""),
-        Line::from("Operators manipulate the stack of values [S1, S2, ...]:"),
+        Line::from(vec![
+            Span::raw("Operators manipulate the stack of values [S1, S2, ...], and also run by name, e.g. "),
+            ":sqrt".blue(),
+            Span::raw(":"),
+        ]),
         Line::from(""),
+    ];
+    lines.extend(ops::builtins().map(|op| operation(op.as_ref(), keys)));
+    lines.extend(vec![
         Line::from(vec![
             Span::raw("  "),
-            "+ - * /".blue(),
-            Span::raw(" : perform the arithmetic operation on S2 and S1."),
+            ":solve NAME".blue(),
+            Span::raw(" : root near S1 of the operation NAME, computing f(x) from x in S1."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "%".blue(),
-            Span::raw(" : compute the modulo of S2 divided by S1."),
+            ":bookmark NAME".blue(),
+            Span::raw(" / "),
+            ":restore NAME".blue(),
+            Span::raw(" : save the stack under NAME / go back to it."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "^".blue(),
-            Span::raw(" : raise S2 to the power of S1."),
+            ":diff NAME".blue(),
+            Span::raw(" : entries added, removed or changed since the bookmark NAME."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "P".blue(),
-            Span::raw(" : pop S1 off the stack."),
+            ":copy".blue(),
+            Span::raw(" : copy the whole stack to the clipboard, one value per line."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "d".blue(),
-            Span::raw(" : duplicate S1."),
+            ":export markdown|latex [LABEL...]".blue(),
+            Span::raw(" : copy the stack as a table, with the labels from the bottom."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "v".blue(),
-            Span::raw(" : compute the square root of S1."),
+            ":report NAME".blue(),
+            Span::raw(" : write the inputs of the session and the stack to NAME.md."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "k".blue(),
-            Span::raw(" : pop S1 and use it to set the precision."),
+            ":reload".blue(),
+            Span::raw(" : with --watch, load the state saved by another instance."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "o".blue(),
-            Span::raw(" : pop S1 and use it to set the output base (2–36)."),
+            ":record NAME".blue(),
+            Span::raw(" : record the keys typed next as the macro NAME, until "),
+            "[Ctrl-q]".blue(),
+            Span::raw(", run with "),
+            ":NAME".blue(),
+            Span::raw("."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "r".blue(),
-            Span::raw(" : swap S1 and S2."),
+            ":macro NAME [KEYS]".blue(),
+            Span::raw(" : define the macro NAME as the keys, or delete it without."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            ":watch [NAME]".blue(),
+            Span::raw(" : show what the macro NAME leaves on top as the stack changes."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            ":dc PROGRAM".blue(),
+            Span::raw(" : run the GNU dc program on the stack."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Backspace]".blue(),
+            Span::raw(" on an empty input : pop S1 off the stack."),
         ]),
         Line::from(vec![
             Span::raw("  "),
@@ -124,40 +175,128 @@ fn help() -> Text<'static> {
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "c".blue(),
-            Span::raw(" : clear the stack."),
+            "'".blue(),
+            Span::raw(" : toggle the decimal separator."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "C".blue(),
-            Span::raw(" : clear the registers."),
+            "T".blue(),
+            Span::raw(" : toggle showing numbers as H:MM:SS durations."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "n".blue(),
-            Span::raw(" : reset precision and output base."),
+            "S".blue(),
+            Span::raw(" : toggle showing numbers with SI prefixes, e.g. 4.7k."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "y".blue(),
-            Span::raw(" : rotate stack forward (S1→S2→S3→…→S1)."),
+            "I".blue(),
+            Span::raw(" : toggle showing numbers as IP addresses."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "Y".blue(),
-            Span::raw(" : rotate stack backward (S1→…→S3→S2→S1)."),
+            "F".blue(),
+            Span::raw(" : toggle showing numbers with the format of the configuration."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "'".blue(),
-            Span::raw(" : toggle the decimal separator."),
+            "[Up]".blue(),
+            Span::raw(" : edit S1."),
         ]),
         Line::from(vec![
             Span::raw("  "),
-            "[Up]".blue(),
-            Span::raw(" : edit S1."),
+            "[Esc]".blue(),
+            Span::raw(" while an operation is computing : cancel it."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-f]".blue(),
+            Span::raw(" : search the stack, "),
+            "[Enter]".blue(),
+            Span::raw(" / "),
+            "[Down]".blue(),
+            Span::raw(" for the next / previous match."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-o]".blue(),
+            Span::raw(" : history of all sessions, "),
+            "[Enter]".blue(),
+            Span::raw(" / "),
+            "[Tab]".blue(),
+            Span::raw(" to run / edit an entry."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-g]".blue(),
+            Span::raw(" : find an operation by name or description, and its keys."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-k]".blue(),
+            Span::raw(" : cheat-sheet of all the keys."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-p]".blue(),
+            Span::raw(" : preview the result of the operation keys, "),
+            "[Enter]".blue(),
+            Span::raw(" to apply."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-b]".blue(),
+            Span::raw(" : histogram of the values of the stack."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-e]".blue(),
+            Span::raw(" : bits of S1, byte by byte."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-a]".blue(),
+            Span::raw(" : show how long ago each entry was pushed."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-y]".blue(),
+            Span::raw(" : show how each entry was computed."),
         ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Shift-Enter]".blue(),
+            Span::raw(" : new input line, run with the others on "),
+            "[Enter]".blue(),
+            Span::raw("."),
+        ]),
+        Line::from(""),
+        Line::from("Tabs hold independent stacks, shown above the stack when there are several:"),
         Line::from(""),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-t]".blue(),
+            Span::raw(" : open a new tab."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-w]".blue(),
+            Span::raw(" : close the current tab."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Ctrl-r]".blue(),
+            Span::raw(" : rename the current tab to the content of the input."),
+        ]),
+        Line::from(vec![
+            Span::raw("  "),
+            "[Tab] [Shift-Tab]".blue(),
+            Span::raw(" : switch to the next / previous tab."),
+        ]),
+        Line::from(""),
+    ]);
+    lines.extend(plugin_lines(plugins, keys));
+    lines.extend(vec![
         Line::from(vec![
             Span::raw("Negative numbers can be entered as "),
             "_123".blue(),
@@ -165,6 +304,49 @@ fn help() -> Text<'static> {
             "123-".blue(),
             Span::raw(" (no space between the digits and the sign)."),
         ]),
+        Line::from(vec![
+            Span::raw("Beyond the leading one, "),
+            "_".blue(),
+            Span::raw(" separates groups of digits, as in "),
+            "1_000_000".blue(),
+            Span::raw("."),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Fractions can be entered as "),
+            "3/4".blue(),
+            Span::raw(", and mixed numbers as "),
+            "1+3/4=".blue(),
+            Span::raw(". A "),
+            "%".blue(),
+            Span::raw(" suffix divides by 100, e.g. "),
+            "15%".blue(),
+            Span::raw(" is 0.15."),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Unit suffixes convert to meters, bytes or seconds, e.g. "),
+            "5km".blue(),
+            Span::raw(", "),
+            "3.5GiB".blue(),
+            Span::raw(" or "),
+            "2h30m".blue(),
+            Span::raw(", SI prefixes scale, e.g. "),
+            "4.7k".blue(),
+            Span::raw(", and durations are entered as "),
+            "1:30".blue(),
+            Span::raw(" or "),
+            "01:02:03.5".blue(),
+            Span::raw("."),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Infix expressions such as "),
+            "(2+3)*4^2=".blue(),
+            Span::raw(" are evaluated and their result pushed. "),
+            "-".blue(),
+            Span::raw(" right after a number negates it, unless an operand follows."),
+        ]),
         Line::from(""),
         Line::from("Helix Calc supports numbers of arbitrary length, and uses ~ to indicate when a number is truncated."),
         Line::from("For instance, 1e100 will be represented as:"),
@@ -175,24 +357,102 @@ fn help() -> Text<'static> {
             Span::raw("0000000000000000000"),
         ]),
         Line::from(""),
+        Line::from(vec![
+            Span::raw("Tiny numbers show the count of zeros after the point instead, e.g. "),
+            Span::raw("0.0"),
+            "{12}".yellow(),
+            Span::raw("437."),
+        ]),
+        Line::from(""),
         Line::from("Check out the code and report bugs at:"),
         Line::from("   https://github.com/chbug/hc"),
         Line::from(""),
         Line::from("The name is inspired by Helix Editor, and the functionality by the venerable GNU dc."),
-    ];
+    ]);
     Text::from(lines)
 }
 
+/// The help line of an operation, e.g. "  + :add : add S2 and S1."
+fn operation(op: &dyn Operation, keys: &[(String, String)]) -> Line<'static> {
+    let mut spans = vec![Span::raw("  ")];
+    for (key, _) in keys.iter().filter(|(_, name)| name == op.name()) {
+        spans.push(key.clone().blue());
+        spans.push(Span::raw(" "));
+    }
+    spans.push(format!(":{}", op.name()).blue());
+    spans.push(Span::raw(format!(" : {}.", op.description())));
+    Line::from(spans)
+}
+
+/// The help section listing the operations added by plugins, if any.
+fn plugin_lines(plugins: &[Arc<dyn Operation>], keys: &[(String, String)]) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = plugins
+        .iter()
+        .map(|op| operation(op.as_ref(), keys))
+        .collect();
+    if !lines.is_empty() {
+        lines.insert(0, Line::from("Operations added by plugins:"));
+        lines.insert(1, Line::from(""));
+        lines.push(Line::from(""));
+    }
+    lines
+}
+
 impl Default for HelpState {
     fn default() -> Self {
-        let help = help();
+        Self::with_plugins(&Registry::default())
+    }
+}
+
+impl HelpState {
+    /// The help, including the operations of the given plugins.
+    pub fn with_plugins(registry: &Registry) -> Self {
+        let plugins: Vec<_> = registry.iter().cloned().collect();
+        let help = help(&plugins, &[]);
         let h = help.height();
         Self {
+            plugins,
             content: help,
             visible: false,
             vs_state: ScrollbarState::default().content_length(h),
         }
     }
+
+    /// Show the keys currently bound to the operations, as (key, operation
+    /// name), rather than those they ask for.
+    pub fn set_keys(&mut self, keys: &[(String, String)]) {
+        self.content = help(&self.plugins, keys);
+        self.vs_state = self.vs_state.content_length(self.content.height());
+    }
+}
+
+/// The area of the popup: enough for the content if the terminal allows,
+/// leaving a margin around, but never so small as to be unreadable.
+fn popup_area(content: &Text, area: Rect) -> Rect {
+    let longest = content.lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16;
+    let width = (longest.saturating_add(2))
+        .min(area.width * 9 / 10)
+        .max(MIN_WIDTH)
+        .min(area.width);
+    // Lines longer than the popup wrap, roughly as many times as they fill
+    // it.
+    let inner = usize::from(width.saturating_sub(2).max(1));
+    let wrapped: usize = content
+        .lines
+        .iter()
+        .map(|l| l.width().div_ceil(inner).max(1))
+        .sum();
+    let height = (u16::try_from(wrapped).unwrap_or(u16::MAX).saturating_add(2))
+        .min(area.height * 9 / 10)
+        .max(MIN_HEIGHT)
+        .min(area.height);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
 }
 
 impl StatefulWidget for Help {
@@ -201,10 +461,7 @@ impl StatefulWidget for Help {
         if !state.visible {
             return;
         }
-        let vertical = Layout::vertical([Constraint::Percentage(50)]).flex(Flex::Center);
-        let horizontal = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
-        let [area] = vertical.areas(area);
-        let [area] = horizontal.areas(area);
+        let area = popup_area(&state.content, area);
         Clear.render(area, buf);
 
         Paragraph::new(state.content.clone())
@@ -220,3 +477,46 @@ impl StatefulWidget for Help {
         Scrollbar::new(ScrollbarOrientation::VerticalRight).render(area, buf, &mut state.vs_state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_under_their_bound_keys() {
+        let keys = [("v", "sqrt"), ("A-s", "sqrt")].map(|(k, n)| (k.to_owned(), n.to_owned()));
+        let lines: Vec<String> = help(&[], &keys)
+            .lines
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert!(lines.iter().any(|l| l.starts_with("  v A-s :sqrt : ")));
+        // Unbound, e.g. when a plugin took the key first.
+        assert!(lines.iter().any(|l| l.starts_with("  :add : ")));
+    }
+
+    #[test]
+    fn popup_fits_the_content() {
+        let short = Text::from(vec![Line::from("x".repeat(60)); 20]);
+        // All of it, centered, on a large terminal.
+        assert_eq!(
+            popup_area(&short, Rect::new(0, 0, 200, 100)),
+            Rect::new(69, 39, 62, 22)
+        );
+        // Wrapped at 90% of the width, and scrolling past 90% of the height.
+        assert_eq!(
+            popup_area(&short, Rect::new(0, 0, 50, 30)),
+            Rect::new(3, 2, 45, 27)
+        );
+        // Never smaller than readable, unless the terminal is.
+        let tiny = Text::from("?");
+        assert_eq!(
+            popup_area(&tiny, Rect::new(0, 0, 80, 24)),
+            Rect::new(20, 7, 40, 10)
+        );
+        assert_eq!(
+            popup_area(&short, Rect::new(0, 0, 30, 8)),
+            Rect::new(0, 0, 30, 8)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Histogram of the stack values: how many fall in each of equal ranges, for
+//! a quick look at the distribution of a dataset.
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use ratatui::{
+    style::{Color, Stylize},
+    text::Line,
+    widgets::{Bar, BarChart, Block},
+};
+
+/// A range of values, from `low` to the `low` of the next one, and how many
+/// values fall in it.
+#[derive(Debug, PartialEq)]
+pub struct Bin {
+    pub low: BigDecimal,
+    pub count: u64,
+}
+
+/// The values in at most `max` ranges of equal width from the least to the
+/// greatest value: about the square root of their number, as is customary.
+pub fn bins<'a>(values: impl Iterator<Item = &'a BigDecimal> + Clone, max: usize) -> Vec<Bin> {
+    let (Some(least), Some(greatest)) = (values.clone().min(), values.clone().max()) else {
+        return vec![];
+    };
+    let n = values.clone().count();
+    let span = greatest - least;
+    let count = match span.is_zero() {
+        true => 1,
+        false => ((n as f64).sqrt().ceil() as usize).clamp(1, max.max(1)),
+    };
+    let width = &span / BigDecimal::from(count as u64);
+    let mut bins: Vec<Bin> = (0..count)
+        .map(|i| Bin {
+            low: (least + &width * BigDecimal::from(i as u64)).normalized(),
+            count: 0,
+        })
+        .collect();
+    let (span, least) = (span.to_f64().unwrap_or(0.0), least.to_f64().unwrap_or(0.0));
+    for v in values {
+        let offset = (v.to_f64().unwrap_or(0.0) - least) / span;
+        // The greatest value belongs to the last range.
+        let i = match offset.is_finite() {
+            true => ((offset * count as f64) as usize).min(count - 1),
+            false => 0,
+        };
+        bins[i].count += 1;
+    }
+    bins
+}
+
+/// The bins as horizontal bars, with the low end of each range on its left.
+pub fn chart(bins: &[Bin], label: impl Fn(&BigDecimal) -> Line<'static>) -> BarChart<'static> {
+    let bars: Vec<Bar> = bins
+        .iter()
+        .map(|bin| Bar::with_label(label(&bin.low), bin.count))
+        .collect();
+    BarChart::horizontal(bars)
+        .bar_width(1)
+        .bar_gap(0)
+        .bar_style(Color::Blue)
+        .block(Block::bordered().title_bottom(" Histogram "))
+        .bg(Color::Black)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(values: &[i32], max: usize) -> Vec<(String, u64)> {
+        let values: Vec<BigDecimal> = values.iter().map(|&v| BigDecimal::from(v)).collect();
+        bins(values.iter(), max)
+            .into_iter()
+            .map(|b| (b.low.to_string(), b.count))
+            .collect()
+    }
+
+    #[test]
+    fn equal_ranges() {
+        assert_eq!(counts(&[], 10), []);
+        assert_eq!(counts(&[3, 3], 10), [("3".into(), 2)]);
+        // 9 values in 3 ranges of width 3, the greatest in the last one.
+        assert_eq!(
+            counts(&[1, 2, 3, 4, 5, 6, 7, 8, 10], 10),
+            [("1".into(), 3), ("4".into(), 3), ("7".into(), 3)]
+        );
+        // As many ranges as fit.
+        assert_eq!(
+            counts(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12], 2),
+            [("0".into(), 6), ("6".into(), 7)]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! History popup: the inputs and operations of the current and past sessions.
+use crate::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, Clear, List, ListState, StatefulWidget, Widget},
+};
+
+/// How many entries are kept.
+const MAX_ENTRIES: usize = 1000;
+
+/// The stateful History widget.
+#[derive(Default)]
+pub struct History {}
+
+/// State for the History widget: the entries, oldest first, and the
+/// filtering and selection when visible.
+#[derive(Default)]
+pub struct HistoryState {
+    entries: Vec<String>,
+    session: usize, // The index of the first entry of this session.
+    visible: bool,
+    query: String,
+    list: ListState,
+}
+
+/// What to do with the selected entry.
+#[derive(Debug, PartialEq)]
+pub enum HistoryAction {
+    Run(String),
+    Edit(String),
+}
+
+impl HistoryState {
+    pub fn new(mut entries: Vec<String>) -> Self {
+        entries.drain(..entries.len().saturating_sub(MAX_ENTRIES));
+        HistoryState {
+            session: entries.len(),
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// Record a committed input or operation.
+    pub fn push(&mut self, entry: String) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.remove(0);
+            self.session = self.session.saturating_sub(1);
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// The entries of this session, oldest first.
+    pub fn session(&self) -> &[String] {
+        &self.entries[self.session..]
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        self.query.clear();
+        self.list.select(Some(0));
+    }
+
+    /// The entries containing the query, newest first.
+    fn filtered(&self) -> Vec<&String> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| e.contains(self.query.as_str()))
+            .collect()
+    }
+
+    pub fn handle_key(&mut self, k: KeyEvent) -> Option<HistoryAction> {
+        let selected = self
+            .filtered()
+            .get(self.list.selected().unwrap_or(0))
+            .map(|e| e.to_string());
+        match (k.code, k.modifiers) {
+            (KeyCode::Esc, _) => self.visible = false,
+            (KeyCode::Up, KeyModifiers::NONE) => self.list.select_previous(),
+            (KeyCode::Down, KeyModifiers::NONE) => {
+                let last = self.filtered().len().saturating_sub(1);
+                self.list
+                    .select(Some((self.list.selected().unwrap_or(0) + 1).min(last)));
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                self.visible = selected.is_none();
+                return selected.map(HistoryAction::Run);
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => {
+                self.visible = selected.is_none();
+                return selected.map(HistoryAction::Edit);
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.query.pop();
+                self.list.select(Some(0));
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) => {
+                self.query.push(c);
+                self.list.select(Some(0));
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl StatefulWidget for History {
+    type State = HistoryState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut HistoryState) {
+        if !state.visible {
+            return;
+        }
+        let vertical = Layout::vertical([Constraint::Percentage(50)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        Clear.render(area, buf);
+
+        let title = Line::from(vec![
+            " History ".into(),
+            "/".blue(),
+            state.query.clone().into(),
+        ]);
+        let entries: Vec<String> = state.filtered().into_iter().cloned().collect();
+        let list = List::new(entries)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .title_bottom("<Enter> run, <Tab> edit, <Esc> close")
+                    .bg(Color::Black),
+            )
+            .highlight_style(Style::new().bg(Color::Blue));
+        StatefulWidget::render(list, area, buf, &mut state.list);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn filter_and_select() {
+        let mut history = HistoryState::new(vec!["1".into(), "12".into(), ":sqrt".into()]);
+        history.set_visible(true);
+        assert_eq!(history.filtered(), vec![":sqrt", "12", "1"]);
+        history.handle_key(key(KeyCode::Char('1')));
+        assert_eq!(history.filtered(), vec!["12", "1"]);
+        history.handle_key(key(KeyCode::Down));
+        assert_eq!(
+            history.handle_key(key(KeyCode::Tab)),
+            Some(HistoryAction::Edit("1".into()))
+        );
+        assert!(!history.is_visible());
+
+        history.set_visible(true);
+        assert_eq!(
+            history.handle_key(key(KeyCode::Enter)),
+            Some(HistoryAction::Run(":sqrt".into()))
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        let mut history = HistoryState::new((0..MAX_ENTRIES + 5).map(|i| i.to_string()).collect());
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        history.push("last".into());
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.entries()[0], "6");
+        assert_eq!(history.session(), ["last"]);
+    }
+}
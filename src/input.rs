@@ -1,35 +1,42 @@
-use bigdecimal::num_bigint::BigInt;
+use crate::event::{Event, KeyCode};
+#[cfg(target_arch = "wasm32")]
+use crate::event::{KeyEventKind, KeyModifiers};
 use bigdecimal::BigDecimal;
-use crossterm::event::Event;
+use hc_core::expr::{self, ExprError};
+pub use hc_core::number::InputError;
+use hc_core::{dice, number};
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
     style::{Color, Stylize},
+    text::{Line, Span},
     widgets::{Block, Paragraph, StatefulWidget, Widget},
 };
-use std::str::FromStr;
-use thiserror::Error;
+#[cfg(not(target_arch = "wasm32"))]
 use tui_input::backend::crossterm::EventHandler;
-use tui_input::Input;
-
-#[derive(Error, Debug, PartialEq)]
-pub enum InputError {
-    #[error("Input is empty")]
-    Empty,
-    #[error("Input is invalid")]
-    Invalid,
-}
+use tui_input::{Input, InputRequest};
 
 /// Number input widget. This is specialized for the handling of
 /// helix calc numbers.
 #[derive(Debug, Clone, Default)]
 pub struct InputState {
     input: Input,
+    lines: Vec<String>, // The lines above the one being edited, if any.
     cursor: (u16, u16),
 }
 
 #[derive(Debug, Clone, Default)]
-pub struct InputWidget {}
+pub struct InputWidget {
+    result: Option<Line<'static>>,
+}
+
+impl InputWidget {
+    /// Show the tentative result of the expression typed, dimmed under it.
+    pub fn with_result(mut self, result: Line<'static>) -> Self {
+        self.result = Some(result);
+        self
+    }
+}
 
 impl InputState {
     pub fn with_value(mut self, value: String) -> Self {
@@ -37,43 +44,126 @@ impl InputState {
         self
     }
 
+    /// Several lines, the last one being edited.
+    pub fn with_lines(mut self, mut lines: Vec<String>) -> Self {
+        let last = lines.pop().unwrap_or_default();
+        self.lines = lines;
+        self.with_value(last)
+    }
+
     pub fn reset(&mut self) {
         self.input.reset();
+        self.lines.clear();
     }
 
     pub fn handle_event(&mut self, event: &Event) {
-        self.input.handle_event(event);
+        match event {
+            // Backspace on an empty line goes back to the end of the previous one.
+            Event::Key(k) if k.code == KeyCode::Backspace && self.text().is_empty() => {
+                if let Some(line) = self.lines.pop() {
+                    self.input = self.input.clone().with_value(line);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            _ => {
+                self.input.handle_event(event);
+            }
+            #[cfg(target_arch = "wasm32")]
+            _ => {
+                if let Some(request) = request(event) {
+                    self.input.handle(request);
+                }
+            }
+        }
+    }
+
+    /// Start a new line below the current one.
+    pub fn new_line(&mut self) {
+        self.lines.push(self.text().to_owned());
+        self.input.reset();
+    }
+
+    /// The number of lines of the input.
+    pub fn lines(&self) -> usize {
+        self.lines.len() + 1
+    }
+
+    /// Whether the input has more than one line.
+    pub fn is_multiline(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    /// All the lines of the input, leaving it empty.
+    pub fn take_lines(&mut self) -> Vec<String> {
+        let mut lines = std::mem::take(&mut self.lines);
+        lines.push(self.text().to_owned());
+        self.input.reset();
+        lines
     }
 
-    pub fn value(&self) -> Result<BigDecimal, InputError> {
-        let s = self.input.value();
-        if s.is_empty() {
-            return Err(InputError::Empty);
+    /// Insert pasted text at the cursor. Line breaks start new lines, except
+    /// for a final one, and tabs become spaces.
+    pub fn paste(&mut self, text: &str) {
+        let text = text.replace('\r', "");
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        for c in text.chars() {
+            if c == '\n' {
+                self.new_line();
+                continue;
+            }
+            let c = if c.is_whitespace() { ' ' } else { c };
+            self.input.handle(InputRequest::InsertChar(c));
         }
-        let s = s.to_owned();
-        let (negative, s) = if let Some(stripped) = s.strip_prefix('_') {
-            (true, stripped)
-        } else {
-            (false, s.as_str())
-        };
-        let result = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-            parse_radix_int(hex, 16)
-        } else if let Some(bin) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
-            parse_radix_int(bin, 2)
-        } else if let Some(oct) = s.strip_prefix("0o").or_else(|| s.strip_prefix("0O")) {
-            parse_radix_int(oct, 8)
-        } else {
-            BigDecimal::from_str(s).map_err(|_| InputError::Invalid)
-        }?;
-        Ok(if negative { -result } else { result })
+    }
+
+    /// The number typed, fractions being computed to `precision` digits.
+    pub fn value(&self, precision: u64) -> Result<BigDecimal, InputError> {
+        number::parse(self.text(), precision)
+    }
+
+    /// The raw text of the input, when it's not meant to be a number.
+    pub fn text(&self) -> &str {
+        self.input.value()
+    }
+
+    /// All the whitespace-separated numbers in the input, as [`Self::value`].
+    pub fn values(&self, precision: u64) -> Result<Vec<BigDecimal>, InputError> {
+        number::parse_all(self.text(), precision)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.input.value().is_empty()
+        self.input.value().is_empty() && self.lines.is_empty()
     }
 
     pub fn is_valid(&self) -> bool {
-        self.is_empty() || self.value().is_ok()
+        self.text().is_empty()
+            || self.command().is_some()
+            || dice::parse(self.text()).is_some()
+            // Whatever the precision of fractions, the least is quickest.
+            || self.values(0).is_ok()
+            || expr::compile(self.text()).is_ok()
+    }
+
+    /// The name of the command, when the input starts with ':'.
+    pub fn command(&self) -> Option<&str> {
+        self.text().strip_prefix(':')
+    }
+
+    /// The 1-based position of the character making the input invalid, if any.
+    pub fn error_position(&self) -> Option<usize> {
+        if self.text().is_empty() || self.command().is_some() || dice::parse(self.text()).is_some()
+        {
+            return None;
+        }
+        let err = self.values(0).err()?;
+        if expr::is_expression(self.text()) {
+            match expr::compile(self.text()) {
+                Err(ExprError::Syntax(_, pos)) => Some(pos),
+                _ => None,
+            }
+        } else {
+            err.position()
+        }
     }
 
     pub fn cursor(&self) -> (u16, u16) {
@@ -85,95 +175,222 @@ impl StatefulWidget for InputWidget {
     type State = InputState;
 
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        let width = area.width.max(3) - 3;
+        // Without room for the borders, fall back to a bare line and signal
+        // invalid input through the text color instead.
+        let border = if area.height >= 3 { 1 } else { 0 };
+        let width = area.width.max(1 + 2 * border) - 1 - 2 * border;
         let scroll = state.input.visual_scroll(width as usize);
+        let color = if state.is_valid() {
+            Color::White
+        } else {
+            Color::Red
+        };
 
-        let input = Paragraph::new(state.input.value().to_owned())
-            .block(
-                Block::bordered()
-                    .border_style(if state.is_valid() {
-                        Color::White
-                    } else {
-                        Color::Red
-                    })
-                    .bg(Color::Black),
-            )
-            .scroll((0, scroll as u16));
-
-        input.render(area, buf);
+        let current = match state.error_position() {
+            // Highlight the offending character.
+            Some(pos) => {
+                let chars: Vec<char> = state.text().chars().collect();
+                let split = (pos - 1).min(chars.len());
+                let after = (split + 1).min(chars.len());
+                Line::from(vec![
+                    Span::raw(chars[..split].iter().collect::<String>()),
+                    Span::from(chars[split..after].iter().collect::<String>())
+                        .black()
+                        .on_red(),
+                    Span::raw(chars[after..].iter().collect::<String>()),
+                ])
+            }
+            None => Line::raw(state.text().to_owned()),
+        };
+        // The previous lines, if any, above the current one, scrolled to keep
+        // the current one visible.
+        let mut text: Vec<Line> = state.lines.iter().map(|l| Line::raw(l.clone())).collect();
+        text.push(current);
+        let rows = area.height.saturating_sub(2 * border).max(1);
+        let scroll_y = (text.len() as u16).saturating_sub(rows);
+        let input = Paragraph::new(text).scroll((scroll_y, scroll as u16));
+        if border == 1 {
+            let mut block = Block::bordered().border_style(color).bg(Color::Black);
+            if let Some(result) = self.result {
+                block = block.title_bottom(result.right_aligned().dim());
+            }
+            input.block(block).render(area, buf);
+        } else {
+            input.fg(color).bg(Color::Black).render(area, buf);
+        }
 
-        let x = state.input.visual_cursor().max(scroll) - scroll + 1;
-        state.cursor = (area.x + x as u16, area.y + 1);
+        let x = (state.input.visual_cursor().max(scroll) - scroll) as u16 + border;
+        let y = state.lines.len() as u16 - scroll_y + border;
+        state.cursor = (area.x + x, area.y + y);
     }
 }
 
-fn parse_radix_int(digits: &str, radix: u32) -> Result<BigDecimal, InputError> {
-    if digits.is_empty() {
-        return Err(InputError::Invalid);
+/// The editing done by the key, as with the crossterm backend of tui-input,
+/// which doesn't build for the browser.
+#[cfg(target_arch = "wasm32")]
+fn request(event: &Event) -> Option<InputRequest> {
+    use InputRequest::*;
+    let Event::Key(k) = event else {
+        return None;
+    };
+    if k.kind == KeyEventKind::Release {
+        return None;
+    }
+    match (k.code, k.modifiers) {
+        (KeyCode::Backspace, KeyModifiers::NONE) | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+            Some(DeletePrevChar)
+        }
+        (KeyCode::Backspace, KeyModifiers::ALT) | (KeyCode::Char('w'), KeyModifiers::CONTROL) => {
+            Some(DeletePrevWord)
+        }
+        (KeyCode::Delete, KeyModifiers::NONE) => Some(DeleteNextChar),
+        (KeyCode::Delete, KeyModifiers::CONTROL) => Some(DeleteNextWord),
+        (KeyCode::Left, KeyModifiers::NONE) | (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
+            Some(GoToPrevChar)
+        }
+        (KeyCode::Left, KeyModifiers::CONTROL) => Some(GoToPrevWord),
+        (KeyCode::Right, KeyModifiers::NONE) | (KeyCode::Char('f'), KeyModifiers::CONTROL) => {
+            Some(GoToNextChar)
+        }
+        (KeyCode::Right, KeyModifiers::CONTROL) => Some(GoToNextWord),
+        (KeyCode::Home, KeyModifiers::NONE) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+            Some(GoToStart)
+        }
+        (KeyCode::End, KeyModifiers::NONE) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+            Some(GoToEnd)
+        }
+        (KeyCode::Char('u'), KeyModifiers::CONTROL) => Some(DeleteLine),
+        (KeyCode::Char('k'), KeyModifiers::CONTROL) => Some(DeleteTillEnd),
+        (KeyCode::Char(c), KeyModifiers::NONE) => Some(InsertChar(c)),
+        _ => None,
     }
-    let n = BigInt::parse_bytes(digits.as_bytes(), radix).ok_or(InputError::Invalid)?;
-    Ok(BigDecimal::from(n))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const PRECISION: u64 = 12;
+
     #[test]
     fn test_is_valid() {
         let mut widget = InputState::default();
         // Since InputWidget::default() has empty input, is_valid() calls is_empty() || value().is_ok()
         // is_empty() is true, so is_valid() is true.
         assert!(widget.is_valid());
-        assert_eq!(widget.value(), Err(InputError::Empty));
+        assert_eq!(widget.value(PRECISION), Err(InputError::Empty));
 
         widget = widget.with_value("123".to_string());
         assert!(widget.is_valid());
-        assert_eq!(widget.value(), Ok(BigDecimal::from(123)));
+        assert_eq!(widget.value(PRECISION), Ok(BigDecimal::from(123)));
 
         widget = widget.with_value("abc".to_string());
         assert!(!widget.is_valid());
-        assert_eq!(widget.value(), Err(InputError::Invalid));
+        assert_eq!(
+            widget.value(PRECISION),
+            Err(InputError::InvalidChar('a', 1))
+        );
+    }
+
+    #[test]
+    fn test_dice_are_valid() {
+        let widget = InputState::default().with_value("3d6+2".to_string());
+        assert!(widget.is_valid());
+        assert_eq!(widget.error_position(), None);
     }
 
     #[test]
     fn test_underscore_is_negative() {
         let widget = InputState::default().with_value("_123".to_string());
         assert!(widget.is_valid());
-        assert_eq!(widget.value(), Ok(BigDecimal::from(-123)));
+        assert_eq!(widget.value(PRECISION), Ok(BigDecimal::from(-123)));
     }
 
     #[test]
     fn test_hex_prefix() {
         let widget = InputState::default().with_value("0xff".to_string());
         assert!(widget.is_valid());
-        assert_eq!(widget.value(), Ok(BigDecimal::from(255)));
+        assert_eq!(widget.value(PRECISION), Ok(BigDecimal::from(255)));
     }
 
     #[test]
     fn test_binary_prefix() {
         let widget = InputState::default().with_value("0b1010".to_string());
         assert!(widget.is_valid());
-        assert_eq!(widget.value(), Ok(BigDecimal::from(10)));
+        assert_eq!(widget.value(PRECISION), Ok(BigDecimal::from(10)));
     }
 
     #[test]
     fn test_octal_prefix() {
         let widget = InputState::default().with_value("0o17".to_string());
         assert!(widget.is_valid());
-        assert_eq!(widget.value(), Ok(BigDecimal::from(15)));
+        assert_eq!(widget.value(PRECISION), Ok(BigDecimal::from(15)));
     }
 
     #[test]
     fn test_negative_hex() {
         let widget = InputState::default().with_value("_0xff".to_string());
         assert!(widget.is_valid());
-        assert_eq!(widget.value(), Ok(BigDecimal::from(-255)));
+        assert_eq!(widget.value(PRECISION), Ok(BigDecimal::from(-255)));
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let widget = InputState::default().with_value(" 1  0x10 _2 ".to_string());
+        assert!(widget.is_valid());
+        assert_eq!(
+            widget.values(PRECISION),
+            Ok(vec![
+                BigDecimal::from(1),
+                BigDecimal::from(16),
+                BigDecimal::from(-2)
+            ])
+        );
+        let widget = InputState::default().with_value("1 x".to_string());
+        assert_eq!(
+            widget.values(PRECISION),
+            Err(InputError::InvalidChar('x', 3))
+        );
     }
 
     #[test]
     fn test_incomplete_prefix_is_invalid() {
         let widget = InputState::default().with_value("0x".to_string());
         assert!(!widget.is_valid());
+        assert_eq!(widget.value(PRECISION), Err(InputError::MissingDigits(3)));
+    }
+
+    #[test]
+    fn test_command() {
+        let widget = InputState::default().with_value(":hyp".to_string());
+        assert!(widget.is_valid());
+        assert_eq!(widget.command(), Some("hyp"));
+        assert_eq!(widget.error_position(), None);
+        let widget = InputState::default().with_value("1:30".to_string());
+        assert_eq!(widget.command(), None);
+    }
+
+    #[test]
+    fn test_multiple_lines() {
+        let mut widget = InputState::default();
+        widget.paste("1 2\r\n+\n");
+        assert!(widget.is_multiline());
+        widget.handle_event(&Event::Key(KeyCode::Backspace.into()));
+        assert_eq!(widget.text(), "");
+        widget.handle_event(&Event::Key(KeyCode::Backspace.into()));
+        assert_eq!(widget.text(), "1 2");
+        assert!(!widget.is_multiline());
+        widget.new_line();
+        widget.paste("3\t*");
+        assert_eq!(widget.take_lines(), ["1 2", "3 *"]);
+        assert!(widget.is_empty());
+    }
+
+    #[test]
+    fn test_error_position() {
+        let widget = InputState::default().with_value("1 2.3.4".to_string());
+        assert_eq!(widget.error_position(), Some(6));
+        let widget = InputState::default().with_value("(1+)".to_string());
+        assert_eq!(widget.error_position(), Some(4));
     }
 }
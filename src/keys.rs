@@ -0,0 +1,85 @@
+//! Cheat-sheet overlay: a dense grid of the bound keys.
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+
+/// The keys and what they do, laid out in as many columns as fit.
+pub struct KeySheet {
+    entries: Vec<(String, String)>,
+}
+
+impl KeySheet {
+    pub fn new(entries: Vec<(String, String)>) -> Self {
+        KeySheet { entries }
+    }
+
+    /// The entries as rows of cells of equal width, filled column by column.
+    fn rows(&self, width: usize) -> Vec<Line<'static>> {
+        let key_width = self.entries.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        let cell_width = self
+            .entries
+            .iter()
+            .map(|(_, name)| key_width + 1 + name.chars().count() + 2)
+            .max()
+            .unwrap_or(1);
+        let columns = (width / cell_width).max(1);
+        let height = self.entries.len().div_ceil(columns);
+        (0..height)
+            .map(|row| {
+                let spans = (0..columns)
+                    .filter_map(|column| self.entries.get(column * height + row))
+                    .flat_map(|(key, name)| {
+                        [
+                            Span::from(format!("{key:>key_width$} ")).blue(),
+                            Span::raw(format!("{name:<0$}", cell_width - key_width - 1)),
+                        ]
+                    });
+                Line::from(spans.collect::<Vec<_>>())
+            })
+            .collect()
+    }
+}
+
+impl Widget for &KeySheet {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows = self.rows(area.width.saturating_sub(2) as usize);
+        let width = rows.iter().map(Line::width).max().unwrap_or(0) as u16 + 2;
+        let [area] = Layout::vertical([Constraint::Length(rows.len() as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Length(width)])
+            .flex(Flex::Center)
+            .areas(area);
+        Clear.render(area, buf);
+        Paragraph::new(rows)
+            .block(
+                Block::bordered()
+                    .title("<Any key to close>")
+                    .bg(Color::Black),
+            )
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_major_grid() {
+        let entries = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|k| (k.to_string(), format!("op_{k}")))
+            .collect();
+        let rows: Vec<String> = KeySheet::new(entries)
+            .rows(20)
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(rows, ["a op_a  d op_d  ", "b op_b  e op_e  ", "c op_c  "]);
+    }
+}
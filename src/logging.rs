@@ -0,0 +1,91 @@
+//! Debug log, written to a file since the terminal belongs to the interface.
+//! It records the events, the operations applied and the saves of the state,
+//! to reproduce what led to a bug.
+use anyhow::Context;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+struct FileLogger {
+    file: Mutex<File>,
+    start: Instant,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Only ours, not those of the dependencies.
+        metadata.target().starts_with("hc")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Ok(mut file) = self.file.lock() {
+            // Failing to log is no reason to disturb the user.
+            let _ = writeln!(
+                file,
+                "{:>9.3} {:<5} {}",
+                self.start.elapsed().as_secs_f64(),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Log to the end of the file, from now on.
+pub fn init(path: &Path) -> anyhow::Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    log::set_boxed_logger(Box::new(FileLogger {
+        file: Mutex::new(file),
+        start: Instant::now(),
+    }))?;
+    log::set_max_level(LevelFilter::Debug);
+    log::info!("hc {} started", env!("CARGO_PKG_VERSION"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn only_our_records() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("hc-log-{}", std::process::id()));
+        let logger = FileLogger {
+            file: Mutex::new(File::create(&path)?),
+            start: Instant::now(),
+        };
+        for target in ["hc::hc", "ratatui", "hc_core::stack"] {
+            logger.log(
+                &Record::builder()
+                    .level(Level::Debug)
+                    .target(target)
+                    .args(format_args!("from {target}"))
+                    .build(),
+            );
+        }
+        let log = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(path)?;
+        let messages: Vec<&str> = log.lines().map(|l| &l[10..]).collect();
+        assert_eq!(messages, ["DEBUG from hc::hc", "DEBUG from hc_core::stack"]);
+        Ok(())
+    }
+}
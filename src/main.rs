@@ -1,42 +1,509 @@
+// The browser front-end only needs the interactive app, none of the rest.
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
 use anyhow::Context;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+#[cfg(not(target_arch = "wasm32"))]
+use crossterm::{
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    execute,
+};
+use hc_core::{
+    bc::Bc,
+    dc::Dc,
+    export::TableFormat,
+    plugin::Registry,
+    stack::Stack,
+    state::{self, FileStorage, State, Storage},
+};
+use std::{
+    io::{self, stdin, stdout, IsTerminal},
+    panic,
+    sync::mpsc,
+    thread,
+    time::SystemTime,
+};
 
-mod format;
+mod bench;
+mod bits;
+mod config;
+mod conflict;
+#[cfg(not(target_arch = "wasm32"))]
+mod crypt;
+mod event;
 mod hc;
 mod help;
+mod histogram;
+mod history;
 mod input;
-mod stack;
-mod state;
+mod keys;
+mod logging;
+mod palette;
+mod remote;
+mod repl;
+mod report;
+#[cfg(not(target_arch = "wasm32"))]
+mod title;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
 #[derive(Parser)]
 #[command(version, about, long_about=None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg(help = "Operations to perform at startup")]
     extra: Vec<String>,
+    #[arg(
+        long,
+        value_name = "OP",
+        help = "Reduce the numbers piped to stdin with the operation, e.g. +"
+    )]
+    reduce: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "reduce",
+        help = "Line-oriented interface in plain text, e.g. for screen readers"
+    )]
+    plain: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["reduce", "plain"],
+        help = "Run the GNU dc program given instead of the operations, or else read from stdin"
+    )]
+    dc: bool,
+    #[arg(
+        long,
+        value_name = "PROGRAM",
+        conflicts_with_all = ["reduce", "plain", "dc"],
+        help = "Run the bc-like program, printing the values of its expressions"
+    )]
+    eval: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["reduce", "plain", "dc", "eval"],
+        help = "Run the bc-like program of the file"
+    )]
+    eval_file: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        value_name = "KEYS",
+        conflicts_with_all = ["reduce", "plain", "dc", "eval", "eval_file"],
+        help = "Press the keys without a terminal, e.g. '1<Enter>2+', and print the screen"
+    )]
+    keys: Option<String>,
+    #[arg(
+        long,
+        help = "On exit, print the whole stack, one value per line, rather than its top"
+    )]
+    print_stack: bool,
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        conflicts_with = "print_stack",
+        help = "On exit, print the stack as a table in markdown or latex"
+    )]
+    export: Option<TableFormat>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["encrypt", "key_file"],
+        help = "Append a debug log of the events and operations to the file"
+    )]
+    log: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Keep the state in the file rather than in the configuration directory"
+    )]
+    state: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        help = "Encrypt the state with a passphrase, asked for at startup as when it's encrypted"
+    )]
+    encrypt: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "encrypt",
+        help = "Encrypt the state with the contents of the file as the key"
+    )]
+    key_file: Option<std::path::PathBuf>,
+    #[arg(long, help = "Load the saved state, but don't save it on exit")]
+    read_only: bool,
+    #[arg(
+        long,
+        conflicts_with = "read_only",
+        help = "Start with an empty stack and the default settings, and save on exit"
+    )]
+    clean: bool,
+    #[arg(
+        long,
+        help = "Tell when another instance saves the state, for :reload to load it"
+    )]
+    watch: bool,
+    #[cfg(unix)]
+    #[arg(
+        long,
+        value_name = "SOCKET",
+        help = "Take requests on the Unix socket: push VALUE..., op NAME, stack, top"
+    )]
+    listen: Option<std::path::PathBuf>,
+    #[cfg(feature = "dynamic-plugins")]
+    #[arg(long, help = "Shared library providing additional operations")]
+    plugin: Vec<std::path::PathBuf>,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Time representative work of the engine, e.g. to spot regressions
+    Bench {
+        #[arg(
+            default_value = "",
+            help = "Only the benchmarks whose name contains this"
+        )]
+        filter: String,
+    },
+}
+
+/// Register the functions of the user's script, if there is one.
+#[cfg(feature = "scripting")]
+fn load_script(registry: &mut Registry) -> anyhow::Result<()> {
+    let path = state::config_dir()?.join("script.rhai");
+    if !path.exists() {
+        return Ok(());
+    }
+    let source = std::fs::read_to_string(&path)?;
+    hc_core::script::load(registry, &source)
+        .with_context(|| format!("failed to load {}", path.display()))
+}
+
+#[cfg(feature = "dynamic-plugins")]
+fn load_plugins(registry: &mut Registry, paths: &[std::path::PathBuf]) -> anyhow::Result<()> {
+    for path in paths {
+        // Safety: the user vouches for the libraries passed on the command line.
+        unsafe { registry.load(path) }
+            .with_context(|| format!("failed to load plugin {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Where the state is kept.
+#[cfg(not(target_arch = "wasm32"))]
+fn storage(cli: &Cli) -> anyhow::Result<FileStorage> {
+    Ok(match &cli.state {
+        Some(path) => FileStorage::new(path.clone()),
+        None => FileStorage::user()?,
+    })
+}
+
+/// The secret to encrypt the state with: the key file if given, or else a
+/// passphrase, asked for if the state is encrypted or is to be.
+#[cfg(not(target_arch = "wasm32"))]
+fn secret(cli: &Cli, storage: &FileStorage) -> anyhow::Result<Option<crypt::Secret>> {
+    if let Some(path) = &cli.key_file {
+        let key =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        anyhow::ensure!(!key.is_empty(), "{} is empty", path.display());
+        return Ok(Some(crypt::Secret::new(key)));
+    }
+    let encrypted = storage.encrypted();
+    if !encrypted && !cli.encrypt {
+        return Ok(None);
+    }
+    let prompt = format!("Passphrase of {}: ", storage.path().display());
+    let passphrase = rpassword::prompt_password(prompt).context("failed to read the passphrase")?;
+    anyhow::ensure!(!passphrase.is_empty(), "the passphrase is empty");
+    if !encrypted {
+        // A typo would lock the state away for good.
+        let again = rpassword::prompt_password("Passphrase again: ")
+            .context("failed to read the passphrase")?;
+        anyhow::ensure!(again == passphrase, "the passphrases differ");
+    }
+    Ok(Some(crypt::Secret::new(passphrase.into_bytes())))
+}
+
+/// Build the app from the saved state, or the configured initial one. A
+/// state that can't be loaded is moved aside rather than lost, and the app
+/// starts afresh with a notice about it. It's left as is if read-only, and
+/// one that can't be decrypted is an error: it's most likely a wrong
+/// passphrase.
+fn restore(
+    storage: &FileStorage,
+    config: &config::Config,
+    read_only: bool,
+) -> anyhow::Result<(hc::App, Option<String>)> {
+    let err = match storage.load().and_then(hc::App::new) {
+        Ok(app) => return Ok((app, None)),
+        Err(err) if err.is::<state::Encrypted>() => return Err(err),
+        Err(_) if !storage.exists() => return Ok((hc::App::new(config.initial_state())?, None)),
+        Err(err) => err,
+    };
+    if read_only {
+        let notice = format!("The saved state could not be loaded ({err:#}).");
+        return Ok((hc::App::new(config.initial_state())?, Some(notice)));
+    }
+    let backup = storage.back_up()?;
+    let notice = format!(
+        "The saved state could not be loaded ({err:#}). It was moved to {}.",
+        backup.display()
+    );
+    Ok((hc::App::new(config.initial_state())?, Some(notice)))
+}
+
+/// How the state is saved on exit.
+struct SaveOptions {
+    /// When the file was written when loaded, to tell whether another
+    /// instance saved it since.
+    loaded: Option<SystemTime>,
+    /// Save at most that many entries per stack.
+    max: Option<usize>,
+    /// Archive the entries beyond `max`.
+    archive: bool,
+}
+
+/// Save the state with at most `max` entries per stack, if given, and the
+/// others archived if asked for. If another instance saved it since it was
+/// loaded, ask whether to overwrite it.
+fn save(storage: &FileStorage, mut state: State, options: &SaveOptions) -> anyhow::Result<()> {
+    let _lock = storage.lock()?;
+    if storage.modified() != options.loaded {
+        let resolution = match stdin().is_terminal() {
+            true => conflict::ask(stdin().lock(), stdout())?,
+            false => conflict::Resolution::NewSession,
+        };
+        match resolution {
+            conflict::Resolution::Mine => {}
+            conflict::Resolution::Theirs => return Ok(()),
+            conflict::Resolution::NewSession => {
+                let path = storage.save_new(&state)?;
+                eprintln!(
+                    "Another instance saved the state: this one was saved to {}, \
+                     for hc --state to pick it up.",
+                    path.display()
+                );
+                return Ok(());
+            }
+        }
+    }
+    if let Some(max) = options.max {
+        let dropped = state.cap(max);
+        if options.archive && !dropped.is_empty() {
+            storage.archive(&dropped)?;
+        }
+    }
+    storage.save(&state)
+}
+
+/// Run a dc program on an empty stack, as dc would, and print its output.
+fn run_dc(program: &[String]) -> anyhow::Result<()> {
+    let program = match program.is_empty() {
+        true => io::read_to_string(stdin())?,
+        false => program.join(" "),
+    };
+    let mut dc = Dc::default();
+    let result = dc.run(&mut Stack::new(), &program);
+    print!("{}", dc.take_output());
+    Ok(result?)
+}
+
+/// Run a bc-like program, and print the values of its expressions.
+fn run_bc(program: &str) -> anyhow::Result<()> {
+    let mut bc = Bc::default();
+    let result = bc.run(program);
+    print!("{}", bc.take_output());
+    Ok(result?)
+}
+
+/// On a panic of the interface, restore the terminal before the message is
+/// printed, so that it can be read. Panics of the operations, which run on
+/// other threads, are left to the interface to report as failures.
+fn set_panic_hook() {
+    // The hook of ratatui restores the terminal, then prints the message.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        log::error!("{info}");
+        if thread::current().name() == Some("main") {
+            hook(info);
+        }
+    }));
+}
+
+/// The lines of stdin, read on a thread as they arrive.
+fn read_lines() -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in stdin().lines() {
+            // Stop reading once the app is gone, or on a read error.
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() -> anyhow::Result<()> {
+    web::run()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    if let Some(path) = &cli.log {
+        // The log has all the values typed, in plain text.
+        if storage(&cli)?.encrypted() {
+            anyhow::bail!("--log would write the values of the encrypted state in plain text");
+        }
+        logging::init(path)?;
+    }
+    if let Some(Command::Bench { filter }) = &cli.command {
+        return bench::bench(filter, stdout());
+    }
+    if cli.dc {
+        return run_dc(&cli.extra);
+    }
+    if let Some(program) = &cli.eval {
+        return run_bc(program);
+    }
+    if let Some(path) = &cli.eval_file {
+        let program = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        return run_bc(&program);
+    }
 
     // Initial loading and pre-UI calculations.
     // We haven't taken over the screen yet, so it's fine to
     // just return an error.
-    let state = state::load().unwrap_or_default();
-    let mut app = hc::App::new(state)?;
+    let mut storage = storage(&cli)?;
+    let secret = secret(&cli, &storage)?;
+    let encrypted = secret.is_some();
+    if let Some(secret) = secret {
+        storage = storage.with_cipher(std::sync::Arc::new(secret));
+    }
+    // Compiled-in plugins get registered here.
+    #[allow(unused_mut)]
+    let mut registry = Registry::default();
+    #[cfg(feature = "scripting")]
+    load_script(&mut registry)?;
+    #[cfg(feature = "dynamic-plugins")]
+    load_plugins(&mut registry, &cli.plugin)?;
+    let config = config::load()?;
+    let (mut app, mut notice) = restore(&storage, &config, cli.read_only)?;
+    if cli.clean {
+        app = hc::App::new(config.clean_state(app.state()))?;
+    }
+    let mut options = SaveOptions {
+        loaded: storage.modified(),
+        max: config.max_saved_entries,
+        archive: config.archive_dropped,
+    };
+    // The keys of the configuration may be for the operations of plugins.
+    let mut app = app.with_plugins(&registry).with_config(config);
+    if cli.read_only {
+        app = app.with_read_only();
+    }
+    if encrypted {
+        app = app.with_encrypted();
+    }
+    if cli.watch {
+        app = app.with_watch(storage.clone());
+    }
+    // The socket is removed once dropped, however this returns.
+    #[cfg(unix)]
+    let mut socket = None;
+    #[cfg(unix)]
+    if let Some(path) = &cli.listen {
+        let (listening, requests) = remote::listen(path)
+            .with_context(|| format!("failed to listen on {}", path.display()))?;
+        socket = Some(listening);
+        app = app.with_remote(requests);
+    }
+    if !app.conflicts().is_empty() {
+        let ignored = format!("Ignored key bindings: {}.", app.conflicts().join(", "));
+        notice = Some(match notice {
+            Some(notice) => format!("{notice}\n{ignored}"),
+            None => ignored,
+        });
+    }
+    if let Some(script) = config::init_script()? {
+        app.run_init(&script).context("failed to run init.hc")?;
+    }
     app.add_extra(cli.extra.join(" "))?;
+    if let Some(keys) = &cli.keys {
+        // A fixed size, for the same keys to always show the same.
+        app.feed_keys(keys)?;
+        println!("{}", app.render_to_string(80, 24));
+        return Ok(());
+    }
+    if cli.plain {
+        if let Some(notice) = notice {
+            println!("{notice}");
+        }
+        if cli.read_only {
+            println!("Read-only: the state won't be saved.");
+        }
+        repl::run(&mut app, &registry, stdin().lock(), stdout())?;
+        if cli.read_only {
+            return Ok(());
+        }
+        if let Some(synced) = app.synced() {
+            options.loaded = synced;
+        }
+        return save(&storage, app.state(), &options);
+    }
+    if let Some(op) = &cli.reduce {
+        if stdin().is_terminal() {
+            anyhow::bail!("--reduce reads the numbers piped to stdin");
+        }
+        app = app.with_reduce(op, read_lines())?;
+    }
+    if let Some(notice) = notice {
+        app = app.with_notice(notice);
+    }
 
     // From here on, we need to restore prior to failing.
     let mut term = ratatui::init();
-    let result = app.run(&mut term);
+    set_panic_hook();
+    // The title is cosmetic, don't fail if the terminal won't cooperate.
+    let _ = title::push();
+    // Bracketed paste lets us receive pasted text in one go, instead of
+    // interpreting it key by key.
+    // The mouse wheel scrolls the popups.
+    let _ = execute!(stdout(), EnableBracketedPaste, EnableMouseCapture);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| app.run(&mut term)));
+    let _ = execute!(stdout(), DisableBracketedPaste, DisableMouseCapture);
     ratatui::restore();
+    let _ = title::pop();
+    #[cfg(unix)]
+    drop(socket);
     // Don't attempt to save the state if something went wrong,
-    // to avoid corrupting it.
+    // to avoid corrupting it, but keep a copy of the stack after a crash.
+    let Ok(result) = result else {
+        let path = storage.save_crash(&app.state())?;
+        eprintln!("The stack was saved to {}.", path.display());
+        std::process::exit(101);
+    };
     result.context("UI failure")?;
+    if let Some(synced) = app.synced() {
+        // Reloaded since startup, if at all.
+        options.loaded = synced;
+    }
     let state = app.state();
-    state::save(&state)?;
-    // Provide the top of the stack in the output for convenience.
-    if !state.stack.is_empty() {
-        println!("{}", state.stack[0]);
+    let top = state.active().stack.first().cloned();
+    if !cli.read_only {
+        save(&storage, state, &options)?;
+    }
+    // Provide the top of the active stack in the output for convenience,
+    // or all of it, in the order it was pushed.
+    if let Some(format) = cli.export {
+        print!("{}", app.stack_table(format, vec![]));
+    } else if cli.print_stack {
+        println!("{}", app.stack_text());
+    } else if let Some(top) = top {
+        println!("{top}");
     }
     Ok(())
 }
@@ -0,0 +1,191 @@
+//! Command palette: the operations whose name or description matches a
+//! query, with the keys bound to them, to find and run them.
+use crate::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListState, StatefulWidget, Widget},
+};
+
+/// The stateful Palette widget.
+#[derive(Default)]
+pub struct Palette {}
+
+/// An operation as listed in the palette.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub keys: Vec<String>,
+    pub description: String,
+}
+
+/// State for the Palette widget: the operations, and the filtering and
+/// selection when visible.
+#[derive(Default)]
+pub struct PaletteState {
+    entries: Vec<Entry>,
+    visible: bool,
+    query: String,
+    list: ListState,
+}
+
+/// What to do with the selected operation, given by name.
+#[derive(Debug, PartialEq)]
+pub enum PaletteAction {
+    Run(String),
+    Edit(String),
+}
+
+impl PaletteState {
+    /// Show the palette of the operations, sorted by name.
+    pub fn open(&mut self, mut entries: Vec<Entry>) {
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        self.entries = entries;
+        self.visible = true;
+        self.query.clear();
+        self.list.select(Some(0));
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// The operations matching the query, ignoring case: those whose name
+    /// matches first, then those whose description does.
+    fn filtered(&self) -> Vec<&Entry> {
+        let query = self.query.to_lowercase();
+        let (mut named, described): (Vec<&Entry>, Vec<&Entry>) = self
+            .entries
+            .iter()
+            .filter(|e| e.name.contains(&query) || e.description.to_lowercase().contains(&query))
+            .partition(|e| e.name.contains(&query));
+        named.extend(described);
+        named
+    }
+
+    pub fn handle_key(&mut self, k: KeyEvent) -> Option<PaletteAction> {
+        let selected = self
+            .filtered()
+            .get(self.list.selected().unwrap_or(0))
+            .map(|e| e.name.clone());
+        match (k.code, k.modifiers) {
+            (KeyCode::Esc, _) => self.visible = false,
+            (KeyCode::Up, KeyModifiers::NONE) => self.list.select_previous(),
+            (KeyCode::Down, KeyModifiers::NONE) => {
+                let last = self.filtered().len().saturating_sub(1);
+                self.list
+                    .select(Some((self.list.selected().unwrap_or(0) + 1).min(last)));
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                self.visible = selected.is_none();
+                return selected.map(PaletteAction::Run);
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => {
+                self.visible = selected.is_none();
+                return selected.map(PaletteAction::Edit);
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.query.pop();
+                self.list.select(Some(0));
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) => {
+                self.query.push(c);
+                self.list.select(Some(0));
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl StatefulWidget for Palette {
+    type State = PaletteState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut PaletteState) {
+        if !state.visible {
+            return;
+        }
+        let vertical = Layout::vertical([Constraint::Percentage(50)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(70)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        Clear.render(area, buf);
+
+        let title = Line::from(vec![
+            " Operations ".into(),
+            "/".blue(),
+            state.query.clone().into(),
+        ]);
+        let entries: Vec<Line> = state
+            .filtered()
+            .into_iter()
+            .map(|e| {
+                let mut spans = vec![Span::raw(format!(":{}", e.name))];
+                for key in &e.keys {
+                    spans.push(" ".into());
+                    spans.push(key.clone().blue());
+                }
+                spans.push(format!(" : {}", e.description).into());
+                Line::from(spans)
+            })
+            .collect();
+        let list = List::new(entries)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .title_bottom("<Enter> run, <Tab> edit, <Esc> close")
+                    .bg(Color::Black),
+            )
+            .highlight_style(Style::new().bg(Color::Blue));
+        StatefulWidget::render(list, area, buf, &mut state.list);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn entry(name: &str, description: &str) -> Entry {
+        Entry {
+            name: name.into(),
+            keys: vec![],
+            description: description.into(),
+        }
+    }
+
+    #[test]
+    fn filter_and_select() {
+        let mut palette = PaletteState::default();
+        palette.open(vec![
+            entry("sqrt", "square root of S1"),
+            entry("pow", "S2 to the power of S1"),
+            entry("roots", "complex roots of the polynomial"),
+        ]);
+        let names = |p: &PaletteState| -> Vec<String> {
+            p.filtered().iter().map(|e| e.name.clone()).collect()
+        };
+        assert_eq!(names(&palette), ["pow", "roots", "sqrt"]);
+        for c in "ROOT".chars() {
+            palette.handle_key(key(KeyCode::Char(c)));
+        }
+        // The name matches first, then the description.
+        assert_eq!(names(&palette), ["roots", "sqrt"]);
+        palette.handle_key(key(KeyCode::Down));
+        assert_eq!(
+            palette.handle_key(key(KeyCode::Enter)),
+            Some(PaletteAction::Run("sqrt".into()))
+        );
+        assert!(!palette.is_visible());
+
+        palette.open(vec![entry("solve", "root of NAME")]);
+        assert_eq!(
+            palette.handle_key(key(KeyCode::Tab)),
+            Some(PaletteAction::Edit("solve".into()))
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Remote control of the running calculator over a Unix socket, for editors
+//! and scripts: one request per line, one reply line per request.
+//!
+//! - `push VALUE...` pushes the numbers, and replies `ok`.
+//! - `op NAME` applies the operation, by name or key, and replies `ok` once
+//!   it's done.
+//! - `stack` replies the values, bottom first, separated by spaces.
+//! - `top` replies S1.
+//!
+//! Failures reply `error: ` followed by the reason.
+use std::sync::mpsc::Sender;
+#[cfg(unix)]
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// A line received, and where to send its reply.
+pub struct Request {
+    pub line: String,
+    pub reply: Sender<String>,
+}
+
+/// The socket listened on, removed once dropped.
+#[cfg(unix)]
+pub struct Socket(PathBuf);
+
+#[cfg(unix)]
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Listen on the socket, replacing a stale one, and pass on the requests of
+/// all the clients. Anything else at the path is left alone, and an error.
+#[cfg(unix)]
+pub fn listen(path: &Path) -> io::Result<(Socket, Receiver<Request>)> {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the file exists and isn't a socket",
+            ));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another instance is listening on it",
+            ));
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let tx = tx.clone();
+            thread::spawn(move || serve(stream, tx));
+        }
+    });
+    Ok((Socket(path.to_owned()), rx))
+}
+
+/// Relay the requests of a client until it disconnects, or the app stops
+/// listening.
+#[cfg(unix)]
+fn serve(stream: UnixStream, requests: Sender<Request>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let (reply, replies) = mpsc::channel();
+        let request = Request { line: line?, reply };
+        if requests.send(request).is_err() {
+            break;
+        }
+        let Ok(reply) = replies.recv() else {
+            break;
+        };
+        writeln!(writer, "{reply}")?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_requests_and_replies() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("hc-remote-{}", std::process::id()));
+        let (socket, requests) = listen(&path)?;
+        assert_eq!(
+            listen(&path).err().map(|err| err.kind()),
+            Some(io::ErrorKind::AddrInUse)
+        );
+        let mut client = UnixStream::connect(&path)?;
+        writeln!(client, "top")?;
+        let request = requests.recv().unwrap();
+        assert_eq!(request.line, "top");
+        request.reply.send("42".into()).unwrap();
+        let mut reply = String::new();
+        BufReader::new(client).read_line(&mut reply)?;
+        assert_eq!(reply, "42\n");
+        drop(socket);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn leave_other_files_alone() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("hc-notes-{}", std::process::id()));
+        fs::write(&path, "notes")?;
+        assert_eq!(
+            listen(&path).err().map(|err| err.kind()),
+            Some(io::ErrorKind::AlreadyExists)
+        );
+        assert_eq!(fs::read_to_string(&path)?, "notes");
+        fs::remove_file(path)
+    }
+}
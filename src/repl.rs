@@ -0,0 +1,85 @@
+//! Plain line-oriented front-end, e.g. for screen readers. Each line is typed
+//! into the calculator as in the interface, and the outcome is written back
+//! as a line of text, without colors or layout to decode.
+use std::io::{BufRead, Write};
+
+use hc_core::{ops, plugin::Registry};
+
+use crate::hc::App;
+
+pub fn run<R: BufRead, W: Write>(
+    app: &mut App,
+    registry: &Registry,
+    input: R,
+    mut output: W,
+) -> anyhow::Result<()> {
+    writeln!(
+        output,
+        "Type numbers and operations, ? for the operations, stack to list the stack, q to quit."
+    )?;
+    for line in input.lines() {
+        let line = line?;
+        match line.trim() {
+            "?" => {
+                for op in ops::builtins().chain(registry.iter().cloned()) {
+                    let key = op.key().map(|k| format!(", key {k}")).unwrap_or_default();
+                    writeln!(output, "{}{key}: {}", op.name(), op.description())?;
+                }
+            }
+            "stack" => {
+                let values = app.plain_values();
+                if values.is_empty() {
+                    writeln!(output, "empty")?;
+                }
+                for (i, v) in values.iter().enumerate() {
+                    writeln!(output, "{}: {v}", i + 1)?;
+                }
+            }
+            line => match app.submit(line) {
+                Ok(()) => match app.plain_values().first() {
+                    Some(top) => writeln!(output, "1: {top}")?,
+                    None => writeln!(output, "empty")?,
+                },
+                Err(err) => writeln!(output, "error: {err}")?,
+            },
+        }
+        if app.exiting() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hc_core::state::State;
+
+    #[test]
+    fn lines_in_text_out() -> anyhow::Result<()> {
+        let mut app = App::new(State::default())?;
+        let input = "1 2 +\n3 /\n0 /\nstack\nu\nq\n4\n";
+        let mut output = vec![];
+        run(
+            &mut app,
+            &Registry::default(),
+            input.as_bytes(),
+            &mut output,
+        )?;
+        let output = String::from_utf8(output)?;
+        let lines: Vec<&str> = output.lines().skip(1).collect();
+        assert_eq!(
+            lines,
+            [
+                "1: 3",
+                "1: 1",
+                "error: element 1 must be non-zero",
+                "1: 0",
+                "2: 1",
+                "1: 1",
+                "1: 1"
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Session reports: what was typed and what came out of it, as a record
+//! to keep, e.g. for billable calculations.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The report of a session, in Markdown: its name, when it was written,
+/// the tape of the inputs and operations, and the final stack as a table.
+pub fn report(name: &str, now: SystemTime, tape: &[String], stack: &str) -> String {
+    let mut out = format!("# {name}\n\nReport of {}.\n\n## Tape\n\n", timestamp(now));
+    match tape.is_empty() {
+        true => out += "Nothing was typed.\n",
+        false => {
+            for entry in tape {
+                out += &format!("    {entry}\n");
+            }
+        }
+    }
+    out += "\n## Stack\n\n";
+    out += stack;
+    out
+}
+
+/// The time in UTC, e.g. `2024-03-01 17:05:09 UTC`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // The civil date of the day, from "chrono-Compatible Low-Level Date
+    // Algorithms" by Howard Hinnant.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1709312709);
+        assert_eq!(timestamp(leap_day), "2024-03-01 17:05:09 UTC");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1709164800);
+        assert_eq!(timestamp(leap_day), "2024-02-29 00:00:00 UTC");
+    }
+
+    #[test]
+    fn markdown_report() {
+        let tape = ["1 2".to_string(), ":+".to_string()];
+        assert_eq!(
+            report("invoice", UNIX_EPOCH, &tape, "| Value |\n"),
+            "# invoice\n\n\
+             Report of 1970-01-01 00:00:00 UTC.\n\n\
+             ## Tape\n\n    1 2\n    :+\n\n\
+             ## Stack\n\n| Value |\n"
+        );
+    }
+}
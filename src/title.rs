@@ -0,0 +1,22 @@
+//! Terminal window title.
+use crossterm::{execute, terminal::SetTitle};
+use std::io::{stdout, Write};
+
+/// Save the current title on the terminal's title stack. This is an xterm
+/// extension, silently ignored by terminals that don't support it.
+pub fn push() -> std::io::Result<()> {
+    let mut out = stdout();
+    out.write_all(b"\x1b[22;0t")?;
+    out.flush()
+}
+
+/// Restore the title saved by `push()`.
+pub fn pop() -> std::io::Result<()> {
+    let mut out = stdout();
+    out.write_all(b"\x1b[23;0t")?;
+    out.flush()
+}
+
+pub fn set(title: &str) -> std::io::Result<()> {
+    execute!(stdout(), SetTitle(title))
+}
@@ -0,0 +1,138 @@
+//! The app in a browser page, drawn by ratzilla rather than in a terminal,
+//! see web/index.html. The state is kept in the local storage of the page.
+use crate::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crate::hc::App;
+use anyhow::{anyhow, Context};
+use hc_core::state::{State, Storage};
+use ratatui::Terminal;
+use ratzilla::{DomBackend, WebRenderer};
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+/// The item of the local storage holding the state.
+const ITEM: &str = "helix-calc";
+
+/// The state as JSON in the local storage of the browser.
+struct LocalStorage(web_sys::Storage);
+
+impl LocalStorage {
+    fn new() -> anyhow::Result<LocalStorage> {
+        let storage = web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .context("no local storage")?;
+        Ok(LocalStorage(storage))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn load(&self) -> anyhow::Result<State> {
+        let item = self.0.get_item(ITEM).map_err(|err| anyhow!("{err:?}"))?;
+        let Some(json) = item else {
+            return Ok(State::default());
+        };
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    fn save(&self, state: &State) -> anyhow::Result<()> {
+        let json = serde_json::to_string(state)?;
+        self.0
+            .set_item(ITEM, &json)
+            .map_err(|err| anyhow!("{err:?}"))
+    }
+}
+
+/// Build the app from the saved state, or afresh with a notice if it can't
+/// be loaded.
+fn restore(storage: &LocalStorage) -> anyhow::Result<App> {
+    match storage.load().and_then(App::new) {
+        Ok(app) => Ok(app),
+        Err(err) => {
+            let notice = format!("The saved state could not be loaded ({err:#}).");
+            Ok(App::new(State::default())?.with_notice(notice))
+        }
+    }
+}
+
+/// The key as the app knows it, if it does.
+fn key_event(key: ratzilla::event::KeyEvent) -> Option<KeyEvent> {
+    use ratzilla::event::KeyCode as Key;
+    let code = match key.code {
+        Key::Char(c) => KeyCode::Char(c),
+        Key::F(n) => KeyCode::F(n),
+        Key::Backspace => KeyCode::Backspace,
+        Key::Enter => KeyCode::Enter,
+        Key::Left => KeyCode::Left,
+        Key::Right => KeyCode::Right,
+        Key::Up => KeyCode::Up,
+        Key::Down => KeyCode::Down,
+        Key::Tab if key.shift => KeyCode::BackTab,
+        Key::Tab => KeyCode::Tab,
+        Key::Delete => KeyCode::Delete,
+        Key::Home => KeyCode::Home,
+        Key::End => KeyCode::End,
+        Key::PageUp => KeyCode::PageUp,
+        Key::PageDown => KeyCode::PageDown,
+        Key::Esc => KeyCode::Esc,
+        Key::Unidentified => return None,
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    modifiers.set(KeyModifiers::CONTROL, key.ctrl);
+    modifiers.set(KeyModifiers::ALT, key.alt);
+    modifiers.set(KeyModifiers::SHIFT, key.shift);
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// Run the app in the page. The state is saved after each key, as there's
+/// no telling when the page is closed, and quitting starts again from it.
+pub fn run() -> anyhow::Result<()> {
+    // The browser gives no entropy to the engine for its random numbers.
+    hc_core::random::seed((js_sys::Math::random() * u64::MAX as f64) as u64);
+    let storage = LocalStorage::new()?;
+    let app = Rc::new(RefCell::new(restore(&storage)?));
+    // ratzilla's errors may hold a JsValue, which isn't Send, so they are
+    // converted explicitly rather than with `?`.
+    let backend = DomBackend::new().map_err(|err| anyhow!("{err:?}"))?;
+    let mut terminal = Terminal::new(backend)?;
+    let on_key = {
+        let app = app.clone();
+        move |key| {
+            let Some(key) = key_event(key) else {
+                return;
+            };
+            let mut app = app.borrow_mut();
+            if !app.handle_event(Event::Key(key)) {
+                return;
+            }
+            if let Some(text) = app.take_clipboard() {
+                if let Some(window) = web_sys::window() {
+                    // The copy is done once the promise resolves, or not.
+                    let _ = window.navigator().clipboard().write_text(&text);
+                }
+            }
+            if let Err(err) = storage.save(&app.state()) {
+                log::error!("failed to save the state: {err:#}");
+            }
+            if app.exiting() {
+                match restore(&storage) {
+                    Ok(restored) => *app = restored,
+                    Err(err) => log::error!("failed to start again: {err:#}"),
+                }
+            }
+        }
+    };
+    terminal
+        .on_key_event(on_key)
+        .map_err(|err| anyhow!("{err:?}"))?;
+    let mut title = String::new();
+    terminal.draw_web(move |frame| {
+        let mut app = app.borrow_mut();
+        app.refresh_watcher(Duration::ZERO);
+        app.draw(frame);
+        if app.title() != title {
+            title = app.title();
+            if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+                document.set_title(&title);
+            }
+        }
+    });
+    Ok(())
+}